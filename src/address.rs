@@ -0,0 +1,333 @@
+use crate::call_stack::{CallFrame, MemoryAccess};
+use crate::dwarf_index::DwarfIndex;
+use crate::evaluate::attributes;
+use crate::memory_view::{read_words, target_endian};
+use crate::registers::Registers;
+use crate::symbol_table::{SymbolKind, SymbolTable};
+use anyhow::{anyhow, Result};
+use gimli::{AttributeValue, Dwarf, Reader};
+use log::error;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Range;
+
+/// The maximum number of stack bytes that `annotate_stack` reads.
+pub const MAX_ANNOTATED_STACK_SIZE: u32 = 4096;
+
+/// Describes the memory layout of the debug target.
+#[derive(Debug, Clone)]
+pub struct MemoryRegions {
+    /// The address ranges of the peripherals.
+    pub peripherals: Vec<Range<u64>>,
+
+    /// The address range of the heap, if the program has one.
+    pub heap: Option<Range<u64>>,
+}
+
+impl Default for MemoryRegions {
+    /// Creates the memory layout of a ARM Cortex-M target, which has the peripherals and the
+    /// system control space at fixed addresses.
+    fn default() -> MemoryRegions {
+        MemoryRegions {
+            peripherals: vec![0x4000_0000..0x6000_0000, 0xE000_0000..0x1_0000_0000],
+            heap: None,
+        }
+    }
+}
+
+/// Describes what a value plausibly is, when it is interpreted as a address.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AddressKind {
+    /// A address in the machine code of a function.
+    Code {
+        /// The name of the function.
+        function: String,
+
+        /// The offset from the start of the function.
+        offset: u64,
+    },
+
+    /// A address in a static variable.
+    Static {
+        /// The name of the static variable.
+        name: String,
+
+        /// The offset from the start of the static variable.
+        offset: u64,
+    },
+
+    /// A address in the stack region of a frame.
+    Stack {
+        /// The index of the frame in the call stack.
+        frame_index: usize,
+
+        /// The offset from the CFA of the frame, it is always negative.
+        cfa_offset: i64,
+    },
+
+    /// A address in the heap.
+    Heap,
+
+    /// A address of a peripheral register.
+    Peripheral,
+
+    /// The value is not a known address.
+    Unknown,
+}
+
+impl fmt::Display for AddressKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddressKind::Code { function, offset } => write!(f, "<{}+{:#x}>", function, offset),
+            AddressKind::Static { name, offset } => write!(f, "&{}+{:#x}", name, offset),
+            AddressKind::Stack {
+                frame_index,
+                cfa_offset,
+            } => write!(f, "stack frame #{} CFA{}", frame_index, cfa_offset),
+            AddressKind::Heap => write!(f, "heap"),
+            AddressKind::Peripheral => write!(f, "peripheral"),
+            AddressKind::Unknown => write!(f, "?"),
+        }
+    }
+}
+
+/// The debug information and the state of the debug target that values are classified against.
+pub struct AddressContext<'a, R: Reader<Offset = usize>> {
+    /// A reference to gimli-rs `Dwarf` struct.
+    pub dwarf: &'a Dwarf<R>,
+
+    /// A index of `dwarf`, which makes finding the function of a address fast.
+    pub index: &'a DwarfIndex,
+
+    /// The virtually unwound call stack.
+    pub call_stack: &'a [CallFrame],
+
+    /// A `Registers` struct containing the register numbers of the architecture.
+    pub registers: &'a Registers,
+
+    /// A optional symbol table, used for code and static variables without DWARF debug
+    /// information.
+    pub symbols: Option<&'a SymbolTable>,
+
+    /// The memory layout of the debug target.
+    pub regions: &'a MemoryRegions,
+}
+
+/// Will classify what a value plausibly is when it is interpreted as a address.
+///
+/// Description:
+///
+/// * `context` - The debug information and the state of the debug target.
+/// * `value` - The value, usually a register value or a word read from memory.
+///
+/// The value is checked against the functions in the DWARF debug information, the symbol table,
+/// the stack region of each frame, the heap and the peripherals in that order.
+/// The lowest bit of a code address is ignored because it is used to indicate Thumb mode.
+pub fn classify_address<R: Reader<Offset = usize>>(
+    context: &AddressContext<R>,
+    value: u64,
+) -> Result<AddressKind> {
+    let AddressContext {
+        dwarf,
+        index,
+        call_stack,
+        registers,
+        symbols,
+        regions,
+    } = context;
+    if let Some((function, offset)) = find_function(dwarf, index, value & !1)? {
+        return Ok(AddressKind::Code { function, offset });
+    }
+
+    if let Some(symbols) = symbols {
+        if let Some((symbol, offset)) = symbols.find_function(value & !1) {
+            return Ok(AddressKind::Code {
+                function: symbol.name.clone(),
+                offset,
+            });
+        }
+
+        let object = symbols.symbols.iter().find(|s| {
+            s.kind == SymbolKind::Object && s.address <= value && value < s.address + s.size
+        });
+        if let Some(symbol) = object {
+            return Ok(AddressKind::Static {
+                name: symbol.name.clone(),
+                offset: value - symbol.address,
+            });
+        }
+    }
+
+    if let Some(sp_reg) = registers.stack_pointer_register {
+        for (frame_index, frame) in call_stack.iter().enumerate() {
+            if let (Some(sp), Some(cfa)) = (frame.registers[sp_reg], frame.cfa) {
+                if sp as u64 <= value && value < cfa as u64 {
+                    return Ok(AddressKind::Stack {
+                        frame_index,
+                        cfa_offset: value as i64 - cfa as i64,
+                    });
+                }
+            }
+        }
+    }
+
+    if let Some(heap) = &regions.heap {
+        if heap.contains(&value) {
+            return Ok(AddressKind::Heap);
+        }
+    }
+
+    if regions.peripherals.iter().any(|r| r.contains(&value)) {
+        return Ok(AddressKind::Peripheral);
+    }
+
+    Ok(AddressKind::Unknown)
+}
+
+/// Find the name of the function containing a address and the offset into it.
+fn find_function<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    index: &DwarfIndex,
+    address: u64,
+) -> Result<Option<(String, u64)>> {
+    let offset = match u32::try_from(address)
+        .ok()
+        .and_then(|a| index.find_function(a))
+    {
+        Some(val) => val,
+        None => return Ok(None),
+    };
+
+    let header = match offset.section_offset.as_debug_info_offset() {
+        Some(offset) => dwarf.debug_info.header_from_offset(offset)?,
+        None => return Ok(None),
+    };
+    let unit = dwarf.unit(header)?;
+    let mut die = unit.entry(offset.unit_offset)?;
+
+    // The low pc can also be a index into `.debug_addr`, e.g. `DW_FORM_addrx`.
+    let low_pc = match die.attr_value(gimli::DW_AT_low_pc)? {
+        Some(val) => match dwarf.attr_address(&unit, val)? {
+            Some(val) => val,
+            None => return Ok(None),
+        },
+        None => return Ok(None),
+    };
+
+    // The name can be located in the abstract origin or the specification DIE.
+    let mut name = attributes::name_attribute(dwarf, &die)?;
+    while name.is_none() {
+        let origin = match die.attr_value(gimli::DW_AT_abstract_origin)? {
+            Some(val) => Some(val),
+            None => die.attr_value(gimli::DW_AT_specification)?,
+        };
+        die = match origin {
+            Some(AttributeValue::UnitRef(offset)) => unit.entry(offset)?,
+            _ => break,
+        };
+        name = attributes::name_attribute(dwarf, &die)?;
+    }
+
+    Ok(Some((
+        name.unwrap_or_else(|| "<unknown>".to_string()),
+        address - low_pc,
+    )))
+}
+
+/// A word on the stack with information about what it is.
+#[derive(Debug, Clone)]
+pub struct StackWord {
+    /// The address of the word.
+    pub address: u32,
+
+    /// The value of the word.
+    pub value: u32,
+
+    /// The index of the frame whose stack region the word belongs to.
+    pub frame_index: Option<usize>,
+
+    /// `true` if the word is the saved return address of the frame.
+    pub return_address: bool,
+
+    /// What the value plausibly is when it is interpreted as a address.
+    pub points_to: AddressKind,
+}
+
+/// Will read and annotate the stack of the debug target.
+///
+/// Description:
+///
+/// * `context` - The debug information and the state of the debug target.
+/// * `mem` - A struct for accessing the memory of the debug target.
+///
+/// The stack is read from the stack pointer of the first frame to the CFA of the last frame
+/// with a known CFA, but at most `MAX_ANNOTATED_STACK_SIZE` bytes.
+/// The region of a frame is from its stack pointer up to its CFA, the word that contains the
+/// address the frame returns to is marked as the return address.
+pub fn annotate_stack<R: Reader<Offset = usize>, M: MemoryAccess>(
+    context: &AddressContext<R>,
+    mem: &mut M,
+) -> Result<Vec<StackWord>> {
+    let (call_stack, registers) = (context.call_stack, context.registers);
+    let sp_reg = registers
+        .stack_pointer_register
+        .ok_or_else(|| anyhow!("Requires sp register id"))?;
+    let start = match call_stack.first().and_then(|f| f.registers[sp_reg]) {
+        Some(val) => val,
+        None => return Ok(vec![]),
+    };
+    let end = match call_stack.iter().filter_map(|f| f.cfa).max() {
+        Some(val) if val > start => val,
+        _ => return Ok(vec![]),
+    };
+    // A corrupt stack pointer or CFA would otherwise read a huge part of the memory.
+    let end = end.min(start.saturating_add(MAX_ANNOTATED_STACK_SIZE));
+
+    let data = match mem.get_address(&start, (end - start) as usize) {
+        Some(val) => val,
+        None => {
+            error!("Can not read the stack from address {:#010x}", start);
+            return Err(anyhow!(
+                "Can not read the stack from address {:#010x}",
+                start
+            ));
+        }
+    };
+
+    let mut words = vec![];
+    for word in read_words(start, &data, 4, target_endian(context.dwarf)) {
+        let (address, value) = (word.address, word.value as u32);
+
+        let frame_index = call_stack
+            .iter()
+            .position(|f| match (f.registers[sp_reg], f.cfa) {
+                (Some(sp), Some(cfa)) => sp <= address && address < cfa,
+                _ => false,
+            });
+
+        // A interrupted caller continues at the stacked program counter, otherwise the caller
+        // code location is a address in the call instruction.
+        let return_address = match frame_index.and_then(|i| call_stack.get(i + 1)) {
+            Some(caller) if caller.exception_return.is_some() => {
+                caller.code_location as u32 == value
+            }
+            Some(caller) => {
+                registers
+                    .architecture
+                    .return_address(caller.code_location as u32)
+                    == Some(value)
+            }
+            None => false,
+        };
+
+        words.push(StackWord {
+            address,
+            value,
+            frame_index,
+            return_address,
+            points_to: classify_address(context, value as u64)?,
+        });
+    }
+
+    Ok(words)
+}
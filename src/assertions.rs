@@ -0,0 +1,161 @@
+use crate::call_stack::MemoryAccess;
+use crate::evaluate::evaluate::EvaluatorValue;
+use crate::evaluate::expression::{evaluate_condition, Condition};
+use anyhow::Result;
+use gimli::{Reader, RunTimeEndian};
+use log::{info, trace};
+
+/// A invariant that is checked every time the debug target halts, e.g. `assert count < 10`.
+#[derive(Debug, Clone)]
+pub struct Assertion {
+    /// The id of the assertion.
+    pub id: u32,
+
+    /// The condition as it was written.
+    pub text: String,
+
+    /// The parsed condition.
+    pub condition: Condition,
+
+    /// `false` if the assertion is disabled and not checked.
+    pub enabled: bool,
+
+    /// The number of halts where the condition was false.
+    pub failures: u32,
+}
+
+/// A assertion that was false at a halt.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssertionFailure {
+    /// The id of the assertion.
+    pub id: u32,
+
+    /// The condition as it was written.
+    pub text: String,
+}
+
+impl AssertionFailure {
+    /// Get the text used to mark the stop, e.g. `assertion failed: count < 10`.
+    pub fn stop_reason(&self) -> String {
+        format!("assertion failed: {}", self.text)
+    }
+}
+
+/// The assertions of a debug session.
+#[derive(Debug, Clone, Default)]
+pub struct Assertions {
+    /// The assertions, in the order they were added.
+    pub assertions: Vec<Assertion>,
+
+    /// The id of the next added assertion.
+    next_id: u32,
+}
+
+impl Assertions {
+    /// Creates a empty `Assertions`.
+    pub fn new() -> Assertions {
+        Assertions {
+            assertions: vec![],
+            next_id: 1,
+        }
+    }
+
+    /// Add a enabled assertion.
+    ///
+    /// Description:
+    ///
+    /// * `text` - The condition, see `Condition::parse` for the grammar.
+    ///
+    /// Will return the id of the new assertion.
+    pub fn add(&mut self, text: &str) -> Result<u32> {
+        let condition = Condition::parse(text)?;
+        let id = self.next_id.max(1);
+        self.next_id = id + 1;
+        self.assertions.push(Assertion {
+            id,
+            text: text.trim().to_string(),
+            condition,
+            enabled: true,
+            failures: 0,
+        });
+        Ok(id)
+    }
+
+    /// Remove a assertion.
+    ///
+    /// Description:
+    ///
+    /// * `id` - The id of the assertion.
+    ///
+    /// Will return the removed assertion.
+    pub fn remove(&mut self, id: u32) -> Option<Assertion> {
+        let index = self.assertions.iter().position(|a| a.id == id)?;
+        Some(self.assertions.remove(index))
+    }
+
+    /// Enable or disable a assertion.
+    ///
+    /// Description:
+    ///
+    /// * `id` - The id of the assertion.
+    /// * `enabled` - `true` to enable the assertion.
+    ///
+    /// Will return `false` if there is no assertion with the id.
+    pub fn set_enabled(&mut self, id: u32, enabled: bool) -> bool {
+        match self.assertions.iter_mut().find(|a| a.id == id) {
+            Some(assertion) => {
+                assertion.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get the names of the variables and pseudo-variables the enabled assertions read.
+    pub fn variables(&self) -> Vec<&str> {
+        self.assertions
+            .iter()
+            .filter(|a| a.enabled)
+            .flat_map(|a| a.condition.variables())
+            .collect()
+    }
+
+    /// Check the enabled assertions, this should be done every time the debug target halts.
+    ///
+    /// Description:
+    ///
+    /// * `lookup` - Returns the value of a variable or pseudo-variable, `None` if it doesn't exist.
+    /// * `mem` - A struct for accessing the memory of the debug target.
+    /// * `endian` - The endianness of the debug target.
+    ///
+    /// Will return the assertions that are false and count the failures.
+    /// A halt with failed assertions should always be reported, also when a auto-continue rule
+    /// or a breakpoint condition would resume the debug target.
+    /// Assertions that can not be evaluated, e.g. because a variable is not in scope, are skipped.
+    pub fn check<R: Reader<Offset = usize>, F, M: MemoryAccess>(
+        &mut self,
+        lookup: &mut F,
+        mem: &mut M,
+        endian: RunTimeEndian,
+    ) -> Vec<AssertionFailure>
+    where
+        F: FnMut(&str) -> Result<Option<EvaluatorValue<R>>>,
+    {
+        let mut result = vec![];
+        for assertion in self.assertions.iter_mut().filter(|a| a.enabled) {
+            match evaluate_condition(&assertion.condition, lookup, mem, endian) {
+                Ok(true) => (),
+                Ok(false) => {
+                    info!("Assertion {} failed: {}", assertion.id, assertion.text);
+                    assertion.failures += 1;
+                    result.push(AssertionFailure {
+                        id: assertion.id,
+                        text: assertion.text.clone(),
+                    });
+                }
+                Err(err) => trace!("Skipped assertion {}, because: {:?}", assertion.id, err),
+            };
+        }
+        result
+    }
+}
@@ -0,0 +1,183 @@
+use crate::breakpoints::BreakpointHalt;
+use anyhow::{anyhow, Result};
+use log::error;
+use std::ops::Range;
+
+/// The condition of a auto-continue rule.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AutoContinueCondition {
+    /// The program counter is in the address range, e.g. a idle loop with `wfi` or `wfe`.
+    PcRange(Range<u32>),
+
+    /// The debug target halted on a `bkpt` instruction compiled into the firmware, with the
+    /// immediate of the instruction or `None` for all immediates, e.g. `bkpt`-based tracing.
+    FirmwareBreakpoint(Option<u8>),
+
+    /// The debug target halted on the debugger breakpoint with the id.
+    Breakpoint(u32),
+}
+
+impl AutoContinueCondition {
+    /// Will return `true` if a halt matches the condition.
+    ///
+    /// Description:
+    ///
+    /// * `pc` - The program counter at the halt.
+    /// * `halt` - The classified breakpoint halt, `None` if the halt was not caused by a
+    ///   breakpoint.
+    pub fn matches(&self, pc: u32, halt: Option<BreakpointHalt>) -> bool {
+        match self {
+            AutoContinueCondition::PcRange(range) => range.contains(&pc),
+            AutoContinueCondition::FirmwareBreakpoint(None) => {
+                matches!(halt, Some(BreakpointHalt::Firmware { .. }))
+            }
+            AutoContinueCondition::FirmwareBreakpoint(Some(immediate)) => match halt {
+                Some(BreakpointHalt::Firmware { immediate: val, .. }) => val == *immediate,
+                _ => false,
+            },
+            AutoContinueCondition::Breakpoint(id) => halt == Some(BreakpointHalt::Debugger(*id)),
+        }
+    }
+}
+
+/// A rule that resumes the debug target without reporting the halt.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutoContinueRule {
+    /// The name of the rule.
+    pub name: String,
+
+    /// The condition of the rule.
+    pub condition: AutoContinueCondition,
+
+    /// `false` if the rule is disabled.
+    pub enabled: bool,
+
+    /// The number of halts the rule has resumed.
+    pub hits: u64,
+}
+
+/// The auto-continue rules for known benign halts.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AutoContinueRules {
+    /// The rules, in the order they are checked.
+    pub rules: Vec<AutoContinueRule>,
+}
+
+impl AutoContinueRules {
+    /// Creates a empty `AutoContinueRules`.
+    pub fn new() -> AutoContinueRules {
+        AutoContinueRules { rules: vec![] }
+    }
+
+    /// Add a enabled rule.
+    ///
+    /// Description:
+    ///
+    /// * `name` - The unique name of the rule.
+    /// * `condition` - The condition of the rule.
+    pub fn add(&mut self, name: &str, condition: AutoContinueCondition) -> Result<()> {
+        if self.rules.iter().any(|r| r.name == name) {
+            error!("A auto-continue rule named {} already exists", name);
+            return Err(anyhow!(
+                "A auto-continue rule named {} already exists",
+                name
+            ));
+        }
+
+        self.rules.push(AutoContinueRule {
+            name: name.to_string(),
+            condition,
+            enabled: true,
+            hits: 0,
+        });
+        Ok(())
+    }
+
+    /// Remove a rule.
+    ///
+    /// Description:
+    ///
+    /// * `name` - The name of the rule.
+    ///
+    /// Will return the removed rule.
+    pub fn remove(&mut self, name: &str) -> Option<AutoContinueRule> {
+        let index = self.rules.iter().position(|r| r.name == name)?;
+        Some(self.rules.remove(index))
+    }
+
+    /// Enable or disable a rule.
+    ///
+    /// Description:
+    ///
+    /// * `name` - The name of the rule.
+    /// * `enabled` - `true` to enable the rule.
+    ///
+    /// Will return `false` if there is no rule with the name.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.rules.iter_mut().find(|r| r.name == name) {
+            Some(rule) => {
+                rule.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Check if a halt should be resumed without reporting it, this should be done every time
+    /// the debug target halts.
+    ///
+    /// Description:
+    ///
+    /// * `pc` - The program counter at the halt.
+    /// * `halt` - The classified breakpoint halt, `None` if the halt was not caused by a
+    ///   breakpoint.
+    ///
+    /// Will return the name of the first enabled rule that matches, and count the hit.
+    /// The debug target should be resumed if a name is returned.
+    pub fn check(&mut self, pc: u32, halt: Option<BreakpointHalt>) -> Option<&str> {
+        let rule = self
+            .rules
+            .iter_mut()
+            .find(|r| r.enabled && r.condition.matches(pc, halt))?;
+        rule.hits += 1;
+        Some(&rule.name)
+    }
+
+    /// Reset the hit counters of all the rules.
+    pub fn reset_hits(&mut self) {
+        for rule in &mut self.rules {
+            rule.hits = 0;
+        }
+    }
+
+    /// Describe the rules and their hit counters, e.g. for a `info auto-continue` command.
+    ///
+    /// Every rule is one line with its name, condition, state and number of hits.
+    pub fn summary(&self) -> String {
+        let mut result = String::new();
+        for rule in &self.rules {
+            let condition = match &rule.condition {
+                AutoContinueCondition::PcRange(range) => {
+                    format!("pc in [{:#010x}, {:#010x})", range.start, range.end)
+                }
+                AutoContinueCondition::FirmwareBreakpoint(Some(immediate)) => {
+                    format!("bkpt {:#04x}", immediate)
+                }
+                AutoContinueCondition::FirmwareBreakpoint(None) => "bkpt".to_string(),
+                AutoContinueCondition::Breakpoint(id) => format!("breakpoint {}", id),
+            };
+            result = format!(
+                "{}{}: {} {} hits={}\n",
+                result,
+                rule.name,
+                condition,
+                match rule.enabled {
+                    true => "enabled",
+                    false => "disabled",
+                },
+                rule.hits
+            );
+        }
+        result
+    }
+}
@@ -0,0 +1,769 @@
+use crate::call_stack::MemoryAccess;
+use crate::registers::Architecture;
+use crate::source_path::path_ends_with;
+use crate::variable::TargetWrite;
+use anyhow::{anyhow, Result};
+use log::error;
+use std::collections::{BTreeMap, BTreeSet};
+use std::num::NonZeroU64;
+
+/// The Thumb encoding of the `bkpt` instruction, the low byte is the immediate.
+pub const BKPT_OPCODE: u16 = 0xbe00;
+
+/// A breakpoint in the breakpoint table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoint {
+    /// The id of the breakpoint.
+    pub id: u32,
+
+    /// The machine code address of the breakpoint.
+    pub address: u64,
+
+    /// The source file the breakpoint was set in, `None` for address and function breakpoints.
+    pub source_file: Option<String>,
+
+    /// The source line the breakpoint was set on.
+    pub line: Option<u64>,
+
+    /// The user tags of the breakpoint.
+    pub tags: BTreeSet<String>,
+
+    /// `false` if the breakpoint is disabled, a disabled breakpoint is never programmed.
+    pub enabled: bool,
+
+    /// `true` if the breakpoint is removed at the next halt, e.g. for run to line.
+    pub temporary: bool,
+
+    /// The condition of a conditional breakpoint, the target is resumed when it is false.
+    pub condition: Option<String>,
+}
+
+/// A selection of breakpoints used for bulk operations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BreakpointGroup<'a> {
+    /// All the breakpoints.
+    All,
+
+    /// The breakpoints set in a source file.
+    File(&'a str),
+
+    /// The breakpoints with a tag.
+    Tag(&'a str),
+}
+
+impl<'a> BreakpointGroup<'a> {
+    /// Will return `true` if the breakpoint is in the group.
+    ///
+    /// Description:
+    ///
+    /// * `breakpoint` - The breakpoint.
+    pub fn contains(&self, breakpoint: &Breakpoint) -> bool {
+        match self {
+            BreakpointGroup::All => true,
+            BreakpointGroup::File(file) => {
+                matches!(&breakpoint.source_file, Some(path) if path_ends_with(path, file))
+            }
+            BreakpointGroup::Tag(tag) => breakpoint.tags.contains(*tag),
+        }
+    }
+}
+
+/// The table of all the breakpoints, shared by all the ways of setting breakpoints.
+#[derive(Debug, Clone, Default)]
+pub struct BreakpointTable {
+    /// The breakpoints, in the order they were added.
+    pub breakpoints: Vec<Breakpoint>,
+
+    /// The id of the next added breakpoint.
+    next_id: u32,
+}
+
+impl BreakpointTable {
+    /// Creates a new empty `BreakpointTable`.
+    pub fn new() -> BreakpointTable {
+        BreakpointTable {
+            breakpoints: vec![],
+            next_id: 1,
+        }
+    }
+
+    /// Add a enabled breakpoint.
+    ///
+    /// Description:
+    ///
+    /// * `address` - The machine code address of the breakpoint.
+    /// * `source_file` - The source file the breakpoint was set in.
+    /// * `line` - The source line the breakpoint was set on.
+    ///
+    /// Will return the id of the new breakpoint.
+    pub fn add(&mut self, address: u64, source_file: Option<&str>, line: Option<u64>) -> u32 {
+        let id = self.next_id.max(1);
+        self.next_id = id + 1;
+        self.breakpoints.push(Breakpoint {
+            id,
+            address,
+            source_file: source_file.map(|f| f.to_string()),
+            line,
+            tags: BTreeSet::new(),
+            enabled: true,
+            temporary: false,
+            condition: None,
+        });
+        id
+    }
+
+    /// Add a temporary breakpoint that is removed at the next halt, used to run to a line.
+    ///
+    /// Description:
+    ///
+    /// * `address` - The machine code address of the breakpoint.
+    /// * `source_file` - The source file the breakpoint was set in.
+    /// * `line` - The source line the breakpoint was set on.
+    ///
+    /// Will return the id of the new breakpoint.
+    /// The other breakpoints are left as they are, so the prior breakpoint state is restored
+    /// when the temporary breakpoint is removed.
+    pub fn add_temporary(
+        &mut self,
+        address: u64,
+        source_file: Option<&str>,
+        line: Option<u64>,
+    ) -> u32 {
+        let id = self.add(address, source_file, line);
+        if let Some(breakpoint) = self.breakpoints.last_mut() {
+            breakpoint.temporary = true;
+        }
+        id
+    }
+
+    /// Remove the temporary breakpoints, this should be done every time the debug target halts.
+    ///
+    /// Will return the removed breakpoints.
+    pub fn remove_temporary(&mut self) -> Vec<Breakpoint> {
+        let (removed, kept) = self.breakpoints.drain(..).partition(|b| b.temporary);
+        self.breakpoints = kept;
+        removed
+    }
+
+    /// Add a tag to a breakpoint.
+    ///
+    /// Description:
+    ///
+    /// * `id` - The id of the breakpoint.
+    /// * `tag` - The tag.
+    ///
+    /// Will return `false` if there is no breakpoint with the id.
+    pub fn tag(&mut self, id: u32, tag: &str) -> bool {
+        match self.breakpoints.iter_mut().find(|b| b.id == id) {
+            Some(breakpoint) => {
+                breakpoint.tags.insert(tag.to_string());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set or clear the condition of a breakpoint.
+    ///
+    /// Description:
+    ///
+    /// * `id` - The id of the breakpoint.
+    /// * `condition` - The condition, e.g. `count > 10`, `None` makes the breakpoint
+    ///   unconditional.
+    ///
+    /// Will return `false` if there is no breakpoint with the id.
+    pub fn set_condition(&mut self, id: u32, condition: Option<&str>) -> bool {
+        match self.breakpoints.iter_mut().find(|b| b.id == id) {
+            Some(breakpoint) => {
+                breakpoint.condition = condition.map(|c| c.to_string());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get the breakpoints in a group.
+    ///
+    /// Description:
+    ///
+    /// * `group` - The group of breakpoints.
+    pub fn group(&self, group: BreakpointGroup) -> Vec<&Breakpoint> {
+        self.breakpoints
+            .iter()
+            .filter(|b| group.contains(b))
+            .collect()
+    }
+
+    /// Enable or disable all the breakpoints in a group.
+    ///
+    /// Description:
+    ///
+    /// * `group` - The group of breakpoints.
+    /// * `enabled` - `true` to enable the breakpoints, `false` to disable them.
+    ///
+    /// Will return the number of breakpoints in the group.
+    pub fn set_enabled(&mut self, group: BreakpointGroup, enabled: bool) -> usize {
+        let mut count = 0;
+        for breakpoint in self.breakpoints.iter_mut().filter(|b| group.contains(b)) {
+            breakpoint.enabled = enabled;
+            count += 1;
+        }
+        count
+    }
+
+    /// Delete all the breakpoints in a group.
+    ///
+    /// Description:
+    ///
+    /// * `group` - The group of breakpoints.
+    ///
+    /// Will return the deleted breakpoints.
+    pub fn delete(&mut self, group: BreakpointGroup) -> Vec<Breakpoint> {
+        let (deleted, kept) = self.breakpoints.drain(..).partition(|b| group.contains(b));
+        self.breakpoints = kept;
+        deleted
+    }
+
+    /// Get the addresses of the enabled breakpoints, which are the ones that should be
+    /// programmed.
+    pub fn enabled_addresses(&self) -> Vec<u64> {
+        self.breakpoints
+            .iter()
+            .filter(|b| b.enabled)
+            .map(|b| b.address)
+            .collect()
+    }
+}
+
+/// The cause of a breakpoint halt.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BreakpointHalt {
+    /// A breakpoint set by the debugger, with the id of the breakpoint.
+    Debugger(u32),
+
+    /// A breakpoint instruction compiled into the firmware, e.g. `cortex_m::asm::bkpt`.
+    Firmware {
+        /// The immediate of the instruction, always `0` on RISC-V.
+        immediate: u8,
+
+        /// The size of the instruction in bytes.
+        size: u32,
+    },
+
+    /// The breakpoint is not known and the instruction at the program counter is not a
+    /// breakpoint instruction.
+    Unknown,
+}
+
+impl BreakpointHalt {
+    /// Get the stop reason to report for the halt.
+    pub fn stop_reason(&self) -> &'static str {
+        match self {
+            BreakpointHalt::Debugger(_) => "breakpoint",
+            BreakpointHalt::Firmware { .. } => "instruction breakpoint in firmware",
+            BreakpointHalt::Unknown => "unknown breakpoint",
+        }
+    }
+}
+
+/// The behavior when the debug target halts on a breakpoint instruction compiled into the
+/// firmware.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FirmwareBreakpointConfig {
+    /// `true` if the breakpoint instructions are stepped over and the debug target resumed
+    /// without stopping.
+    pub step_over: bool,
+
+    /// The immediates that always stop, even if `step_over` is set, e.g. the immediate used by
+    /// the panic handler.
+    pub always_stop: BTreeSet<u8>,
+}
+
+impl FirmwareBreakpointConfig {
+    /// Get the address to resume the debug target at after a halt.
+    ///
+    /// Description:
+    ///
+    /// * `halt` - The classified breakpoint halt.
+    /// * `pc` - The current program counter.
+    ///
+    /// Will return `None` if the halt should be reported, otherwise the address after the
+    /// breakpoint instruction.
+    /// Resuming at the same address would only hit the breakpoint instruction again.
+    pub fn resume_address(&self, halt: BreakpointHalt, pc: u32) -> Option<u32> {
+        match halt {
+            BreakpointHalt::Firmware { immediate, size }
+                if self.step_over && !self.always_stop.contains(&immediate) =>
+            {
+                pc.checked_add(size)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Find the cause of a breakpoint halt.
+///
+/// Description:
+///
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `pc` - The current program counter.
+/// * `table` - The breakpoint table.
+/// * `architecture` - The architecture of the debug target, it decides the breakpoint
+///   instructions.
+///
+/// A enabled breakpoint in the table at the program counter is a debugger breakpoint, otherwise
+/// the instruction at the program counter is read to find out if it is a breakpoint instruction.
+pub fn classify_breakpoint_halt<M: MemoryAccess>(
+    mem: &mut M,
+    pc: u32,
+    table: &BreakpointTable,
+    architecture: Architecture,
+) -> BreakpointHalt {
+    if let Some(breakpoint) = table
+        .breakpoints
+        .iter()
+        .find(|b| b.enabled && b.address == pc as u64)
+    {
+        return BreakpointHalt::Debugger(breakpoint.id);
+    }
+
+    // A 2 byte instruction can be at the end of the memory, where 4 bytes can not be read.
+    let bytes = match mem.get_address(&pc, 4) {
+        Some(val) => Some(val),
+        None => mem.get_address(&pc, 2),
+    };
+    match bytes.and_then(|bytes| architecture.decode_breakpoint(&bytes)) {
+        Some((immediate, size)) => BreakpointHalt::Firmware { immediate, size },
+        None => BreakpointHalt::Unknown,
+    }
+}
+
+/// The breakpoints that should be programmed into the hardware comparators at a halt.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RotationPlan {
+    /// The breakpoint addresses that are programmed into the comparators.
+    pub active: Vec<u64>,
+
+    /// The breakpoint addresses that are not programmed, because there are not enough
+    /// comparators.
+    pub inactive: Vec<u64>,
+}
+
+impl RotationPlan {
+    /// Get the changes needed to go from the currently programmed breakpoints to this plan.
+    ///
+    /// Description:
+    ///
+    /// * `programmed` - The breakpoint addresses that are currently programmed.
+    ///
+    /// Will return the addresses to remove and the addresses to add, in that order.
+    pub fn changes(&self, programmed: &[u64]) -> (Vec<u64>, Vec<u64>) {
+        let remove = programmed
+            .iter()
+            .filter(|a| !self.active.contains(a))
+            .copied()
+            .collect();
+        let add = self
+            .active
+            .iter()
+            .filter(|a| !programmed.contains(a))
+            .copied()
+            .collect();
+        (remove, add)
+    }
+}
+
+/// Select which breakpoints to program when there are more breakpoints than hardware
+/// comparators, e.g. the Cortex-M FPB unit.
+///
+/// Description:
+///
+/// * `addresses` - The addresses of all the breakpoints.
+/// * `pc` - The current program counter.
+/// * `comparators` - The number of hardware comparators.
+///
+/// The breakpoints nearest to the program counter are programmed.
+/// Calling this on every halt and re-programming the comparators makes all the breakpoints work,
+/// as long as the program does not jump past a breakpoint that is not programmed.
+pub fn plan_rotation(addresses: &[u64], pc: u64, comparators: usize) -> RotationPlan {
+    let mut sorted = addresses.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    sorted.sort_by_key(|address| (address.abs_diff(pc), *address));
+
+    let split = comparators.min(sorted.len());
+    let inactive = sorted.split_off(split);
+    RotationPlan {
+        active: sorted,
+        inactive,
+    }
+}
+
+/// How a breakpoint is implemented on the debug target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BreakpointKind {
+    /// The breakpoint is programmed into a hardware comparator.
+    Hardware,
+
+    /// The instruction at the breakpoint is replaced with a breakpoint instruction.
+    Software,
+}
+
+/// A write that patches the code in the memory of the debug target, e.g. a breakpoint
+/// instruction or the original instruction it replaced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryWrite {
+    /// The address of the first byte.
+    pub address: u32,
+
+    /// The bytes that are written.
+    pub bytes: Vec<u8>,
+}
+
+/// Perform the writes that patch the code.
+///
+/// Description:
+///
+/// * `target` - A struct for writing to the debug target, code in flash requires a
+///   `TargetWrite` that can program the flash.
+/// * `writes` - The writes returned by `SoftwareBreakpoints`.
+pub fn apply_memory_writes<W: TargetWrite>(target: &mut W, writes: &[MemoryWrite]) -> Result<()> {
+    for write in writes {
+        if target.write_memory(write.address, &write.bytes).is_none() {
+            error!("Could not write {:?}", write);
+            return Err(anyhow!("Could not write {:?}", write));
+        }
+    }
+    Ok(())
+}
+
+/// Keeps track of the breakpoints that do not fit in the hardware comparators and are instead
+/// patched into the code as breakpoint instructions.
+///
+/// The patches are inserted when the target is resumed and removed when it halts, so the memory
+/// read while halted is always the original code.
+/// To resume from a software breakpoint, the target is first stepped one instruction without the
+/// patches and then resumed with them.
+#[derive(Debug, Clone, Default)]
+pub struct SoftwareBreakpoints {
+    /// The number of hardware comparators.
+    pub comparators: usize,
+
+    /// The addresses of the breakpoints that use a hardware comparator.
+    pub hardware: BTreeSet<u32>,
+
+    /// The addresses of the software breakpoints and the original instruction bytes.
+    pub software: BTreeMap<u32, Vec<u8>>,
+
+    /// `true` if the breakpoint instructions are currently written to the target.
+    pub inserted: bool,
+
+    /// The architecture of the debug target, it decides the breakpoint instructions.
+    pub architecture: Architecture,
+}
+
+impl SoftwareBreakpoints {
+    /// Creates a new `SoftwareBreakpoints` without any breakpoints.
+    ///
+    /// Description:
+    ///
+    /// * `comparators` - The number of hardware comparators, which are used before any code is
+    ///   patched.
+    pub fn new(comparators: usize) -> SoftwareBreakpoints {
+        SoftwareBreakpoints {
+            comparators,
+            hardware: BTreeSet::new(),
+            software: BTreeMap::new(),
+            inserted: false,
+            architecture: Architecture::default(),
+        }
+    }
+
+    /// Creates a new `SoftwareBreakpoints` without any breakpoints for a architecture.
+    ///
+    /// Description:
+    ///
+    /// * `comparators` - The number of hardware comparators, which are used before any code is
+    ///   patched.
+    /// * `architecture` - The architecture of the debug target, it decides the breakpoint
+    ///   instruction.
+    pub fn for_architecture(comparators: usize, architecture: Architecture) -> SoftwareBreakpoints {
+        SoftwareBreakpoints {
+            architecture,
+            ..SoftwareBreakpoints::new(comparators)
+        }
+    }
+
+    /// Add a breakpoint, a hardware comparator is used if one is free.
+    ///
+    /// Description:
+    ///
+    /// * `address` - The address of the breakpoint.
+    /// * `mem` - A struct for accessing the memory of the debug target, used to save the original
+    ///   instruction.
+    ///
+    /// Only the bytes that the breakpoint instruction replaces are saved, see
+    /// `Architecture::breakpoint_instruction`.
+    /// Should only be called while the patches are removed.
+    pub fn add<M: MemoryAccess>(&mut self, address: u32, mem: &mut M) -> Result<BreakpointKind> {
+        let address = address & !1;
+        if self.hardware.contains(&address) {
+            return Ok(BreakpointKind::Hardware);
+        }
+        if self.software.contains_key(&address) {
+            return Ok(BreakpointKind::Software);
+        }
+        if self.inserted {
+            error!("Software breakpoints can only be added while the target is halted");
+            return Err(anyhow!(
+                "Software breakpoints can only be added while the target is halted"
+            ));
+        }
+
+        if self.hardware.len() < self.comparators {
+            self.hardware.insert(address);
+            return Ok(BreakpointKind::Hardware);
+        }
+
+        // A 2 byte instruction can be at the end of the memory, where 4 bytes can not be read.
+        let bytes = match mem.get_address(&address, 4) {
+            Some(val) => Some(val),
+            None => mem.get_address(&address, 2),
+        };
+        match bytes {
+            Some(mut bytes) if bytes.len() >= 2 => {
+                let size = self.architecture.breakpoint_instruction(&bytes).len();
+                if bytes.len() < size {
+                    error!("Could not read the instruction at {:#010x}", address);
+                    return Err(anyhow!(
+                        "Could not read the instruction at {:#010x}",
+                        address
+                    ));
+                }
+                bytes.truncate(size);
+                self.software.insert(address, bytes);
+                Ok(BreakpointKind::Software)
+            }
+            _ => {
+                error!("Could not read the instruction at {:#010x}", address);
+                Err(anyhow!(
+                    "Could not read the instruction at {:#010x}",
+                    address
+                ))
+            }
+        }
+    }
+
+    /// Remove a breakpoint.
+    ///
+    /// Description:
+    ///
+    /// * `address` - The address of the breakpoint.
+    ///
+    /// Will return the write that restores the original instruction if the patch is inserted.
+    pub fn remove(&mut self, address: u32) -> Vec<MemoryWrite> {
+        let address = address & !1;
+        self.hardware.remove(&address);
+        match self.software.remove(&address) {
+            Some(original) if self.inserted => vec![MemoryWrite {
+                address,
+                bytes: original,
+            }],
+            _ => vec![],
+        }
+    }
+
+    /// Will return `true` if there is a software breakpoint at a address, e.g. the program
+    /// counter before resuming.
+    ///
+    /// Description:
+    ///
+    /// * `address` - The address.
+    pub fn is_software(&self, address: u32) -> bool {
+        self.software.contains_key(&(address & !1))
+    }
+
+    /// Get the writes that insert the breakpoint instructions, before the target is resumed.
+    ///
+    /// The writes can be performed with `apply_memory_writes`.
+    pub fn insert_writes(&mut self) -> Vec<MemoryWrite> {
+        if self.inserted {
+            return vec![];
+        }
+        self.inserted = true;
+        self.software
+            .iter()
+            .map(|(address, original)| MemoryWrite {
+                address: *address,
+                bytes: self.architecture.breakpoint_instruction(original).to_vec(),
+            })
+            .collect()
+    }
+
+    /// Get the writes that restore the original instructions, after the target halted.
+    pub fn remove_writes(&mut self) -> Vec<MemoryWrite> {
+        if !self.inserted {
+            return vec![];
+        }
+        self.inserted = false;
+        self.software
+            .iter()
+            .map(|(address, original)| MemoryWrite {
+                address: *address,
+                bytes: original.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Parse a source location on the form `<file>:<line>`, e.g. the argument of `until`.
+///
+/// Description:
+///
+/// * `location` - The source location.
+///
+/// Will return `None` if the location is not on the form `<file>:<line>`.
+pub fn parse_source_location(location: &str) -> Option<(&str, NonZeroU64)> {
+    let (file, line) = location.trim().rsplit_once(':')?;
+    if file.is_empty() {
+        return None;
+    }
+    Some((file, line.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A memory with a instruction at `0x100` and `0x104`.
+    fn memory(
+        first: &'static [u8],
+        second: &'static [u8],
+    ) -> impl FnMut(&u32, usize) -> Option<Vec<u8>> {
+        move |address: &u32, num_bytes: usize| {
+            let bytes = match address {
+                0x100 => first,
+                0x104 => second,
+                _ => return None,
+            };
+            Some(bytes.get(..num_bytes)?.to_vec())
+        }
+    }
+
+    #[test]
+    fn patch_and_restore() {
+        let mut mem = memory(&[0x01, 0x20, 0x00, 0xbf], &[0x70, 0x47, 0x00, 0xbf]);
+        let mut breakpoints = SoftwareBreakpoints::new(1);
+        assert_eq!(
+            breakpoints.add(0x100, &mut mem).unwrap(),
+            BreakpointKind::Hardware
+        );
+        // The Thumb bit of the address is removed.
+        assert_eq!(
+            breakpoints.add(0x105, &mut mem).unwrap(),
+            BreakpointKind::Software
+        );
+        assert!(breakpoints.is_software(0x104));
+
+        let inserted = vec![MemoryWrite {
+            address: 0x104,
+            bytes: vec![0x00, 0xbe],
+        }];
+        assert_eq!(breakpoints.insert_writes(), inserted);
+        assert_eq!(breakpoints.insert_writes(), vec![]);
+        assert!(breakpoints.add(0x108, &mut mem).is_err());
+
+        let restored = vec![MemoryWrite {
+            address: 0x104,
+            bytes: vec![0x70, 0x47],
+        }];
+        assert_eq!(breakpoints.remove_writes(), restored);
+        assert_eq!(breakpoints.remove_writes(), vec![]);
+
+        // A removed breakpoint is restored at once if the patch is inserted.
+        breakpoints.insert_writes();
+        assert_eq!(breakpoints.remove(0x104), restored);
+        assert_eq!(breakpoints.remove_writes(), vec![]);
+    }
+
+    #[test]
+    fn patch_compressed_instructions() {
+        // `c.li a0, 0` is compressed and `addi a0, a0, 1` is not.
+        let mut mem = memory(&[0x01, 0x45, 0x05, 0x05], &[0x13, 0x05, 0x15, 0x00]);
+        let architecture = Architecture::RiscV32 { compressed: true };
+        let mut breakpoints = SoftwareBreakpoints::for_architecture(0, architecture);
+        breakpoints.add(0x100, &mut mem).unwrap();
+        breakpoints.add(0x104, &mut mem).unwrap();
+        assert_eq!(
+            breakpoints.insert_writes(),
+            vec![
+                MemoryWrite {
+                    address: 0x100,
+                    bytes: vec![0x02, 0x90],
+                },
+                MemoryWrite {
+                    address: 0x104,
+                    bytes: vec![0x73, 0x00, 0x10, 0x00],
+                },
+            ]
+        );
+        assert_eq!(
+            breakpoints.remove_writes(),
+            vec![
+                MemoryWrite {
+                    address: 0x100,
+                    bytes: vec![0x01, 0x45],
+                },
+                MemoryWrite {
+                    address: 0x104,
+                    bytes: vec![0x13, 0x05, 0x15, 0x00],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rotate_nearest_breakpoints() {
+        let plan = plan_rotation(&[0x100, 0x300, 0x200, 0x200], 0x210, 2);
+        assert_eq!(plan.active, vec![0x200, 0x300]);
+        assert_eq!(plan.inactive, vec![0x100]);
+        assert_eq!(plan.changes(&[0x100, 0x200]), (vec![0x100], vec![0x300]));
+
+        let plan = plan_rotation(&[0x100], 0, 4);
+        assert_eq!(plan.active, vec![0x100]);
+        assert!(plan.inactive.is_empty());
+    }
+
+    #[test]
+    fn classify_halts() {
+        let mut table = BreakpointTable::new();
+        let id = table.add(0x100, None, None);
+        let mut mem = memory(&[0x00, 0xbe, 0x00, 0xbf], &[0xab, 0xbe, 0x00, 0xbf]);
+        let architecture = Architecture::CortexM;
+
+        let halt = classify_breakpoint_halt(&mut mem, 0x100, &table, architecture);
+        assert_eq!(halt, BreakpointHalt::Debugger(id));
+        let halt = classify_breakpoint_halt(&mut mem, 0x104, &table, architecture);
+        assert_eq!(
+            halt,
+            BreakpointHalt::Firmware {
+                immediate: 0xab,
+                size: 2
+            }
+        );
+
+        // A disabled breakpoint is not the cause of the halt.
+        table.set_enabled(BreakpointGroup::All, false);
+        let halt = classify_breakpoint_halt(&mut mem, 0x100, &table, architecture);
+        assert_eq!(
+            halt,
+            BreakpointHalt::Firmware {
+                immediate: 0,
+                size: 2
+            }
+        );
+        let halt = classify_breakpoint_halt(&mut mem, 0x108, &table, architecture);
+        assert_eq!(halt, BreakpointHalt::Unknown);
+    }
+}
@@ -0,0 +1,869 @@
+use crate::call_stack::{
+    call_frame_registers, evaluate_frame_base, find_non_inlined_function_die, CallFrame,
+    MemoryAccess,
+};
+use crate::evaluate::attributes;
+use crate::evaluate::evaluate::EvaluatorValue;
+use crate::evaluate::evaluate_pieces;
+use crate::registers::{Architecture, Registers};
+use crate::source_information::SourceInformation;
+use crate::utils::{get_current_unit, DwarfOffset};
+use crate::variable::find_variable_type_die;
+use anyhow::{anyhow, Result};
+use gimli::{
+    AttributeValue, DebuggingInformationEntry, Dwarf, Expression, Location, Operation, Piece,
+    Reader, Unit, UnitOffset,
+};
+use log::{error, info, trace};
+
+/// Describes a call to a function, it is parsed from a `DW_TAG_call_site` DIE.
+#[derive(Debug, Clone)]
+pub struct CallSite<R: Reader<Offset = usize>> {
+    /// The offset to the call site DIE.
+    pub offset: DwarfOffset,
+
+    /// The address the called function returns to.
+    pub return_address: u64,
+
+    /// The DIE of the called function, `None` if it is not known.
+    pub origin: Option<DwarfOffset>,
+
+    /// The parameter values the function is called with.
+    pub parameters: Vec<CallSiteParameter<R>>,
+}
+
+/// Describes the value of a parameter at a call site, it is parsed from a
+/// `DW_TAG_call_site_parameter` DIE.
+#[derive(Debug, Clone)]
+pub struct CallSiteParameter<R: Reader<Offset = usize>> {
+    /// The formal parameter DIE this value is passed to, if it is known.
+    pub parameter: Option<UnitOffset>,
+
+    /// The register the value is passed in, if it is passed in a register.
+    pub register: Option<u16>,
+
+    /// The expression that computes the value, it is evaluated in the frame of the caller.
+    pub value: Option<Expression<R>>,
+}
+
+/// Will find the call site that returns to the given address.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `return_address` - The address the called function returns to.
+///
+/// Both the DWARF 5 `DW_TAG_call_site` and the GNU `DW_TAG_GNU_call_site` DIEs are searched.
+/// Will return `None` if there is no call site with the given return address.
+pub fn find_call_site<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    return_address: u32,
+) -> Result<Option<CallSite<R>>> {
+    // The return address can be the first address after the calling function.
+    let unit = get_current_unit(dwarf, return_address.saturating_sub(1))?;
+    let mut cursor = unit.entries();
+    while let Some((_, die)) = cursor.next_dfs()? {
+        if !is_call_site_die(die) {
+            continue;
+        }
+
+        if call_site_return_address(die)? == Some(return_address as u64) {
+            return Ok(Some(parse_call_site(dwarf, &unit, die)?));
+        }
+    }
+
+    trace!(
+        "No call site found for return address {:#010x}",
+        return_address
+    );
+    Ok(None)
+}
+
+/// Will check if the given DIE is a call site DIE.
+///
+/// Description:
+///
+/// * `die` - A reference to DIE.
+pub fn is_call_site_die<R: Reader<Offset = usize>>(die: &DebuggingInformationEntry<R>) -> bool {
+    matches!(
+        die.tag(),
+        gimli::DW_TAG_call_site | gimli::DW_TAG_GNU_call_site
+    )
+}
+
+/// Will parse a call site DIE and its parameter DIEs.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `unit` - A reference to gimli-rs `Unit` struct which contains the given DIE.
+/// * `die` - A reference to the call site DIE.
+pub fn parse_call_site<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    unit: &Unit<R>,
+    die: &DebuggingInformationEntry<R>,
+) -> Result<CallSite<R>> {
+    let return_address = match call_site_return_address(die)? {
+        Some(val) => val,
+        None => {
+            error!("Call site is missing the return address");
+            return Err(anyhow!("Call site is missing the return address"));
+        }
+    };
+
+    let origin = match die.attr_value(gimli::DW_AT_call_origin)? {
+        Some(val) => Some(val),
+        None => die.attr_value(gimli::DW_AT_abstract_origin)?,
+    };
+    let origin = match origin {
+        Some(val) => resolve_reference(dwarf, unit, val)?,
+        None => None,
+    };
+
+    let mut parameters = vec![];
+    let mut tree = unit.entries_tree(Some(die.offset()))?;
+    let mut children = tree.root()?.children();
+    while let Some(child) = children.next()? {
+        let die = child.entry();
+        match die.tag() {
+            gimli::DW_TAG_call_site_parameter | gimli::DW_TAG_GNU_call_site_parameter => (),
+            _ => continue,
+        };
+
+        let parameter = match die.attr_value(gimli::DW_AT_call_parameter)? {
+            Some(AttributeValue::UnitRef(offset)) => Some(offset),
+            _ => match die.attr_value(gimli::DW_AT_abstract_origin)? {
+                Some(AttributeValue::UnitRef(offset)) => Some(offset),
+                _ => None,
+            },
+        };
+
+        let register = match die.attr_value(gimli::DW_AT_location)? {
+            Some(AttributeValue::Exprloc(expr)) => expression_register(unit, expr),
+            _ => None,
+        };
+
+        let value = match die.attr_value(gimli::DW_AT_call_value)? {
+            Some(AttributeValue::Exprloc(expr)) => Some(expr),
+            _ => match die.attr_value(gimli::DW_AT_GNU_call_site_value)? {
+                Some(AttributeValue::Exprloc(expr)) => Some(expr),
+                _ => None,
+            },
+        };
+
+        parameters.push(CallSiteParameter {
+            parameter,
+            register,
+            value,
+        });
+    }
+
+    Ok(CallSite {
+        offset: DwarfOffset {
+            section_offset: unit.header.offset(),
+            unit_offset: die.offset(),
+        },
+        return_address,
+        origin,
+        parameters,
+    })
+}
+
+/// Will try to recover the value of a parameter using the call site in the caller frame.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `parameter` - The offset to the formal parameter DIE.
+/// * `caller_frame` - The call frame of the calling function.
+/// * `registers` - A register struct containing the register numbers of the architecture.
+/// * `mem` - A struct for accessing the memory of the debug target.
+///
+/// The call site parameter is matched to the formal parameter by `DW_AT_call_parameter`, or by
+/// the register the parameter is passed in.
+/// Its `DW_AT_call_value` expression is then evaluated with the register values of the caller
+/// frame.
+/// Will return `None` if the value could not be recovered.
+pub fn recover_parameter<R: Reader<Offset = usize>, M: MemoryAccess>(
+    dwarf: &Dwarf<R>,
+    parameter: DwarfOffset,
+    caller_frame: &CallFrame,
+    registers: &Registers,
+    mem: &mut M,
+) -> Result<Option<EvaluatorValue<R>>> {
+    let call_site = match find_caller_call_site(dwarf, caller_frame, registers.architecture)? {
+        Some(val) => val,
+        None => return Ok(None),
+    };
+
+    let header = dwarf.debug_info.header_from_offset(
+        match parameter.section_offset.as_debug_info_offset() {
+            Some(val) => val,
+            None => {
+                error!("Could not convert section offset into debug info offset");
+                return Err(anyhow!(
+                    "Could not convert section offset into debug info offset"
+                ));
+            }
+        },
+    )?;
+    let unit = dwarf.unit(header)?;
+    let die = unit.entry(parameter.unit_offset)?;
+    let origin = match die.attr_value(gimli::DW_AT_abstract_origin)? {
+        Some(AttributeValue::UnitRef(offset)) => Some(offset),
+        _ => None,
+    };
+    let register = parameter_entry_register(dwarf, &unit, &die)?;
+
+    let same_unit = call_site.offset.section_offset == parameter.section_offset;
+    let call_site_parameter = call_site.parameters.iter().find(|p| match p.parameter {
+        Some(offset)
+            if same_unit && (offset == parameter.unit_offset || Some(offset) == origin) =>
+        {
+            true
+        }
+        _ => p.register.is_some() && p.register == register,
+    });
+    let expr = match call_site_parameter.and_then(|p| p.value.clone()) {
+        Some(val) => val,
+        None => return Ok(None),
+    };
+    let (caller_registers, pieces) =
+        match evaluate_call_site_value(dwarf, &call_site, expr, caller_frame, registers, mem)? {
+            Some(val) => val,
+            None => return Ok(None),
+        };
+
+    let (type_section_offset, type_unit_offset) = find_variable_type_die(dwarf, &unit, &die)?;
+    let value = EvaluatorValue::evaluate_variable_with_type(
+        dwarf,
+        &caller_registers,
+        mem,
+        &pieces,
+        type_section_offset,
+        type_unit_offset,
+    )?;
+
+    Ok(Some(value))
+}
+
+/// Will recover the register values at the entry of a function from the call site in the caller
+/// frame.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `frame_registers` - The register values of the called frame, see `call_frame_registers`.
+/// * `caller_frame` - The call frame of the calling function.
+/// * `mem` - A struct for accessing the memory of the debug target.
+///
+/// The argument registers are not preserved over a call, so the CFI can not recover their entry
+/// values, but the call site parameters describe the values that were passed in them.
+/// The recovered values are added to the entry registers of `frame_registers`, which are used
+/// to evaluate `DW_OP_entry_value` expressions.
+pub fn recover_entry_registers<R: Reader<Offset = usize>, M: MemoryAccess>(
+    dwarf: &Dwarf<R>,
+    frame_registers: &mut Registers,
+    caller_frame: &CallFrame,
+    mem: &mut M,
+) -> Result<()> {
+    let architecture = frame_registers.architecture;
+    let call_site = match find_caller_call_site(dwarf, caller_frame, architecture)? {
+        Some(val) => val,
+        None => return Ok(()),
+    };
+
+    let mut entry_registers = frame_registers.entry_registers.clone().unwrap_or_default();
+    for parameter in &call_site.parameters {
+        let (register, expr) = match (parameter.register, &parameter.value) {
+            (Some(register), Some(expr)) if !entry_registers.contains_key(&register) => {
+                (register, expr.clone())
+            }
+            _ => continue,
+        };
+        let (caller_registers, pieces) = match evaluate_call_site_value(
+            dwarf,
+            &call_site,
+            expr,
+            caller_frame,
+            frame_registers,
+            mem,
+        )? {
+            Some(val) => val,
+            None => continue,
+        };
+
+        // Only a value that fits in the register can have been passed in it.
+        let value = match pieces.as_slice() {
+            [piece] => match piece.location {
+                Location::Value { value } => value.to_u64(u64::MAX).ok(),
+                Location::Register { register } => caller_registers
+                    .get_register_value(&register.0)
+                    .map(|val| *val as u64),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(value) = value {
+            entry_registers.insert(register, value as u32);
+        }
+    }
+    frame_registers.entry_registers = Some(entry_registers);
+
+    Ok(())
+}
+
+/// Will find the call site in the caller frame that called the function of the frame above it.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `caller_frame` - The call frame of the calling function.
+/// * `architecture` - The architecture of the debug target.
+///
+/// A interrupted caller frame has no call site, the frame above it is a exception handler.
+fn find_caller_call_site<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    caller_frame: &CallFrame,
+    architecture: Architecture,
+) -> Result<Option<CallSite<R>>> {
+    if caller_frame.exception_return.is_some() {
+        return Ok(None);
+    }
+    match caller_frame.continue_address(architecture) {
+        Some(return_address) => find_call_site(dwarf, return_address),
+        None => Ok(None),
+    }
+}
+
+/// Will evaluate the value expression of a call site parameter in the caller frame.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `call_site` - The call site in the caller frame.
+/// * `expr` - The `DW_AT_call_value` expression.
+/// * `caller_frame` - The call frame of the calling function.
+/// * `registers` - A register struct containing the register numbers of the architecture.
+/// * `mem` - A struct for accessing the memory of the debug target.
+///
+/// Will return the register values of the caller frame and the pieces of the value, or `None`
+/// if the value could not be evaluated.
+fn evaluate_call_site_value<R: Reader<Offset = usize>, M: MemoryAccess>(
+    dwarf: &Dwarf<R>,
+    call_site: &CallSite<R>,
+    expr: Expression<R>,
+    caller_frame: &CallFrame,
+    registers: &Registers,
+    mem: &mut M,
+) -> Result<Option<(Registers, Vec<Piece<R>>)>> {
+    // Evaluate the value in the caller frame.
+    let mut caller_registers = call_frame_registers(caller_frame, None, registers);
+    let pc = caller_frame.code_location as u32;
+
+    let caller_header = dwarf.debug_info.header_from_offset(
+        match call_site.offset.section_offset.as_debug_info_offset() {
+            Some(val) => val,
+            None => {
+                error!("Could not convert section offset into debug info offset");
+                return Err(anyhow!(
+                    "Could not convert section offset into debug info offset"
+                ));
+            }
+        },
+    )?;
+    let caller_unit = dwarf.unit(caller_header)?;
+    let frame_base = match find_non_inlined_function_die(dwarf, pc) {
+        Ok((_, offset)) => {
+            let fb_die = caller_unit.entry(offset)?;
+            evaluate_frame_base(dwarf, &caller_unit, pc, &fb_die, &mut caller_registers, mem).ok()
+        }
+        Err(_) => None,
+    };
+
+    let pieces = match evaluate_pieces(
+        dwarf,
+        &caller_unit,
+        pc,
+        expr,
+        frame_base,
+        &caller_registers,
+        mem,
+    ) {
+        Ok(val) => val,
+        Err(err) => {
+            info!("Could not evaluate call site value: {}", err);
+            return Ok(None);
+        }
+    };
+
+    // `DW_AT_call_value` describes a value and not a location, thus a address result is the
+    // value itself.
+    let pieces: Vec<Piece<R>> = pieces
+        .into_iter()
+        .map(|piece| match piece.location {
+            Location::Address { address } => Piece {
+                size_in_bits: piece.size_in_bits,
+                bit_offset: piece.bit_offset,
+                location: Location::Value {
+                    value: gimli::Value::Generic(address),
+                },
+            },
+            _ => piece,
+        })
+        .collect();
+
+    Ok(Some((caller_registers, pieces)))
+}
+
+/// Will retrieve the return address of a call site DIE.
+///
+/// Description:
+///
+/// * `die` - A reference to the call site DIE.
+fn call_site_return_address<R: Reader<Offset = usize>>(
+    die: &DebuggingInformationEntry<R>,
+) -> Result<Option<u64>> {
+    let value = match die.attr_value(gimli::DW_AT_call_return_pc)? {
+        Some(val) => Some(val),
+        // GNU call sites use the low pc attribute for the return address.
+        None => die.attr_value(gimli::DW_AT_low_pc)?,
+    };
+
+    Ok(match value {
+        Some(AttributeValue::Addr(address)) => Some(address),
+        _ => None,
+    })
+}
+
+/// Will find the register a parameter is passed in.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `unit` - A reference to gimli-rs `Unit` struct which contains the given DIE.
+/// * `die` - A reference to the formal parameter DIE.
+///
+/// The first location of the parameter is used, because it is the location at the entry of the
+/// function.
+fn parameter_entry_register<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    unit: &Unit<R>,
+    die: &DebuggingInformationEntry<R>,
+) -> Result<Option<u16>> {
+    Ok(match die.attr_value(gimli::DW_AT_location)? {
+        Some(AttributeValue::Exprloc(expr)) => expression_register(unit, expr),
+        Some(AttributeValue::LocationListsRef(offset)) => {
+            let mut locations = dwarf.locations(unit, offset)?;
+            match locations.next()? {
+                Some(llent) => expression_register(unit, llent.data),
+                None => None,
+            }
+        }
+        _ => None,
+    })
+}
+
+/// Will find the register a expression starts with.
+///
+/// Description:
+///
+/// * `unit` - A reference to gimli-rs `Unit` struct which contains the expression.
+/// * `expr` - The expression.
+///
+/// Both `DW_OP_reg*` and `DW_OP_entry_value(DW_OP_reg*)` are recognized.
+fn expression_register<R: Reader<Offset = usize>>(
+    unit: &Unit<R>,
+    expr: Expression<R>,
+) -> Option<u16> {
+    let mut operations = expr.operations(unit.encoding());
+    match operations.next() {
+        Ok(Some(Operation::Register { register })) => Some(register.0),
+        Ok(Some(Operation::EntryValue { expression })) => {
+            expression_register(unit, Expression(expression))
+        }
+        _ => None,
+    }
+}
+
+/// A call from one function to another, it is parsed from a call site DIE.
+#[derive(Debug, Clone)]
+pub struct CallGraphEdge {
+    /// The function that makes the call.
+    pub caller: DwarfOffset,
+
+    /// The function that is called, `None` if it is not known, e.g. calls through a pointer.
+    pub callee: Option<DwarfOffset>,
+
+    /// The address of the call instruction, if it is known.
+    pub call_pc: Option<u64>,
+
+    /// The address the called function returns to.
+    pub return_address: u64,
+
+    /// The source code location of the call.
+    pub source: Option<SourceInformation>,
+}
+
+/// A static call graph built from the call site DIEs.
+#[derive(Debug, Clone)]
+pub struct CallGraph {
+    /// All the calls found in the debug information.
+    pub edges: Vec<CallGraphEdge>,
+}
+
+impl CallGraph {
+    /// Build the call graph from all the compilation units.
+    ///
+    /// Description:
+    ///
+    /// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+    /// * `cwd` - The work directory of the debugged program.
+    ///
+    /// The caller of a call site is the inner most function or inlined function that contains
+    /// it.
+    /// Both callers and callees are resolved to their `DW_AT_abstract_origin` DIE, so that all
+    /// the calls to and from a function use the same offset.
+    pub fn new<R: Reader<Offset = usize>>(dwarf: &Dwarf<R>, cwd: &str) -> Result<CallGraph> {
+        let mut edges = vec![];
+        let mut units = dwarf.units();
+        while let Some(header) = units.next()? {
+            let unit = dwarf.unit(header)?;
+            add_unit_edges(dwarf, &unit, cwd, &mut edges)?;
+        }
+
+        Ok(CallGraph { edges })
+    }
+
+    /// Find all the calls to a function.
+    ///
+    /// Description:
+    ///
+    /// * `callee` - The offset to the subprogram DIE of the called function.
+    pub fn callers_of(&self, callee: DwarfOffset) -> Vec<&CallGraphEdge> {
+        self.edges
+            .iter()
+            .filter(|e| e.callee == Some(callee))
+            .collect()
+    }
+
+    /// Find all the calls made by a function.
+    ///
+    /// Description:
+    ///
+    /// * `caller` - The offset to the subprogram DIE of the calling function.
+    pub fn callees_of(&self, caller: DwarfOffset) -> Vec<&CallGraphEdge> {
+        self.edges.iter().filter(|e| e.caller == caller).collect()
+    }
+}
+
+/// A function that can be stepped into from the current source line.
+#[derive(Debug, Clone)]
+pub struct StepInTarget {
+    /// The function that is called.
+    pub callee: DwarfOffset,
+
+    /// The name of the called function.
+    pub name: Option<String>,
+
+    /// The first machine code address of the called function, a breakpoint placed there will
+    /// stop the step in the chosen function.
+    pub entry_address: Option<u64>,
+
+    /// The addresses the calls to the function returns to.
+    pub return_addresses: Vec<u64>,
+}
+
+/// Will find all the functions that are called from the source line of a address.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `pc` - A machine code address, usually the current program counter value.
+/// * `cwd` - The work directory of the debugged program.
+///
+/// The call site DIEs located on the same source line as `pc` are used to find the called
+/// functions.
+/// Each called function is only listed once, in the order they are called, and calls through
+/// function pointers are not included.
+pub fn find_step_in_targets<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    pc: u32,
+    cwd: &str,
+) -> Result<Vec<StepInTarget>> {
+    let current = SourceInformation::get_from_address(dwarf, pc as u64, cwd)?;
+    if current.line.is_none() {
+        return Ok(vec![]);
+    }
+
+    let unit = get_current_unit(dwarf, pc)?;
+    let mut edges = vec![];
+    add_unit_edges(dwarf, &unit, cwd, &mut edges)?;
+    edges.retain(|e| match &e.source {
+        Some(source) => {
+            source.file == current.file
+                && source.directory == current.directory
+                && source.line == current.line
+        }
+        None => false,
+    });
+    edges.sort_by_key(|e| e.return_address);
+
+    let mut targets: Vec<StepInTarget> = vec![];
+    for edge in edges {
+        let callee = match edge.callee {
+            Some(val) => val,
+            None => continue,
+        };
+
+        if let Some(target) = targets.iter_mut().find(|t| t.callee == callee) {
+            target.return_addresses.push(edge.return_address);
+            continue;
+        }
+
+        let header = dwarf.debug_info.header_from_offset(
+            match callee.section_offset.as_debug_info_offset() {
+                Some(val) => val,
+                None => {
+                    error!("Could not convert section offset into debug info offset");
+                    return Err(anyhow!(
+                        "Could not convert section offset into debug info offset"
+                    ));
+                }
+            },
+        )?;
+        let callee_unit = dwarf.unit(header)?;
+        let die = callee_unit.entry(callee.unit_offset)?;
+        let entry_address = match die.attr_value(gimli::DW_AT_low_pc)? {
+            Some(AttributeValue::Addr(address)) => Some(address),
+            _ => None,
+        };
+
+        targets.push(StepInTarget {
+            callee,
+            name: attributes::name_attribute(dwarf, &die)?,
+            entry_address,
+            return_addresses: vec![edge.return_address],
+        });
+    }
+
+    Ok(targets)
+}
+
+/// Will add all the calls in a compilation unit to the list of call graph edges.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `unit` - A reference to gimli-rs `Unit` struct.
+/// * `cwd` - The work directory of the debugged program.
+/// * `edges` - The list the calls are added to.
+fn add_unit_edges<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    unit: &Unit<R>,
+    cwd: &str,
+    edges: &mut Vec<CallGraphEdge>,
+) -> Result<()> {
+    let mut functions: Vec<(isize, DwarfOffset)> = vec![];
+    let mut depth = 0;
+    let mut cursor = unit.entries();
+    while let Some((delta_depth, die)) = cursor.next_dfs()? {
+        depth += delta_depth;
+        while let Some((function_depth, _)) = functions.last() {
+            if *function_depth >= depth {
+                functions.pop();
+            } else {
+                break;
+            }
+        }
+
+        match die.tag() {
+            gimli::DW_TAG_subprogram | gimli::DW_TAG_inlined_subroutine => {
+                functions.push((depth, function_origin(dwarf, unit, die)?));
+            }
+            _ if is_call_site_die(die) => {
+                let caller = match functions.last() {
+                    Some((_, offset)) => *offset,
+                    None => continue,
+                };
+                let call_site = parse_call_site(dwarf, unit, die)?;
+                let call_pc = match die.attr_value(gimli::DW_AT_call_pc)? {
+                    Some(AttributeValue::Addr(address)) => Some(address),
+                    _ => None,
+                };
+                // A return address of 0 has no call instruction before it.
+                let call_address = match call_pc.or_else(|| call_site.return_address.checked_sub(1))
+                {
+                    Some(val) => val,
+                    None => continue,
+                };
+
+                let source =
+                    match SourceInformation::get_die_call_source_information(dwarf, unit, die, cwd)
+                    {
+                        Ok(source) if source.line.is_some() => Some(source),
+                        // Fall back to the line table at the call instruction.
+                        _ => SourceInformation::get_from_address(dwarf, call_address, cwd).ok(),
+                    };
+
+                edges.push(CallGraphEdge {
+                    caller,
+                    callee: call_site.origin,
+                    call_pc,
+                    return_address: call_site.return_address,
+                    source,
+                });
+            }
+            _ => (),
+        };
+    }
+
+    Ok(())
+}
+
+/// Will find the DIE that describes the origin of a function.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `unit` - A reference to gimli-rs `Unit` struct which contains the given DIE.
+/// * `die` - A reference to a subprogram or inlined subroutine DIE.
+///
+/// Will return the offset of the given DIE if it has no `DW_AT_abstract_origin` attribute.
+fn function_origin<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    unit: &Unit<R>,
+    die: &DebuggingInformationEntry<R>,
+) -> Result<DwarfOffset> {
+    let origin = match die.attr_value(gimli::DW_AT_abstract_origin)? {
+        Some(val) => resolve_reference(dwarf, unit, val)?,
+        None => None,
+    };
+
+    Ok(origin.unwrap_or(DwarfOffset {
+        section_offset: unit.header.offset(),
+        unit_offset: die.offset(),
+    }))
+}
+
+/// Will resolve a reference attribute value into the offset of the referenced DIE.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `unit` - A reference to gimli-rs `Unit` struct which contains the attribute.
+/// * `value` - The attribute value.
+///
+/// Will return `None` if the attribute value is not a reference.
+fn resolve_reference<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    unit: &Unit<R>,
+    value: AttributeValue<R>,
+) -> Result<Option<DwarfOffset>> {
+    match value {
+        AttributeValue::UnitRef(offset) => Ok(Some(DwarfOffset {
+            section_offset: unit.header.offset(),
+            unit_offset: offset,
+        })),
+        AttributeValue::DebugInfoRef(di_offset) => {
+            let offset = gimli::UnitSectionOffset::DebugInfoOffset(di_offset);
+            let mut iter = dwarf.debug_info.units();
+            while let Some(header) = iter.next()? {
+                let ref_unit = dwarf.unit(header)?;
+                if let Some(unit_offset) = offset.to_unit_offset(&ref_unit) {
+                    return Ok(Some(DwarfOffset {
+                        section_offset: ref_unit.header.offset(),
+                        unit_offset,
+                    }));
+                }
+            }
+            error!("Could not find the referenced die");
+            Err(anyhow!("Could not find the referenced die"))
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gimli::{DebugInfoOffset, EndianSlice, LittleEndian, UnitSectionOffset};
+
+    type TestReader = EndianSlice<'static, LittleEndian>;
+
+    /// A DWARF 4 compilation unit where `main` calls `callee(5)` and returns to `0x08000104`,
+    /// `callee` is at offset 12, `main` at offset 21 and the call site at offset 26.
+    fn dwarf() -> Dwarf<TestReader> {
+        static DEBUG_INFO: [u8; 43] = [
+            39, 0, 0, 0, 4, 0, 0, 0, 0, 0, 4, // Unit header.
+            1, // Compile unit.
+            2, 0, 0, 0, 0, // Subprogram `callee`.
+            3, 1, 0x50, // Formal parameter in `DW_OP_reg0`.
+            0,    // End of `callee`.
+            2, 7, 0, 0, 0, // Subprogram `main`.
+            4, 0x04, 0x01, 0x00, 0x08, 12, 0, 0, 0, // Call site of `callee`.
+            5, 1, 0x50, 1, 0x35, // Parameter in `DW_OP_reg0` with the value `DW_OP_lit5`.
+            0,    // End of the call site.
+            0,    // End of `main`.
+            0,    // End of the compile unit.
+        ];
+        static DEBUG_STR: [u8; 12] = *b"callee\0main\0";
+        static DEBUG_ABBREV: [u8; 43] = [
+            1, 0x11, 1, 0, 0, // DW_TAG_compile_unit
+            2, 0x2e, 1, 0x03, 0x0e, 0, 0, // DW_TAG_subprogram
+            3, 0x05, 0, 0x02, 0x18, 0, 0, // DW_TAG_formal_parameter
+            4, 0x89, 0x82, 0x01, 1, 0x11, 0x01, 0x31, 0x13, 0, 0, // DW_TAG_GNU_call_site
+            // DW_TAG_GNU_call_site_parameter
+            5, 0x8a, 0x82, 0x01, 0, 0x02, 0x18, 0x91, 0x42, 0x18, 0, 0, 0,
+        ];
+        Dwarf {
+            debug_info: gimli::DebugInfo::new(&DEBUG_INFO, LittleEndian),
+            debug_abbrev: gimli::DebugAbbrev::new(&DEBUG_ABBREV, LittleEndian),
+            debug_str: gimli::DebugStr::new(&DEBUG_STR, LittleEndian),
+            ..Default::default()
+        }
+    }
+
+    fn offset(unit_offset: usize) -> DwarfOffset {
+        DwarfOffset {
+            section_offset: UnitSectionOffset::DebugInfoOffset(DebugInfoOffset(0)),
+            unit_offset: UnitOffset(unit_offset),
+        }
+    }
+
+    #[test]
+    fn parse_gnu_call_site() {
+        let dwarf = dwarf();
+        let unit = dwarf.unit(dwarf.units().next().unwrap().unwrap()).unwrap();
+        let die = unit.entry(UnitOffset(26)).unwrap();
+        assert!(is_call_site_die(&die));
+
+        let call_site = parse_call_site(&dwarf, &unit, &die).unwrap();
+        assert_eq!(call_site.offset, offset(26));
+        assert_eq!(call_site.return_address, 0x0800_0104);
+        assert_eq!(call_site.origin, Some(offset(12)));
+        assert_eq!(call_site.parameters.len(), 1);
+        assert_eq!(call_site.parameters[0].register, Some(0));
+        assert_eq!(
+            call_site.parameters[0].value.map(|expr| expr.0.slice()),
+            Some(&[0x35][..])
+        );
+
+        // A subprogram is not a call site.
+        assert!(!is_call_site_die(&unit.entry(UnitOffset(21)).unwrap()));
+    }
+
+    #[test]
+    fn call_graph_edges() {
+        let graph = CallGraph::new(&dwarf(), "/").unwrap();
+        assert_eq!(graph.edges.len(), 1);
+
+        let callers = graph.callers_of(offset(12));
+        assert_eq!(callers.len(), 1);
+        assert_eq!(callers[0].caller, offset(21));
+        assert_eq!(callers[0].call_pc, None);
+        assert_eq!(callers[0].return_address, 0x0800_0104);
+        assert_eq!(graph.callees_of(offset(21)).len(), 1);
+        assert!(graph.callees_of(offset(12)).is_empty());
+    }
+}
@@ -5,25 +5,36 @@
  *
  * Dwarf source: Dwarf 5 section 6.4.1
  */
+use crate::call_site::{recover_entry_registers, recover_parameter};
+use crate::dwarf_index::DwarfIndex;
 use crate::evaluate::evaluate;
 use crate::evaluate::evaluate::BaseTypeValue;
 use crate::evaluate::evaluate::EvaluatorValue;
 use crate::evaluate::evaluate::ValueInformation;
-use crate::registers::Registers;
+use crate::evaluate::evaluate_pieces;
+use crate::evaluate::expression::{evaluate_expression, Expression};
+use crate::evaluate::projection::evaluate_array_field;
+use crate::registers::{Architecture, Registers};
 use crate::source_information::SourceInformation;
+use crate::symbol_table::SymbolTable;
 use crate::utils::{die_in_range, get_current_unit, DwarfOffset};
-use crate::variable::{is_variable_die, Variable};
+use crate::variable::{
+    find_variable_location, find_variable_type_die, get_var_name, is_variable_die, Variable,
+    VariableLocation,
+};
 use anyhow::{anyhow, Result};
 use gimli::AttributeValue::DebugInfoRef;
 use gimli::AttributeValue::UnitRef;
 use gimli::DebugFrame;
 use gimli::{RegisterRule::*, UnwindSection};
 use log::{error, trace};
-use std::convert::TryInto;
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use std::ops::Range;
 
 use gimli::{
-    AttributeValue::DebugStrRef, DebuggingInformationEntry, Dwarf, EntriesTreeNode, Reader, Unit,
-    UnitOffset, UnitSectionOffset,
+    AttributeValue::DebugStrRef, DebuggingInformationEntry, Dwarf, EntriesTreeNode, Reader,
+    RunTimeEndian, Unit, UnitOffset, UnitSectionOffset,
 };
 
 /// A trait used for reading memory of the debug target.
@@ -42,6 +53,191 @@ pub trait MemoryAccess {
     fn get_address(&mut self, address: &u32, num_bytes: usize) -> Option<Vec<u8>>;
 }
 
+/// Makes it possible to use a closure for reading the memory of the debug target.
+///
+/// This allows the memory reads of the evaluation and unwinding functions to be done by a
+/// closure, instead of having to implement `MemoryAccess` for a new struct.
+/// The register values are read with `Registers::read_registers`, which also takes a closure.
+impl<F> MemoryAccess for F
+where
+    F: FnMut(&u32, usize) -> Option<Vec<u8>>,
+{
+    fn get_address(&mut self, address: &u32, num_bytes: usize) -> Option<Vec<u8>> {
+        self(address, num_bytes)
+    }
+}
+
+/// A snapshot of a range of the memory of the debug target.
+///
+/// The range is read once, reads inside the range are served from the snapshot and reads
+/// outside of it are forwarded to the wrapped memory.
+/// This is used to evaluate values mapped over read sensitive peripheral registers, without
+/// reading the registers once per member.
+pub struct MemorySnapshot<'a, M: MemoryAccess> {
+    /// The address of the first byte in the snapshot.
+    pub address: u32,
+
+    /// The bytes of the snapshot.
+    pub data: Vec<u8>,
+
+    /// The memory that the reads outside the snapshot are forwarded to.
+    memory: &'a mut M,
+}
+
+impl<'a, M: MemoryAccess> MemorySnapshot<'a, M> {
+    /// Creates a new `MemorySnapshot` by reading a range of the memory.
+    ///
+    /// Description:
+    ///
+    /// * `memory` - Used to read the memory of the debugged target.
+    /// * `address` - The address of the first byte in the range.
+    /// * `num_bytes` - The size of the range in bytes.
+    ///
+    /// Will return `None` if the range could not be read.
+    pub fn new(memory: &'a mut M, address: u32, num_bytes: usize) -> Option<MemorySnapshot<'a, M>> {
+        let data = memory.get_address(&address, num_bytes)?;
+        Some(MemorySnapshot {
+            address,
+            data,
+            memory,
+        })
+    }
+}
+
+impl<'a, M: MemoryAccess> MemoryAccess for MemorySnapshot<'a, M> {
+    fn get_address(&mut self, address: &u32, num_bytes: usize) -> Option<Vec<u8>> {
+        if *address >= self.address {
+            let start = (*address - self.address) as usize;
+            if let Some(bytes) = self.data.get(start..start + num_bytes) {
+                return Some(bytes.to_vec());
+            }
+        }
+
+        self.memory.get_address(address, num_bytes)
+    }
+}
+
+/// The default number of bytes read at once by `ReadAheadMemory`.
+pub const READ_AHEAD_BLOCK_SIZE: usize = 64;
+
+/// A read-ahead cache of the memory of the debug target.
+///
+/// Reads are rounded out to aligned blocks and the blocks are kept until the cache is cleared,
+/// which coalesces the reads of adjacent struct members into one read.
+/// Only blocks inside the given RAM ranges are read ahead, since reading outside the requested
+/// bytes can fail or have side effects in e.g. peripheral registers, the other reads are
+/// forwarded as they are.
+/// The cache must be cleared every time the debug target has been running.
+pub struct ReadAheadMemory<'a, M: MemoryAccess> {
+    /// The number of bytes in a block.
+    pub block_size: usize,
+
+    /// The address ranges of the RAM that can be read ahead.
+    ram_ranges: &'a [Range<u32>],
+
+    /// The cached blocks, by their address.
+    blocks: HashMap<u32, Vec<u8>>,
+
+    /// The number of reads forwarded to the wrapped memory.
+    pub transactions: u64,
+
+    /// The memory that the reads are forwarded to.
+    memory: &'a mut M,
+}
+
+impl<'a, M: MemoryAccess> ReadAheadMemory<'a, M> {
+    /// Creates a new empty `ReadAheadMemory`.
+    ///
+    /// Description:
+    ///
+    /// * `memory` - Used to read the memory of the debugged target.
+    /// * `block_size` - The number of bytes read at once, e.g. `READ_AHEAD_BLOCK_SIZE`.
+    /// * `ram_ranges` - The address ranges of the RAM of the debug target, nothing is read ahead
+    ///   outside them.
+    pub fn new(
+        memory: &'a mut M,
+        block_size: usize,
+        ram_ranges: &'a [Range<u32>],
+    ) -> ReadAheadMemory<'a, M> {
+        ReadAheadMemory {
+            block_size: block_size.max(1),
+            ram_ranges,
+            blocks: HashMap::new(),
+            transactions: 0,
+            memory,
+        }
+    }
+
+    /// Remove all the cached blocks, this should be done every time the debug target halts.
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+    }
+
+    /// Get the address range of the aligned blocks that contain a read.
+    ///
+    /// Will return `None` if the blocks are not inside one of the RAM ranges.
+    fn block_range(&self, address: u32, num_bytes: usize) -> Option<Range<u32>> {
+        let block_size = u32::try_from(self.block_size).ok()?;
+        let start = address - address % block_size;
+        let end = address.checked_add(u32::try_from(num_bytes).ok()?)?;
+        let end = end.checked_add((block_size - end % block_size) % block_size)?;
+        self.ram_ranges
+            .iter()
+            .any(|ram| ram.start <= start && end <= ram.end)
+            .then_some(start..end)
+    }
+}
+
+impl<'a, M: MemoryAccess> MemoryAccess for ReadAheadMemory<'a, M> {
+    fn get_address(&mut self, address: &u32, num_bytes: usize) -> Option<Vec<u8>> {
+        if num_bytes == 0 {
+            return Some(vec![]);
+        }
+        let blocks = match self.block_range(*address, num_bytes) {
+            Some(val) => val,
+            None => {
+                self.transactions += 1;
+                return self.memory.get_address(address, num_bytes);
+            }
+        };
+
+        // Read all the missing blocks in one read.
+        let missing: Vec<u32> = blocks
+            .clone()
+            .step_by(self.block_size)
+            .filter(|b| !self.blocks.contains_key(b))
+            .collect();
+        if let (Some(first), Some(last)) = (missing.first(), missing.last()) {
+            let length = (last - first) as usize + self.block_size;
+            self.transactions += 1;
+            match self.memory.get_address(first, length) {
+                Some(data) if data.len() == length => {
+                    for (block, data) in (*first..)
+                        .step_by(self.block_size)
+                        .zip(data.chunks(self.block_size))
+                    {
+                        self.blocks.insert(block, data.to_vec());
+                    }
+                }
+                // The rounded out range could not be read.
+                _ => {
+                    self.transactions += 1;
+                    return self.memory.get_address(address, num_bytes);
+                }
+            };
+        }
+
+        let mut result = Vec::with_capacity(blocks.len());
+        for block in blocks.clone().step_by(self.block_size) {
+            result.extend(self.blocks.get(&block)?);
+        }
+        let offset = (address - blocks.start) as usize;
+        result
+            .get(offset..offset + num_bytes)
+            .map(|bytes| bytes.to_vec())
+    }
+}
+
 /// Will preform a stack trace on the debugged target.
 ///
 /// Description:
@@ -73,15 +269,86 @@ pub fn stack_trace<'a, R: Reader<Offset = usize>, M: MemoryAccess>(
     //    }
     //    println!("\n\n");
     let call_stacktrace = unwind_call_stack(registers.clone(), memory, debug_frame)?;
+    create_stack_frames(dwarf, &call_stacktrace, &registers, memory, cwd, None)
+    //Ok(das)
+}
+
+/// Will preform a stack trace, using the symbol table for frames without DWARF debug information.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `debug_frame` - A reference to the DWARF section `.debug_frame`.
+/// * `registers` - A `Registers` struct which is used to read the register values.
+/// * `memory` - Used to read the memory of the debugged target.
+/// * `cwd` - The work directory of the debugged program.
+/// * `symbols` - The symbol table of the debugged program.
+///
+/// Works like `stack_trace`, but a frame that can not be evaluated using the DWARF debug
+/// information is replaced with a symbol only frame instead of failing the whole stack trace.
+pub fn stack_trace_with_symbols<R: Reader<Offset = usize>, M: MemoryAccess>(
+    dwarf: &Dwarf<R>,
+    debug_frame: &DebugFrame<R>,
+    registers: Registers,
+    memory: &mut M,
+    cwd: &str,
+    symbols: &SymbolTable,
+) -> Result<Vec<StackFrame<R>>> {
+    let call_stacktrace = unwind_call_stack(registers.clone(), memory, debug_frame)?;
+    create_stack_frames(
+        dwarf,
+        &call_stacktrace,
+        &registers,
+        memory,
+        cwd,
+        Some(symbols),
+    )
+}
 
+/// Will evaluate the stack frames of a virtually unwound call stack.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `call_stack` - The virtually unwound call stack.
+/// * `registers` - A `Registers` struct which is used to read the register values.
+/// * `memory` - Used to read the memory of the debugged target.
+/// * `cwd` - The work directory of the debugged program.
+/// * `symbols` - A optional symbol table, a frame that can not be evaluated using the DWARF
+///   debug information is replaced with a symbol only frame if it is set.
+///
+/// This makes it possible to unwind the call stack once and reuse it.
+pub fn create_stack_frames<R: Reader<Offset = usize>, M: MemoryAccess>(
+    dwarf: &Dwarf<R>,
+    call_stack: &[CallFrame],
+    registers: &Registers,
+    memory: &mut M,
+    cwd: &str,
+    symbols: Option<&SymbolTable>,
+) -> Result<Vec<StackFrame<R>>> {
     let mut stack_trace = vec![];
-    for call_frame in call_stacktrace {
-        let stack_frame = create_stack_frame(dwarf, call_frame, &registers, memory, cwd)?;
+    for (i, call_frame) in call_stack.iter().enumerate() {
+        let stack_frame = match create_stack_frame(
+            dwarf,
+            call_frame.clone(),
+            call_stack.get(i + 1),
+            registers,
+            memory,
+            cwd,
+        ) {
+            Ok(val) => val,
+            Err(err) => match symbols {
+                Some(symbols) => {
+                    trace!("Using symbol only frame, because: {:?}", err);
+                    create_symbol_stack_frame(call_frame.clone(), symbols)
+                }
+                None => return Err(err),
+            },
+        };
 
         stack_trace.push(stack_frame);
     }
     Ok(stack_trace)
-    //Ok(das)
 }
 
 pub fn new_stack_trace<'a, R: Reader<Offset = usize>, M: MemoryAccess>(
@@ -96,14 +363,17 @@ pub fn new_stack_trace<'a, R: Reader<Offset = usize>, M: MemoryAccess>(
         .ok_or_else(|| anyhow!("Requires pc register id"))?;
     let link_reg = registers
         .link_register
-        .ok_or_else(|| anyhow!("Requires pc register id"))?;
+        .ok_or_else(|| anyhow!("Requires lr register id"))?;
     let sp_reg = registers
         .stack_pointer_register
-        .ok_or_else(|| anyhow!("Requires pc register id"))?;
+        .ok_or_else(|| anyhow!("Requires sp register id"))?;
 
-    let mut regs = [None; 16];
-    for (reg, val) in &registers.registers {
-        regs[*reg as usize] = Some(*val);
+    let mut regs = [None; UNWIND_REGISTER_COUNT];
+    for (reg, val) in registers.register_values() {
+        // Registers that are not unwound, e.g. floating point registers, are skipped.
+        if let Some(reg) = regs.get_mut(*reg as usize) {
+            *reg = Some(*val);
+        }
     }
     let code_location = registers
         .get_register_value(&(pc_reg as u16))
@@ -135,7 +405,7 @@ pub fn new_stack_trace_rec<'a, R: Reader<Offset = usize>, M: MemoryAccess>(
     link_reg: usize,
     sp_reg: usize,
     code_location: Option<u64>,
-    mut unwind_registers: [Option<u32>; 16],
+    mut unwind_registers: [Option<u32>; UNWIND_REGISTER_COUNT],
     base: &mut gimli::BaseAddresses,
     ctx: &mut gimli::UnwindContext<R>,
 ) -> Result<Vec<StackFrame<R>>> {
@@ -148,13 +418,16 @@ pub fn new_stack_trace_rec<'a, R: Reader<Offset = usize>, M: MemoryAccess>(
         }
     };
 
-    // Get unwind info
-    let unwind_info = match debug_frame.unwind_info_for_address(
-        base,
-        ctx,
-        current_location,
-        gimli::DebugFrame::cie_from_offset,
-    ) {
+    // Get unwind info, the frame description entry covers the whole function and the row only a
+    // part of it.
+    let unwind_info = debug_frame
+        .fde_for_address(base, current_location, gimli::DebugFrame::cie_from_offset)
+        .and_then(|fde| {
+            let function_range = fde.initial_address()..fde.initial_address() + fde.len();
+            let row = fde.unwind_info_for_address(debug_frame, base, ctx, current_location)?;
+            Ok((function_range, row))
+        });
+    let (function_range, unwind_info) = match unwind_info {
         Ok(val) => val,
         Err(err) => {
             trace!("Stopped unwinding call stack, because: {:?}", err);
@@ -166,8 +439,8 @@ pub fn new_stack_trace_rec<'a, R: Reader<Offset = usize>, M: MemoryAccess>(
     let cfa = unwind_cfa(unwind_registers, unwind_info)?;
 
     // Unwind registers
-    let mut new_registers = [None; 16];
-    for i in 0..16_usize {
+    let mut new_registers = [None; UNWIND_REGISTER_COUNT];
+    for i in 0..UNWIND_REGISTER_COUNT {
         let reg_rule = unwind_info.register(gimli::Register(i as u16));
 
         new_registers[i] = match reg_rule {
@@ -176,8 +449,6 @@ pub fn new_stack_trace_rec<'a, R: Reader<Offset = usize>, M: MemoryAccess>(
                 // Source: https://github.com/gimli-rs/gimli/blob/00f4ee6a288d2e7f02b6841a5949d839e99d8359/src/read/cfi.rs#L2289-L2311
                 if i == sp_reg {
                     cfa
-                } else if i == pc_reg {
-                    Some(current_location as u32)
                 } else {
                     None
                 }
@@ -244,13 +515,20 @@ pub fn new_stack_trace_rec<'a, R: Reader<Offset = usize>, M: MemoryAccess>(
         };
     }
 
+    // A program counter without a rule continues at the return address of the caller.
+    if let Undefined = unwind_info.register(gimli::Register(pc_reg as u16)) {
+        new_registers[pc_reg] =
+            new_registers[link_reg].map(|ra| registers.architecture.return_program_counter(ra));
+    }
+
     let call_frame = CallFrame {
         id: current_location,
         registers: unwind_registers,
         code_location: current_location,
         cfa,
-        start_address: unwind_info.start_address(),
-        end_address: unwind_info.end_address(),
+        start_address: function_range.start,
+        end_address: function_range.end,
+        exception_return: None,
     };
 
     unwind_registers = new_registers;
@@ -258,7 +536,7 @@ pub fn new_stack_trace_rec<'a, R: Reader<Offset = usize>, M: MemoryAccess>(
     let mut stack_trace = vec![];
 
     stack_trace.push(create_stack_frame(
-        dwarf, call_frame, registers, memory, cwd,
+        dwarf, call_frame, None, registers, memory, cwd,
     )?);
 
     // Get next_code_location
@@ -300,12 +578,11 @@ pub fn new_stack_trace_rec<'a, R: Reader<Offset = usize>, M: MemoryAccess>(
                     None => None,
                 }
             } else {
-                // Call address is equal to return address, but the first bit needs to be removed
-                // because of thumb mode.
-                // And take minus one to ensure that it is the caller address and not the return
-                // address.
-                // This address will not be aligend to the instruction address.
-                unwind_registers[link_reg as usize].map(|pc| u64::from(pc & !1) - 1)
+                // The return address is turned into a address in the call instruction, this
+                // address will not be aligend to the instruction address.
+                unwind_registers[link_reg as usize]
+                    .and_then(|pc| registers.architecture.call_address(pc))
+                    .map(u64::from)
             }
         }
     };
@@ -327,6 +604,25 @@ pub fn new_stack_trace_rec<'a, R: Reader<Offset = usize>, M: MemoryAccess>(
     Ok(stack_trace)
 }
 
+/// The number of registers that are virtually unwound, it covers the Cortex-M core registers and
+/// the RISC-V integer registers and program counter.
+pub const UNWIND_REGISTER_COUNT: usize = 33;
+
+/// The `EXC_RETURN` bit which is set if the exception frame is on the process stack.
+const EXC_RETURN_SPSEL: u32 = 1 << 2;
+
+/// The `EXC_RETURN` bit which is clear if the exception frame holds the floating point registers.
+const EXC_RETURN_FTYPE: u32 = 1 << 4;
+
+/// The `xPSR` bit which is set if the stack was realigned when the exception frame was pushed.
+const XPSR_STACK_ALIGNED: u32 = 1 << 9;
+
+/// The size of a exception frame in bytes.
+const EXCEPTION_FRAME_SIZE: u32 = 0x20;
+
+/// The size of a exception frame which holds the floating point registers in bytes.
+const EXTENDED_EXCEPTION_FRAME_SIZE: u32 = 0x68;
+
 /// Describes what a call frame contains.
 #[derive(Debug, Clone)]
 pub struct CallFrame {
@@ -334,7 +630,7 @@ pub struct CallFrame {
     pub id: u64,
 
     /// Preserved register values of the call frame.
-    pub registers: [Option<u32>; 16],
+    pub registers: [Option<u32>; UNWIND_REGISTER_COUNT],
 
     /// The current code location in the frame.
     pub code_location: u64,
@@ -342,11 +638,36 @@ pub struct CallFrame {
     /// The Canonical Frame Address for this frame.
     pub cfa: Option<u32>,
 
-    /// First machine code address of this frame.
+    /// First machine code address of the function of this frame.
     pub start_address: u64,
 
-    /// Last machine code address of this frame.
+    /// The address after the last machine code address of the function of this frame.
     pub end_address: u64,
+
+    /// The `EXC_RETURN` value of the exception handler that interrupted this frame.
+    /// The code location is then the interrupted instruction, and not a address in a call
+    /// instruction.
+    pub exception_return: Option<u32>,
+}
+
+impl CallFrame {
+    /// Get the program counter value the frame continues at when the frame above it returns.
+    ///
+    /// Description:
+    ///
+    /// * `architecture` - The architecture of the debug target.
+    ///
+    /// A interrupted frame continues at its code location, otherwise it continues at the return
+    /// address after the call instruction.
+    /// Will return `None` if the code location is not in a call instruction.
+    pub fn continue_address(&self, architecture: Architecture) -> Option<u32> {
+        match self.exception_return {
+            Some(_) => Some(self.code_location as u32),
+            None => architecture
+                .return_address(self.code_location as u32)
+                .map(|ra| architecture.return_program_counter(ra)),
+        }
+    }
 }
 
 /// Will virtually unwind the call stack.
@@ -358,200 +679,661 @@ pub struct CallFrame {
 /// * `debug_frame` - A reference to the DWARF section `.debug_frame`.
 ///
 /// This function will virtually unwind the call stack and return a `Vec` of `CallFrame`s.
+/// It drives a `CallStackUnwinder` to completion by reading all the required memory using
+/// `memory`.
 pub fn unwind_call_stack<R: Reader<Offset = usize>, M: MemoryAccess>(
     registers: Registers,
     memory: &mut M,
     debug_frame: &'_ DebugFrame<R>,
 ) -> Result<Vec<CallFrame>> {
+    drive_unwinder(registers, memory, debug_frame, None)
+}
+
+/// Will virtually unwind the call stack using a index of the frame description entries.
+///
+/// Description:
+///
+/// * `registers` - A `Registers` struct which is used to read the register values.
+/// * `memory` - Used to read the memory of the debugged target.
+/// * `debug_frame` - A reference to the DWARF section `.debug_frame`.
+/// * `fde_index` - A index of the frame description entries in `debug_frame`.
+///
+/// Works like `unwind_call_stack`, but the frame description entries are looked up in the index
+/// instead of searching the `.debug_frame` section for every frame.
+pub fn unwind_call_stack_with_index<R: Reader<Offset = usize>, M: MemoryAccess>(
+    registers: Registers,
+    memory: &mut M,
+    debug_frame: &'_ DebugFrame<R>,
+    fde_index: &FdeIndex<R>,
+) -> Result<Vec<CallFrame>> {
+    drive_unwinder(registers, memory, debug_frame, Some(fde_index))
+}
+
+/// Drive a `CallStackUnwinder` to completion by reading all the required memory.
+fn drive_unwinder<R: Reader<Offset = usize>, M: MemoryAccess>(
+    registers: Registers,
+    memory: &mut M,
+    debug_frame: &'_ DebugFrame<R>,
+    fde_index: Option<&FdeIndex<R>>,
+) -> Result<Vec<CallFrame>> {
+    let mut unwinder = CallStackUnwinder::new(&registers)?;
+    loop {
+        let result = match fde_index {
+            Some(index) => unwinder.unwind_with_index(debug_frame, index)?,
+            None => unwinder.unwind(debug_frame)?,
+        };
+        match result {
+            UnwindResult::Complete => break,
+            UnwindResult::RequiresAddress { address, size } => {
+                match memory.get_address(&address, size) {
+                    Some(data) => unwinder.add_address(address, &data)?,
+                    None => {
+                        error!("Can not read {} bytes from address 0x{:x}", size, address);
+                        return Err(anyhow!(
+                            "Can not read {} bytes from address 0x{:x}",
+                            size,
+                            address
+                        ));
+                    }
+                }
+            }
+        };
+    }
+
+    Ok(unwinder.get_call_stack())
+}
+
+/// A index of the frame description entries in the `.debug_frame` section, sorted by address.
+///
+/// Searching the `.debug_frame` section for the entry of a address requires parsing all the
+/// entries before it, the index is built once and makes the lookup a binary search.
+pub struct FdeIndex<R: Reader<Offset = usize>> {
+    /// The frame description entries sorted by their initial address.
+    entries: Vec<gimli::FrameDescriptionEntry<R>>,
+}
+
+impl<R: Reader<Offset = usize>> FdeIndex<R> {
+    /// Creates a new `FdeIndex` by parsing all the frame description entries.
+    ///
+    /// Description:
+    ///
+    /// * `debug_frame` - A reference to the DWARF section `.debug_frame`.
+    pub fn new(debug_frame: &DebugFrame<R>) -> Result<FdeIndex<R>> {
+        let bases = gimli::BaseAddresses::default();
+        let mut entries = vec![];
+        let mut iter = debug_frame.entries(&bases);
+        while let Some(entry) = iter.next()? {
+            if let gimli::CieOrFde::Fde(partial) = entry {
+                entries.push(partial.parse(gimli::DebugFrame::cie_from_offset)?);
+            }
+        }
+        entries.sort_by_key(|fde| fde.initial_address());
+
+        Ok(FdeIndex { entries })
+    }
+
+    /// Find the frame description entry that contains a address.
+    ///
+    /// Description:
+    ///
+    /// * `address` - A machine code address.
+    pub fn find(&self, address: u64) -> Option<&gimli::FrameDescriptionEntry<R>> {
+        let index = self
+            .entries
+            .partition_point(|fde| fde.initial_address() <= address);
+        let fde = self.entries.get(index.checked_sub(1)?)?;
+        match fde.contains(address) {
+            true => Some(fde),
+            false => None,
+        }
+    }
+}
+
+/// The register values that restarts a call frame from the entry of its function.
+#[derive(Debug, Clone)]
+pub struct RestartFrame {
+    /// The register values that should be written to the debug target.
+    pub registers: Vec<(u16, u32)>,
+
+    /// The registers whose values at the entry of the function could not be recovered.
+    /// These are usually the registers the arguments are passed in, thus the restarted function
+    /// may use other argument values then in the original call.
+    pub unknown_registers: Vec<u16>,
+}
+
+/// Will find the register values that restarts a call frame.
+///
+/// Description:
+///
+/// * `call_stack` - The virtually unwound call stack.
+/// * `frame_index` - The index of the call frame in `call_stack` that will be restarted.
+/// * `registers` - A `Registers` struct containing the register numbers of the architecture.
+///
+/// The stack pointer is set to the CFA of the frame, and the program counter to the first
+/// address of the frame's function.
+/// The link register is set to the return address into the calling frame, or to the
+/// `EXC_RETURN` value if the frame is a exception handler.
+/// The other core registers are set to the values recovered for the calling frame.
+/// All the frames above the restarted frame are discarded, this is only safe if they have not
+/// done anything that the restarted function does not expect.
+pub fn restart_frame_registers(
+    call_stack: &[CallFrame],
+    frame_index: usize,
+    registers: &Registers,
+) -> Result<RestartFrame> {
     let pc_reg = registers
         .program_counter_register
         .ok_or_else(|| anyhow!("Requires pc register id"))?;
     let link_reg = registers
         .link_register
-        .ok_or_else(|| anyhow!("Requires pc register id"))?;
+        .ok_or_else(|| anyhow!("Requires lr register id"))?;
     let sp_reg = registers
         .stack_pointer_register
-        .ok_or_else(|| anyhow!("Requires pc register id"))?;
+        .ok_or_else(|| anyhow!("Requires sp register id"))?;
+
+    let frame = match call_stack.get(frame_index) {
+        Some(val) => val,
+        None => {
+            error!("There is no call frame with index {}", frame_index);
+            return Err(anyhow!("There is no call frame with index {}", frame_index));
+        }
+    };
+    let caller_frame = match call_stack.get(frame_index + 1) {
+        Some(val) => val,
+        None => {
+            error!("Can not restart a call frame without a calling frame");
+            return Err(anyhow!(
+                "Can not restart a call frame without a calling frame"
+            ));
+        }
+    };
+    let cfa = match frame.cfa {
+        Some(val) => val,
+        None => {
+            error!("Can not restart a call frame without a CFA");
+            return Err(anyhow!("Can not restart a call frame without a CFA"));
+        }
+    };
+
+    let mut result = RestartFrame {
+        registers: vec![],
+        unknown_registers: vec![],
+    };
+    let register_count = registers.architecture.register_count();
+    for (i, value) in caller_frame
+        .registers
+        .iter()
+        .enumerate()
+        .take(register_count)
+    {
+        let value = if i == sp_reg {
+            Some(cfa)
+        } else if i == pc_reg {
+            Some(frame.start_address as u32)
+        } else if i == link_reg {
+            match caller_frame.exception_return {
+                // The frame is a exception handler, which returns using `EXC_RETURN`.
+                Some(exc_return) => Some(exc_return),
+                // The code location of the caller is a address in the call instruction.
+                None => registers
+                    .architecture
+                    .return_address(caller_frame.code_location as u32),
+            }
+        } else {
+            *value
+        };
 
-    let mut regs = [None; 16];
-    for (reg, val) in &registers.registers {
-        regs[*reg as usize] = Some(*val);
+        match value {
+            Some(val) => result.registers.push((i as u16, val)),
+            None => result.unknown_registers.push(i as u16),
+        };
     }
-    let code_location = registers
-        .get_register_value(&(pc_reg as u16))
-        .map(|v| *v as u64);
 
-    unwind_call_stack_recursive(
-        debug_frame,
-        memory,
-        pc_reg,
-        link_reg,
-        sp_reg,
-        code_location,
-        regs,
-        &mut gimli::BaseAddresses::default(),
-        &mut Box::new(gimli::UnwindContext::new()),
-    )
+    Ok(result)
+}
+
+/// Where the debug target should stop when stepping out of a call frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StepOut {
+    /// The return address into the calling frame, a temporary breakpoint is placed here.
+    pub return_address: u32,
+
+    /// The CFA of the frame that is stepped out of, which is the stack pointer value after it
+    /// returned.
+    pub cfa: u32,
+}
+
+impl StepOut {
+    /// Will return `true` if a halt at the return address is the step out, and not a halt in a
+    /// deeper recursive call of the same function.
+    ///
+    /// Description:
+    ///
+    /// * `sp` - The stack pointer value at the halt.
+    pub fn is_complete(&self, sp: u32) -> bool {
+        sp >= self.cfa
+    }
 }
 
-/// Helper function for virtually unwind the call stack recursively.
+/// Will find where to stop when stepping out of a call frame, e.g. for a DAP `stepOut` request.
 ///
 /// Description:
 ///
-/// * `debug_frame` - A reference to the DWARF section `.debug_frame`.
-/// * `memory` - Used to read the memory of the debugged target.
-/// * `pc_reg` - The register number which is the program counter register.
-/// * `link_reg` - The register number which is the link register.
-/// * `sp_reg` - The register number which is the stack pointer register.
-/// * `code_location` - The code location in the call frame.
-/// * `unwind_registers` - The virtually unwind register values.
-/// * `base` - A base address struct which gimli-rs requires.
-/// * `ctx` - Unwind context struct which gimli-rs requires.
-///
-/// This function will virtually unwind the call stack recursively.
-fn unwind_call_stack_recursive<'a, M: MemoryAccess, R: Reader<Offset = usize>>(
-    debug_frame: &'a DebugFrame<R>,
-    memory: &mut M,
-    pc_reg: usize,
-    link_reg: usize,
-    sp_reg: usize,
-    code_location: Option<u64>,
-    mut unwind_registers: [Option<u32>; 16],
-    base: &mut gimli::BaseAddresses,
-    ctx: &mut gimli::UnwindContext<R>,
-) -> Result<Vec<CallFrame>> {
-    let current_location = match code_location {
+/// * `call_stack` - The virtually unwound call stack.
+/// * `frame_index` - The index of the call frame in `call_stack` that is stepped out of.
+/// * `architecture` - The architecture of the debug target.
+///
+/// The debug target should be resumed with a temporary breakpoint at the return address and
+/// resumed again for each halt there that is not complete.
+/// Stepping out of a exception handler stops at the interrupted instruction.
+pub fn step_out_target(
+    call_stack: &[CallFrame],
+    frame_index: usize,
+    architecture: Architecture,
+) -> Result<StepOut> {
+    let frame = match call_stack.get(frame_index) {
         Some(val) => val,
         None => {
-            trace!("Stopped unwinding call stack, because: Reached end of stack");
-            return Ok(vec![]);
+            error!("There is no call frame with index {}", frame_index);
+            return Err(anyhow!("There is no call frame with index {}", frame_index));
         }
     };
-
-    let unwind_info = match debug_frame.unwind_info_for_address(
-        base,
-        ctx,
-        current_location,
-        gimli::DebugFrame::cie_from_offset,
-    ) {
-        Ok(val) => val,
-        Err(err) => {
-            trace!("Stopped unwinding call stack, because: {:?}", err);
-            return Ok(vec![]);
+    let caller_frame = match call_stack.get(frame_index + 1) {
+        Some(val) => val,
+        None => {
+            error!("Can not step out of the outermost call frame");
+            return Err(anyhow!("Can not step out of the outermost call frame"));
+        }
+    };
+    let cfa = match frame.cfa {
+        Some(val) => val,
+        None => {
+            error!("Can not step out of a call frame without a CFA");
+            return Err(anyhow!("Can not step out of a call frame without a CFA"));
         }
     };
 
-    let cfa = unwind_cfa(unwind_registers, unwind_info)?;
+    let return_address = match caller_frame.continue_address(architecture) {
+        Some(val) => val,
+        None => {
+            error!("The calling frame has no return address");
+            return Err(anyhow!("The calling frame has no return address"));
+        }
+    };
 
-    let mut new_registers = [None; 16];
-    for i in 0..16_usize {
-        let reg_rule = unwind_info.register(gimli::Register(i as u16));
+    Ok(StepOut {
+        return_address,
+        cfa,
+    })
+}
 
-        new_registers[i] = match reg_rule {
-            Undefined => {
-                // If the column is empty then it defaults to undefined.
-                // Source: https://github.com/gimli-rs/gimli/blob/00f4ee6a288d2e7f02b6841a5949d839e99d8359/src/read/cfi.rs#L2289-L2311
-                if i == sp_reg {
-                    cfa
-                } else if i == pc_reg {
-                    Some(current_location as u32)
-                } else {
-                    None
+/// Describes what the `CallStackUnwinder` requires to continue unwinding the call stack.
+#[derive(Debug, Clone)]
+pub enum UnwindResult {
+    /// The call stack has been completely unwound.
+    Complete,
+
+    /// The value at a address in the memory of the debug target is required to continue.
+    RequiresAddress {
+        /// The address that needs to be read.
+        address: u32,
+
+        /// The number of bytes that needs to be read.
+        size: usize,
+    },
+}
+
+/// A resumable state machine for virtually unwinding the call stack.
+///
+/// This is used by hosts that can not block on reading the memory of the debug target.
+/// Call `unwind` until it returns `UnwindResult::Complete`, every time it returns
+/// `UnwindResult::RequiresAddress` the memory needs to be read and given to the unwinder using
+/// `add_address`.
+pub struct CallStackUnwinder<R: Reader<Offset = usize>> {
+    /// The register number which is the program counter register.
+    program_counter_register: usize,
+
+    /// The register number which is the link register.
+    link_register: usize,
+
+    /// The register number which is the stack pointer register.
+    stack_pointer_register: usize,
+
+    /// The architecture of the debug target.
+    architecture: Architecture,
+
+    /// The code location of the next call frame to unwind.
+    code_location: Option<u64>,
+
+    /// The address and `EXC_RETURN` value of a Cortex-M exception frame that needs to be
+    /// unwound before the next call frame.
+    exception_frame: Option<(u32, u32)>,
+
+    /// The `EXC_RETURN` value of the exception handler that interrupted the next call frame.
+    exception_return: Option<u32>,
+
+    /// The virtually unwound register values.
+    unwind_registers: [Option<u32>; UNWIND_REGISTER_COUNT],
+
+    /// The memory values that have been read from the debug target.
+    addresses: HashMap<u32, u32>,
+
+    /// The call frames that have been unwound.
+    call_stack: Vec<CallFrame>,
+
+    /// A base address struct which gimli-rs requires.
+    bases: gimli::BaseAddresses,
+
+    /// Unwind context struct which gimli-rs requires.
+    ctx: Box<gimli::UnwindContext<R>>,
+}
+
+impl<R: Reader<Offset = usize>> CallStackUnwinder<R> {
+    /// Creates a new `CallStackUnwinder`.
+    ///
+    /// Description:
+    ///
+    /// * `registers` - A `Registers` struct which is used to read the register values.
+    ///
+    /// The program counter, link register and stack pointer register numbers are required to
+    /// be set in `registers`.
+    pub fn new(registers: &Registers) -> Result<CallStackUnwinder<R>> {
+        let program_counter_register = registers
+            .program_counter_register
+            .ok_or_else(|| anyhow!("Requires pc register id"))?;
+        let link_register = registers
+            .link_register
+            .ok_or_else(|| anyhow!("Requires lr register id"))?;
+        let stack_pointer_register = registers
+            .stack_pointer_register
+            .ok_or_else(|| anyhow!("Requires sp register id"))?;
+
+        let mut unwind_registers = [None; UNWIND_REGISTER_COUNT];
+        for (reg, val) in registers.register_values() {
+            // Registers that are not unwound, e.g. floating point registers, are skipped.
+            if let Some(unwind_register) = unwind_registers.get_mut(*reg as usize) {
+                *unwind_register = Some(*val);
+            }
+        }
+        let code_location = registers
+            .get_register_value(&(program_counter_register as u16))
+            .map(|v| *v as u64);
+
+        Ok(CallStackUnwinder {
+            program_counter_register,
+            link_register,
+            stack_pointer_register,
+            architecture: registers.architecture,
+            code_location,
+            exception_frame: None,
+            exception_return: None,
+            unwind_registers,
+            addresses: HashMap::new(),
+            call_stack: vec![],
+            bases: gimli::BaseAddresses::default(),
+            ctx: Box::new(gimli::UnwindContext::new()),
+        })
+    }
+
+    /// Add a value read from the memory of the debug target.
+    ///
+    /// Description:
+    ///
+    /// * `address` - The address the value was read from.
+    /// * `value` - The bytes read from the address.
+    ///
+    /// This is used to give the unwinder the value it required in
+    /// `UnwindResult::RequiresAddress`.
+    pub fn add_address(&mut self, address: u32, value: &[u8]) -> Result<()> {
+        let value = u32::from_le_bytes(match value.try_into() {
+            Ok(val) => val,
+            Err(err) => {
+                error!("{:?}", err);
+                return Err(anyhow!("{:?}", err));
+            }
+        });
+        self.addresses.insert(address, value);
+        Ok(())
+    }
+
+    /// Get the call frames that have been unwound so far.
+    pub fn get_call_stack(&self) -> Vec<CallFrame> {
+        self.call_stack.clone()
+    }
+
+    /// Continue virtually unwinding the call stack.
+    ///
+    /// Description:
+    ///
+    /// * `debug_frame` - A reference to the DWARF section `.debug_frame`.
+    ///
+    /// Will unwind call frames until the whole call stack is unwound or until a value from the
+    /// memory of the debug target is required.
+    pub fn unwind(&mut self, debug_frame: &'_ DebugFrame<R>) -> Result<UnwindResult> {
+        self.unwind_frames(debug_frame, None)
+    }
+
+    /// Continue virtually unwinding the call stack using a index of the frame description
+    /// entries.
+    ///
+    /// Description:
+    ///
+    /// * `debug_frame` - A reference to the DWARF section `.debug_frame`.
+    /// * `fde_index` - A index of the frame description entries in `debug_frame`.
+    ///
+    /// Works like `unwind`, but the frame description entries are looked up in the index.
+    pub fn unwind_with_index(
+        &mut self,
+        debug_frame: &'_ DebugFrame<R>,
+        fde_index: &FdeIndex<R>,
+    ) -> Result<UnwindResult> {
+        self.unwind_frames(debug_frame, Some(fde_index))
+    }
+
+    /// Unwind call frames, the frame description entries are looked up in the index if it is
+    /// given.
+    fn unwind_frames(
+        &mut self,
+        debug_frame: &'_ DebugFrame<R>,
+        fde_index: Option<&FdeIndex<R>>,
+    ) -> Result<UnwindResult> {
+        loop {
+            if let Some((address, exc_return)) = self.exception_frame {
+                if let Some(result) = self.unwind_exception_frame(address, exc_return) {
+                    return Ok(result);
                 }
             }
-            SameValue => unwind_registers[i],
-            Offset(offset) => {
-                let address = (offset
-                    + match cfa {
-                        Some(val) => i64::from(val),
-                        None => return Err(anyhow!("Expected CFA to have a value")),
-                    }) as u32;
 
-                let value = {
-                    let value = match memory.get_address(&address, 4) {
-                        Some(val) => {
-                            let mut result = vec![];
-                            for v in val {
-                                result.push(v);
-                            }
+            let current_location = match self.code_location {
+                Some(val) => val,
+                None => {
+                    trace!("Stopped unwinding call stack, because: Reached end of stack");
+                    return Ok(UnwindResult::Complete);
+                }
+            };
 
-                            u32::from_le_bytes(match result.as_slice().try_into() {
-                                Ok(val) => val,
-                                Err(err) => {
-                                    error!("{:?}", err);
-                                    return Err(anyhow!("{:?}", err));
-                                }
-                            })
+            let bases = &self.bases;
+            let ctx = &mut self.ctx;
+            let fde = match fde_index {
+                Some(index) => match index.find(current_location) {
+                    Some(fde) => Ok(fde.clone()),
+                    None => Err(gimli::Error::NoUnwindInfoForAddress),
+                },
+                None => debug_frame.fde_for_address(
+                    bases,
+                    current_location,
+                    gimli::DebugFrame::cie_from_offset,
+                ),
+            };
+            // The frame description entry covers the whole function, a row only a part of it.
+            let unwind_info = fde.and_then(|fde| {
+                let function_range = fde.initial_address()..fde.initial_address() + fde.len();
+                let row = fde.unwind_info_for_address(debug_frame, bases, ctx, current_location)?;
+                Ok((function_range, row))
+            });
+            let (function_range, unwind_info) = match unwind_info {
+                Ok(val) => val,
+                Err(err) => {
+                    trace!("Stopped unwinding call stack, because: {:?}", err);
+                    self.code_location = None;
+                    return Ok(UnwindResult::Complete);
+                }
+            };
+
+            let cfa = unwind_cfa(self.unwind_registers, unwind_info)?;
+
+            let mut new_registers = [None; UNWIND_REGISTER_COUNT];
+            for (i, new_register) in new_registers.iter_mut().enumerate() {
+                let reg_rule = unwind_info.register(gimli::Register(i as u16));
+
+                *new_register = match reg_rule {
+                    Undefined => {
+                        // If the column is empty then it defaults to undefined.
+                        // Source: https://github.com/gimli-rs/gimli/blob/00f4ee6a288d2e7f02b6841a5949d839e99d8359/src/read/cfi.rs#L2289-L2311
+                        if i == self.stack_pointer_register {
+                            cfa
+                        } else {
+                            None
                         }
-                        None => {
-                            error!("Can not read 4 bytes from address 0x{:x}", address);
-                            return Err(anyhow!(
-                                "Can not read 4 bytes from address 0x{:x}",
-                                address
-                            ));
+                    }
+                    SameValue => self.unwind_registers[i],
+                    Offset(offset) => {
+                        let address = (offset
+                            + match cfa {
+                                Some(val) => i64::from(val),
+                                None => return Err(anyhow!("Expected CFA to have a value")),
+                            }) as u32;
+
+                        match self.addresses.get(&address) {
+                            Some(value) => Some(*value),
+                            None => return Ok(UnwindResult::RequiresAddress { address, size: 4 }),
                         }
-                    };
-                    value
+                    }
+                    ValOffset(offset) => {
+                        let value = (offset
+                            + match cfa {
+                                Some(val) => i64::from(val),
+                                None => return Err(anyhow!("Expected CFA to have a value")),
+                            }) as u32;
+
+                        Some(value)
+                    }
+                    Register(reg) => self.unwind_registers[reg.0 as usize],
+                    Expression(_expr) => {
+                        error!("Unimplemented");
+                        return Err(anyhow!("Unimplemented")); // TODO
+                    }
+                    ValExpression(_expr) => {
+                        error!("Unimplemented");
+                        return Err(anyhow!("Unimplemented")); // TODO
+                    }
+                    Architectural => {
+                        error!("Unimplemented");
+                        return Err(anyhow!("Unimplemented"));
+                    }
                 };
-
-                Some(value)
             }
-            ValOffset(offset) => {
-                let value = (offset
-                    + match cfa {
-                        Some(val) => i64::from(val),
-                        None => return Err(anyhow!("Expected CFA to have a value")),
-                    }) as u32;
 
-                Some(value)
-            }
-            Register(reg) => unwind_registers[reg.0 as usize],
-            Expression(_expr) => {
-                error!("Unimplemented");
-                return Err(anyhow!("Unimplemented")); // TODO
+            // A program counter without a rule continues at the return address of the caller.
+            if let Undefined =
+                unwind_info.register(gimli::Register(self.program_counter_register as u16))
+            {
+                new_registers[self.program_counter_register] = new_registers[self.link_register]
+                    .map(|ra| self.architecture.return_program_counter(ra));
             }
-            ValExpression(_expr) => {
-                error!("Unimplemented");
-                return Err(anyhow!("Unimplemented")); // TODO
-            }
-            Architectural => {
-                error!("Unimplemented");
-                return Err(anyhow!("Unimplemented"));
+
+            self.call_stack.push(CallFrame {
+                id: current_location,
+                registers: self.unwind_registers,
+                code_location: current_location,
+                cfa,
+                start_address: function_range.start,
+                end_address: function_range.end,
+                exception_return: self.exception_return.take(),
+            });
+
+            self.unwind_registers = new_registers;
+
+            // Source: https://github.com/probe-rs/probe-rs/blob/8112c28912125a54aad016b4b935abf168812698/probe-rs/src/debug/mod.rs#L297-L302
+            // Next function is where our current return register is pointing to.
+            // We want the calling instruction for a backtrace, not the next instruction to be
+            // executed, the unwinding stops if the return address can not come from a call.
+            let architecture = self.architecture;
+            let return_address = self.unwind_registers[self.link_register];
+            match (return_address, cfa) {
+                (Some(exc_return), Some(cfa)) if architecture.is_exception_return(exc_return) => {
+                    // The caller was interrupted, its registers are in a exception frame.
+                    self.code_location = None;
+                    if exc_return & EXC_RETURN_SPSEL != 0 {
+                        trace!("Stopped unwinding call stack, because: Exception frame on PSP");
+                    } else {
+                        self.exception_frame = Some((cfa, exc_return));
+                    }
+                }
+                _ => {
+                    self.code_location = return_address
+                        .and_then(|pc| architecture.call_address(pc))
+                        .map(u64::from);
+                }
             }
-        };
+        }
     }
 
-    let mut call_stack = vec![CallFrame {
-        id: current_location,
-        registers: unwind_registers,
-        code_location: current_location,
-        cfa,
-        start_address: unwind_info.start_address(),
-        end_address: unwind_info.end_address(),
-    }];
+    /// Unwind a Cortex-M exception frame, which the hardware pushed to the stack when the
+    /// caller was interrupted.
+    ///
+    /// Description:
+    ///
+    /// * `address` - The address of the exception frame.
+    /// * `exc_return` - The `EXC_RETURN` value of the exception handler.
+    ///
+    /// Will return `Some` if a value from the memory of the debug target is required.
+    fn unwind_exception_frame(&mut self, address: u32, exc_return: u32) -> Option<UnwindResult> {
+        let mut words = [0; 8];
+        for (i, word) in words.iter_mut().enumerate() {
+            let word_address = match address.checked_add(4 * i as u32) {
+                Some(val) => val,
+                None => {
+                    trace!("Stopped unwinding call stack, because: Invalid exception frame");
+                    self.exception_frame = None;
+                    return None;
+                }
+            };
+            *word = match self.addresses.get(&word_address) {
+                Some(val) => *val,
+                None => {
+                    return Some(UnwindResult::RequiresAddress {
+                        address: word_address,
+                        size: 4,
+                    })
+                }
+            };
+        }
+        let [r0, r1, r2, r3, r12, lr, pc, xpsr] = words;
 
-    unwind_registers = new_registers;
+        // The other registers are restored by the exception handler and already unwound.
+        for (register, value) in [(0, r0), (1, r1), (2, r2), (3, r3), (12, r12)] {
+            self.unwind_registers[register] = Some(value);
+        }
+        self.unwind_registers[self.link_register] = Some(lr);
+        self.unwind_registers[self.program_counter_register] = Some(pc);
 
-    // Source: https://github.com/probe-rs/probe-rs/blob/8112c28912125a54aad016b4b935abf168812698/probe-rs/src/debug/mod.rs#L297-L302
-    // Next function is where our current return register is pointing to.
-    // We just have to remove the lowest bit (indicator for Thumb mode).
-    //
-    // We also have to subtract one, as we want the calling instruction for
-    // a backtrace, not the next instruction to be executed.
-    let next_code_location = unwind_registers[link_reg as usize].map(|pc| u64::from(pc & !1) - 1);
+        let mut size = if exc_return & EXC_RETURN_FTYPE == 0 {
+            EXTENDED_EXCEPTION_FRAME_SIZE
+        } else {
+            EXCEPTION_FRAME_SIZE
+        };
+        if xpsr & XPSR_STACK_ALIGNED != 0 {
+            size += 4;
+        }
+        self.unwind_registers[self.stack_pointer_register] = address.checked_add(size);
 
-    call_stack.append(&mut unwind_call_stack_recursive(
-        debug_frame,
-        memory,
-        pc_reg,
-        link_reg,
-        sp_reg,
-        next_code_location,
-        unwind_registers,
-        base,
-        ctx,
-    )?);
-    Ok(call_stack)
+        // The interrupted instruction has not been executed, so the pc is used as it is.
+        self.code_location = Some(u64::from(pc));
+        self.exception_frame = None;
+        self.exception_return = Some(exc_return);
+        None
+    }
 }
 
 /// A function for virtually unwind the Canonical Frame address.
@@ -563,7 +1345,7 @@ fn unwind_call_stack_recursive<'a, M: MemoryAccess, R: Reader<Offset = usize>>(
 ///
 /// Will virtually unwind the Canonical Frame address.
 fn unwind_cfa<R: Reader<Offset = usize>>(
-    registers: [Option<u32>; 16],
+    registers: [Option<u32>; UNWIND_REGISTER_COUNT],
     unwind_info: &gimli::UnwindTableRow<R>,
 ) -> Result<Option<u32>> {
     match unwind_info.cfa() {
@@ -604,6 +1386,18 @@ pub struct StackFrame<R: Reader<Offset = usize>> {
 
     /// The frame base address value.
     pub frame_base: u64,
+
+    /// `true` if there is no DWARF debug information for this frame, and the name is from the
+    /// symbol table.
+    pub symbol_only: bool,
+
+    /// `true` if the frame's function is inlined, the frames of the functions it is inlined into
+    /// are added by `expand_inlined_frames`.
+    pub inlined: bool,
+
+    /// The source location of the inlined call in a frame that was added by
+    /// `expand_inlined_frames`, `None` if the location is found from the code location.
+    pub call_location: Option<SourceInformation>,
 }
 
 impl<R: Reader<Offset = usize>> StackFrame<R> {
@@ -627,48 +1421,695 @@ impl<R: Reader<Offset = usize>> StackFrame<R> {
             };
         }
 
-        None
+        None
+    }
+
+    /// Evaluate a user typed expression in the context of this stack frame, e.g. for a DAP
+    /// "evaluate" request with a frame id.
+    ///
+    /// Description:
+    ///
+    /// * `text` - The expression, see `Expression::parse` for the grammar.
+    /// * `registers` - A register struct with the register numbers of the debug target.
+    /// * `mem` - A struct for accessing the memory of the debug target.
+    /// * `endian` - The endianness of the debug target.
+    ///
+    /// The variables are the already evaluated variables and arguments of the frame, and the
+    /// register pseudo-variables, e.g. `$sp`, are the register values recovered for the frame.
+    pub fn evaluate_expression<M: MemoryAccess>(
+        &self,
+        text: &str,
+        registers: &Registers,
+        mem: &mut M,
+        endian: RunTimeEndian,
+    ) -> Result<EvaluatorValue<R>> {
+        let expression = Expression::parse(text)?;
+        let frame_registers = call_frame_registers(&self.call_frame, None, registers);
+        evaluate_expression(
+            &expression,
+            &mut |name: &str| match name.starts_with('$') {
+                true => Ok(frame_registers.pseudo_variable(name).map(|value| {
+                    EvaluatorValue::Value(
+                        BaseTypeValue::Reg32(value),
+                        ValueInformation::new(None, vec![]),
+                    )
+                })),
+                false => Ok(self
+                    .variables
+                    .iter()
+                    .chain(&self.arguments)
+                    .find(|v| v.name.as_deref() == Some(name))
+                    .map(|v| v.value.clone())),
+            },
+            mem,
+            endian,
+        )
+    }
+}
+
+/// Get the register values of a call frame, as they were when the frame was the current frame.
+///
+/// Description:
+///
+/// * `call_frame` - A call frame from the virtually unwound call stack.
+/// * `caller_frame` - The call frame of the function that called `call_frame`, if it is known.
+/// * `registers` - A register struct with the register numbers of the debug target.
+///
+/// The register values recovered in `caller_frame` are used as the register values at the entry
+/// of the function, which are needed to evaluate `DW_OP_entry_value` expressions.
+pub fn call_frame_registers(
+    call_frame: &CallFrame,
+    caller_frame: Option<&CallFrame>,
+    registers: &Registers,
+) -> Registers {
+    let mut frame_registers = Registers::default();
+    frame_registers.program_counter_register = registers.program_counter_register;
+    frame_registers.link_register = registers.link_register;
+    frame_registers.stack_pointer_register = registers.stack_pointer_register;
+    frame_registers.architecture = registers.architecture;
+    frame_registers.cfa = call_frame.cfa;
+    for (i, value) in call_frame.registers.iter().enumerate() {
+        if let Some(val) = value {
+            frame_registers.add_register_value(i as u16, *val);
+        }
+    }
+
+    // The callee saved registers in the caller frame have the same values as at the entry of
+    // this function, and the stack pointer at entry is the CFA.
+    if let Some(caller_frame) = caller_frame {
+        let mut entry_registers = HashMap::new();
+        for (i, value) in caller_frame.registers.iter().enumerate() {
+            if let Some(val) = value {
+                entry_registers.insert(i as u16, *val);
+            }
+        }
+        if let (Some(sp), Some(cfa)) = (registers.stack_pointer_register, call_frame.cfa) {
+            entry_registers.insert(sp as u16, cfa);
+        }
+        frame_registers.entry_registers = Some(entry_registers);
+    }
+    frame_registers
+}
+
+/// Gets the stack frame information.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `call_frame` - A call frame which is used to evaluate the stack frame.
+/// * `caller_frame` - The call frame of the function that called `call_frame`, if it is known.
+/// * `registers` - A register struct for accessing the register values.
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `cwd` - The work directory of the debugged program.
+///
+/// This function will find stack frame information using a call frame.
+/// The register values recovered in `caller_frame` are used as the register values at the entry
+/// of the function, which are needed to evaluate `DW_OP_entry_value` expressions.
+pub fn create_stack_frame<M: MemoryAccess, R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    call_frame: CallFrame,
+    caller_frame: Option<&CallFrame>,
+    registers: &Registers,
+    mem: &mut M,
+    cwd: &str,
+) -> Result<StackFrame<R>> {
+    let mut call_frames = vec![call_frame];
+    call_frames.extend(caller_frame.cloned());
+    build_stack_frame(dwarf, None, &call_frames, registers, mem, cwd)
+}
+
+/// Gets the stack frame information, using a index to find the function of the frame.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `index` - A index of the DWARF debug information.
+/// * `call_frames` - The call frame which is used to evaluate the stack frame, followed by the
+///   call frame of its caller if it is known, e.g. the unwound call stack from the frame.
+/// * `registers` - A register struct for accessing the register values.
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `cwd` - The work directory of the debugged program.
+///
+/// The same as `create_stack_frame`, but only the compilation unit of the frame is parsed.
+pub fn create_stack_frame_with_index<M: MemoryAccess, R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    index: &DwarfIndex,
+    call_frames: &[CallFrame],
+    registers: &Registers,
+    mem: &mut M,
+    cwd: &str,
+) -> Result<StackFrame<R>> {
+    build_stack_frame(dwarf, Some(index), call_frames, registers, mem, cwd)
+}
+
+/// Gets the stack frame information of the first call frame, the second call frame is its
+/// caller.
+///
+/// The function of the frame is found with the index if there is one, otherwise all the
+/// compilation units are searched.
+fn build_stack_frame<M: MemoryAccess, R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    index: Option<&DwarfIndex>,
+    call_frames: &[CallFrame],
+    registers: &Registers,
+    mem: &mut M,
+    cwd: &str,
+) -> Result<StackFrame<R>> {
+    let (call_frame, caller_frame) = match call_frames {
+        [first, rest @ ..] => (first.clone(), rest.first()),
+        [] => {
+            error!("Requires a call frame");
+            return Err(anyhow!("Requires a call frame"));
+        }
+    };
+
+    // Find the corresponding function to the call frame.
+    let pc = call_frame.code_location as u32;
+    let (section_offset, unit_offset) = match index {
+        Some(index) => find_function_die_with_index(dwarf, index, pc)?,
+        None => find_function_die(dwarf, pc)?,
+    };
+    let header =
+        dwarf
+            .debug_info
+            .header_from_offset(match section_offset.as_debug_info_offset() {
+                Some(val) => val,
+                None => {
+                    return Err(anyhow!(
+                        "Could not convert section offset to debug info offset"
+                    ))
+                }
+            })?;
+    let unit = gimli::Unit::new(dwarf, header)?;
+    let mut tree = unit.entries_tree(Some(unit_offset))?;
+    let node = tree.root()?;
+
+    let die = unit.entry(unit_offset)?;
+    let name = function_name(dwarf, &unit, &die)?;
+
+    // Get source information about the function
+    let source = SourceInformation::get_die_source_information(dwarf, &unit, node.entry(), cwd)?;
+
+    // Get all the variable dies to evaluate.
+    let dies_to_check = get_functions_variables_die_offset(
+        dwarf,
+        section_offset,
+        unit_offset,
+        call_frame.code_location as u32,
+    )?;
+
+    // Get register values
+    let mut temporary_registers = call_frame_registers(&call_frame, caller_frame, registers);
+    if let Some(caller_frame) = caller_frame {
+        if let Err(err) =
+            recover_entry_registers(dwarf, &mut temporary_registers, caller_frame, mem)
+        {
+            trace!("Could not recover the entry registers: {:?}", err);
+        }
+    }
+
+    let (fb_section_offset, fb_unit_offset) = match index {
+        Some(index) => find_non_inlined_function_die_with_index(index, pc)?,
+        None => find_non_inlined_function_die(dwarf, pc)?,
+    };
+    let fb_header =
+        dwarf
+            .debug_info
+            .header_from_offset(match fb_section_offset.as_debug_info_offset() {
+                Some(val) => val,
+                None => {
+                    return Err(anyhow!(
+                        "Could not convert section offset to debug info offset"
+                    ))
+                }
+            })?;
+    let fb_unit = gimli::Unit::new(dwarf, fb_header)?;
+    let fb_die = fb_unit.entry(fb_unit_offset)?;
+    //println!("name: {:?}, tag: {:?}", name, die.tag().static_string());
+    let frame_base =
+        match evaluate_frame_base(dwarf, &unit, pc, &fb_die, &mut temporary_registers, mem) {
+            Ok(val) => val,
+            Err(err) => {
+                error!("{:?}", err);
+                return Err(anyhow!("{:?}", err));
+            }
+        };
+
+    let mut variables = vec![];
+    let mut arguments = vec![];
+
+    for variable_die in dies_to_check {
+        let dwarf_offset = DwarfOffset {
+            section_offset,
+            unit_offset: variable_die,
+        };
+        let argument = is_argument(dwarf, section_offset, variable_die)?;
+        let mut vc = match Variable::get_variable(
+            dwarf,
+            &temporary_registers,
+            mem,
+            dwarf_offset,
+            Some(frame_base),
+            cwd,
+        ) {
+            Ok(v) => v,
+            Err(err) if argument => {
+                // The argument is kept, its value may be recovered from the caller frame.
+                log::error!("Error: {:?}", err);
+                Variable::optimized_out(dwarf, dwarf_offset, cwd)?
+            }
+            Err(err) => {
+                log::error!("Error: {:?}", err);
+                continue;
+            }
+        };
+
+        if argument {
+            // Try to recover optimized out parameters from the call site in the caller frame.
+            let recoverable = matches!(
+                vc.value,
+                EvaluatorValue::OptimizedOut | EvaluatorValue::LocationOutOfRange
+            );
+            if let (true, gimli::DW_TAG_subprogram, Some(caller_frame)) =
+                (recoverable, die.tag(), caller_frame)
+            {
+                match recover_parameter(dwarf, dwarf_offset, caller_frame, registers, mem) {
+                    Ok(Some(value)) => vc.value = value,
+                    Ok(None) => (),
+                    Err(err) => trace!("Could not recover parameter: {:?}", err),
+                };
+            }
+            arguments.push(vc);
+        } else {
+            variables.push(vc);
+        }
+    }
+
+    let mut regs = vec![];
+    for key in 0..call_frame.registers.len() {
+        if let Some(value) = call_frame.registers[key] {
+            regs.push(Variable {
+                name: Some(format!("R{}", key)),
+                value: EvaluatorValue::Value(
+                    BaseTypeValue::Reg32(value),
+                    ValueInformation {
+                        raw: None,
+                        pieces: vec![],
+                    },
+                ),
+                source: None,
+            });
+        };
+    }
+
+    Ok(StackFrame {
+        call_frame,
+        name,
+        source,
+        variables,
+        arguments,
+        registers: regs,
+        frame_base,
+        symbol_only: false,
+        inlined: die.tag() == gimli::DW_TAG_inlined_subroutine,
+        call_location: None,
+    })
+}
+
+/// Find and evaluate a variable in a stack frame, without evaluating the other variables of the
+/// frame.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `call_frame` - A call frame from the virtually unwound call stack.
+/// * `frame_registers` - The register values of the frame, see `call_frame_registers`.
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `cwd` - The work directory of the debugged program.
+/// * `name` - The name of the variable.
+///
+/// The function, the frame base and the variable are all found using the code location, the CFA
+/// and the register values recovered for the frame, so any frame of the call stack can be
+/// used and not only the current one.
+/// Optimized out arguments are not recovered from the caller frame, use `create_stack_frame`
+/// for that.
+/// Will return `Ok(None)` if there is no variable with the given name in the frame.
+pub fn find_frame_variable<M: MemoryAccess, R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    call_frame: &CallFrame,
+    frame_registers: &Registers,
+    mem: &mut M,
+    cwd: &str,
+    name: &str,
+) -> Result<Option<Variable<R>>> {
+    let pc = call_frame.code_location as u32;
+    let (section_offset, unit_offset) = find_function_die(dwarf, pc)?;
+    let unit = get_unit(dwarf, section_offset)?;
+    let (fb_section_offset, fb_unit_offset) = find_non_inlined_function_die(dwarf, pc)?;
+    let fb_unit = get_unit(dwarf, fb_section_offset)?;
+    let fb_die = fb_unit.entry(fb_unit_offset)?;
+
+    let mut temporary_registers = frame_registers.clone();
+    let frame_base = evaluate_frame_base(dwarf, &unit, pc, &fb_die, &mut temporary_registers, mem)?;
+
+    for variable_die in get_functions_variables_die_offset(dwarf, section_offset, unit_offset, pc)?
+    {
+        let die = unit.entry(variable_die)?;
+        if get_var_name(dwarf, &unit, &die)?.as_deref() != Some(name) {
+            continue;
+        }
+        return Ok(Some(Variable::get_variable(
+            dwarf,
+            &temporary_registers,
+            mem,
+            DwarfOffset {
+                section_offset,
+                unit_offset: variable_die,
+            },
+            Some(frame_base),
+            cwd,
+        )?));
+    }
+
+    Ok(None)
+}
+
+/// Evaluate one field of every element of a array variable in a stack frame, without evaluating
+/// the other fields, e.g. for `sensors[*].temp`.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `call_frame` - A call frame from the virtually unwound call stack.
+/// * `frame_registers` - The register values of the frame, see `call_frame_registers`.
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `name` - The name of the array variable.
+/// * `field` - The name of the field, nested fields are separated by `.`, e.g. `pos.x`.
+///
+/// See `evaluate_array_field`, the array is required to be stored in one piece of memory.
+/// Will return `Ok(None)` if there is no variable with the given name in the frame, or if it has
+/// no location.
+pub fn evaluate_frame_array_field<M: MemoryAccess, R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    call_frame: &CallFrame,
+    frame_registers: &Registers,
+    mem: &mut M,
+    name: &str,
+    field: &str,
+) -> Result<Option<Vec<EvaluatorValue<R>>>> {
+    let pc = call_frame.code_location as u32;
+    let (section_offset, unit_offset) = find_function_die(dwarf, pc)?;
+    let unit = get_unit(dwarf, section_offset)?;
+    let (fb_section_offset, fb_unit_offset) = find_non_inlined_function_die(dwarf, pc)?;
+    let fb_unit = get_unit(dwarf, fb_section_offset)?;
+    let fb_die = fb_unit.entry(fb_unit_offset)?;
+
+    let mut temporary_registers = frame_registers.clone();
+    let frame_base = evaluate_frame_base(dwarf, &unit, pc, &fb_die, &mut temporary_registers, mem)?;
+
+    for variable_die in get_functions_variables_die_offset(dwarf, section_offset, unit_offset, pc)?
+    {
+        let die = unit.entry(variable_die)?;
+        if get_var_name(dwarf, &unit, &die)?.as_deref() != Some(name) {
+            continue;
+        }
+        let expression = match find_variable_location(dwarf, &unit, &die, pc)? {
+            VariableLocation::Expression(expr) => expr,
+            VariableLocation::LocationListEntry(llent) => llent.data,
+            _ => return Ok(None),
+        };
+        let pieces = evaluate_pieces(
+            dwarf,
+            &unit,
+            pc,
+            expression,
+            Some(frame_base),
+            &temporary_registers,
+            mem,
+        )?;
+        let array_type = find_variable_type_die(dwarf, &unit, &die)?;
+        return Ok(Some(evaluate_array_field(
+            dwarf,
+            &temporary_registers,
+            mem,
+            &pieces,
+            array_type,
+            field,
+        )?));
+    }
+
+    Ok(None)
+}
+
+/// Get the compilation unit at a offset into the `.debug_info` section.
+fn get_unit<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    section_offset: UnitSectionOffset,
+) -> Result<Unit<R>> {
+    match section_offset.as_debug_info_offset() {
+        Some(offset) => Ok(gimli::Unit::new(
+            dwarf,
+            dwarf.debug_info.header_from_offset(offset)?,
+        )?),
+        None => Err(anyhow!(
+            "Could not convert section offset to debug info offset"
+        )),
     }
 }
 
-/// Gets the stack frame information.
+/// Add a virtual frame for every function that a inlined function is inlined into.
 ///
 /// Description:
 ///
 /// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
-/// * `call_frame` - A call frame which is used to evaluate the stack frame.
+/// * `frames` - The stack frames, e.g. from `create_stack_frames`.
 /// * `registers` - A register struct for accessing the register values.
 /// * `mem` - A struct for accessing the memory of the debug target.
 /// * `cwd` - The work directory of the debugged program.
 ///
-/// This function will find stack frame information using a call frame.
-pub fn create_stack_frame<M: MemoryAccess, R: Reader<Offset = usize>>(
+/// A frame of a inlined function, e.g. a inlined closure or `#[inline]` function, is followed by
+/// a frame for the function it was inlined into, with the source location of the inlined call
+/// in `call_location`.
+/// The added frames share the call frame, registers and frame base of the frame, and have the
+/// variables and arguments that are in scope in their function, see `evaluate_scope_variables`.
+pub fn expand_inlined_frames<R: Reader<Offset = usize>, M: MemoryAccess>(
     dwarf: &Dwarf<R>,
-    call_frame: CallFrame,
+    frames: Vec<StackFrame<R>>,
     registers: &Registers,
     mem: &mut M,
     cwd: &str,
-) -> Result<StackFrame<R>> {
-    // Find the corresponding function to the call frame.
-    let (section_offset, unit_offset) = find_function_die(dwarf, call_frame.code_location as u32)?;
-    let header =
-        dwarf
-            .debug_info
-            .header_from_offset(match section_offset.as_debug_info_offset() {
-                Some(val) => val,
-                None => {
-                    return Err(anyhow!(
-                        "Could not convert section offset to debug info offset"
-                    ))
+) -> Result<Vec<StackFrame<R>>> {
+    let mut result = vec![];
+    for (i, frame) in frames.iter().enumerate() {
+        if !frame.inlined {
+            result.push(frame.clone());
+            continue;
+        }
+
+        let chain = inlined_chain(dwarf, frame.call_frame.code_location as u32, cwd)?;
+        let caller_frame = frames.get(i + 1).map(|f| &f.call_frame);
+        let mut frame_registers = call_frame_registers(&frame.call_frame, caller_frame, registers);
+        if let Some(caller_frame) = caller_frame {
+            if let Err(err) =
+                recover_entry_registers(dwarf, &mut frame_registers, caller_frame, mem)
+            {
+                trace!("Could not recover the entry registers: {:?}", err);
+            }
+        }
+
+        result.push(frame.clone());
+        // The first function in the chain is the function of the frame itself.
+        for j in 1..chain.len() {
+            let function = &chain[j];
+            let (variables, arguments) = evaluate_scope_variables(
+                dwarf,
+                function.offset,
+                &frame_registers,
+                mem,
+                frame.frame_base,
+                cwd,
+            )?;
+            result.push(StackFrame {
+                name: function.name.clone(),
+                source: function.source.clone(),
+                variables,
+                arguments,
+                inlined: function.call_site.is_some(),
+                call_location: chain[j - 1].call_site.clone(),
+                ..frame.clone()
+            });
+        }
+    }
+    Ok(result)
+}
+
+/// A list of evaluated variables.
+pub type Variables<R> = Vec<Variable<R>>;
+
+/// Evaluate the variables and arguments in scope in a function or inlined subroutine.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `function` - The offset to the subprogram or inlined subroutine DIE.
+/// * `frame_registers` - The register values of the frame, see `call_frame_registers`.
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `frame_base` - The frame base of the frame.
+/// * `cwd` - The work directory of the debugged program.
+///
+/// The variables of the inlined subroutines inside the function are not included, see
+/// `get_functions_variables_die_offset`.
+/// Will return the variables and the arguments, in that order.
+pub fn evaluate_scope_variables<R: Reader<Offset = usize>, M: MemoryAccess>(
+    dwarf: &Dwarf<R>,
+    function: DwarfOffset,
+    frame_registers: &Registers,
+    mem: &mut M,
+    frame_base: u64,
+    cwd: &str,
+) -> Result<(Variables<R>, Variables<R>)> {
+    let pc = frame_registers
+        .program_counter_register
+        .and_then(|pc| frame_registers.get_register_value(&(pc as u16)))
+        .copied()
+        .ok_or_else(|| anyhow!("Requires that the program counter registers has a value"))?;
+    let section_offset = function.section_offset;
+
+    let mut variables = vec![];
+    let mut arguments = vec![];
+    for variable_die in
+        get_functions_variables_die_offset(dwarf, section_offset, function.unit_offset, pc)?
+    {
+        let dwarf_offset = DwarfOffset {
+            section_offset,
+            unit_offset: variable_die,
+        };
+        let argument = is_argument(dwarf, section_offset, variable_die)?;
+        let variable = match Variable::get_variable(
+            dwarf,
+            frame_registers,
+            mem,
+            dwarf_offset,
+            Some(frame_base),
+            cwd,
+        ) {
+            Ok(v) => v,
+            Err(err) if argument => {
+                log::error!("Error: {:?}", err);
+                Variable::optimized_out(dwarf, dwarf_offset, cwd)?
+            }
+            Err(err) => {
+                log::error!("Error: {:?}", err);
+                continue;
+            }
+        };
+
+        match argument {
+            true => arguments.push(variable),
+            false => variables.push(variable),
+        };
+    }
+    Ok((variables, arguments))
+}
+
+/// Find the name of the inlined function that contains a machine code address.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `address` - A machine code address.
+/// * `cwd` - The work directory of the debugged program.
+///
+/// Will return the inner most inlined function, or `None` if the address is not in inlined code.
+pub fn find_inlined_function<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    address: u32,
+    cwd: &str,
+) -> Result<Option<String>> {
+    let chain = inlined_chain(dwarf, address, cwd)?;
+    Ok(chain
+        .into_iter()
+        .next()
+        .filter(|function| function.call_site.is_some())
+        .map(|function| function.name))
+}
+
+/// A function in the chain of inlined functions that contain a machine code address.
+struct InlinedFunction {
+    /// The name of the function.
+    name: String,
+
+    /// The source code declaration location of the function.
+    source: SourceInformation,
+
+    /// The source location of the inlined call, `None` for the function that is not inlined.
+    call_site: Option<SourceInformation>,
+
+    /// The offset to the subprogram or inlined subroutine DIE.
+    offset: DwarfOffset,
+}
+
+/// Get the functions that contain a machine code address, the inner most first.
+fn inlined_chain<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    address: u32,
+    cwd: &str,
+) -> Result<Vec<InlinedFunction>> {
+    let unit = get_current_unit(dwarf, address)?;
+    let mut cursor = unit.entries();
+
+    // The offsets of the functions that contain the address and their depth, the outer most
+    // first.
+    let mut functions: Vec<(isize, UnitOffset)> = vec![];
+    let mut depth = 0;
+    assert!(cursor.next_dfs()?.is_some());
+    while let Some((delta_depth, current)) = cursor.next_dfs()? {
+        depth += delta_depth;
+        if depth <= 0 {
+            break;
+        }
+
+        match current.tag() {
+            gimli::DW_TAG_subprogram | gimli::DW_TAG_inlined_subroutine => {
+                if let Some(true) = die_in_range(dwarf, &unit, current, address) {
+                    functions.retain(|(d, _)| *d < depth);
+                    functions.push((depth, current.offset()));
                 }
-            })?;
-    let unit = gimli::Unit::new(dwarf, header)?;
-    let mut tree = unit.entries_tree(Some(unit_offset))?;
-    let node = tree.root()?;
+            }
+            _ => (),
+        };
+    }
 
-    let die = unit.entry(unit_offset)?;
-    // Get the name of the function.
-    let name = match die.attr_value(gimli::DW_AT_name)? {
+    let mut chain = vec![];
+    for (_, offset) in functions.iter().rev() {
+        let die = unit.entry(*offset)?;
+        let call_site = match die.tag() {
+            gimli::DW_TAG_inlined_subroutine => Some(
+                SourceInformation::get_die_call_source_information(dwarf, &unit, &die, cwd)?,
+            ),
+            _ => None,
+        };
+        chain.push(InlinedFunction {
+            name: function_name(dwarf, &unit, &die)?,
+            source: SourceInformation::get_die_source_information(dwarf, &unit, &die, cwd)?,
+            call_site,
+            offset: DwarfOffset {
+                section_offset: unit.header.offset(),
+                unit_offset: *offset,
+            },
+        });
+    }
+    Ok(chain)
+}
+
+/// Get the name of a subprogram or inlined subroutine DIE, the name is read from the abstract
+/// origin if the DIE has no name.
+fn function_name<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    unit: &Unit<R>,
+    die: &DebuggingInformationEntry<R>,
+) -> Result<String> {
+    Ok(match die.attr_value(gimli::DW_AT_name)? {
         Some(DebugStrRef(offset)) => format!("{:?}", dwarf.string(offset)?.to_string()?),
         _ => match die.attr_value(gimli::DW_AT_abstract_origin)? {
             Some(offset) => match offset {
@@ -708,89 +2149,32 @@ pub fn create_stack_frame<M: MemoryAccess, R: Reader<Offset = usize>>(
             },
             None => "<unknown>".to_string(),
         },
-    };
-
-    // Get source information about the function
-    let source = SourceInformation::get_die_source_information(dwarf, &unit, node.entry(), cwd)?;
-
-    // Get all the variable dies to evaluate.
-    let dies_to_check = get_functions_variables_die_offset(
-        dwarf,
-        section_offset,
-        unit_offset,
-        call_frame.code_location as u32,
-    )?;
-
-    // Get register values
-    let mut temporary_registers = Registers::default();
-    temporary_registers.program_counter_register = registers.program_counter_register;
-    temporary_registers.link_register = registers.link_register;
-    temporary_registers.stack_pointer_register = registers.stack_pointer_register;
-    temporary_registers.cfa = call_frame.cfa;
-    let pc = call_frame.code_location as u32;
-    for i in 0..call_frame.registers.len() {
-        match call_frame.registers[i] {
-            Some(val) => temporary_registers.add_register_value(i as u16, val),
-            None => (),
-        };
-    }
-
-    let (fb_section_offset, fb_unit_offset) = find_non_inlined_function_die(dwarf, pc)?;
-    let fb_header =
-        dwarf
-            .debug_info
-            .header_from_offset(match fb_section_offset.as_debug_info_offset() {
-                Some(val) => val,
-                None => {
-                    return Err(anyhow!(
-                        "Could not convert section offset to debug info offset"
-                    ))
-                }
-            })?;
-    let fb_unit = gimli::Unit::new(dwarf, fb_header)?;
-    let fb_die = fb_unit.entry(fb_unit_offset)?;
-    //println!("name: {:?}, tag: {:?}", name, die.tag().static_string());
-    let frame_base =
-        match evaluate_frame_base(dwarf, &unit, pc, &fb_die, &mut temporary_registers, mem) {
-            Ok(val) => val,
-            Err(err) => {
-                error!("{:?}", err);
-                return Err(anyhow!("{:?}", err));
-            }
-        };
-
-    let mut variables = vec![];
-    let mut arguments = vec![];
-
-    for variable_die in dies_to_check {
-        let vc = match Variable::get_variable(
-            dwarf,
-            &temporary_registers,
-            mem,
-            DwarfOffset {
-                section_offset,
-                unit_offset: variable_die,
-            },
-            Some(frame_base),
-            cwd,
-        ) {
-            Ok(v) => v,
-            Err(err) => {
-                log::error!("Error: {:?}", err);
-                continue;
-            }
-        };
+    })
+}
 
-        if is_argument(dwarf, section_offset, variable_die)? {
-            arguments.push(vc);
-        } else {
-            variables.push(vc);
-        }
-    }
+/// Creates a stack frame for a call frame without DWARF debug information.
+///
+/// Description:
+///
+/// * `call_frame` - A call frame which is used to create the stack frame.
+/// * `symbols` - The symbol table used to find the name of the frame's function.
+///
+/// The name is the name of the function symbol with the offset into it, e.g. `memcpy+0x1a`.
+/// The stack frame has no variables, arguments or source information, and the frame base is set
+/// to the CFA.
+pub fn create_symbol_stack_frame<R: Reader<Offset = usize>>(
+    call_frame: CallFrame,
+    symbols: &SymbolTable,
+) -> StackFrame<R> {
+    let name = match symbols.find_function(call_frame.code_location) {
+        Some((symbol, 0)) => symbol.name.clone(),
+        Some((symbol, offset)) => format!("{}+{:#x}", symbol.name, offset),
+        None => format!("<unknown> {:#010x}", call_frame.code_location),
+    };
 
     let mut regs = vec![];
-    for key in 0..call_frame.registers.len() {
-        if let Some(value) = call_frame.registers[key] {
+    for (key, value) in call_frame.registers.iter().enumerate() {
+        if let Some(value) = *value {
             regs.push(Variable {
                 name: Some(format!("R{}", key)),
                 value: EvaluatorValue::Value(
@@ -805,15 +2189,23 @@ pub fn create_stack_frame<M: MemoryAccess, R: Reader<Offset = usize>>(
         };
     }
 
-    Ok(StackFrame {
+    StackFrame {
+        frame_base: call_frame.cfa.unwrap_or(0) as u64,
         call_frame,
         name,
-        source,
-        variables,
-        arguments,
+        source: SourceInformation {
+            directory: None,
+            file: None,
+            line: None,
+            column: None,
+        },
+        variables: vec![],
+        arguments: vec![],
         registers: regs,
-        frame_base,
-    })
+        symbol_only: true,
+        inlined: false,
+        call_location: None,
+    }
 }
 
 /// Will find the DIE representing the searched function
@@ -830,6 +2222,35 @@ pub fn find_function_die<R: Reader<Offset = usize>>(
     address: u32,
 ) -> Result<(gimli::UnitSectionOffset, gimli::UnitOffset)> {
     let unit = get_current_unit(dwarf, address)?;
+    find_function_die_in_unit(dwarf, &unit, address)
+}
+
+/// Will find the DIE representing the searched function, using a index to find the compilation
+/// unit.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `index` - A index of the DWARF debug information.
+/// * `address` - Used to find which function this machine code address belongs too.
+///
+/// The same as `find_function_die`, but only the compilation unit of the address is parsed.
+pub fn find_function_die_with_index<R: Reader<Offset = usize>>(
+    dwarf: &'_ Dwarf<R>,
+    index: &DwarfIndex,
+    address: u32,
+) -> Result<(gimli::UnitSectionOffset, gimli::UnitOffset)> {
+    let unit = index.unit(dwarf, address)?;
+    find_function_die_in_unit(dwarf, &unit, address)
+}
+
+/// Find the innermost function or inlined function DIE in a compilation unit that contains a
+/// address.
+fn find_function_die_in_unit<R: Reader<Offset = usize>>(
+    dwarf: &'_ Dwarf<R>,
+    unit: &Unit<R>,
+    address: u32,
+) -> Result<(gimli::UnitSectionOffset, gimli::UnitOffset)> {
     let mut cursor = unit.entries();
 
     let mut depth = 0;
@@ -847,7 +2268,7 @@ pub fn find_function_die<R: Reader<Offset = usize>>(
 
         match current.tag() {
             gimli::DW_TAG_subprogram | gimli::DW_TAG_inlined_subroutine => {
-                if let Some(true) = die_in_range(dwarf, &unit, current, address) {
+                if let Some(true) = die_in_range(dwarf, unit, current, address) {
                     match res {
                         Some(val) => {
                             match val {
@@ -941,6 +2362,27 @@ pub fn find_non_inlined_function_die<R: Reader<Offset = usize>>(
     Ok((unit.header.offset(), dies[0].offset()))
 }
 
+/// Will find the DIE representing the searched non inlined function, using a index.
+///
+/// Description:
+///
+/// * `index` - A index of the DWARF debug information.
+/// * `address` - Used to find which function this machine code address belongs too.
+///
+/// The same as `find_non_inlined_function_die`, but no DIEs are walked.
+pub fn find_non_inlined_function_die_with_index(
+    index: &DwarfIndex,
+    address: u32,
+) -> Result<(gimli::UnitSectionOffset, gimli::UnitOffset)> {
+    match index.find_function(address) {
+        Some(offset) => Ok((offset.section_offset, offset.unit_offset)),
+        None => {
+            error!("No function contains {:#010x}", address);
+            Err(anyhow!("No function contains {:#010x}", address))
+        }
+    }
+}
+
 /// Will find all the in range variable DIEs in a subroutine.
 ///
 /// Description:
@@ -953,6 +2395,8 @@ pub fn find_non_inlined_function_die<R: Reader<Offset = usize>>(
 ///
 /// This function will go done the subtree of a subroutine DIE and return all in range variable
 /// DIEs.
+/// The subtrees of inlined subroutines are skipped, their variables are in the frames that
+/// `expand_inlined_frames` adds.
 pub fn get_functions_variables_die_offset<R: Reader<Offset = usize>>(
     dwarf: &Dwarf<R>,
     section_offset: UnitSectionOffset,
@@ -971,6 +2415,9 @@ pub fn get_functions_variables_die_offset<R: Reader<Offset = usize>>(
         if let Some(false) = die_in_range(dwarf, unit, die, pc) {
             return Ok(());
         };
+        if die.tag() == gimli::DW_TAG_inlined_subroutine {
+            return Ok(());
+        }
 
         if is_variable_die(die) {
             list.push(die.offset());
@@ -1102,3 +2549,130 @@ fn is_argument<R: Reader<Offset = usize>>(
         _ => Ok(false),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A memory where every byte is the low byte of its address, the reads are recorded.
+    fn memory(reads: &mut Vec<(u32, usize)>) -> impl FnMut(&u32, usize) -> Option<Vec<u8>> + '_ {
+        move |address: &u32, num_bytes: usize| {
+            reads.push((*address, num_bytes));
+            Some(
+                (0..num_bytes)
+                    .map(|i| address.wrapping_add(i as u32) as u8)
+                    .collect(),
+            )
+        }
+    }
+
+    #[test]
+    fn read_ahead_inside_ram() {
+        let mut reads = vec![];
+        let mut inner = memory(&mut reads);
+        let ram = [0x2000_0000..0x2000_1000];
+        let mut memory = ReadAheadMemory::new(&mut inner, 16, &ram);
+
+        assert_eq!(memory.get_address(&0x2000_0004, 4), Some(vec![4, 5, 6, 7]));
+        assert_eq!(memory.get_address(&0x2000_0008, 2), Some(vec![8, 9]));
+        assert_eq!(
+            memory.get_address(&0x2000_000e, 4),
+            Some(vec![0x0e, 0x0f, 0x10, 0x11])
+        );
+        assert_eq!(memory.transactions, 2);
+        drop(memory);
+        drop(inner);
+        assert_eq!(reads, vec![(0x2000_0000, 16), (0x2000_0010, 16)]);
+    }
+
+    #[test]
+    fn no_read_ahead_outside_ram() {
+        let mut reads = vec![];
+        let mut inner = memory(&mut reads);
+        let ram = [0x2000_0000..0x2000_1000];
+        let mut memory = ReadAheadMemory::new(&mut inner, 16, &ram);
+
+        // A peripheral register, and a read that would wrap around the address space.
+        assert_eq!(memory.get_address(&0x4000_0004, 4), Some(vec![4, 5, 6, 7]));
+        assert_eq!(
+            memory.get_address(&0xffff_fffe, 4),
+            Some(vec![0xfe, 0xff, 0, 1])
+        );
+        // A read that crosses the end of the RAM.
+        assert!(memory.get_address(&0x2000_0ffe, 4).is_some());
+        drop(memory);
+        drop(inner);
+        assert_eq!(
+            reads,
+            vec![(0x4000_0004, 4), (0xffff_fffe, 4), (0x2000_0ffe, 4)]
+        );
+    }
+
+    fn call_frame(code_location: u64, cfa: u32, exception_return: Option<u32>) -> CallFrame {
+        let mut registers = [None; UNWIND_REGISTER_COUNT];
+        for (i, register) in registers.iter_mut().enumerate().take(12) {
+            *register = Some(i as u32);
+        }
+        CallFrame {
+            id: code_location,
+            registers,
+            code_location,
+            cfa: Some(cfa),
+            start_address: code_location & !0xff,
+            end_address: (code_location & !0xff) + 0x100,
+            exception_return,
+        }
+    }
+
+    #[test]
+    fn restart_frame_only_core_registers() {
+        let registers = Registers::for_architecture(Architecture::CortexM);
+        let call_stack = [
+            call_frame(0x0800_0110, 0x2000_0f00, None),
+            call_frame(0x0800_0205, 0x2000_0f20, None),
+        ];
+        let restart = restart_frame_registers(&call_stack, 0, &registers).unwrap();
+
+        assert_eq!(restart.unknown_registers, vec![12]);
+        assert!(restart.registers.contains(&(13, 0x2000_0f00)));
+        assert!(restart.registers.contains(&(14, 0x0800_0207)));
+        assert!(restart.registers.contains(&(15, 0x0800_0100)));
+    }
+
+    #[test]
+    fn restart_exception_handler() {
+        let registers = Registers::for_architecture(Architecture::CortexM);
+        let call_stack = [
+            call_frame(0x0800_0110, 0x2000_0f00, None),
+            call_frame(0x0800_0204, 0x2000_0f20, Some(0xffff_fff9)),
+        ];
+        let restart = restart_frame_registers(&call_stack, 0, &registers).unwrap();
+
+        assert!(restart.registers.contains(&(14, 0xffff_fff9)));
+        assert_eq!(
+            restart.registers.len() + restart.unknown_registers.len(),
+            16
+        );
+    }
+
+    #[test]
+    fn step_out_to_return_address() {
+        let call_stack = [
+            call_frame(0x0800_0110, 0x2000_0f00, None),
+            call_frame(0x0800_0205, 0x2000_0f20, None),
+        ];
+        let target = step_out_target(&call_stack, 0, Architecture::CortexM).unwrap();
+        assert_eq!(target.return_address, 0x0800_0206);
+        assert_eq!(target.cfa, 0x2000_0f00);
+    }
+
+    #[test]
+    fn step_out_of_exception_handler() {
+        let call_stack = [
+            call_frame(0x0800_0110, 0x2000_0f00, None),
+            call_frame(0x0800_0204, 0x2000_0f20, Some(0xffff_fff9)),
+        ];
+        let target = step_out_target(&call_stack, 0, Architecture::CortexM).unwrap();
+        assert_eq!(target.return_address, 0x0800_0204);
+    }
+}
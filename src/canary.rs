@@ -0,0 +1,145 @@
+use crate::call_stack::MemoryAccess;
+use anyhow::{anyhow, Result};
+use log::error;
+
+/// A memory region that is expected to always contain a repeated byte pattern, e.g. a stack
+/// guard or a heap canary.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GuardRegion {
+    /// The name of the region, used to identify it in the violations.
+    pub name: String,
+
+    /// The start address of the region.
+    pub address: u32,
+
+    /// The number of bytes in the region.
+    pub length: usize,
+
+    /// The expected content of the region, it is repeated to fill the whole region.
+    pub pattern: Vec<u8>,
+}
+
+impl GuardRegion {
+    /// Creates a new `GuardRegion`.
+    ///
+    /// Description:
+    ///
+    /// * `name` - The name of the region.
+    /// * `address` - The start address of the region.
+    /// * `length` - The number of bytes in the region.
+    /// * `pattern` - The expected content, e.g. `0xdeadbeef` as bytes for a stack guard.
+    pub fn new(name: &str, address: u32, length: usize, pattern: &[u8]) -> GuardRegion {
+        GuardRegion {
+            name: name.to_string(),
+            address,
+            length,
+            pattern: pattern.to_vec(),
+        }
+    }
+
+    /// Get the expected value of the byte at a offset into the region.
+    fn expected_byte(&self, offset: usize) -> u8 {
+        self.pattern[offset % self.pattern.len()]
+    }
+}
+
+/// A guard region that no longer contains its expected pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanaryViolation {
+    /// The name of the region.
+    pub name: String,
+
+    /// The address of the first corrupted byte.
+    pub address: u32,
+
+    /// The expected value of the first corrupted byte.
+    pub expected: u8,
+
+    /// The actual value of the first corrupted byte.
+    pub actual: u8,
+
+    /// The number of corrupted bytes in the region.
+    pub corrupted_bytes: usize,
+}
+
+/// Keeps track of the guard regions that are verified every time the debug target halts.
+///
+/// No regions are registered by default, which makes the checks opt in.
+#[derive(Debug, Clone, Default)]
+pub struct CanaryMonitor {
+    /// The registered guard regions.
+    pub regions: Vec<GuardRegion>,
+}
+
+impl CanaryMonitor {
+    /// Creates a new `CanaryMonitor` without any guard regions.
+    pub fn new() -> CanaryMonitor {
+        CanaryMonitor { regions: vec![] }
+    }
+
+    /// Register a guard region, a region with the same name is replaced.
+    ///
+    /// Description:
+    ///
+    /// * `region` - The guard region.
+    pub fn add_region(&mut self, region: GuardRegion) -> Result<()> {
+        if region.pattern.is_empty() {
+            error!("Guard region {} has an empty pattern", region.name);
+            return Err(anyhow!("Guard region {} has an empty pattern", region.name));
+        }
+
+        self.remove_region(&region.name);
+        self.regions.push(region);
+        Ok(())
+    }
+
+    /// Remove a guard region.
+    ///
+    /// Description:
+    ///
+    /// * `name` - The name of the region.
+    ///
+    /// Will return `true` if the region was registered.
+    pub fn remove_region(&mut self, name: &str) -> bool {
+        let len = self.regions.len();
+        self.regions.retain(|r| r.name != name);
+        len != self.regions.len()
+    }
+
+    /// Verify all the guard regions.
+    ///
+    /// Description:
+    ///
+    /// * `mem` - A struct for accessing the memory of the debug target.
+    ///
+    /// This should be done every time the debug target halts, a non empty result should be
+    /// reported as its own stop reason.
+    pub fn check<M: MemoryAccess>(&self, mem: &mut M) -> Result<Vec<CanaryViolation>> {
+        let mut violations = vec![];
+        for region in &self.regions {
+            let data = match mem.get_address(&region.address, region.length) {
+                Some(val) => val,
+                None => {
+                    error!("Could not read the guard region {}", region.name);
+                    return Err(anyhow!("Could not read the guard region {}", region.name));
+                }
+            };
+
+            let mut corrupted = data
+                .iter()
+                .enumerate()
+                .filter(|(i, b)| **b != region.expected_byte(*i));
+            if let Some((offset, actual)) = corrupted.next() {
+                violations.push(CanaryViolation {
+                    name: region.name.clone(),
+                    address: region.address + offset as u32,
+                    expected: region.expected_byte(offset),
+                    actual: *actual,
+                    corrupted_bytes: corrupted.count() + 1,
+                });
+            }
+        }
+
+        Ok(violations)
+    }
+}
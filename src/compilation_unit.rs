@@ -0,0 +1,104 @@
+use anyhow::Result;
+use gimli::{AttributeValue, DwLang, Dwarf, Reader, Unit, UnitSectionOffset};
+
+/// Information about a compilation unit.
+#[derive(Debug, Clone)]
+pub struct CompilationUnitInfo {
+    /// The offset to the compilation unit.
+    pub offset: UnitSectionOffset,
+
+    /// The name of the compilation unit, usually the path to the main source file.
+    pub name: Option<String>,
+
+    /// The work directory of the compilation.
+    pub comp_dir: Option<String>,
+
+    /// The producer of the compilation unit, usually the name and version of the compiler.
+    pub producer: Option<String>,
+
+    /// The source language of the compilation unit.
+    pub language: Option<DwLang>,
+
+    /// The optimization level flag found in the producer, e.g. `-O2` or `opt-level=3`.
+    pub optimization_hint: Option<String>,
+
+    /// `true` if the compilation unit has line number information.
+    pub has_line_info: bool,
+}
+
+/// Will retrieve information about all the compilation units.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+///
+/// This is useful for spotting mixed language programs and crates compiled without debug
+/// information.
+pub fn compilation_units<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+) -> Result<Vec<CompilationUnitInfo>> {
+    let mut result = vec![];
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        let unit = dwarf.unit(header)?;
+        result.push(compilation_unit_info(dwarf, &unit)?);
+    }
+
+    Ok(result)
+}
+
+/// Will retrieve information about a compilation unit.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `unit` - A reference to gimli-rs `Unit` struct.
+pub fn compilation_unit_info<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    unit: &Unit<R>,
+) -> Result<CompilationUnitInfo> {
+    let mut entries = unit.entries();
+    let root = entries.next_dfs()?.map(|(_, die)| die.clone());
+
+    let (producer, language) = match &root {
+        Some(die) => {
+            let producer = match die.attr_value(gimli::DW_AT_producer)? {
+                Some(value) => Some(dwarf.attr_string(unit, value)?.to_string()?.to_string()),
+                None => None,
+            };
+            let language = match die.attr_value(gimli::DW_AT_language)? {
+                Some(AttributeValue::Language(val)) => Some(val),
+                _ => None,
+            };
+            (producer, language)
+        }
+        None => (None, None),
+    };
+
+    let name = match &unit.name {
+        Some(val) => Some(val.to_string()?.to_string()),
+        None => None,
+    };
+    let comp_dir = match &unit.comp_dir {
+        Some(val) => Some(val.to_string()?.to_string()),
+        None => None,
+    };
+
+    Ok(CompilationUnitInfo {
+        offset: unit.header.offset(),
+        name,
+        comp_dir,
+        optimization_hint: producer.as_deref().and_then(optimization_hint),
+        producer,
+        language,
+        has_line_info: unit.line_program.is_some(),
+    })
+}
+
+/// Find the optimization level flag in a producer string.
+fn optimization_hint(producer: &str) -> Option<String> {
+    producer
+        .split_whitespace()
+        .find(|flag| flag.starts_with("-O") || flag.contains("opt-level="))
+        .map(|flag| flag.to_string())
+}
@@ -0,0 +1,413 @@
+use crate::call_stack::MemoryAccess;
+use crate::memory_view::read_word;
+use anyhow::{anyhow, Result};
+use gimli::RunTimeEndian;
+use log::error;
+use std::collections::BTreeSet;
+
+/// The `DBG_SLEEP` bit in `DBGMCU_CR`, it keeps the debug clocks running in sleep mode.
+pub const DBGMCU_CR_DBG_SLEEP: u32 = 1;
+
+/// The `DBG_STOP` bit in `DBGMCU_CR`, it keeps the debug clocks running in stop mode.
+pub const DBGMCU_CR_DBG_STOP: u32 = 1 << 1;
+
+/// The `DBG_STANDBY` bit in `DBGMCU_CR`, it keeps the debug clocks running in standby mode.
+pub const DBGMCU_CR_DBG_STANDBY: u32 = 1 << 2;
+
+/// The debug MCU configuration of a STM32 family.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DbgmcuFamily {
+    /// The prefix of the chip names in the family, e.g. `STM32F4`.
+    pub prefix: &'static str,
+
+    /// The address of the `DBGMCU_CR` register.
+    pub cr_address: u32,
+
+    /// The bits of `DBGMCU_CR` that keep the debug connection working in the low-power modes.
+    pub low_power_bits: u32,
+}
+
+impl DbgmcuFamily {
+    /// Get the write that keeps the debug connection working in the low-power modes, this
+    /// should be done when attaching to the debug target.
+    ///
+    /// Description:
+    ///
+    /// * `enabled` - `true` to keep the debug clocks running, `false` to restore the reset
+    ///   behavior of stopping them.
+    pub fn low_power_debug(&self, enabled: bool) -> RegisterWrite {
+        RegisterWrite {
+            address: self.cr_address,
+            mask: self.low_power_bits,
+            value: match enabled {
+                true => self.low_power_bits,
+                false => 0,
+            },
+        }
+    }
+}
+
+/// The debug MCU configuration of the supported STM32 families.
+pub const DBGMCU_FAMILIES: [DbgmcuFamily; 12] = [
+    DbgmcuFamily {
+        prefix: "STM32F0",
+        cr_address: 0x4001_5804,
+        low_power_bits: DBGMCU_CR_DBG_STOP | DBGMCU_CR_DBG_STANDBY,
+    },
+    DbgmcuFamily {
+        prefix: "STM32F1",
+        cr_address: 0xE004_2004,
+        low_power_bits: DBGMCU_CR_DBG_SLEEP | DBGMCU_CR_DBG_STOP | DBGMCU_CR_DBG_STANDBY,
+    },
+    DbgmcuFamily {
+        prefix: "STM32F2",
+        cr_address: 0xE004_2004,
+        low_power_bits: DBGMCU_CR_DBG_SLEEP | DBGMCU_CR_DBG_STOP | DBGMCU_CR_DBG_STANDBY,
+    },
+    DbgmcuFamily {
+        prefix: "STM32F3",
+        cr_address: 0xE004_2004,
+        low_power_bits: DBGMCU_CR_DBG_SLEEP | DBGMCU_CR_DBG_STOP | DBGMCU_CR_DBG_STANDBY,
+    },
+    DbgmcuFamily {
+        prefix: "STM32F4",
+        cr_address: 0xE004_2004,
+        low_power_bits: DBGMCU_CR_DBG_SLEEP | DBGMCU_CR_DBG_STOP | DBGMCU_CR_DBG_STANDBY,
+    },
+    DbgmcuFamily {
+        prefix: "STM32F7",
+        cr_address: 0xE004_2004,
+        low_power_bits: DBGMCU_CR_DBG_SLEEP | DBGMCU_CR_DBG_STOP | DBGMCU_CR_DBG_STANDBY,
+    },
+    DbgmcuFamily {
+        prefix: "STM32G0",
+        cr_address: 0x4001_5804,
+        low_power_bits: DBGMCU_CR_DBG_STOP | DBGMCU_CR_DBG_STANDBY,
+    },
+    DbgmcuFamily {
+        prefix: "STM32G4",
+        cr_address: 0xE004_2004,
+        low_power_bits: DBGMCU_CR_DBG_SLEEP | DBGMCU_CR_DBG_STOP | DBGMCU_CR_DBG_STANDBY,
+    },
+    DbgmcuFamily {
+        prefix: "STM32H7",
+        cr_address: 0x5C00_1004,
+        low_power_bits: DBGMCU_CR_DBG_SLEEP | DBGMCU_CR_DBG_STOP | DBGMCU_CR_DBG_STANDBY,
+    },
+    DbgmcuFamily {
+        prefix: "STM32L0",
+        cr_address: 0x4001_5804,
+        low_power_bits: DBGMCU_CR_DBG_SLEEP | DBGMCU_CR_DBG_STOP | DBGMCU_CR_DBG_STANDBY,
+    },
+    DbgmcuFamily {
+        prefix: "STM32L1",
+        cr_address: 0xE004_2004,
+        low_power_bits: DBGMCU_CR_DBG_SLEEP | DBGMCU_CR_DBG_STOP | DBGMCU_CR_DBG_STANDBY,
+    },
+    DbgmcuFamily {
+        prefix: "STM32L4",
+        cr_address: 0xE004_2004,
+        low_power_bits: DBGMCU_CR_DBG_SLEEP | DBGMCU_CR_DBG_STOP | DBGMCU_CR_DBG_STANDBY,
+    },
+];
+
+/// A read-modify-write of a 32 bit register of the debug target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegisterWrite {
+    /// The address of the register.
+    pub address: u32,
+
+    /// The bits that are written.
+    pub mask: u32,
+
+    /// The new value of the bits in `mask`.
+    pub value: u32,
+}
+
+impl RegisterWrite {
+    /// Get the new value of the register.
+    ///
+    /// Description:
+    ///
+    /// * `current` - The current value of the register.
+    pub fn apply(&self, current: u32) -> u32 {
+        (current & !self.mask) | (self.value & self.mask)
+    }
+
+    /// Read the register and get its new value.
+    ///
+    /// Description:
+    ///
+    /// * `mem` - A struct for accessing the memory of the debug target.
+    /// * `endian` - The endianness of the debug target.
+    ///
+    /// The new value has to be written by the caller since `MemoryAccess` can only read.
+    pub fn read_and_apply<M: MemoryAccess>(
+        &self,
+        mem: &mut M,
+        endian: RunTimeEndian,
+    ) -> Result<u32> {
+        match mem
+            .get_address(&self.address, 4)
+            .and_then(|val| read_word(&val, endian))
+        {
+            Some(val) => Ok(self.apply(val)),
+            None => {
+                error!("Could not read the register at {:#010x}", self.address);
+                Err(anyhow!(
+                    "Could not read the register at {:#010x}",
+                    self.address
+                ))
+            }
+        }
+    }
+}
+
+/// Find the debug MCU configuration of a chip.
+///
+/// Description:
+///
+/// * `chip` - The name of the chip, e.g. `STM32F407VGTx`.
+///
+/// Will return `None` if the chip is not in a supported family.
+pub fn find_family(chip: &str) -> Option<&'static DbgmcuFamily> {
+    let chip = chip.to_uppercase();
+    DBGMCU_FAMILIES
+        .iter()
+        .find(|family| chip.starts_with(family.prefix))
+}
+
+/// A debug MCU bit that stops the counter of a peripheral while the core is halted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FreezeBit {
+    /// The name of the peripheral, e.g. `IWDG` or `TIM2`.
+    pub peripheral: &'static str,
+
+    /// The address of the register the bit is in.
+    pub address: u32,
+
+    /// The bit number in the register.
+    pub bit: u8,
+}
+
+/// Creates a `FreezeBit`.
+const fn freeze(peripheral: &'static str, address: u32, bit: u8) -> FreezeBit {
+    FreezeBit {
+        peripheral,
+        address,
+        bit,
+    }
+}
+
+/// The freeze bits of the STM32F1 family, they are in `DBGMCU_CR`.
+const STM32F1_FREEZE_BITS: [FreezeBit; 13] = [
+    freeze("IWDG", 0xE004_2004, 8),
+    freeze("WWDG", 0xE004_2004, 9),
+    freeze("TIM1", 0xE004_2004, 10),
+    freeze("TIM2", 0xE004_2004, 11),
+    freeze("TIM3", 0xE004_2004, 12),
+    freeze("TIM4", 0xE004_2004, 13),
+    freeze("CAN1", 0xE004_2004, 14),
+    freeze("I2C1", 0xE004_2004, 15),
+    freeze("I2C2", 0xE004_2004, 16),
+    freeze("TIM8", 0xE004_2004, 17),
+    freeze("TIM5", 0xE004_2004, 18),
+    freeze("TIM6", 0xE004_2004, 19),
+    freeze("TIM7", 0xE004_2004, 20),
+];
+
+/// The freeze bits of the STM32F2 and STM32F4 families, they are in `DBGMCU_APB1_FZ` and
+/// `DBGMCU_APB2_FZ`.
+const STM32F4_FREEZE_BITS: [FreezeBit; 22] = [
+    freeze("TIM2", 0xE004_2008, 0),
+    freeze("TIM3", 0xE004_2008, 1),
+    freeze("TIM4", 0xE004_2008, 2),
+    freeze("TIM5", 0xE004_2008, 3),
+    freeze("TIM6", 0xE004_2008, 4),
+    freeze("TIM7", 0xE004_2008, 5),
+    freeze("TIM12", 0xE004_2008, 6),
+    freeze("TIM13", 0xE004_2008, 7),
+    freeze("TIM14", 0xE004_2008, 8),
+    freeze("RTC", 0xE004_2008, 10),
+    freeze("WWDG", 0xE004_2008, 11),
+    freeze("IWDG", 0xE004_2008, 12),
+    freeze("I2C1", 0xE004_2008, 21),
+    freeze("I2C2", 0xE004_2008, 22),
+    freeze("I2C3", 0xE004_2008, 23),
+    freeze("CAN1", 0xE004_2008, 25),
+    freeze("CAN2", 0xE004_2008, 26),
+    freeze("TIM1", 0xE004_200C, 0),
+    freeze("TIM8", 0xE004_200C, 1),
+    freeze("TIM9", 0xE004_200C, 16),
+    freeze("TIM10", 0xE004_200C, 17),
+    freeze("TIM11", 0xE004_200C, 18),
+];
+
+/// The freeze bits of the STM32L4 family, they are in `DBGMCU_APB1FZR1` and `DBGMCU_APB2FZR`.
+const STM32L4_FREEZE_BITS: [FreezeBit; 19] = [
+    freeze("TIM2", 0xE004_2008, 0),
+    freeze("TIM3", 0xE004_2008, 1),
+    freeze("TIM4", 0xE004_2008, 2),
+    freeze("TIM5", 0xE004_2008, 3),
+    freeze("TIM6", 0xE004_2008, 4),
+    freeze("TIM7", 0xE004_2008, 5),
+    freeze("RTC", 0xE004_2008, 10),
+    freeze("WWDG", 0xE004_2008, 11),
+    freeze("IWDG", 0xE004_2008, 12),
+    freeze("I2C1", 0xE004_2008, 21),
+    freeze("I2C2", 0xE004_2008, 22),
+    freeze("I2C3", 0xE004_2008, 23),
+    freeze("CAN1", 0xE004_2008, 25),
+    freeze("LPTIM1", 0xE004_2008, 31),
+    freeze("TIM1", 0xE004_2010, 11),
+    freeze("TIM8", 0xE004_2010, 13),
+    freeze("TIM15", 0xE004_2010, 16),
+    freeze("TIM16", 0xE004_2010, 17),
+    freeze("TIM17", 0xE004_2010, 18),
+];
+
+/// Get the freeze bits of a chip.
+///
+/// Description:
+///
+/// * `chip` - The name of the chip, e.g. `STM32F407VGTx`.
+///
+/// Will return a empty slice if the freeze bits of the chip family are not known.
+pub fn freeze_bits(chip: &str) -> &'static [FreezeBit] {
+    match find_family(chip).map(|family| family.prefix) {
+        Some("STM32F1") => &STM32F1_FREEZE_BITS,
+        Some("STM32F2") | Some("STM32F4") => &STM32F4_FREEZE_BITS,
+        Some("STM32L4") => &STM32L4_FREEZE_BITS,
+        _ => &[],
+    }
+}
+
+/// The peripheral counters that are stopped while the core is halted, e.g. so that stepping
+/// does not trip the independent watchdog.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FreezeMask {
+    /// The names of the peripherals, e.g. `IWDG` and `TIM2`.
+    pub peripherals: BTreeSet<String>,
+}
+
+impl FreezeMask {
+    /// Parse a freeze mask from a comma separated list of peripherals, e.g. a config file
+    /// value like `IWDG, WWDG, TIM2`.
+    ///
+    /// Description:
+    ///
+    /// * `text` - The list of peripherals, the names are not case sensitive.
+    pub fn parse(text: &str) -> FreezeMask {
+        FreezeMask {
+            peripherals: text
+                .split(',')
+                .map(|p| p.trim().to_uppercase())
+                .filter(|p| !p.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Get the register writes that program the mask, this should be done when attaching to the
+    /// debug target and every time the mask is modified.
+    ///
+    /// Description:
+    ///
+    /// * `chip` - The name of the chip, e.g. `STM32F407VGTx`.
+    ///
+    /// The known freeze bits of the chip that are not in the mask are cleared, so a peripheral
+    /// that is removed from the mask starts to count during halts again.
+    /// Will return a error if a peripheral in the mask has no freeze bit on the chip.
+    pub fn writes(&self, chip: &str) -> Result<Vec<RegisterWrite>> {
+        let bits = freeze_bits(chip);
+        for peripheral in &self.peripherals {
+            if !bits.iter().any(|b| b.peripheral == peripheral) {
+                error!("{} has no freeze bit on {}", peripheral, chip);
+                return Err(anyhow!("{} has no freeze bit on {}", peripheral, chip));
+            }
+        }
+
+        let mut writes: Vec<RegisterWrite> = vec![];
+        for bit in bits {
+            let index = match writes.iter().position(|w| w.address == bit.address) {
+                Some(val) => val,
+                None => {
+                    writes.push(RegisterWrite {
+                        address: bit.address,
+                        mask: 0,
+                        value: 0,
+                    });
+                    writes.len() - 1
+                }
+            };
+            writes[index].mask |= 1 << bit.bit;
+            if self.peripherals.contains(bit.peripheral) {
+                writes[index].value |= 1 << bit.bit;
+            }
+        }
+
+        Ok(writes)
+    }
+
+    /// Describe the freeze bits of a chip, e.g. for a command that lists the freeze mask.
+    ///
+    /// Description:
+    ///
+    /// * `chip` - The name of the chip, e.g. `STM32F407VGTx`.
+    ///
+    /// Every peripheral is one line with its name and if it is frozen during halts.
+    pub fn summary(&self, chip: &str) -> String {
+        let mut result = String::new();
+        for bit in freeze_bits(chip) {
+            result = format!(
+                "{}{}: {}\n",
+                result,
+                bit.peripheral,
+                match self.peripherals.contains(bit.peripheral) {
+                    true => "frozen",
+                    false => "running",
+                }
+            );
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn low_power_write() {
+        let family = find_family("stm32f407vgtx").unwrap();
+        assert_eq!(family.prefix, "STM32F4");
+        let write = family.low_power_debug(true);
+        assert_eq!(write.apply(0x100), 0x100 | family.low_power_bits);
+        assert_eq!(family.low_power_debug(false).apply(0x107), 0x100);
+        assert!(find_family("nRF52840").is_none());
+
+        let mut mem = |_: &u32, _: usize| Some(vec![0, 0, 1, 0]);
+        assert_eq!(
+            write.read_and_apply(&mut mem, RunTimeEndian::Big).unwrap(),
+            0x100 | family.low_power_bits
+        );
+    }
+
+    #[test]
+    fn freeze_mask_writes() {
+        let mask = FreezeMask::parse(" iwdg, TIM1 ,,");
+        assert_eq!(mask.peripherals.len(), 2);
+
+        let writes = mask.writes("STM32F407VGTx").unwrap();
+        assert_eq!(writes.len(), 2);
+        assert_eq!(writes[0].address, 0xE004_2008);
+        assert_eq!(writes[0].value, 1 << 12);
+        // The bits that are not in the mask are cleared.
+        assert_ne!(writes[0].mask, writes[0].value);
+        assert_eq!(writes[1].address, 0xE004_200C);
+        assert_eq!(writes[1].value, 1);
+
+        assert!(FreezeMask::parse("LPTIM1").writes("STM32F407VGTx").is_err());
+        assert!(mask.summary("STM32F407VGTx").contains("IWDG: frozen\n"));
+        assert!(mask.summary("STM32F407VGTx").contains("TIM2: running\n"));
+    }
+}
@@ -0,0 +1,1192 @@
+use crate::assertions::{AssertionFailure, Assertions};
+use crate::breakpoints::{Breakpoint, BreakpointTable};
+use crate::call_site::recover_entry_registers;
+use crate::call_stack::{
+    call_frame_registers, create_stack_frame_with_index, create_symbol_stack_frame,
+    evaluate_frame_array_field, expand_inlined_frames, find_frame_variable, step_out_target,
+    unwind_call_stack_with_index, CallFrame, FdeIndex, MemoryAccess, ReadAheadMemory, StackFrame,
+    StepOut, READ_AHEAD_BLOCK_SIZE, UNWIND_REGISTER_COUNT,
+};
+use crate::canary::{CanaryMonitor, CanaryViolation};
+use crate::disassembly::{interleave_source, SourceBlock};
+use crate::dwarf_index::DwarfIndex;
+use crate::evaluate::children::{child_values, ChildValue};
+use crate::evaluate::evaluate::{
+    ArrayTypeValue, BaseTypeValue, EvaluatorValue, SubrangeTypeValue, ValueInformation,
+};
+use crate::evaluate::expression::{evaluate_condition, evaluate_expression, Condition, Expression};
+use crate::evaluate::format::{format_value_with_address, EvaluateContext, FormatOptions};
+use crate::evaluate::pretty::read_std_contents;
+use crate::evaluate::trait_object::resolve_trait_objects;
+use crate::fault::FaultStatus;
+use crate::memory_view::{target_endian, MemoryView, DEFAULT_ROW_LENGTH};
+use crate::panic::{panic_report, PanicReport};
+use crate::registers::Registers;
+use crate::section::{missing_sections, missing_sections_message};
+use crate::source_information::{
+    find_breakpoint_location, find_breakpoint_locations, find_line_range_with_index,
+    SourceInformation,
+};
+use crate::stepping::{SourceStep, StepKind};
+use crate::symbol_table::SymbolTable;
+#[cfg(feature = "telemetry")]
+use crate::telemetry::Telemetry;
+use crate::triage::{TriageFrame, TriageReport};
+use crate::variable::{
+    find_static_variable, find_static_variables, write_variable, Variable, VariableWrite,
+};
+use crate::watchpoints::{memory_range, WatchKind, Watchpoint};
+use anyhow::{anyhow, Result};
+use gimli::{DebugFrame, Dwarf, Reader};
+use log::{error, trace};
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+use std::ops::Range;
+
+/// The number of bytes of machine code around the program counter in a triage report.
+const TRIAGE_CODE_BYTES: u32 = 64;
+
+/// Run a operation that reads the memory of the debug target, the operation is recorded in the
+/// telemetry of the session when the `telemetry` feature is enabled.
+///
+/// The memory inside the RAM ranges of the session is read ahead during the operation.
+/// The operation can not borrow the session, since the memory is borrowed while it runs.
+macro_rules! measure {
+    ($session:ident, $operation:expr, |$memory:ident| $body:expr) => {{
+        let ram_ranges = &$session.ram_ranges;
+        #[cfg(feature = "telemetry")]
+        let result = $session.telemetry.measure(
+            $operation,
+            &$session.registers,
+            &mut $session.memory,
+            |memory| {
+                let $memory = &mut ReadAheadMemory::new(memory, READ_AHEAD_BLOCK_SIZE, ram_ranges);
+                $body
+            },
+        );
+        #[cfg(not(feature = "telemetry"))]
+        let result = {
+            let $memory =
+                &mut ReadAheadMemory::new(&mut $session.memory, READ_AHEAD_BLOCK_SIZE, ram_ranges);
+            $body
+        };
+        result
+    }};
+}
+
+/// A high level facade over the DWARF debug information and the debug target.
+///
+/// It keeps the `Dwarf` and `.debug_frame` sections together with the current register values
+/// and a way to read the memory of the debug target.
+/// This makes it possible to retrieve debug information with one function call, without having
+/// to know about compilation units, frame bases or the memory that the evaluation requires.
+pub struct DebugSession<'a, R: Reader<Offset = usize>, M: MemoryAccess> {
+    /// A reference to gimli-rs `Dwarf` struct.
+    pub dwarf: &'a Dwarf<R>,
+
+    /// A reference to the DWARF section `.debug_frame`.
+    pub debug_frame: &'a DebugFrame<R>,
+
+    /// The register values of the debug target at the current halt.
+    pub registers: Registers,
+
+    /// Used to read the memory of the debugged target.
+    pub memory: M,
+
+    /// The work directory of the debugged program.
+    pub cwd: String,
+
+    /// The symbol table used for the functions without DWARF debug information.
+    pub symbols: Option<SymbolTable>,
+
+    /// The formatting preferences used when formatting values.
+    pub format_options: FormatOptions,
+
+    /// The guard regions that are verified when `check_canaries` is called.
+    pub canaries: CanaryMonitor,
+
+    /// The address ranges of the RAM of the debug target, the memory in them is read ahead when
+    /// unwinding and evaluating.
+    pub ram_ranges: Vec<Range<u32>>,
+
+    /// The index of the frame description entries, it is built the first time the call stack
+    /// is unwound.
+    fde_index: Option<FdeIndex<R>>,
+
+    /// The unwound call stack of the current halt, it is cleared when the registers are set.
+    call_stack: Option<Vec<CallFrame>>,
+
+    /// The index of the DWARF debug information, it is built the first time a stack frame,
+    /// source location or static variable is looked up.
+    dwarf_index: OnceCell<DwarfIndex>,
+
+    /// The time and the debug target accesses of the unwinding and evaluation operations.
+    #[cfg(feature = "telemetry")]
+    pub telemetry: Telemetry,
+}
+
+impl<'a, R: Reader<Offset = usize>, M: MemoryAccess> DebugSession<'a, R, M> {
+    /// Creates a new `DebugSession`.
+    ///
+    /// Description:
+    ///
+    /// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+    /// * `debug_frame` - A reference to the DWARF section `.debug_frame`.
+    /// * `registers` - The register values of the debug target.
+    /// * `memory` - Used to read the memory of the debugged target.
+    /// * `cwd` - The work directory of the debugged program.
+    pub fn new(
+        dwarf: &'a Dwarf<R>,
+        debug_frame: &'a DebugFrame<R>,
+        registers: Registers,
+        memory: M,
+        cwd: &str,
+    ) -> DebugSession<'a, R, M> {
+        DebugSession {
+            dwarf,
+            debug_frame,
+            registers,
+            memory,
+            cwd: cwd.to_string(),
+            symbols: None,
+            format_options: FormatOptions::default(),
+            canaries: CanaryMonitor::new(),
+            ram_ranges: vec![],
+            fde_index: None,
+            call_stack: None,
+            dwarf_index: OnceCell::new(),
+            #[cfg(feature = "telemetry")]
+            telemetry: Telemetry::new(),
+        }
+    }
+
+    /// Replace the register values, this should be done every time the debug target halts.
+    ///
+    /// Description:
+    ///
+    /// * `registers` - The new register values of the debug target.
+    pub fn set_registers(&mut self, registers: Registers) {
+        self.registers = registers;
+        self.call_stack = None;
+    }
+
+    /// Set the address ranges of the RAM of the debug target, e.g. from the memory map of the
+    /// chip.
+    ///
+    /// Description:
+    ///
+    /// * `ram_ranges` - The address ranges of the RAM.
+    ///
+    /// The memory in them is read in blocks of `READ_AHEAD_BLOCK_SIZE` bytes when unwinding and
+    /// evaluating, which reduces the number of reads from the debug target.
+    pub fn set_ram_ranges(&mut self, ram_ranges: Vec<Range<u32>>) {
+        self.ram_ranges = ram_ranges;
+    }
+
+    /// Set the symbol table, which is used for the functions without DWARF debug information.
+    ///
+    /// Description:
+    ///
+    /// * `symbols` - The symbol table of the debugged program.
+    pub fn set_symbols(&mut self, symbols: SymbolTable) {
+        self.symbols = Some(symbols);
+    }
+
+    /// Set the formatting preferences used when formatting values.
+    ///
+    /// Description:
+    ///
+    /// * `format_options` - The new formatting preferences.
+    pub fn set_format_options(&mut self, format_options: FormatOptions) {
+        self.format_options = format_options;
+    }
+
+    /// Format a value into a `String` using the formatting preferences of the session.
+    ///
+    /// Description:
+    ///
+    /// * `value` - The value that will be formatted.
+    /// * `hex` - The hex preference of the request, e.g. the `hex` field of the DAP
+    ///   `ValueFormat`, `None` keeps the preference of the session.
+    pub fn format_value(&self, value: &EvaluatorValue<R>, hex: Option<bool>) -> String {
+        format_value_with_address(value, &self.format_options.with_hex(hex))
+    }
+
+    /// Verify the registered guard regions, this should be done every time the debug target
+    /// halts.
+    ///
+    /// A non empty result means that memory has been corrupted and should be reported as its own
+    /// stop reason.
+    pub fn check_canaries(&mut self) -> Result<Vec<CanaryViolation>> {
+        self.canaries.check(&mut self.memory)
+    }
+
+    /// Detect if the debug target is halted in a panic, this should be done every time the debug
+    /// target halts.
+    ///
+    /// A result with a report should be reported as its own stop reason, e.g. "panicked at 'xyz',
+    /// src/main.rs:42", with the frame of the report selected.
+    pub fn check_panic(&mut self) -> Result<Option<PanicReport>> {
+        let stack_frames = self.stack_trace()?;
+        panic_report(&stack_frames, &mut self.memory, target_endian(self.dwarf))
+    }
+
+    /// Will preform a stack trace on the debugged target.
+    ///
+    /// This function will virtually unwind the call stack and evaluate all the variables in each
+    /// of the stack frames.
+    /// If a symbol table is set, the frames without DWARF debug information are symbol only
+    /// frames.
+    pub fn stack_trace(&mut self) -> Result<Vec<StackFrame<R>>> {
+        Ok(self.stack_trace_page(0, None)?.0)
+    }
+
+    /// Will perform a stack trace that also contains the functions that inlined functions are
+    /// inlined into, e.g. for a DAP `stackTrace` response.
+    ///
+    /// The frames of the functions that inlined a call are added by `expand_inlined_frames`,
+    /// the other frames are the same as in `stack_trace`.
+    pub fn stack_trace_with_inlined(&mut self) -> Result<Vec<StackFrame<R>>> {
+        let frames = self.stack_trace()?;
+        expand_inlined_frames(
+            self.dwarf,
+            frames,
+            &self.registers,
+            &mut self.memory,
+            &self.cwd,
+        )
+    }
+
+    /// Will evaluate a page of the stack frames of the debugged target.
+    ///
+    /// Description:
+    ///
+    /// * `start_frame` - The index of the first frame in the page, e.g. the DAP `startFrame`.
+    /// * `levels` - The maximum number of frames in the page, e.g. the DAP `levels`, `None` for
+    ///   all the remaining frames.
+    ///
+    /// The call stack is unwound once per halt, and only the frames in the page are evaluated.
+    /// This makes it possible to show the top frames quickly and to evaluate the deeper frames
+    /// when they are requested.
+    /// Will return the frames in the page and the total number of frames.
+    pub fn stack_trace_page(
+        &mut self,
+        start_frame: usize,
+        levels: Option<usize>,
+    ) -> Result<(Vec<StackFrame<R>>, usize)> {
+        let call_stack = self.unwind()?;
+        let levels = levels.unwrap_or(call_stack.len());
+        let frames = self.evaluate_frames(&call_stack, start_frame, levels, false)?;
+        Ok((frames, call_stack.len()))
+    }
+
+    /// Evaluate the stack frames in a part of the call stack.
+    ///
+    /// Description:
+    ///
+    /// * `call_stack` - The unwound call stack.
+    /// * `start_frame` - The index of the first frame to evaluate.
+    /// * `levels` - The maximum number of frames to evaluate.
+    /// * `always_symbols` - If the frames that can not be evaluated are symbol only frames also
+    ///   when no symbol table is set.
+    ///
+    /// A frame that can not be evaluated is a error if there is no symbol table to fall back to.
+    fn evaluate_frames(
+        &mut self,
+        call_stack: &[CallFrame],
+        start_frame: usize,
+        levels: usize,
+        always_symbols: bool,
+    ) -> Result<Vec<StackFrame<R>>> {
+        let no_symbols = SymbolTable::default();
+        let symbols = match &self.symbols {
+            Some(symbols) => Some(symbols),
+            None => always_symbols.then_some(&no_symbols),
+        };
+        let (dwarf, registers, cwd) = (self.dwarf, &self.registers, &self.cwd);
+        let index = dwarf_index(&self.dwarf_index, dwarf)?;
+
+        let mut frames = vec![];
+        for (i, call_frame) in call_stack.iter().enumerate().skip(start_frame).take(levels) {
+            let stack_frame = match measure!(self, "stack frame", |memory| {
+                create_stack_frame_with_index(
+                    dwarf,
+                    index,
+                    &call_stack[i..],
+                    registers,
+                    memory,
+                    cwd,
+                )
+            }) {
+                Ok(val) => val,
+                Err(err) => match symbols {
+                    Some(symbols) => {
+                        trace!("Using symbol only frame, because: {:?}", err);
+                        create_symbol_stack_frame(call_frame.clone(), symbols)
+                    }
+                    None => return Err(err),
+                },
+            };
+            frames.push(stack_frame);
+        }
+
+        Ok(frames)
+    }
+
+    /// Group disassembled instructions by their source lines, e.g. for a `code` command or the
+    /// locations of a DAP "disassemble" response.
+    ///
+    /// Description:
+    ///
+    /// * `addresses` - The addresses of the decoded instructions, in order.
+    pub fn interleave_source(&self, addresses: &[u64]) -> Result<Vec<SourceBlock>> {
+        let index = dwarf_index(&self.dwarf_index, self.dwarf)?;
+        interleave_source(self.dwarf, index, &self.cwd, addresses)
+    }
+
+    /// Describe the required DWARF sections that are missing, e.g. to warn the user when the
+    /// program is loaded.
+    ///
+    /// Will return `None` if all the required sections are present.
+    /// Without them `inspect` still works, it falls back to symbol table frames.
+    pub fn missing_sections(&self) -> Option<String> {
+        missing_sections_message(&missing_sections(self.dwarf, self.debug_frame))
+    }
+
+    /// Inspect a debug target that was attached to without a reset, e.g. a running device in the
+    /// field.
+    ///
+    /// The expected flow is to attach without a reset, halt, set the registers with
+    /// `set_registers`, call this function and resume the debug target.
+    /// Unlike `stack_trace` this never fails because of missing debug information: the frames
+    /// without DWARF debug information are symbol only frames, also when no symbol table is set,
+    /// and if the call stack can not be unwound, e.g. because the program counter is in a ROM
+    /// bootloader, the result is a single frame for the current program counter.
+    pub fn inspect(&mut self) -> Result<Vec<StackFrame<R>>> {
+        let call_stack = match self.unwind() {
+            Ok(val) if !val.is_empty() => val,
+            Ok(_) => vec![self.current_call_frame()?],
+            Err(err) => {
+                trace!("Using the current frame only, because: {:?}", err);
+                vec![self.current_call_frame()?]
+            }
+        };
+
+        self.evaluate_frames(&call_stack, 0, call_stack.len(), true)
+    }
+
+    /// Create a crash triage report, e.g. for a `report` command at a fault or panic halt.
+    ///
+    /// Description:
+    ///
+    /// * `stop_reason` - Why the debug target halted, e.g. the panic message.
+    ///
+    /// The stack trace is from `inspect`, so the report can be created without debug
+    /// information.
+    /// The code around the program counter is included as raw bytes, and the program output and
+    /// the memory map should be added by the caller.
+    pub fn triage_report(&mut self, stop_reason: &str) -> Result<TriageReport> {
+        let fault = match FaultStatus::read(&mut self.memory, target_endian(self.dwarf)) {
+            Ok(val) => Some(val),
+            Err(err) => {
+                trace!("The report has no fault status, because: {:?}", err);
+                None
+            }
+        };
+
+        let mut frames = vec![];
+        for frame in self.inspect()? {
+            let source = match &frame.call_location {
+                Some(val) => Some(val.clone()),
+                None => self.source_at(frame.call_frame.code_location as u32).ok(),
+            };
+            let location = source.and_then(|s| match (s.file, s.line) {
+                (Some(file), Some(line)) => Some(format!("{}:{}", file, line)),
+                _ => None,
+            });
+            let variables = frame
+                .arguments
+                .iter()
+                .chain(frame.variables.iter())
+                .map(|v| {
+                    (
+                        v.name.clone().unwrap_or_else(|| "<unknown>".to_string()),
+                        self.format_value(&v.value, None),
+                    )
+                })
+                .collect();
+            frames.push(TriageFrame {
+                name: frame.name,
+                location,
+                code_location: frame.call_frame.code_location,
+                variables,
+            });
+        }
+
+        let pc = self
+            .registers
+            .program_counter_register
+            .and_then(|pc| self.registers.get_register_value(&(pc as u16)).copied());
+        let code = pc.and_then(|pc| {
+            let start = (pc & !0xf).saturating_sub(TRIAGE_CODE_BYTES / 2);
+            let data = self
+                .memory
+                .get_address(&start, TRIAGE_CODE_BYTES as usize)?;
+            Some(MemoryView::new(
+                start,
+                &data,
+                DEFAULT_ROW_LENGTH,
+                self.symbols.as_ref(),
+            ))
+        });
+
+        Ok(TriageReport {
+            stop_reason: stop_reason.to_string(),
+            fault,
+            registers: self.registers.get_registers_as_list(),
+            frames,
+            code,
+            output: None,
+            memory_map: vec![],
+        })
+    }
+
+    /// Creates a call frame from the current register values, without unwinding.
+    fn current_call_frame(&self) -> Result<CallFrame> {
+        let pc = match self
+            .registers
+            .program_counter_register
+            .and_then(|pc| self.registers.get_register_value(&(pc as u16)))
+        {
+            Some(val) => *val as u64,
+            None => {
+                error!("The program counter register has no value");
+                return Err(anyhow!("The program counter register has no value"));
+            }
+        };
+
+        let mut registers = [None; UNWIND_REGISTER_COUNT];
+        for (i, register) in registers.iter_mut().enumerate() {
+            *register = self.registers.get_register_value(&(i as u16)).copied();
+        }
+
+        Ok(CallFrame {
+            id: 0,
+            registers,
+            code_location: pc,
+            cfa: None,
+            start_address: pc,
+            end_address: pc,
+            exception_return: None,
+        })
+    }
+
+    /// Will virtually unwind the call stack of the debugged target.
+    ///
+    /// The index of the frame description entries is reused between the calls, which makes
+    /// repeated unwinding fast on large programs.
+    /// The call stack is only unwound once until the registers are set again.
+    pub fn unwind(&mut self) -> Result<Vec<CallFrame>> {
+        if let Some(call_stack) = &self.call_stack {
+            return Ok(call_stack.clone());
+        }
+
+        if self.fde_index.is_none() {
+            self.fde_index = Some(FdeIndex::new(self.debug_frame)?);
+        }
+
+        let registers = self.registers.clone();
+        let debug_frame = self.debug_frame;
+        let call_stack = match &self.fde_index {
+            Some(fde_index) => measure!(self, "unwind", |memory| {
+                unwind_call_stack_with_index(registers, memory, debug_frame, fde_index)
+            })?,
+            None => vec![],
+        };
+        self.call_stack = Some(call_stack.clone());
+        Ok(call_stack)
+    }
+
+    /// Prepare a source level step from the current program counter.
+    ///
+    /// Description:
+    ///
+    /// * `kind` - Step over or step into.
+    ///
+    /// Will return `Ok(None)` if the current code has no source line, a single instruction step
+    /// should be done instead.
+    pub fn source_step(&mut self, kind: StepKind) -> Result<Option<SourceStep>> {
+        let call_frames = self.unwind()?;
+        let frame = match call_frames.first() {
+            Some(val) => val,
+            None => return Ok(None),
+        };
+        Ok(
+            match find_line_range_with_index(
+                self.dwarf,
+                dwarf_index(&self.dwarf_index, self.dwarf)?,
+                frame.code_location,
+            )? {
+                Some(line) => SourceStep::new(kind, line, frame),
+                None => None,
+            },
+        )
+    }
+
+    /// Prepare to step out of the current function, e.g. for a DAP `stepOut` request.
+    ///
+    /// Description:
+    ///
+    /// * `table` - The breakpoint table, a temporary breakpoint is added at the return address.
+    ///
+    /// The debug target should then be resumed, and resumed again for every halt at the
+    /// temporary breakpoint where `StepOut::is_complete` is `false`.
+    pub fn step_out(&mut self, table: &mut BreakpointTable) -> Result<StepOut> {
+        let call_frames = self.unwind()?;
+        let target = step_out_target(&call_frames, 0, self.registers.architecture)?;
+        table.add_temporary(target.return_address as u64, None, None);
+        Ok(target)
+    }
+
+    /// Get the writes that change the value of a variable in the current stack frame, e.g. for
+    /// a "set variable" request.
+    ///
+    /// Description:
+    ///
+    /// * `name` - The name of the variable.
+    /// * `new_value` - The new value as text, it is parsed as the type of the variable.
+    ///
+    /// The writes can be performed with `apply_variable_writes`, after which the registers
+    /// should be set again since the call stack and the values may have changed.
+    pub fn set_variable(&mut self, name: &str, new_value: &str) -> Result<Vec<VariableWrite>> {
+        match self.variable(name)? {
+            Some(variable) => write_variable(&variable.value, new_value, target_endian(self.dwarf)),
+            None => {
+                error!("Could not find the variable {}", name);
+                Err(anyhow!("Could not find the variable {}", name))
+            }
+        }
+    }
+
+    /// Get the writes that change the value of a expression, e.g. for a "set variable" request
+    /// on a member in a variables view.
+    ///
+    /// Description:
+    ///
+    /// * `text` - The expression, e.g. `my_struct.field[2]`, see `Expression::parse`.
+    /// * `new_value` - The new value as text, it is parsed as the type of the value.
+    ///
+    /// The expression has to evaluate to a base type value, so members of structs and elements
+    /// of arrays can be changed one at a time.
+    pub fn set_expression(&mut self, text: &str, new_value: &str) -> Result<Vec<VariableWrite>> {
+        let value = self.evaluate_expression(text)?;
+        write_variable(&value, new_value, target_endian(self.dwarf))
+    }
+
+    /// Get a watchpoint on the memory of a expression, e.g. for a DAP "dataBreakpointInfo"
+    /// request.
+    ///
+    /// Description:
+    ///
+    /// * `text` - The expression, e.g. the name of a variable or `[0x20000000]`.
+    /// * `kind` - The memory accesses that trigger the watchpoint.
+    ///
+    /// The watchpoint covers all the bytes of the value, it can be added to a `Watchpoints`.
+    /// Will return a error if the value is not stored in memory, e.g. a variable in a register.
+    pub fn watchpoint(&mut self, text: &str, kind: WatchKind) -> Result<Watchpoint> {
+        match memory_range(&self.evaluate_expression(text)?)? {
+            Some((address, size)) => Ok(Watchpoint {
+                address,
+                size,
+                kind,
+            }),
+            None => {
+                error!("{} is not stored in memory", text);
+                Err(anyhow!("{} is not stored in memory", text))
+            }
+        }
+    }
+
+    /// Evaluate a user typed expression in the current stack frame, e.g. `my_struct.field[2]`.
+    ///
+    /// Description:
+    ///
+    /// * `text` - The expression, see `Expression::parse` for the grammar.
+    ///
+    /// The expression starts from a variable in the current stack frame or a register
+    /// pseudo-variable like `$pc`.
+    /// Trait objects are replaced with pointers to their data evaluated as the concrete type,
+    /// see `resolve_trait_objects`.
+    /// A field of every element of a array variable, e.g. `sensors[*].temp`, is read without
+    /// reading the rest of the array, see `frame_array_field`.
+    pub fn evaluate_expression(&mut self, text: &str) -> Result<EvaluatorValue<R>> {
+        let expression = Expression::parse(text)?;
+        if let Some(value) = self.wildcard_expression(&expression) {
+            return Ok(value);
+        }
+        let names = expression.variables();
+        let frame = self.expression_frame(&names);
+        let mut values = HashMap::new();
+        for name in names {
+            if let Some(value) = self.expression_variable(name, frame.as_ref())? {
+                values.insert(name.to_string(), value);
+            }
+        }
+        for value in values.values_mut() {
+            *value = self.resolve_trait_objects(value.clone())?;
+        }
+        let endian = target_endian(self.dwarf);
+        measure!(self, &format!("evaluate {}", text), |memory| {
+            evaluate_expression(
+                &expression,
+                &mut |name: &str| Ok(values.get(name).cloned()),
+                memory,
+                endian,
+            )
+        })
+    }
+
+    /// Evaluate a user typed expression and format it for where it is shown, e.g. for a DAP
+    /// "evaluate" request.
+    ///
+    /// Description:
+    ///
+    /// * `text` - The expression, see `Expression::parse` for the grammar.
+    /// * `context` - Where the value is shown, e.g. a hover or the debug console.
+    /// * `hex` - The hex preference of the request, `None` keeps the preference of the session.
+    pub fn evaluate(
+        &mut self,
+        text: &str,
+        context: EvaluateContext,
+        hex: Option<bool>,
+    ) -> Result<String> {
+        let mut value = self.evaluate_expression(text)?;
+        if self.format_options.pretty_print {
+            let endian = target_endian(self.dwarf);
+            value = read_std_contents(value, &mut self.memory, endian);
+        }
+        let options = self.format_options.with_hex(hex).for_context(context);
+        Ok(format_value_with_address(&value, &options))
+    }
+
+    /// Get the children of a expandable value in the current stack frame, e.g. for a DAP
+    /// "variables" request.
+    ///
+    /// Description:
+    ///
+    /// * `path` - The expression of the value, e.g. `point` or the path of a earlier child.
+    ///
+    /// Only the path has to be kept for each expandable value, e.g. in a `HandleKey::Variable`,
+    /// the children are evaluated again when they are requested.
+    pub fn variable_children(&mut self, path: &str) -> Result<Vec<ChildValue<R>>> {
+        let value = self.evaluate_expression(path)?;
+        Ok(child_values(path, &value))
+    }
+
+    /// Will return `true` if the debug target should stay halted at a breakpoint.
+    ///
+    /// Description:
+    ///
+    /// * `breakpoint` - The breakpoint the target halted at.
+    ///
+    /// Will return `Ok(false)` if the condition of the breakpoint is false, the target should
+    /// then be resumed.
+    /// A condition that can not be evaluated is an error, it is up to the caller to stop and
+    /// report it.
+    pub fn breakpoint_condition(&mut self, breakpoint: &Breakpoint) -> Result<bool> {
+        let condition = match &breakpoint.condition {
+            Some(val) => Condition::parse(val)?,
+            None => return Ok(true),
+        };
+        let names = condition.variables();
+        let frame = self.expression_frame(&names);
+        let mut values = HashMap::new();
+        for name in names {
+            if let Some(value) = self.expression_variable(name, frame.as_ref())? {
+                values.insert(name.to_string(), value);
+            }
+        }
+        evaluate_condition(
+            &condition,
+            &mut |name: &str| Ok(values.get(name).cloned()),
+            &mut self.memory,
+            target_endian(self.dwarf),
+        )
+    }
+
+    /// Check the assertions in the current stack frame, this should be done every time the debug
+    /// target halts.
+    ///
+    /// Description:
+    ///
+    /// * `assertions` - The assertions of the session.
+    ///
+    /// Will return the assertions that are false, see `Assertions::check`.
+    pub fn check_assertions(&mut self, assertions: &mut Assertions) -> Vec<AssertionFailure> {
+        let names = assertions.variables();
+        let frame = self.expression_frame(&names);
+        let mut values = HashMap::new();
+        for name in names {
+            match self.expression_variable(name, frame.as_ref()) {
+                Ok(Some(value)) => {
+                    values.insert(name.to_string(), value);
+                }
+                Ok(None) => (),
+                Err(err) => trace!("Could not evaluate {}, because: {:?}", name, err),
+            };
+        }
+        assertions.check(
+            &mut |name: &str| Ok(values.get(name).cloned()),
+            &mut self.memory,
+            target_endian(self.dwarf),
+        )
+    }
+
+    /// Evaluate a `[*]` expression on a array variable in the current stack frame using only the
+    /// debug information of the array.
+    ///
+    /// Description:
+    ///
+    /// * `expression` - The parsed expression.
+    ///
+    /// Will return `None` if it is not such a expression or if the field can not be evaluated
+    /// this way, e.g. a array stored in registers, the expression is then evaluated from the
+    /// value of the whole variable.
+    fn wildcard_expression(&mut self, expression: &Expression) -> Option<EvaluatorValue<R>> {
+        let (name, field) = match expression {
+            Expression::Wildcard(inner, field) => match inner.as_ref() {
+                Expression::Variable(name) if !name.starts_with('$') => (name, field),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        match self.frame_array_field(0, name, field) {
+            Ok(Some(values)) => Some(EvaluatorValue::Array(Box::new(ArrayTypeValue {
+                subrange_type_value: SubrangeTypeValue {
+                    lower_bound: Some(0),
+                    count: Some(values.len() as u64),
+                    base_type_value: None,
+                },
+                values,
+            }))),
+            Ok(None) => None,
+            Err(err) => {
+                trace!(
+                    "Evaluating {} from the whole array, because: {:?}",
+                    expression,
+                    err
+                );
+                None
+            }
+        }
+    }
+
+    /// Evaluate the current stack frame once for the variables in a expression.
+    ///
+    /// Description:
+    ///
+    /// * `names` - The names of the variables in the expression.
+    ///
+    /// Will return `None` if only register pseudo-variables are used, or if the frame can not be
+    /// evaluated, the variables are then looked up in the static variables and registers.
+    fn expression_frame(&mut self, names: &[&str]) -> Option<StackFrame<R>> {
+        if names.iter().all(|name| name.starts_with('$')) {
+            return None;
+        }
+        match self.stack_frame(0) {
+            Ok(val) => val,
+            Err(err) => {
+                trace!("Evaluating without the current frame, because: {:?}", err);
+                None
+            }
+        }
+    }
+
+    /// Get the value of a variable in a expression, a variable in the current stack frame, a
+    /// static variable or a register pseudo-variable like `$pc`.
+    ///
+    /// Description:
+    ///
+    /// * `name` - The name of the variable.
+    /// * `frame` - The current stack frame from `expression_frame`.
+    fn expression_variable(
+        &mut self,
+        name: &str,
+        frame: Option<&StackFrame<R>>,
+    ) -> Result<Option<EvaluatorValue<R>>> {
+        if name.starts_with('$') {
+            return Ok(self.registers.pseudo_variable(name).map(|value| {
+                EvaluatorValue::Value(
+                    BaseTypeValue::Reg32(value),
+                    ValueInformation::new(None, vec![]),
+                )
+            }));
+        }
+        let variable = frame.and_then(|frame| {
+            frame
+                .variables
+                .iter()
+                .chain(&frame.arguments)
+                .find(|v| v.name.as_deref() == Some(name))
+        });
+        match variable {
+            Some(variable) => Ok(Some(variable.value.clone())),
+            None => Ok(self.static_variable(name)?.map(|variable| variable.value)),
+        }
+    }
+
+    /// Evaluate a variable in the current stack frame.
+    ///
+    /// Description:
+    ///
+    /// * `name` - The name of the variable.
+    ///
+    /// Only the top most stack frame is unwound and evaluated, both the local variables and the
+    /// arguments are searched.
+    /// Will return `Ok(None)` if there is no variable with the given name in the current frame.
+    pub fn variable(&mut self, name: &str) -> Result<Option<Variable<R>>> {
+        let stack_frame = match self.stack_frame(0)? {
+            Some(val) => val,
+            None => {
+                trace!("No call frame found for the current program counter");
+                return Ok(None);
+            }
+        };
+
+        Ok(stack_frame
+            .variables
+            .into_iter()
+            .chain(stack_frame.arguments)
+            .find(|v| v.name.as_deref() == Some(name)))
+    }
+
+    /// Evaluate one stack frame of the call stack.
+    ///
+    /// Description:
+    ///
+    /// * `frame_index` - The index of the frame, `0` is the current frame.
+    ///
+    /// The variables of the frame are evaluated with the register values recovered for the frame.
+    /// Will return `Ok(None)` if the call stack has no frame with the given index.
+    pub fn stack_frame(&mut self, frame_index: usize) -> Result<Option<StackFrame<R>>> {
+        let call_frames = self.unwind()?;
+        if frame_index >= call_frames.len() {
+            return Ok(None);
+        }
+
+        let (dwarf, registers, cwd) = (self.dwarf, &self.registers, &self.cwd);
+        let index = dwarf_index(&self.dwarf_index, dwarf)?;
+        let mut stack_frame = measure!(self, "stack frame", |memory| {
+            create_stack_frame_with_index(
+                dwarf,
+                index,
+                &call_frames[frame_index..],
+                registers,
+                memory,
+                cwd,
+            )
+        })?;
+        for variable in stack_frame
+            .variables
+            .iter_mut()
+            .chain(stack_frame.arguments.iter_mut())
+        {
+            variable.value = self.resolve_trait_objects(variable.value.clone())?;
+        }
+        Ok(Some(stack_frame))
+    }
+
+    /// Replace the trait objects in a value with pointers to their data evaluated as the
+    /// concrete type, see `resolve_trait_objects`.
+    ///
+    /// Description:
+    ///
+    /// * `value` - The value to resolve.
+    ///
+    /// Trait objects are evaluated as their concrete type, so they can be formatted, expanded
+    /// and dereferenced.
+    /// The vtables are found in the index of the debug information.
+    fn resolve_trait_objects(&mut self, value: EvaluatorValue<R>) -> Result<EvaluatorValue<R>> {
+        let index = dwarf_index(&self.dwarf_index, self.dwarf)?;
+        Ok(resolve_trait_objects(
+            self.dwarf,
+            index,
+            &self.registers,
+            &mut self.memory,
+            value,
+        ))
+    }
+
+    /// Evaluate one variable in a stack frame of the call stack, e.g. when a outer frame is
+    /// selected.
+    ///
+    /// Description:
+    ///
+    /// * `frame_index` - The index of the frame, `0` is the current frame.
+    /// * `name` - The name of the variable.
+    ///
+    /// Only the given variable is evaluated, with the register values recovered for the frame.
+    /// Will return `Ok(None)` if the call stack has no frame with the given index, or the frame
+    /// has no variable with the given name.
+    pub fn frame_variable(
+        &mut self,
+        frame_index: usize,
+        name: &str,
+    ) -> Result<Option<Variable<R>>> {
+        let (call_frame, frame_registers) = match self.frame_registers(frame_index)? {
+            Some(val) => val,
+            None => return Ok(None),
+        };
+
+        find_frame_variable(
+            self.dwarf,
+            &call_frame,
+            &frame_registers,
+            &mut self.memory,
+            &self.cwd,
+            name,
+        )
+    }
+
+    /// Evaluate one field of every element of a array variable in a stack frame, e.g. for a
+    /// column view of `sensors[*].temp`.
+    ///
+    /// Description:
+    ///
+    /// * `frame_index` - The index of the frame, `0` is the current frame.
+    /// * `name` - The name of the array variable.
+    /// * `field` - The name of the field, nested fields are separated by `.`, e.g. `pos.x`.
+    ///
+    /// Only the field is read from the memory of the debug target, see `evaluate_array_field`.
+    /// Will return `Ok(None)` if the call stack has no frame with the given index, or the frame
+    /// has no variable with the given name.
+    pub fn frame_array_field(
+        &mut self,
+        frame_index: usize,
+        name: &str,
+        field: &str,
+    ) -> Result<Option<Vec<EvaluatorValue<R>>>> {
+        let (call_frame, frame_registers) = match self.frame_registers(frame_index)? {
+            Some(val) => val,
+            None => return Ok(None),
+        };
+
+        evaluate_frame_array_field(
+            self.dwarf,
+            &call_frame,
+            &frame_registers,
+            &mut self.memory,
+            name,
+            field,
+        )
+    }
+
+    /// Get a call frame and the register values recovered for it.
+    ///
+    /// Description:
+    ///
+    /// * `frame_index` - The index of the frame, `0` is the current frame.
+    ///
+    /// Will return `Ok(None)` if the call stack has no frame with the given index.
+    fn frame_registers(&mut self, frame_index: usize) -> Result<Option<(CallFrame, Registers)>> {
+        let call_frames = self.unwind()?;
+        let call_frame = match call_frames.get(frame_index) {
+            Some(val) => val,
+            None => return Ok(None),
+        };
+        let caller_frame = call_frames.get(frame_index + 1);
+        let mut frame_registers = call_frame_registers(call_frame, caller_frame, &self.registers);
+        if let Some(caller_frame) = caller_frame {
+            if let Err(err) = recover_entry_registers(
+                self.dwarf,
+                &mut frame_registers,
+                caller_frame,
+                &mut self.memory,
+            ) {
+                trace!("Could not recover the entry registers: {:?}", err);
+            }
+        }
+
+        Ok(Some((call_frame.clone(), frame_registers)))
+    }
+
+    /// Evaluate a user typed expression in a stack frame of the call stack.
+    ///
+    /// Description:
+    ///
+    /// * `frame_index` - The index of the frame, `0` is the current frame.
+    /// * `text` - The expression, see `Expression::parse` for the grammar.
+    pub fn evaluate_expression_in_frame(
+        &mut self,
+        frame_index: usize,
+        text: &str,
+    ) -> Result<EvaluatorValue<R>> {
+        match self.stack_frame(frame_index)? {
+            Some(stack_frame) => stack_frame.evaluate_expression(
+                text,
+                &self.registers,
+                &mut self.memory,
+                target_endian(self.dwarf),
+            ),
+            None => {
+                error!("There is no stack frame {}", frame_index);
+                Err(anyhow!("There is no stack frame {}", frame_index))
+            }
+        }
+    }
+
+    /// Evaluate a variable with static storage duration by name.
+    ///
+    /// Description:
+    ///
+    /// * `name` - The name of the variable.
+    ///
+    /// The variable is found in a index of the debug information, which is built the first time
+    /// this is called and kept for the whole session.
+    /// Will return `Ok(None)` if there is no static variable with the given name.
+    pub fn static_variable(&mut self, name: &str) -> Result<Option<Variable<R>>> {
+        let (dwarf, registers, cwd) = (self.dwarf, &self.registers, &self.cwd);
+        let index = dwarf_index(&self.dwarf_index, dwarf)?;
+        measure!(self, "static variable", |memory| {
+            find_static_variable(dwarf, index, registers, memory, cwd, name)
+        })
+    }
+
+    /// Evaluate all the variables with static storage duration, e.g. for a "Globals" scope.
+    pub fn static_variables(&mut self) -> Result<Vec<Variable<R>>> {
+        find_static_variables(self.dwarf, &self.registers, &mut self.memory, &self.cwd)
+    }
+
+    /// Retrieve the source code location of a machine code address.
+    ///
+    /// Description:
+    ///
+    /// * `pc` - A machine code address, usually the current program counter value.
+    pub fn source_at(&self, pc: u32) -> Result<SourceInformation> {
+        SourceInformation::get_from_address_with_index(
+            self.dwarf,
+            dwarf_index(&self.dwarf_index, self.dwarf)?,
+            pc as u64,
+            &self.cwd,
+        )
+    }
+
+    /// Find the machine code address where a breakpoint should be placed for a source location.
+    ///
+    /// Description:
+    ///
+    /// * `path` - The path to the source file.
+    /// * `line` - A line number in the source file.
+    /// * `column` - A optional column number in the source file.
+    pub fn breakpoint_for(
+        &self,
+        path: &str,
+        line: NonZeroU64,
+        column: Option<NonZeroU64>,
+    ) -> Result<Option<u64>> {
+        find_breakpoint_location(self.dwarf, &self.cwd, path, line, column)
+    }
+
+    /// Find the machine code addresses of every inlined copy of a source location.
+    ///
+    /// Description:
+    ///
+    /// * `path` - The path to the source file.
+    /// * `line` - A line number in the source file.
+    /// * `column` - A optional column number in the source file.
+    pub fn breakpoints_for(
+        &self,
+        path: &str,
+        line: NonZeroU64,
+        column: Option<NonZeroU64>,
+    ) -> Result<Vec<u64>> {
+        find_breakpoint_locations(self.dwarf, &self.cwd, path, line, column)
+    }
+}
+
+/// Get the index of the DWARF debug information, it is built the first time it is needed.
+///
+/// Description:
+///
+/// * `cell` - Where the index is kept.
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+fn dwarf_index<'b, R: Reader<Offset = usize>>(
+    cell: &'b OnceCell<DwarfIndex>,
+    dwarf: &Dwarf<R>,
+) -> Result<&'b DwarfIndex> {
+    if cell.get().is_none() {
+        let _ = cell.set(DwarfIndex::new(dwarf)?);
+    }
+    match cell.get() {
+        Some(val) => Ok(val),
+        None => Err(anyhow!("The DWARF index could not be built")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gimli::{EndianSlice, LittleEndian};
+
+    type TestReader = EndianSlice<'static, LittleEndian>;
+
+    /// Run a test with a session without debug information, where the memory at
+    /// `0x20000000` holds the little endian word `0x12345678`.
+    fn with_session<F>(test: F)
+    where
+        F: FnOnce(&mut DebugSession<TestReader, fn(&u32, usize) -> Option<Vec<u8>>>),
+    {
+        fn memory(address: &u32, num_bytes: usize) -> Option<Vec<u8>> {
+            let start = address.checked_sub(0x2000_0000)? as usize;
+            [0x78, 0x56, 0x34, 0x12]
+                .get(start..start + num_bytes)
+                .map(|b| b.to_vec())
+        }
+        let dwarf = Dwarf::default();
+        let debug_frame = DebugFrame::new(&[], LittleEndian);
+        let mut session = DebugSession::new(
+            &dwarf,
+            &debug_frame,
+            Registers::default(),
+            memory as fn(&u32, usize) -> Option<Vec<u8>>,
+            "/",
+        );
+        test(&mut session);
+    }
+
+    #[test]
+    fn evaluate_without_debug_information() {
+        with_session(|session| {
+            assert_eq!(
+                session
+                    .evaluate("[0x20000000]", EvaluateContext::Watch, None)
+                    .unwrap(),
+                "305419896"
+            );
+            assert_eq!(
+                session
+                    .evaluate("[0x20000000]", EvaluateContext::Watch, Some(true))
+                    .unwrap(),
+                "0x12345678"
+            );
+            assert!(session
+                .evaluate("unknown", EvaluateContext::Watch, None)
+                .is_err());
+            assert!(session.missing_sections().is_some());
+        });
+    }
+
+    #[test]
+    fn watchpoint_memory_range() {
+        with_session(|session| {
+            let watchpoint = session
+                .watchpoint("[0x20000000]", WatchKind::Write)
+                .unwrap();
+            assert_eq!(watchpoint.address, 0x2000_0000);
+            assert_eq!(watchpoint.size, 4);
+            // A value that is not stored in memory can not be watched.
+            assert!(session.watchpoint("1 + 2", WatchKind::Write).is_err());
+        });
+    }
+}
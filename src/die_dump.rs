@@ -0,0 +1,232 @@
+use crate::evaluate::attributes;
+use anyhow::Result;
+use gimli::{
+    AttributeValue, DebugInfoOffset, DebuggingInformationEntry, Dwarf, Expression, Reader, Unit,
+    UnitOffset,
+};
+
+/// Find the DIE at a offset in the `.debug_info` section.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `offset` - The offset of the DIE in the `.debug_info` section.
+///
+/// Will return the unit that contains the DIE and the offset of the DIE in that unit.
+pub fn find_die<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    offset: DebugInfoOffset,
+) -> Result<Option<(Unit<R>, UnitOffset)>> {
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        if let Some(unit_offset) = offset.to_unit_offset(&header) {
+            return Ok(Some((dwarf.unit(header)?, unit_offset)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Find the DIE of a function by name.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `name` - The name or linkage name of the function.
+///
+/// Will return the unit that contains the DIE and the offset of the DIE in that unit.
+pub fn find_function_die<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    name: &str,
+) -> Result<Option<(Unit<R>, UnitOffset)>> {
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        let unit = dwarf.unit(header)?;
+        let mut offset = None;
+        let mut entries = unit.entries();
+        while let Some((_, die)) = entries.next_dfs()? {
+            if die.tag() != gimli::DW_TAG_subprogram {
+                continue;
+            }
+
+            let linkage_name = match die.attr_value(gimli::DW_AT_linkage_name)? {
+                Some(value) => Some(dwarf.attr_string(&unit, value)?.to_string()?.to_string()),
+                None => None,
+            };
+            if attributes::name_attribute(dwarf, die)?.as_deref() == Some(name)
+                || linkage_name.as_deref() == Some(name)
+            {
+                offset = Some(die.offset());
+                break;
+            }
+        }
+
+        if let Some(offset) = offset {
+            return Ok(Some((unit, offset)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Dump a DIE and its attributes in a readable form.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `unit` - The unit that contains the DIE.
+/// * `die` - The DIE.
+///
+/// The output starts with the section offset and tag of the DIE, followed by one line per
+/// attribute.
+pub fn dump_die<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    unit: &Unit<R>,
+    die: &DebuggingInformationEntry<R>,
+) -> Result<String> {
+    let mut result = format!(
+        "{:#010x}: {}\n",
+        section_offset(unit, die.offset()),
+        die.tag()
+    );
+    let mut attrs = die.attrs();
+    while let Some(attr) = attrs.next()? {
+        result = format!(
+            "{}    {}: {}\n",
+            result,
+            attr.name(),
+            format_attribute(dwarf, unit, attr.value())?
+        );
+    }
+
+    Ok(result)
+}
+
+/// Dump a DIE and all the DIEs below it in a readable form, e.g. a function with its parameters,
+/// variables and lexical blocks.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `unit` - The unit that contains the DIE.
+/// * `offset` - The offset of the DIE in the unit.
+///
+/// Every DIE is indented by its depth in the tree.
+pub fn dump_tree<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    unit: &Unit<R>,
+    offset: UnitOffset,
+) -> Result<String> {
+    let mut result = String::new();
+    let mut depth = 0;
+    let mut entries = unit.entries_at_offset(offset)?;
+    while let Some((delta, die)) = entries.next_dfs()? {
+        depth += delta;
+        if depth <= 0 && !result.is_empty() {
+            break;
+        }
+
+        let indent = "  ".repeat(depth as usize);
+        for line in dump_die(dwarf, unit, die)?.lines() {
+            result = format!("{}{}{}\n", result, indent, line);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Dump the location of a variable in a readable form.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `unit` - The unit that contains the DIE.
+/// * `die` - The DIE of the variable.
+///
+/// A location list is written with one line per entry, with the address range of the entry.
+pub fn dump_location<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    unit: &Unit<R>,
+    die: &DebuggingInformationEntry<R>,
+) -> Result<String> {
+    match die.attr_value(gimli::DW_AT_location)? {
+        Some(AttributeValue::Exprloc(expr)) => format_expression(unit, expr),
+        Some(AttributeValue::LocationListsRef(offset)) => {
+            let mut result = format!("location list {:#x}\n", offset.0);
+            let mut locations = dwarf.locations(unit, offset)?;
+            while let Some(entry) = locations.next()? {
+                result = format!(
+                    "{}    [{:#010x}, {:#010x}): {}\n",
+                    result,
+                    entry.range.begin,
+                    entry.range.end,
+                    format_expression(unit, entry.data)?
+                );
+            }
+            Ok(result)
+        }
+        Some(value) => format_attribute(dwarf, unit, value),
+        None => match die.attr_value(gimli::DW_AT_const_value)? {
+            Some(value) => Ok(format!(
+                "constant {}",
+                format_attribute(dwarf, unit, value)?
+            )),
+            None => Ok("no location".to_string()),
+        },
+    }
+}
+
+/// Format a attribute value in a readable form.
+fn format_attribute<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    unit: &Unit<R>,
+    value: AttributeValue<R>,
+) -> Result<String> {
+    Ok(match value {
+        AttributeValue::Addr(address) => format!("{:#010x}", address),
+        AttributeValue::Udata(val) => val.to_string(),
+        AttributeValue::Sdata(val) => val.to_string(),
+        AttributeValue::Data1(val) => val.to_string(),
+        AttributeValue::Data2(val) => val.to_string(),
+        AttributeValue::Data4(val) => val.to_string(),
+        AttributeValue::Data8(val) => val.to_string(),
+        AttributeValue::Flag(val) => val.to_string(),
+        AttributeValue::UnitRef(offset) => {
+            format!("<{:#010x}>", section_offset(unit, offset))
+        }
+        AttributeValue::DebugInfoRef(offset) => format!("<{:#010x}>", offset.0),
+        AttributeValue::Exprloc(expr) => format_expression(unit, expr)?,
+        AttributeValue::LocationListsRef(offset) => format!("location list {:#x}", offset.0),
+        AttributeValue::RangeListsRef(offset) => format!("range list {:#x}", offset.0),
+        AttributeValue::Encoding(val) => val.to_string(),
+        AttributeValue::Language(val) => val.to_string(),
+        AttributeValue::Inline(val) => val.to_string(),
+        AttributeValue::FileIndex(val) => format!("file {}", val),
+        value => match dwarf.attr_string(unit, value.clone()) {
+            Ok(string) => format!("\"{}\"", string.to_string_lossy()?),
+            Err(_) => format!("{:?}", value),
+        },
+    })
+}
+
+/// Format the operations of a DWARF expression, separated by `; `.
+fn format_expression<R: Reader<Offset = usize>>(
+    unit: &Unit<R>,
+    expr: Expression<R>,
+) -> Result<String> {
+    let mut operations = vec![];
+    let mut iter = expr.operations(unit.encoding());
+    while let Some(operation) = iter.next()? {
+        operations.push(format!("{:?}", operation));
+    }
+
+    Ok(operations.join("; "))
+}
+
+/// Get the `.debug_info` section offset of a DIE, or the unit offset for other sections.
+fn section_offset<R: Reader<Offset = usize>>(unit: &Unit<R>, offset: UnitOffset) -> usize {
+    match offset.to_debug_info_offset(&unit.header) {
+        Some(val) => val.0,
+        None => offset.0,
+    }
+}
@@ -0,0 +1,97 @@
+use crate::call_stack::find_inlined_function;
+use crate::dwarf_index::DwarfIndex;
+use crate::source_information::find_line_row_with_index;
+use anyhow::Result;
+use gimli::{Dwarf, Reader};
+use log::trace;
+use std::fmt;
+use std::num::NonZeroU64;
+
+/// A run of consecutive instructions that belong to the same source line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceBlock {
+    /// The full path of the source file, `None` if the instructions have no source.
+    pub file: Option<String>,
+
+    /// The source line number, `None` if the instructions have no line.
+    pub line: Option<NonZeroU64>,
+
+    /// The name of the inlined function the instructions belong to, `None` if the instructions
+    /// are not inlined.
+    pub inlined_function: Option<String>,
+
+    /// The addresses of the instructions.
+    pub addresses: Vec<u64>,
+}
+
+impl fmt::Display for SourceBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => write!(f, "{}:{}", file, line)?,
+            (Some(file), None) => write!(f, "{}", file)?,
+            _ => write!(f, "<no source>")?,
+        };
+        match &self.inlined_function {
+            Some(name) => write!(f, " [inlined {}]", name),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Group disassembled instructions by the source lines they belong to, like `objdump -S`.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `index` - A index of the DWARF debug information.
+/// * `cwd` - The work directory of the debugged program.
+/// * `addresses` - The addresses of the decoded instructions, in order.
+///
+/// A new block is started every time the source file, the line or the inlined function changes,
+/// so the same line can have several blocks when the compiler has interleaved the code of
+/// several lines.
+/// Instructions that are not covered by the line table are put in blocks without a source.
+pub fn interleave_source<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    index: &DwarfIndex,
+    cwd: &str,
+    addresses: &[u64],
+) -> Result<Vec<SourceBlock>> {
+    let mut blocks: Vec<SourceBlock> = vec![];
+    for address in addresses {
+        let (file, line) = match find_line_row_with_index(dwarf, index, cwd, *address) {
+            Ok(Some(row)) => (row.file, row.line),
+            Ok(None) => (None, None),
+            Err(err) => {
+                trace!(
+                    "No line table row for {:#010x}, because: {:?}",
+                    address,
+                    err
+                );
+                (None, None)
+            }
+        };
+        let inlined_function = match file {
+            Some(_) => find_inlined_function(dwarf, *address as u32, cwd)?,
+            None => None,
+        };
+
+        match blocks.last_mut() {
+            Some(block)
+                if block.file == file
+                    && block.line == line
+                    && block.inlined_function == inlined_function =>
+            {
+                block.addresses.push(*address);
+            }
+            _ => blocks.push(SourceBlock {
+                file,
+                line,
+                inlined_function,
+                addresses: vec![*address],
+            }),
+        };
+    }
+
+    Ok(blocks)
+}
@@ -0,0 +1,180 @@
+use crate::call_stack::MemoryAccess;
+use crate::section::missing_sections;
+use gimli::{DebugFrame, Dwarf, Reader, SectionId};
+use std::fmt;
+
+/// The DWARF versions that can be debugged.
+pub const SUPPORTED_DWARF_VERSIONS: [u16; 4] = [2, 3, 4, 5];
+
+/// The result of one check of a self-test.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheckStatus {
+    /// The check passed.
+    Passed,
+
+    /// The check failed.
+    Failed,
+
+    /// The check was not run, e.g. because a earlier check that it depends on failed.
+    Skipped,
+}
+
+/// One check of a self-test, e.g. "debug_frame present".
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    /// What is checked.
+    pub name: String,
+
+    /// The result of the check.
+    pub status: CheckStatus,
+
+    /// A description of the result, e.g. why the check failed.
+    pub detail: Option<String>,
+}
+
+/// A checklist of the toolchain, probe and target path, e.g. for a `doctor` command.
+///
+/// The checks are run in order from the ELF file to the debug target, so the first failed check
+/// shows where the problem is.
+/// The checks of the probe and the target, e.g. that the probe opens and the target attaches,
+/// are added by the caller with `add`.
+#[derive(Debug, Clone, Default)]
+pub struct DoctorReport {
+    /// The checks in the order they were run.
+    pub checks: Vec<Check>,
+}
+
+impl DoctorReport {
+    /// Creates a new empty `DoctorReport`.
+    pub fn new() -> DoctorReport {
+        DoctorReport { checks: vec![] }
+    }
+
+    /// Add the result of a check.
+    ///
+    /// Description:
+    ///
+    /// * `name` - What is checked.
+    /// * `status` - The result of the check.
+    /// * `detail` - A description of the result, e.g. why the check failed.
+    pub fn add(&mut self, name: &str, status: CheckStatus, detail: Option<String>) {
+        self.checks.push(Check {
+            name: name.to_string(),
+            status,
+            detail,
+        });
+    }
+
+    /// Will return `true` if a check has failed.
+    pub fn has_failed(&self) -> bool {
+        self.checks.iter().any(|c| c.status == CheckStatus::Failed)
+    }
+
+    /// Get the first check that failed, it is the most likely cause of the problem.
+    pub fn first_failure(&self) -> Option<&Check> {
+        self.checks.iter().find(|c| c.status == CheckStatus::Failed)
+    }
+
+    /// Check the debug information of the program.
+    ///
+    /// Description:
+    ///
+    /// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+    /// * `debug_frame` - A reference to the DWARF section `.debug_frame`.
+    ///
+    /// Will check that the DWARF version of every compilation unit is supported and that
+    /// `.debug_frame` is present.
+    pub fn check_debug_info<R: Reader<Offset = usize>>(
+        &mut self,
+        dwarf: &Dwarf<R>,
+        debug_frame: &DebugFrame<R>,
+    ) {
+        let mut versions = vec![];
+        let mut units = dwarf.units();
+        let result = loop {
+            match units.next() {
+                Ok(Some(header)) => {
+                    if !versions.contains(&header.version()) {
+                        versions.push(header.version());
+                    }
+                }
+                Ok(None) => break Ok(()),
+                Err(err) => break Err(err),
+            }
+        };
+        versions.sort_unstable();
+
+        let (status, detail) = match result {
+            Err(err) => (
+                CheckStatus::Failed,
+                format!("Could not read the compilation units: {}", err),
+            ),
+            Ok(()) if versions.is_empty() => (
+                CheckStatus::Failed,
+                "The program has no compilation units".to_string(),
+            ),
+            Ok(()) => {
+                let listed: Vec<String> = versions.iter().map(|v| v.to_string()).collect();
+                match versions
+                    .iter()
+                    .all(|v| SUPPORTED_DWARF_VERSIONS.contains(v))
+                {
+                    true => (CheckStatus::Passed, format!("DWARF {}", listed.join(", "))),
+                    false => (
+                        CheckStatus::Failed,
+                        format!("Unsupported DWARF version in {}", listed.join(", ")),
+                    ),
+                }
+            }
+        };
+        self.add("DWARF version supported", status, Some(detail));
+
+        match missing_sections(dwarf, debug_frame).contains(&SectionId::DebugFrame) {
+            true => self.add(
+                "debug_frame present",
+                CheckStatus::Failed,
+                Some("The call stack can not be unwound".to_string()),
+            ),
+            false => self.add("debug_frame present", CheckStatus::Passed, None),
+        };
+    }
+
+    /// Check that the memory of the debug target can be read.
+    ///
+    /// Description:
+    ///
+    /// * `mem` - A struct for accessing the memory of the debug target.
+    /// * `address` - A address that is always readable, e.g. the start of the flash.
+    pub fn check_memory_read<M: MemoryAccess>(&mut self, mem: &mut M, address: u32) {
+        match mem.get_address(&address, 4) {
+            Some(data) if data.len() == 4 => self.add(
+                "memory read works",
+                CheckStatus::Passed,
+                Some(format!("{:#010x}: {:02x?}", address, data)),
+            ),
+            _ => self.add(
+                "memory read works",
+                CheckStatus::Failed,
+                Some(format!("Could not read 4 bytes from {:#010x}", address)),
+            ),
+        };
+    }
+}
+
+impl fmt::Display for DoctorReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for check in &self.checks {
+            let mark = match check.status {
+                CheckStatus::Passed => "ok",
+                CheckStatus::Failed => "FAILED",
+                CheckStatus::Skipped => "skipped",
+            };
+            write!(f, "[{}] {}", mark, check.name)?;
+            if let Some(detail) = &check.detail {
+                write!(f, ": {}", detail)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
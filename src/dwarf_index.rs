@@ -0,0 +1,270 @@
+use crate::evaluate::attributes;
+use crate::utils::DwarfOffset;
+use crate::variable::get_var_name;
+use anyhow::{anyhow, Result};
+use gimli::{AttributeValue, DebugInfoOffset, Dwarf, Operation, RangeIter, Reader, Unit};
+use log::error;
+use std::collections::HashMap;
+
+/// A machine code address range and the DIE it belongs to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct AddressRange<T> {
+    /// The first address of the range.
+    begin: u64,
+
+    /// The address after the last address of the range.
+    end: u64,
+
+    /// The offset of what the range belongs to.
+    offset: T,
+}
+
+/// A index of the DWARF debug information, it is built once and makes repeated lookups fast.
+///
+/// Finding the unit or function of a address, a static variable by name or address, or a type
+/// by name, otherwise requires parsing every compilation unit and walking its whole DIE tree.
+/// The index keeps the address ranges sorted, so those lookups are binary searches, and the
+/// names and static addresses in hash maps.
+/// Only offsets are stored, so the index does not borrow the `Dwarf` struct.
+#[derive(Debug, Clone, Default)]
+pub struct DwarfIndex {
+    /// The address ranges of the compilation units, sorted by their first address.
+    units: Vec<AddressRange<DebugInfoOffset>>,
+
+    /// The address ranges of the non inlined functions, sorted by their first address.
+    functions: Vec<AddressRange<DwarfOffset>>,
+
+    /// The variables with static storage duration, by name and by qualified name.
+    statics: HashMap<String, Vec<DwarfOffset>>,
+
+    /// The variables with static storage duration, by the address they are stored at.
+    static_addresses: HashMap<u64, DwarfOffset>,
+
+    /// The type DIEs, by qualified name, e.g. `app::Sensor`.
+    types: HashMap<String, DwarfOffset>,
+}
+
+impl DwarfIndex {
+    /// Creates a new `DwarfIndex` by walking all the compilation units once.
+    ///
+    /// Description:
+    ///
+    /// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+    pub fn new<R: Reader<Offset = usize>>(dwarf: &Dwarf<R>) -> Result<DwarfIndex> {
+        let mut index = DwarfIndex::default();
+        let mut units = dwarf.units();
+        while let Some(unit_header) = units.next()? {
+            let unit = dwarf.unit(unit_header)?;
+            if let Some(offset) = unit.header.offset().as_debug_info_offset() {
+                add_ranges(&mut index.units, dwarf.unit_ranges(&unit)?, offset)?;
+            }
+            index.add_unit(dwarf, &unit)?;
+        }
+        index.units.sort_by_key(|range| range.begin);
+        index.functions.sort_by_key(|range| range.begin);
+
+        Ok(index)
+    }
+
+    /// Add the functions and static variables of a compilation unit.
+    fn add_unit<R: Reader<Offset = usize>>(
+        &mut self,
+        dwarf: &Dwarf<R>,
+        unit: &Unit<R>,
+    ) -> Result<()> {
+        let mut cursor = unit.entries();
+
+        // The depth of the function the cursor is in, `None` if it is not in a function.
+        let mut function_depth: Option<isize> = None;
+        // The namespaces the cursor is in and their depths.
+        let mut namespaces: Vec<(isize, String)> = vec![];
+        let mut depth = 0;
+        while let Some((delta_depth, current)) = cursor.next_dfs()? {
+            depth += delta_depth;
+            if function_depth.is_some_and(|d| depth <= d) {
+                function_depth = None;
+            }
+            if function_depth.is_some() {
+                continue;
+            }
+            while namespaces.last().is_some_and(|(d, _)| depth <= *d) {
+                namespaces.pop();
+            }
+
+            let offset = DwarfOffset {
+                section_offset: unit.header.offset(),
+                unit_offset: current.offset(),
+            };
+            match current.tag() {
+                gimli::DW_TAG_subprogram => {
+                    function_depth = Some(depth);
+                    add_ranges(
+                        &mut self.functions,
+                        dwarf.die_ranges(unit, current)?,
+                        offset,
+                    )?;
+                }
+                gimli::DW_TAG_inlined_subroutine => function_depth = Some(depth),
+                gimli::DW_TAG_namespace => {
+                    let name = attributes::name_attribute(dwarf, current)?;
+                    namespaces.push((depth, name.unwrap_or_default()));
+                }
+                gimli::DW_TAG_variable => {
+                    let location = match current.attr_value(gimli::DW_AT_location)? {
+                        Some(val) => val,
+                        None => continue,
+                    };
+                    if let AttributeValue::Exprloc(expr) = location {
+                        if let Ok(Some(Operation::Address { address })) =
+                            expr.operations(unit.encoding()).next()
+                        {
+                            self.static_addresses.entry(address).or_insert(offset);
+                        }
+                    }
+                    if let Some(name) = get_var_name(dwarf, unit, current)? {
+                        let qualified_name = qualify(&namespaces, &name);
+                        if qualified_name != name {
+                            self.statics.entry(qualified_name).or_default().push(offset);
+                        }
+                        self.statics.entry(name).or_default().push(offset);
+                    }
+                }
+                gimli::DW_TAG_structure_type
+                | gimli::DW_TAG_enumeration_type
+                | gimli::DW_TAG_union_type
+                | gimli::DW_TAG_base_type
+                | gimli::DW_TAG_typedef => {
+                    if current.attr_value(gimli::DW_AT_declaration)?.is_some() {
+                        continue;
+                    }
+                    if let Some(name) = attributes::name_attribute(dwarf, current)? {
+                        self.types
+                            .entry(qualify(&namespaces, &name))
+                            .or_insert(offset);
+                    }
+                }
+                _ => (),
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Find the compilation unit that contains a address.
+    ///
+    /// Description:
+    ///
+    /// * `address` - A machine code address.
+    pub fn find_unit(&self, address: u32) -> Option<DebugInfoOffset> {
+        find_range(&self.units, address)
+    }
+
+    /// Parse the compilation unit that contains a address.
+    ///
+    /// Description:
+    ///
+    /// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+    /// * `address` - A machine code address.
+    ///
+    /// The same unit as `get_current_unit` finds, without parsing the other units.
+    pub fn unit<R: Reader<Offset = usize>>(
+        &self,
+        dwarf: &Dwarf<R>,
+        address: u32,
+    ) -> Result<Unit<R>> {
+        match self.find_unit(address) {
+            Some(offset) => Ok(dwarf.unit(dwarf.debug_info.header_from_offset(offset)?)?),
+            None => {
+                error!("No compilation unit contains {:#010x}", address);
+                Err(anyhow!("No compilation unit contains {:#010x}", address))
+            }
+        }
+    }
+
+    /// Find the non inlined function that contains a address.
+    ///
+    /// Description:
+    ///
+    /// * `address` - A machine code address.
+    ///
+    /// The same function as `find_non_inlined_function_die` finds, without walking any DIEs.
+    pub fn find_function(&self, address: u32) -> Option<DwarfOffset> {
+        find_range(&self.functions, address)
+    }
+
+    /// Find the variables with static storage duration that have a name.
+    ///
+    /// Description:
+    ///
+    /// * `name` - The name of the variable, or its name qualified with the namespaces it is in,
+    ///   e.g. `app::STATE`.
+    ///
+    /// There can be several, e.g. statics with the same name in different modules.
+    pub fn find_static_variables(&self, name: &str) -> &[DwarfOffset] {
+        match self.statics.get(name) {
+            Some(offsets) => offsets,
+            None => &[],
+        }
+    }
+
+    /// Find the variable with static storage duration that is stored at a address, e.g. a
+    /// vtable.
+    ///
+    /// Description:
+    ///
+    /// * `address` - The address of the variable.
+    pub fn find_static_variable_at(&self, address: u64) -> Option<DwarfOffset> {
+        self.static_addresses.get(&address).copied()
+    }
+
+    /// Find a type by its name qualified with the namespaces it is in.
+    ///
+    /// Description:
+    ///
+    /// * `name` - The qualified name of the type, e.g. `app::Sensor` or `u32`.
+    ///
+    /// Declarations are skipped, if several types have the name the first one is returned.
+    pub fn find_type(&self, name: &str) -> Option<DwarfOffset> {
+        self.types.get(name).copied()
+    }
+}
+
+/// Qualify a name with the names of the namespaces it is in, e.g. `app::STATE`.
+fn qualify(namespaces: &[(isize, String)], name: &str) -> String {
+    let mut result = String::new();
+    for (_, namespace) in namespaces {
+        result.push_str(namespace);
+        result.push_str("::");
+    }
+    result.push_str(name);
+    result
+}
+
+/// Add all the address ranges from a range iterator.
+fn add_ranges<R: Reader<Offset = usize>, T: Copy>(
+    ranges: &mut Vec<AddressRange<T>>,
+    mut iter: RangeIter<R>,
+    offset: T,
+) -> Result<()> {
+    while let Some(range) = iter.next()? {
+        if range.begin < range.end {
+            ranges.push(AddressRange {
+                begin: range.begin,
+                end: range.end,
+                offset,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Find the range that contains a address, the ranges have to be sorted by their first address.
+fn find_range<T: Copy>(ranges: &[AddressRange<T>], address: u32) -> Option<T> {
+    let address = address as u64;
+    let index = ranges.partition_point(|range| range.begin <= address);
+    let range = ranges.get(index.checked_sub(1)?)?;
+    match address < range.end {
+        true => Some(range.offset),
+        false => None,
+    }
+}
@@ -122,6 +122,38 @@ pub fn type_attribute<R: Reader<Offset = usize>>(
     }
 }
 
+/// This function will return the value of the containing_type attribute in the given DIE.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `unit` - A reference to gimli-rs `Unit` struct which contains the given DIE.
+/// * `die` - A reference to a gimli-rs `Die` struct.
+///
+/// This function will try to retrieve the value of the attribute `DW_AT_containing_type` from the given DIE.
+pub fn containing_type_attribute<R: Reader<Offset = usize>>(
+    dwarf: &gimli::Dwarf<R>,
+    unit: &Unit<R>,
+    die: &DebuggingInformationEntry<R>,
+) -> Result<Option<(gimli::UnitSectionOffset, gimli::UnitOffset)>> {
+    match die.attr_value(gimli::DW_AT_containing_type)? {
+        Some(gimli::AttributeValue::UnitRef(offset)) => Ok(Some((unit.header.offset(), offset))),
+        Some(gimli::AttributeValue::DebugInfoRef(di_offset)) => {
+            let offset = gimli::UnitSectionOffset::DebugInfoOffset(di_offset);
+            let mut iter = dwarf.debug_info.units();
+            while let Ok(Some(header)) = iter.next() {
+                let unit = dwarf.unit(header)?;
+                if let Some(offset) = offset.to_unit_offset(&unit) {
+                    return Ok(Some((unit.header.offset(), offset)));
+                }
+            }
+            error!("Could not find containing type attribute value");
+            Ok(None)
+        }
+        _ => Ok(None),
+    }
+}
+
 /// This function will return the value of the address_class attribute in the given DIE.
 ///
 /// Description:
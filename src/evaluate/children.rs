@@ -0,0 +1,153 @@
+use super::evaluate::EvaluatorValue;
+use gimli::Reader;
+
+/// A child of a composite value, e.g. a field of a struct or a element of a array.
+#[derive(Debug, Clone)]
+pub struct ChildValue<R: Reader<Offset = usize>> {
+    /// The name that is shown for the child, e.g. `x`, `[2]` or `*ptr`.
+    pub name: String,
+
+    /// The expression that evaluates to the child, e.g. `point.x` or `list[2]`.
+    pub path: String,
+
+    /// The value of the child.
+    pub value: EvaluatorValue<R>,
+}
+
+/// Will return `true` if the value can be expanded into children.
+///
+/// Description:
+///
+/// * `value` - The value.
+pub fn has_children<R: Reader<Offset = usize>>(value: &EvaluatorValue<R>) -> bool {
+    match value {
+        EvaluatorValue::Member(member) => has_children(&member.value),
+        EvaluatorValue::VariantPartValue(variant_part) => match variant_part.active_variant() {
+            Some(variant) => has_children(&variant.child.value),
+            None => false,
+        },
+        EvaluatorValue::Struct(structure) => !structure.members.is_empty(),
+        EvaluatorValue::Union(union) => !union.members.is_empty(),
+        EvaluatorValue::Array(array) => !array.values.is_empty(),
+        EvaluatorValue::PointerTypeValue(_) => true,
+        _ => false,
+    }
+}
+
+/// Get the children of a value, one level down in the value tree.
+///
+/// Description:
+///
+/// * `path` - The expression that evaluates to the value, e.g. `point`.
+/// * `value` - The value.
+///
+/// The path of each child is a expression that can be evaluated with `evaluate_expression`, so
+/// a client only has to remember the path of a expandable value and can evaluate its children
+/// when they are requested.
+/// Members and the active variant of a enum are transparent, the children of the value inside
+/// them are returned.
+pub fn child_values<R: Reader<Offset = usize>>(
+    path: &str,
+    value: &EvaluatorValue<R>,
+) -> Vec<ChildValue<R>> {
+    match value {
+        EvaluatorValue::Member(member) => child_values(path, &member.value),
+        EvaluatorValue::VariantPartValue(variant_part) => match variant_part.active_variant() {
+            Some(variant) => child_values(path, &variant.child.value),
+            None => vec![],
+        },
+        EvaluatorValue::Struct(structure) => member_children(path, &structure.members),
+        EvaluatorValue::Union(union) => member_children(path, &union.members),
+        EvaluatorValue::Array(array) => array
+            .values
+            .iter()
+            .enumerate()
+            .map(|(index, element)| ChildValue {
+                name: format!("[{}]", index),
+                path: format!("{}[{}]", path, index),
+                value: element.clone(),
+            })
+            .collect(),
+        EvaluatorValue::PointerTypeValue(pointer) => vec![ChildValue {
+            name: format!("*{}", path),
+            path: format!("(*{})", path),
+            value: pointer.value.clone(),
+        }],
+        _ => vec![],
+    }
+}
+
+/// Get the children of a struct or union from its members.
+///
+/// Description:
+///
+/// * `path` - The expression that evaluates to the struct or union.
+/// * `members` - The members of the struct or union.
+///
+/// Tuple fields are named without the `__` prefix, e.g. `pair.0`.
+/// The children of a enum are the fields of its active variant.
+fn member_children<R: Reader<Offset = usize>>(
+    path: &str,
+    members: &[EvaluatorValue<R>],
+) -> Vec<ChildValue<R>> {
+    members
+        .iter()
+        .enumerate()
+        .flat_map(|(index, member)| match member {
+            EvaluatorValue::Member(member) => {
+                let name = match member.name.as_deref() {
+                    Some(name) => match name.strip_prefix("__") {
+                        Some(field) if field.parse::<usize>().is_ok() => field.to_string(),
+                        _ => name.to_string(),
+                    },
+                    None => index.to_string(),
+                };
+                vec![ChildValue {
+                    path: format!("{}.{}", path, name),
+                    name,
+                    value: member.value.clone(),
+                }]
+            }
+            // The fields of a enum are in the active variant.
+            EvaluatorValue::VariantPartValue(_) => child_values(path, member),
+            _ => vec![],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::evaluate::{
+        BaseTypeValue, MemberValue, StructureTypeValue, ValueInformation,
+    };
+
+    type Value = EvaluatorValue<gimli::EndianSlice<'static, gimli::LittleEndian>>;
+
+    fn member(name: &str, value: u32) -> Value {
+        EvaluatorValue::Member(Box::new(MemberValue {
+            name: Some(name.to_string()),
+            value: EvaluatorValue::Value(
+                BaseTypeValue::U32(value),
+                ValueInformation::new(None, vec![]),
+            ),
+        }))
+    }
+
+    #[test]
+    fn struct_children() {
+        let value: Value = EvaluatorValue::Struct(Box::new(StructureTypeValue {
+            name: "Pair".to_string(),
+            members: vec![member("__0", 1), member("second", 2)],
+        }));
+        assert!(has_children(&value));
+
+        let children = child_values("pair", &value);
+        let names: Vec<(&str, &str)> = children
+            .iter()
+            .map(|c| (c.name.as_str(), c.path.as_str()))
+            .collect();
+        assert_eq!(names, vec![("0", "pair.0"), ("second", "pair.second")]);
+        assert!(!has_children(&children[0].value));
+    }
+}
@@ -1,7 +1,8 @@
 use super::attributes;
-use crate::call_stack::MemoryAccess;
+use crate::call_stack::{MemoryAccess, MemorySnapshot};
 use crate::registers::Registers;
-use std::convert::TryInto;
+use crate::utils::get_die_unit;
+use std::convert::{TryFrom, TryInto};
 
 use gimli::{DwAte, Location, Piece, Reader};
 
@@ -20,6 +21,9 @@ struct MyPiece<R: Reader<Offset = usize>> {
 
     /// Is true if this piece has already been used to evaluate a value.
     pub used_before: bool,
+
+    /// The number of bits of this piece that have already been used to evaluate values.
+    pub used_bits: u64,
 }
 impl<R: Reader<Offset = usize>> MyPiece<R> {
     /// Creates a new `MyPiece`.
@@ -27,6 +31,7 @@ impl<R: Reader<Offset = usize>> MyPiece<R> {
         MyPiece {
             piece,
             used_before: false,
+            used_bits: 0,
         }
     }
 
@@ -37,6 +42,7 @@ impl<R: Reader<Offset = usize>> MyPiece<R> {
     ///
     /// * `bit_size` - How many bits of data needed from the piece.
     pub fn should_remove(&mut self, bit_size: u64) -> bool {
+        self.used_bits += bit_size;
         match self.piece.size_in_bits {
             Some(val) => {
                 if val > bit_size {
@@ -151,6 +157,33 @@ impl<R: Reader<Offset = usize>> EvaluatorValue<R> {
         }
     }
 
+    /// Will return `true` if the value has the size 0, e.g. `PhantomData` and unit structs.
+    pub fn is_zero_sized(&self) -> bool {
+        match self {
+            EvaluatorValue::ZeroSize => true,
+            EvaluatorValue::Member(mem) => mem.value.is_zero_sized(),
+            EvaluatorValue::Struct(stu) => stu.members.iter().all(|m| m.is_zero_sized()),
+            _ => false,
+        }
+    }
+
+    /// Get the memory address the value was read from, e.g. for a DAP `memoryReference`.
+    ///
+    /// The address of a aggregate value is the lowest address of its members.
+    /// Will return `None` if no part of the value is stored in memory, e.g. a value in a
+    /// register.
+    pub fn address(&self) -> Option<u32> {
+        self.clone()
+            .get_variable_information()
+            .iter()
+            .flat_map(|info| info.pieces.iter())
+            .filter_map(|piece| match piece {
+                ValuePiece::Memory { address, .. } => Some(*address),
+                _ => None,
+            })
+            .min()
+    }
+
     /// Will return a `Vec` of location and unparsed value infromation about the value.
     pub fn get_variable_information(self) -> Vec<ValueInformation> {
         match self {
@@ -214,29 +247,7 @@ impl<R: Reader<Offset = usize>> EvaluatorValue<R> {
         let data_offset: u64 = 0;
 
         // Get the unit of the current state.
-        let unit = match unit_offset {
-            gimli::UnitSectionOffset::DebugInfoOffset(offset) => {
-                let header = dwarf.debug_info.header_from_offset(offset)?;
-                dwarf.unit(header)?
-            }
-            gimli::UnitSectionOffset::DebugTypesOffset(_offset) => {
-                let mut iter = dwarf.debug_types.units();
-                let mut result = None;
-                while let Some(header) = iter.next()? {
-                    if header.offset() == unit_offset {
-                        result = Some(dwarf.unit(header)?);
-                        break;
-                    }
-                }
-                match result {
-                    Some(val) => val,
-                    None => {
-                        error!("Could not find unit from offset");
-                        return Err(anyhow!("Could not find unit from offset"));
-                    }
-                }
-            }
-        };
+        let unit = get_unit(dwarf, unit_offset)?;
         info!("Found unit");
 
         // Get the die of the current state.
@@ -258,6 +269,67 @@ impl<R: Reader<Offset = usize>> EvaluatorValue<R> {
         )
     }
 
+    /// Evaluate a list of `Piece`s into a value and parse it to the given type, the memory of the
+    /// value is read once.
+    ///
+    /// Description:
+    ///
+    /// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+    /// * `registers` - A register struct for accessing the register values.
+    /// * `mem` - A struct for accessing the memory of the debug target.
+    /// * `pieces` - A list of gimli-rs pieces containing the location information..
+    /// * `unit_offset` - A offset to the `Unit` which contains the given type DIE.
+    /// * `die_offset` - A offset to the DIE that contains the type of the value.
+    ///
+    /// The whole byte range of a value that is stored in one piece of memory is read into a
+    /// snapshot, then all the members are evaluated from that snapshot.
+    /// This avoids side effects when a struct is mapped over read sensitive peripheral registers.
+    /// Values that are not stored in one piece of memory are evaluated as usual.
+    pub fn evaluate_variable_with_type_snapshot<M: MemoryAccess>(
+        dwarf: &gimli::Dwarf<R>,
+        registers: &Registers,
+        mem: &mut M,
+        pieces: &[Piece<R>],
+        unit_offset: gimli::UnitSectionOffset,
+        die_offset: gimli::UnitOffset,
+    ) -> Result<EvaluatorValue<R>> {
+        let unit = get_unit(dwarf, unit_offset)?;
+        let die = unit.entry(die_offset)?;
+
+        let address = match pieces {
+            [Piece {
+                location: Location::Address { address },
+                bit_offset: None,
+                ..
+            }] => Some(*address),
+            _ => None,
+        };
+
+        if let (Some(address), Some(byte_size)) = (address, attributes::byte_size_attribute(&die)?)
+        {
+            if let Some(mut snapshot) = MemorySnapshot::new(mem, address as u32, byte_size as usize)
+            {
+                return EvaluatorValue::evaluate_variable_with_type(
+                    dwarf,
+                    registers,
+                    &mut snapshot,
+                    pieces,
+                    unit_offset,
+                    die_offset,
+                );
+            }
+        }
+
+        EvaluatorValue::evaluate_variable_with_type(
+            dwarf,
+            registers,
+            mem,
+            pieces,
+            unit_offset,
+            die_offset,
+        )
+    }
+
     /// This function will evaluate the given pieces into a unsigned 32 bit integer.
     ///
     /// Description:
@@ -300,49 +372,84 @@ impl<R: Reader<Offset = usize>> EvaluatorValue<R> {
             return Ok(EvaluatorValue::OptimizedOut);
         }
 
+        let num_bits = 8 * byte_size;
         let mut all_bytes = vec![];
+        let mut all_bits = 0;
         let mut value_pieces = vec![];
-        while all_bytes.len() < byte_size.try_into()? {
+        // Set if a part of the value is optimized out, the rest of the pieces for this value
+        // still needs to be consumed so that the next value uses the correct pieces.
+        let mut optimized_out = false;
+        while all_bits < num_bits {
             if pieces.is_empty() {
-                error!("Unreachable");
-                return Err(anyhow!("Unreachable"));
-                //return Ok(EvaluatorValue::OptimizedOut);
+                // The rest of the value is not described by any piece.
+                return Ok(EvaluatorValue::OptimizedOut);
             }
 
-            // Evaluate the bytes needed from one gimli::Piece.
+            let needed_bits = num_bits - all_bits;
+            let bit_offset = pieces[0].piece.bit_offset.unwrap_or(0);
+
+            // Evaluate the bits needed from one gimli::Piece.
             match pieces[0].piece.clone().location {
                 Location::Empty => {
+                    let bit_size = match pieces[0].piece.size_in_bits {
+                        Some(val) => std::cmp::min(val, needed_bits),
+                        // The whole object is optimized out.
+                        None => return Ok(EvaluatorValue::OptimizedOut),
+                    };
+                    optimized_out = true;
+                    append_bits(&mut all_bytes, &mut all_bits, &[], 0, bit_size);
+
                     // Remove piece if whole object is used.
-                    let bit_size = 8 * (byte_size - all_bytes.len() as u64);
                     if pieces[0].should_remove(bit_size) {
                         pieces.remove(0);
                     }
-                    return Ok(EvaluatorValue::OptimizedOut);
                 }
                 Location::Register { ref register } => {
+                    // The bits of the register that are already used.
+                    let used_bits = match pieces[0].piece.size_in_bits {
+                        Some(_) => pieces[0].used_bits,
+                        None => 8 * data_offset + all_bits,
+                    };
+                    let register_offset = bit_offset + used_bits;
+                    let bit_size = match pieces[0].piece.size_in_bits {
+                        Some(val) => std::cmp::min(val, needed_bits),
+                        // 32 because 32 bit registers
+                        None => std::cmp::min(32_u64.saturating_sub(register_offset), needed_bits),
+                    };
+                    if bit_size == 0 {
+                        error!("Register {} has no bits left to read", register.0);
+                        return Err(anyhow!("Register {} has no bits left to read", register.0));
+                    }
+
                     match registers.get_register_value(&register.0) {
+                        None if registers.cfa.is_some() => {
+                            // The register value could not be recovered when unwinding the
+                            // call stack, thus this part of the value is optimized out.
+                            optimized_out = true;
+                            append_bits(&mut all_bytes, &mut all_bits, &[], 0, bit_size);
+                        }
                         Some(val) => {
-                            // TODO: Mask the important bits?
-                            let mut bytes = vec![];
-                            bytes.extend_from_slice(&val.to_le_bytes());
-
-                            bytes = trim_piece_bytes(bytes, &pieces[0].piece, 4); // 4 because 32 bit registers
-                            let bytes_len = bytes.len();
-
-                            all_bytes.extend_from_slice(&bytes);
+                            append_bits(
+                                &mut all_bytes,
+                                &mut all_bits,
+                                &val.to_le_bytes(),
+                                register_offset,
+                                bit_size,
+                            );
                             value_pieces.extend_from_slice(&[ValuePiece::Register {
                                 register: register.0,
-                                byte_size: bytes_len,
+                                byte_size: bit_size.div_ceil(8) as usize,
+                                bit_offset: register_offset,
+                                bit_size,
                             }]);
-
-                            // Remove piece if whole object is used.
-                            let bit_size = 8 * (bytes_len as u64);
-                            if pieces[0].should_remove(bit_size) {
-                                pieces.remove(0);
-                            }
                         }
                         None => return Err(anyhow!("Requires reg")),
                     };
+
+                    // Remove piece if whole object is used.
+                    if pieces[0].should_remove(bit_size) {
+                        pieces.remove(0);
+                    }
                 }
                 Location::Address { mut address } => {
                     // Check if `data_offset` should be used.
@@ -353,19 +460,15 @@ impl<R: Reader<Offset = usize>> EvaluatorValue<R> {
                             0
                         }
                     };
+                    // Whole bytes of the offset are added to the address.
+                    address += bit_offset / 8;
+                    let bit_offset = bit_offset % 8;
 
-                    let num_bytes = match pieces[0].piece.size_in_bits {
-                        Some(val) => {
-                            let max_num_bytes = (val + 8 - 1) / 8;
-                            let needed_num_bytes = byte_size - all_bytes.len() as u64;
-                            if max_num_bytes < needed_num_bytes {
-                                max_num_bytes
-                            } else {
-                                needed_num_bytes
-                            }
-                        }
-                        None => byte_size - all_bytes.len() as u64,
-                    } as usize;
+                    let bit_size = match pieces[0].piece.size_in_bits {
+                        Some(val) => std::cmp::min(val, needed_bits),
+                        None => needed_bits,
+                    };
+                    let num_bytes = (bit_offset + bit_size).div_ceil(8) as usize;
 
                     let bytes = match mem.get_address(&(address as u32), num_bytes) {
                         Some(val) => val,
@@ -382,22 +485,22 @@ impl<R: Reader<Offset = usize>> EvaluatorValue<R> {
                         }
                     };
 
-                    all_bytes.extend_from_slice(&bytes);
+                    append_bits(&mut all_bytes, &mut all_bits, &bytes, bit_offset, bit_size);
                     value_pieces.extend_from_slice(&[ValuePiece::Memory {
                         address: address as u32,
                         byte_size: num_bytes,
+                        bit_offset,
+                        bit_size,
                     }]);
 
                     // Remove piece if whole object is used.
-                    let bit_size = 8 * num_bytes as u64;
                     if pieces[0].should_remove(bit_size) {
                         pieces.remove(0);
                     }
                 }
                 Location::Value { value } => {
                     // Remove piece if whole object is used.
-                    let bit_size = 8 * (byte_size - all_bytes.len() as u64);
-                    if pieces[0].should_remove(bit_size) {
+                    if pieces[0].should_remove(needed_bits) {
                         pieces.remove(0);
                     }
 
@@ -439,42 +542,53 @@ impl<R: Reader<Offset = usize>> EvaluatorValue<R> {
                     };
                 }
 
-                Location::Bytes { mut value } => {
-                    let mut bytes = vec![];
-                    let mut loops = byte_size as usize - all_bytes.len();
-                    if value.len() < loops {
-                        loops = value.len();
-                    }
-                    for _i in 0..loops {
-                        bytes.push(value.read_u8()?);
+                Location::Bytes { value } => {
+                    let value = value.to_slice()?.to_vec();
+                    let value_offset = bit_offset + pieces[0].used_bits;
+                    let available_bits = (8 * value.len() as u64).saturating_sub(value_offset);
+                    let bit_size = match pieces[0].piece.size_in_bits {
+                        Some(val) => std::cmp::min(std::cmp::min(val, available_bits), needed_bits),
+                        None => std::cmp::min(available_bits, needed_bits),
+                    };
+                    if bit_size == 0 {
+                        // The rest of the value is not described by the implicit value.
+                        return Ok(EvaluatorValue::OptimizedOut);
                     }
 
-                    value_pieces.extend_from_slice(&[ValuePiece::Bytes {
-                        bytes: bytes.clone(),
-                    }]);
-
-                    all_bytes.extend_from_slice(&bytes.into_boxed_slice());
+                    let mut bytes = vec![];
+                    let mut bytes_bits = 0;
+                    append_bits(&mut bytes, &mut bytes_bits, &value, value_offset, bit_size);
+                    append_bits(&mut all_bytes, &mut all_bits, &bytes, 0, bit_size);
+                    value_pieces.extend_from_slice(&[ValuePiece::Bytes { bytes }]);
 
                     // Remove piece if whole object is used.
-                    let bit_size = 8 * (byte_size - all_bytes.len() as u64);
                     if pieces[0].should_remove(bit_size) {
                         pieces.remove(0);
                     }
-
-                    //return Ok(EvaluatorValue::Bytes(value.clone()));
                 }
                 Location::ImplicitPointer {
                     value: _,
                     byte_offset: _,
                 } => {
-                    error!("Unimplemented");
-                    return Err(anyhow!("Unimplemented"));
+                    // The pointer never exists at runtime, only the value it points to can be
+                    // evaluated and that is done when evaluating the pointer type.
+                    let bit_size = match pieces[0].piece.size_in_bits {
+                        Some(val) => std::cmp::min(val, needed_bits),
+                        None => needed_bits,
+                    };
+                    optimized_out = true;
+                    append_bits(&mut all_bytes, &mut all_bits, &[], 0, bit_size);
+
+                    // Remove piece if whole object is used.
+                    if pieces[0].should_remove(bit_size) {
+                        pieces.remove(0);
+                    }
                 }
             }
         }
 
-        while all_bytes.len() > byte_size as usize {
-            all_bytes.pop(); // NOTE: Removes extra bytes if value is from register and less the 4 byts
+        if optimized_out {
+            return Ok(EvaluatorValue::OptimizedOut);
         }
 
         Ok(EvaluatorValue::Value(
@@ -483,6 +597,112 @@ impl<R: Reader<Offset = usize>> EvaluatorValue<R> {
         ))
     }
 
+    /// Evaluate the value a implicit pointer points to.
+    ///
+    /// Description:
+    ///
+    /// * `registers` - A register struct for accessing the register values.
+    /// * `mem` - A struct for accessing the memory of the debug target.
+    /// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+    /// * `target` - A offset to the DIE that describes the value the pointer points to.
+    /// * `byte_offset` - The offset into the value the pointer points to.
+    /// * `type_offset` - Offsets to the `Unit` and the DIE that contains the pointee type.
+    ///
+    /// The value is read from the `DW_AT_const_value` or `DW_AT_location` attribute of the
+    /// `target` DIE.
+    /// Will return `OptimizedOut` if the value can not be evaluated at the current location.
+    fn eval_implicit_pointer<M: MemoryAccess>(
+        registers: &Registers,
+        mem: &mut M,
+        dwarf: &gimli::Dwarf<R>,
+        target: gimli::DebugInfoOffset,
+        byte_offset: i64,
+        type_offset: (gimli::UnitSectionOffset, gimli::UnitOffset),
+    ) -> Result<EvaluatorValue<R>> {
+        let (target_unit, target_offset) = match get_die_unit(dwarf, target) {
+            Ok(val) => val,
+            Err(err) => {
+                error!("Could not find the implicit pointer target die: {}", err);
+                return Err(anyhow!(
+                    "Could not find the implicit pointer target die: {}",
+                    err
+                ));
+            }
+        };
+        let target_die = target_unit.entry(target_offset)?;
+
+        let mut target_pieces = match target_die.attr_value(gimli::DW_AT_const_value)? {
+            Some(gimli::AttributeValue::Block(value)) => vec![Piece {
+                size_in_bits: None,
+                bit_offset: None,
+                location: Location::Bytes { value },
+            }],
+            Some(gimli::AttributeValue::Sdata(value)) => vec![Piece {
+                size_in_bits: None,
+                bit_offset: None,
+                location: Location::Value {
+                    value: gimli::Value::Generic(value as u64),
+                },
+            }],
+            Some(attribute) => match attribute.udata_value() {
+                Some(value) => vec![Piece {
+                    size_in_bits: None,
+                    bit_offset: None,
+                    location: Location::Value {
+                        value: gimli::Value::Generic(value),
+                    },
+                }],
+                None => {
+                    error!("Unimplemented for {:?}", attribute);
+                    return Err(anyhow!("Unimplemented for {:?}", attribute));
+                }
+            },
+            None => {
+                let pc = match registers
+                    .program_counter_register
+                    .and_then(|pc| registers.get_register_value(&(pc as u16)))
+                {
+                    Some(val) => *val,
+                    None => return Ok(EvaluatorValue::OptimizedOut),
+                };
+                let expr = match crate::variable::find_variable_location(
+                    dwarf,
+                    &target_unit,
+                    &target_die,
+                    pc,
+                )? {
+                    crate::variable::VariableLocation::Expression(expr) => expr,
+                    crate::variable::VariableLocation::LocationListEntry(llent) => llent.data,
+                    _ => return Ok(EvaluatorValue::OptimizedOut),
+                };
+
+                // The frame base of the target is not known here, thus values relative to it are
+                // reported as optimized out.
+                match super::evaluate_pieces(dwarf, &target_unit, pc, expr, None, registers, mem) {
+                    Ok(val) => val,
+                    Err(err) => {
+                        info!("Could not evaluate implicit pointer target: {}", err);
+                        return Ok(EvaluatorValue::OptimizedOut);
+                    }
+                }
+            }
+        };
+
+        // Move the start of the value to the byte offset of the pointer.
+        if let Some(piece) = target_pieces.first_mut() {
+            offset_piece(piece, byte_offset)?;
+        }
+
+        EvaluatorValue::evaluate_variable_with_type(
+            dwarf,
+            registers,
+            mem,
+            &target_pieces,
+            type_offset.0,
+            type_offset.1,
+        )
+    }
+
     /// Evaluate and parse the type by going down the tree of type dies.
     ///
     /// Description:
@@ -579,6 +799,38 @@ impl<R: Reader<Offset = usize>> EvaluatorValue<R> {
                     }
                 };
 
+                // The pointer only exists in the debug information, evaluate what it points to.
+                if let Some(Location::ImplicitPointer { value, byte_offset }) =
+                    pieces.first().map(|p| p.piece.location.clone())
+                {
+                    // The whole pointer is described by the implicit pointer piece.
+                    if pieces[0].should_remove(32) {
+                        pieces.remove(0);
+                    }
+
+                    let value = match attributes::type_attribute(dwarf, unit, die)? {
+                        Some(type_offset) => EvaluatorValue::eval_implicit_pointer(
+                            registers,
+                            mem,
+                            dwarf,
+                            value,
+                            byte_offset,
+                            type_offset,
+                        )?,
+                        None => EvaluatorValue::OptimizedOut,
+                    };
+
+                    return Ok(EvaluatorValue::PointerTypeValue(Box::new(
+                        PointerTypeValue {
+                            name,
+                            address: EvaluatorValue::OptimizedOut,
+                            value,
+                            c_string: None,
+                            truncated: false,
+                        },
+                    )));
+                }
+
                 // This vill evaluate the address
                 let address = match address_class.0 {
                     0 => {
@@ -639,11 +891,23 @@ impl<R: Reader<Offset = usize>> EvaluatorValue<R> {
                     _ => EvaluatorValue::OptimizedOut,
                 };
 
+                // A `char` pointer in C and C++ points to a NUL terminated string.
+                let c_string = match &address {
+                    EvaluatorValue::Value(BaseTypeValue::Address32(address_value), _)
+                        if is_c_language(unit)? && is_char_pointer(dwarf, unit, die)? =>
+                    {
+                        read_c_string(mem, *address_value)
+                    }
+                    _ => None,
+                };
+
                 Ok(EvaluatorValue::PointerTypeValue(Box::new(
                     PointerTypeValue {
                         name,
                         address,
                         value,
+                        c_string,
+                        truncated: false,
                     },
                 )))
 
@@ -744,15 +1008,9 @@ impl<R: Reader<Offset = usize>> EvaluatorValue<R> {
 
                 check_alignment(die, data_offset, pieces)?;
 
-                let name = match attributes::name_attribute(dwarf, die)? {
-                    Some(val) => val,
-                    None => {
-                        error!("Expected the structure type die to have a name attribute");
-                        return Err(anyhow!(
-                            "Expected the structure type die to have a name attribute"
-                        ));
-                    }
-                };
+                // C has anonymous structs.
+                let name = attributes::name_attribute(dwarf, die)?
+                    .unwrap_or_else(|| ANONYMOUS_NAME.to_string());
 
                 // Get all the DW_TAG_member dies.
                 let children = get_children(unit, die)?;
@@ -841,13 +1099,9 @@ impl<R: Reader<Offset = usize>> EvaluatorValue<R> {
 
                 check_alignment(die, data_offset, pieces)?;
 
-                let name = match attributes::name_attribute(dwarf, die)? {
-                    Some(val) => val,
-                    None => {
-                        error!("Expected union type die to have a name attribute");
-                        return Err(anyhow!("Expected union type die to have a name attribute"));
-                    }
-                };
+                // C has anonymous unions.
+                let name = attributes::name_attribute(dwarf, die)?
+                    .unwrap_or_else(|| ANONYMOUS_NAME.to_string());
 
                 // Get all children of type DW_TAG_member.
                 let children = get_children(unit, die)?;
@@ -873,6 +1127,12 @@ impl<R: Reader<Offset = usize>> EvaluatorValue<R> {
                 // Sort all the members in the order they need to be evaluated.
                 member_dies.sort_by_key(|m| m.0);
 
+                // Read the raw bytes before the members consume the pieces.
+                let raw = match attributes::byte_size_attribute(die)? {
+                    Some(byte_size) => read_raw_bytes(mem, byte_size, data_offset, pieces),
+                    None => None,
+                };
+
                 // Evaluate all the members.
                 let mut members = vec![];
                 for member_die in &member_dies {
@@ -897,6 +1157,7 @@ impl<R: Reader<Offset = usize>> EvaluatorValue<R> {
                 Ok(EvaluatorValue::Union(Box::new(UnionTypeValue {
                     name,
                     members,
+                    raw,
                 })))
             }
             gimli::DW_TAG_member => {
@@ -957,20 +1218,41 @@ impl<R: Reader<Offset = usize>> EvaluatorValue<R> {
 
                 check_alignment(die, data_offset, pieces)?;
 
-                // Get type attribute unit and die.
-                let (type_unit, die_offset) = get_type_info(dwarf, unit, die)?;
-                let type_die = &type_unit.entry(die_offset)?;
+                // Get type value, C enums often have no type attribute and are then evaluated as
+                // a unsigned integer of the enum size.
+                let variant = match attributes::type_attribute(dwarf, unit, die)? {
+                    Some(_) => {
+                        // Get type attribute unit and die.
+                        let (type_unit, die_offset) = get_type_info(dwarf, unit, die)?;
+                        let type_die = &type_unit.entry(die_offset)?;
 
-                // Get type value.
-                let variant = EvaluatorValue::eval_type(
-                    registers,
-                    mem,
-                    dwarf,
-                    &type_unit,
-                    type_die,
-                    data_offset,
-                    pieces,
-                )?;
+                        EvaluatorValue::eval_type(
+                            registers,
+                            mem,
+                            dwarf,
+                            &type_unit,
+                            type_die,
+                            data_offset,
+                            pieces,
+                        )?
+                    }
+                    None => match attributes::byte_size_attribute(die)? {
+                        Some(byte_size) => EvaluatorValue::handle_eval_piece(
+                            registers,
+                            mem,
+                            byte_size,
+                            data_offset,
+                            gimli::DW_ATE_unsigned,
+                            pieces,
+                        )?,
+                        None => {
+                            error!("Expected enumeration type die to have a type or byte size");
+                            return Err(anyhow!(
+                                "Expected enumeration type die to have a type or byte size"
+                            ));
+                        }
+                    },
+                };
 
                 // Go through the children and find the correct enumerator value.
                 let children = get_children(unit, die)?;
@@ -1001,15 +1283,9 @@ impl<R: Reader<Offset = usize>> EvaluatorValue<R> {
                 }
 
                 // Get the name of the enum type and the enum variant.
-                let name = match attributes::name_attribute(dwarf, die)? {
-                    Some(val) => val,
-                    None => {
-                        error!("Expected enumeration type die to have attribute DW_AT_name");
-                        return Err(anyhow!(
-                            "Expected enumeration type die to have attribute DW_AT_name"
-                        ));
-                    }
-                };
+                // C has anonymous enums.
+                let name = attributes::name_attribute(dwarf, die)?
+                    .unwrap_or_else(|| ANONYMOUS_NAME.to_string());
 
                 Ok(EvaluatorValue::Enum(Box::new(EnumerationTypeValue {
                     name,
@@ -1241,6 +1517,29 @@ impl<R: Reader<Offset = usize>> EvaluatorValue<R> {
                 error!("Unimplemented");
                 Err(anyhow!("Unimplemented"))
             }
+            gimli::DW_TAG_typedef
+            | gimli::DW_TAG_const_type
+            | gimli::DW_TAG_volatile_type
+            | gimli::DW_TAG_restrict_type => {
+                // Typedefs and type modifiers are common in C, they have the same value as the
+                // type they refer to.
+                // A type modifier without a type attribute modifies `void`.
+                if attributes::type_attribute(dwarf, unit, die)?.is_none() {
+                    return Ok(EvaluatorValue::ZeroSize);
+                }
+
+                let (type_unit, die_offset) = get_type_info(dwarf, unit, die)?;
+                let type_die = &type_unit.entry(die_offset)?;
+                EvaluatorValue::eval_type(
+                    registers,
+                    mem,
+                    dwarf,
+                    &type_unit,
+                    type_die,
+                    data_offset,
+                    pieces,
+                )
+            }
             tag => {
                 error!("Unimplemented for tag {:?}", tag);
                 Err(anyhow!("Unimplemented"))
@@ -1308,6 +1607,192 @@ fn format_types<R: Reader<Offset = usize>>(values: &Vec<EvaluatorValue<R>>) -> S
     res
 }
 
+/// The name used for anonymous structs, unions and enums.
+const ANONYMOUS_NAME: &str = "<anonymous>";
+
+/// Written after a string or the elements of a array that are not read in full.
+pub const TRUNCATED_MARKER: &str = "<truncated>";
+
+/// The maximum number of bytes read from a NUL terminated string.
+const MAX_C_STRING_LENGTH: usize = 256;
+
+/// Check if a compilation unit is written in C or C++.
+///
+/// Description:
+///
+/// * `unit` - A compilation unit.
+fn is_c_language<R: Reader<Offset = usize>>(unit: &gimli::Unit<R>) -> Result<bool> {
+    let mut entries = unit.entries();
+    let language = match entries.next_dfs()? {
+        Some((_, die)) => die.attr_value(gimli::DW_AT_language)?,
+        None => None,
+    };
+
+    Ok(matches!(
+        language,
+        Some(gimli::AttributeValue::Language(
+            gimli::DW_LANG_C
+                | gimli::DW_LANG_C89
+                | gimli::DW_LANG_C99
+                | gimli::DW_LANG_C11
+                | gimli::DW_LANG_C17
+                | gimli::DW_LANG_C_plus_plus
+                | gimli::DW_LANG_C_plus_plus_03
+                | gimli::DW_LANG_C_plus_plus_11
+                | gimli::DW_LANG_C_plus_plus_14
+        ))
+    ))
+}
+
+/// Check if a pointer type DIE points to a `char`, type modifiers and typedefs are followed.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `unit` - A compilation unit which contains the given DIE.
+/// * `die` - The pointer type DIE.
+fn is_char_pointer<R: Reader<Offset = usize>>(
+    dwarf: &gimli::Dwarf<R>,
+    unit: &gimli::Unit<R>,
+    die: &gimli::DebuggingInformationEntry<'_, '_, R>,
+) -> Result<bool> {
+    if attributes::type_attribute(dwarf, unit, die)?.is_none() {
+        return Ok(false);
+    }
+
+    let (type_unit, die_offset) = get_type_info(dwarf, unit, die)?;
+    let type_die = type_unit.entry(die_offset)?;
+    match type_die.tag() {
+        gimli::DW_TAG_base_type => Ok(matches!(
+            attributes::encoding_attribute(&type_die)?,
+            Some(gimli::DW_ATE_signed_char | gimli::DW_ATE_unsigned_char)
+        )),
+        gimli::DW_TAG_typedef
+        | gimli::DW_TAG_const_type
+        | gimli::DW_TAG_volatile_type
+        | gimli::DW_TAG_restrict_type => is_char_pointer(dwarf, &type_unit, &type_die),
+        _ => Ok(false),
+    }
+}
+
+/// Read a NUL terminated string from the memory of the debug target.
+///
+/// Description:
+///
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `address` - The address of the first character.
+///
+/// The string is read one byte at a time so that no memory after the string is read, and at
+/// most `MAX_C_STRING_LENGTH` bytes are read, the string also ends at the end of the address
+/// space.
+/// Will return `None` if the first byte can not be read.
+fn read_c_string<M: MemoryAccess>(mem: &mut M, address: u32) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+    while bytes.len() < MAX_C_STRING_LENGTH {
+        let byte_address = match address.checked_add(bytes.len() as u32) {
+            Some(val) => val,
+            None => break,
+        };
+        let byte = match mem.get_address(&byte_address, 1) {
+            Some(val) => *val.first()?,
+            None if bytes.is_empty() => return None,
+            None => break,
+        };
+        if byte == 0 {
+            break;
+        }
+        bytes.push(byte);
+    }
+
+    Some(bytes)
+}
+
+/// Get the unit that a unit section offset points to.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `unit_offset` - A offset to a unit in the `.debug_info` or `.debug_types` section.
+pub fn get_unit<R: Reader<Offset = usize>>(
+    dwarf: &gimli::Dwarf<R>,
+    unit_offset: gimli::UnitSectionOffset,
+) -> Result<gimli::Unit<R>> {
+    Ok(match unit_offset {
+        gimli::UnitSectionOffset::DebugInfoOffset(offset) => {
+            let header = dwarf.debug_info.header_from_offset(offset)?;
+            dwarf.unit(header)?
+        }
+        gimli::UnitSectionOffset::DebugTypesOffset(_offset) => {
+            let mut iter = dwarf.debug_types.units();
+            let mut result = None;
+            while let Some(header) = iter.next()? {
+                if header.offset() == unit_offset {
+                    result = Some(dwarf.unit(header)?);
+                    break;
+                }
+            }
+            match result {
+                Some(val) => val,
+                None => {
+                    error!("Could not find unit from offset");
+                    return Err(anyhow!("Could not find unit from offset"));
+                }
+            }
+        }
+    })
+}
+
+/// Read the raw bytes of a value without consuming the pieces.
+///
+/// Description:
+///
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `byte_size` - The size of the value in bytes.
+/// * `data_offset` - The memory address offset.
+/// * `pieces` - A list of pieces containing the location and size information.
+///
+/// Only values that are completely stored in memory or in the DWARF expression are read.
+fn read_raw_bytes<R: Reader<Offset = usize>, M: MemoryAccess>(
+    mem: &mut M,
+    byte_size: u64,
+    data_offset: u64,
+    pieces: &[MyPiece<R>],
+) -> Option<Vec<u8>> {
+    let piece = pieces.first()?;
+    if let Some(size_in_bits) = piece.piece.size_in_bits {
+        if size_in_bits < 8 * byte_size {
+            return None;
+        }
+    }
+
+    // Only whole bytes can be read.
+    let bit_offset = piece.piece.bit_offset.unwrap_or(0);
+    if bit_offset & 7 != 0 {
+        return None;
+    }
+
+    match &piece.piece.location {
+        Location::Address { address } => {
+            let offset = match piece.used_before {
+                true => data_offset,
+                false => 0,
+            };
+            mem.get_address(
+                &((address + offset + bit_offset / 8) as u32),
+                byte_size as usize,
+            )
+        }
+        Location::Bytes { value } => {
+            let start = ((bit_offset + piece.used_bits) / 8) as usize;
+            let bytes = value.to_slice().ok()?;
+            bytes
+                .get(start..start + byte_size as usize)
+                .map(|b| b.to_vec())
+        }
+        _ => None,
+    }
+}
+
 /// Struct that represents a array type.
 #[derive(Debug, Clone)]
 pub struct ArrayTypeValue<R: Reader<Offset = usize>> {
@@ -1320,11 +1805,25 @@ pub struct ArrayTypeValue<R: Reader<Offset = usize>> {
 
 impl<R: Reader<Offset = usize>> fmt::Display for ArrayTypeValue<R> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "[ {} ]", format_values(&self.values))
+        match self.is_truncated() {
+            true => write!(
+                f,
+                "[ {}, {} ]",
+                format_values(&self.values),
+                TRUNCATED_MARKER
+            ),
+            false => write!(f, "[ {} ]", format_values(&self.values)),
+        }
     }
 }
 
 impl<R: Reader<Offset = usize>> ArrayTypeValue<R> {
+    /// Check if the array has fewer values than its element count, e.g. when only the first
+    /// elements of a large buffer are read.
+    pub fn is_truncated(&self) -> bool {
+        matches!(self.subrange_type_value.count, Some(count) if count > self.values.len() as u64)
+    }
+
     /// Get the type of the array as a `String`.
     pub fn get_type(&self) -> String {
         format!("[ {} ]", format_types(&self.values))
@@ -1343,7 +1842,14 @@ pub struct StructureTypeValue<R: Reader<Offset = usize>> {
 
 impl<R: Reader<Offset = usize>> fmt::Display for StructureTypeValue<R> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} {{ {} }}", self.name, format_values(&self.members))
+        // Zero sized members, e.g. `PhantomData`, only clutter the output.
+        let members: Vec<EvaluatorValue<R>> = self
+            .members
+            .iter()
+            .filter(|m| !m.is_zero_sized())
+            .cloned()
+            .collect();
+        write!(f, "{} {{ {} }}", self.name, format_values(&members))
     }
 }
 
@@ -1369,11 +1875,25 @@ pub struct EnumerationTypeValue<R: Reader<Offset = usize>> {
 
 impl<R: Reader<Offset = usize>> fmt::Display for EnumerationTypeValue<R> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}::{}", self.name, self.variant)
+        match self.enumerator() {
+            Some(name) => write!(f, "{}::{}", self.name, name),
+            None => write!(f, "{}::{}", self.name, self.variant),
+        }
     }
 }
 
 impl<R: Reader<Offset = usize>> EnumerationTypeValue<R> {
+    /// Get the name of the enumerator that has the same value as the enum.
+    ///
+    /// This is used for enums without variant parts, e.g. C enums.
+    pub fn enumerator(&self) -> Option<&str> {
+        let value = get_udata(self.variant.clone().to_value()?).ok()?;
+        self.enumerators
+            .iter()
+            .find(|e| e.const_value == value)
+            .and_then(|e| e.name.as_deref())
+    }
+
     /// Get the type of the enum as a `String`.
     pub fn get_type(&self) -> String {
         format!("{}::{}", self.name, self.variant.get_type())
@@ -1388,6 +1908,9 @@ pub struct UnionTypeValue<R: Reader<Offset = usize>> {
 
     /// The values of the union type.
     pub members: Vec<EvaluatorValue<R>>,
+
+    /// The raw bytes of the union, `None` if they are not stored in memory or in DWARF.
+    pub raw: Option<Vec<u8>>,
 }
 
 impl<R: Reader<Offset = usize>> fmt::Display for UnionTypeValue<R> {
@@ -1401,6 +1924,22 @@ impl<R: Reader<Offset = usize>> UnionTypeValue<R> {
     pub fn get_type(&self) -> String {
         format!("{} ( {} )", self.name, format_types(&self.members))
     }
+
+    /// Interpret the union as one of its members.
+    ///
+    /// Description:
+    ///
+    /// * `member` - The name of the member or the name of the type of the member.
+    ///
+    /// This is used to print a union as a chosen member, e.g. `print u as MemberType`.
+    pub fn member_as(&self, member: &str) -> Option<&EvaluatorValue<R>> {
+        self.members.iter().find(|m| match m {
+            EvaluatorValue::Member(mem) => {
+                mem.name.as_deref() == Some(member) || mem.value.get_type() == member
+            }
+            value => value.get_type() == member,
+        })
+    }
 }
 
 /// Struct that represents a attribute type.
@@ -1443,6 +1982,13 @@ pub struct PointerTypeValue<R: Reader<Offset = usize>> {
 
     /// The value stored at the pointed location
     pub value: EvaluatorValue<R>,
+
+    /// The NUL terminated string the pointer points to, it is only read for `char` pointers in
+    /// C and C++.
+    pub c_string: Option<Vec<u8>>,
+
+    /// Whether `c_string` only holds the first part of the string.
+    pub truncated: bool,
     // DW_TAG_pointer_type contains:
     // * DW_AT_type
     // * DW_AT_name
@@ -1451,6 +1997,14 @@ pub struct PointerTypeValue<R: Reader<Offset = usize>> {
 
 impl<R: Reader<Offset = usize>> fmt::Display for PointerTypeValue<R> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(c_string) = &self.c_string {
+            write!(f, "{:?}", String::from_utf8_lossy(c_string))?;
+            if self.truncated {
+                write!(f, " {}", TRUNCATED_MARKER)?;
+            }
+            return Ok(());
+        }
+
         match &self.name {
             Some(name) => write!(f, "{}::{}", name, self.value),
             None => write!(f, "{}", self.value),
@@ -1559,6 +2113,27 @@ impl<R: Reader<Offset = usize>> fmt::Display for VariantPartValue<R> {
 }
 
 impl<R: Reader<Offset = usize>> VariantPartValue<R> {
+    /// Get the variant that is selected by the discriminant, e.g. the `Some` variant of a
+    /// `Option`.
+    ///
+    /// The variant without a discriminant value is the default variant, it is selected when no
+    /// other variant matches, e.g. the `Some` variant of a niche optimized `Option<&T>`.
+    /// Will return `None` if the discriminant is unknown.
+    pub fn active_variant(&self) -> Option<&VariantValue<R>> {
+        let discriminant = match &self.variant {
+            Some(member) => match member.value.clone().to_value()? {
+                BaseTypeValue::Address32(val) | BaseTypeValue::Reg32(val) => val as u64,
+                val => get_udata(val).ok()?,
+            },
+            None if self.variants.len() == 1 => return self.variants.first(),
+            None => return None,
+        };
+        self.variants
+            .iter()
+            .find(|v| v.discr_value == Some(discriminant))
+            .or_else(|| self.variants.iter().find(|v| v.discr_value.is_none()))
+    }
+
     /// Get the type of the variant_part as a `String`.
     pub fn get_type(&self) -> String {
         // TODO: Improve
@@ -1780,7 +2355,13 @@ impl BaseTypeValue {
                 }
             })), // (DW_ATE_signed = 5, 64)
 
-            //        (DwAte(6), _) => ,     // DW_ATE_signed_char = 6 // TODO: Add type
+            (DwAte(6), 1) => BaseTypeValue::I8(i8::from_le_bytes(match data.try_into() {
+                Ok(val) => val,
+                Err(err) => {
+                    error!("{:?}", err);
+                    return Err(anyhow!("{:?}", err));
+                }
+            })), // (DW_ATE_signed_char = 6, 8)
             (DwAte(7), 1) => BaseTypeValue::U8(u8::from_le_bytes(match data.try_into() {
                 Ok(val) => val,
                 Err(err) => {
@@ -1809,6 +2390,13 @@ impl BaseTypeValue {
                     return Err(anyhow!("{:?}", err));
                 }
             })), // (DW_ATE_unsigned = 7, 64)
+            (DwAte(8), 1) => BaseTypeValue::U8(u8::from_le_bytes(match data.try_into() {
+                Ok(val) => val,
+                Err(err) => {
+                    error!("{:?}", err);
+                    return Err(anyhow!("{:?}", err));
+                }
+            })), // (DW_ATE_unsigned_char = 8, 8)
             _ => {
                 error!("encoding {}, byte_size: {}", encoding, data.len());
                 return Err(anyhow!("encoding {}, byte_size: {}", encoding, data.len()));
@@ -1830,7 +2418,7 @@ impl BaseTypeValue {
             BaseTypeValue::I64(_) => "i64".to_owned(),
             BaseTypeValue::U64(_) => "u64".to_owned(),
             BaseTypeValue::F32(_) => "f32".to_owned(),
-            BaseTypeValue::F64(_) => "f63".to_owned(),
+            BaseTypeValue::F64(_) => "f64".to_owned(),
             BaseTypeValue::Address32(_) => "<32 bit address>".to_owned(),
             BaseTypeValue::Reg32(_) => "<32 bit register value>".to_owned(),
         }
@@ -1993,47 +2581,96 @@ fn get_children<R: Reader<Offset = usize>>(
     Ok(result)
 }
 
-/// Will remove the unnecessary bytes.
+/// Move the start of a piece by a signed byte offset, e.g. the offset of a implicit pointer.
 ///
 /// Description:
 ///
-/// * `bytes` - The bytes to be trimmed of unnecessary bytes.
-/// * `piece` - The piece the given bytes is evaluated from.
-/// * `byte_size` - The byte size of the resulting trim.
+/// * `piece` - The piece to move.
+/// * `byte_offset` - The offset in bytes, it can be negative.
 ///
-/// Some pieces contain more bytes then the type describes.
-/// Thus this function removes those unused bytes.
-fn trim_piece_bytes<R: Reader<Offset = usize>>(
-    mut bytes: Vec<u8>,
-    piece: &Piece<R>,
-    byte_size: usize,
-) -> Vec<u8> {
-    let piece_byte_size = match piece.size_in_bits {
-        Some(size) => ((size + 8 - 1) / 8) as usize,
-        None => byte_size,
-    };
-
-    let piece_byte_offset = match piece.bit_offset {
-        Some(offset) => {
-            //if offset % 8 == 0 {
-            //    error!("Expected the offset to be in bytes, got {} bits", offset);
-            //    return Err(anyhow!("Expected the offset to be in bytes, got {} bits", offset));
-            //}
-            ((offset + 8 - 1) / 8) as usize
+/// A value in memory can be offset in both directions, other values have nothing before their
+/// start, so a negative offset into them is a error.
+/// A constant value is sliced at the offset, its bytes are in little endian order.
+fn offset_piece<R: Reader<Offset = usize>>(piece: &mut Piece<R>, byte_offset: i64) -> Result<()> {
+    let moved = match piece.location {
+        Location::Address { ref mut address } => match address.checked_add_signed(byte_offset) {
+            Some(val) => {
+                *address = val;
+                true
+            }
+            None => false,
+        },
+        Location::Value { ref mut value } => {
+            match (u32::try_from(byte_offset), value.to_u64(u64::MAX)) {
+                (Ok(offset), Ok(val)) if offset < 8 => {
+                    *value = gimli::Value::Generic(val >> (8 * offset));
+                    true
+                }
+                _ => false,
+            }
         }
-        None => 0,
+        _ => match u64::try_from(byte_offset)
+            .ok()
+            .and_then(|offset| offset.checked_mul(8))
+            .and_then(|offset| offset.checked_add(piece.bit_offset.unwrap_or(0)))
+        {
+            Some(val) => {
+                piece.bit_offset = Some(val);
+                true
+            }
+            None => false,
+        },
     };
-
-    for _ in 0..piece_byte_offset {
-        bytes.pop();
+    if !moved {
+        error!(
+            "Invalid byte offset {} into {:?}",
+            byte_offset, piece.location
+        );
+        return Err(anyhow!(
+            "Invalid byte offset {} into {:?}",
+            byte_offset,
+            piece.location
+        ));
     }
+    Ok(())
+}
 
-    while bytes.len() > piece_byte_size {
-        // TODO: Check that this follows the ABI.
-        bytes.remove(0);
-    }
+/// Will append bits to the end of a little endian bit buffer.
+///
+/// Description:
+///
+/// * `bytes` - The bit buffer that the bits are appended to.
+/// * `num_bits` - The number of bits in the `bytes` buffer, it will be updated.
+/// * `source` - The little endian bytes to take the bits from, missing bytes are read as zero.
+/// * `bit_offset` - The offset in bits from the start of `source` to the first bit to append.
+/// * `bit_size` - The number of bits to append.
+///
+/// A piece can start at any bit of a location and have any bit size, as described by the
+/// `DW_OP_bit_piece` operation in DWARF 5 section 2.6.1.2.
+/// Thus the bits of each piece needs to be shifted into place to assemble the value.
+pub fn append_bits(
+    bytes: &mut Vec<u8>,
+    num_bits: &mut u64,
+    source: &[u8],
+    bit_offset: u64,
+    bit_size: u64,
+) {
+    for i in 0..bit_size {
+        let src_bit = bit_offset + i;
+        let bit = match source.get((src_bit / 8) as usize) {
+            Some(byte) => (byte >> (src_bit % 8)) & 1,
+            None => 0,
+        };
 
-    bytes
+        let dst_bit = *num_bits + i;
+        if dst_bit == 8 * bytes.len() as u64 {
+            bytes.push(0);
+        }
+        if let Some(byte) = bytes.last_mut() {
+            *byte |= bit << (dst_bit % 8);
+        }
+    }
+    *num_bits += bit_size;
 }
 
 /// Contains the unparsed value and the location of it.
@@ -2065,6 +2702,12 @@ pub enum ValuePiece {
 
         /// The size of the value.
         byte_size: usize,
+
+        /// The offset of the first bit of the value in the register.
+        bit_offset: u64,
+
+        /// The size of the value in bits.
+        bit_size: u64,
     },
 
     /// Contains which address the value is located and the size of it.
@@ -2074,6 +2717,12 @@ pub enum ValuePiece {
 
         /// The size of the value.
         byte_size: usize,
+
+        /// The offset of the first bit of the value in the byte at `address`.
+        bit_offset: u64,
+
+        /// The size of the value in bits.
+        bit_size: u64,
     },
 
     /// Contains the value stored on the DWARF stack.
@@ -2086,3 +2735,175 @@ pub enum ValuePiece {
     /// TODO
     Bytes { bytes: Vec<u8> },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gimli::{EndianSlice, LittleEndian, Register};
+
+    type TestReader = EndianSlice<'static, LittleEndian>;
+
+    fn register_piece(
+        register: u16,
+        size_in_bits: Option<u64>,
+        bit_offset: Option<u64>,
+    ) -> MyPiece<TestReader> {
+        MyPiece::new(Piece {
+            size_in_bits,
+            bit_offset,
+            location: Location::Register {
+                register: Register(register),
+            },
+        })
+    }
+
+    fn memory_piece(address: u64, size_in_bits: Option<u64>) -> MyPiece<TestReader> {
+        MyPiece::new(Piece {
+            size_in_bits,
+            bit_offset: None,
+            location: Location::Address { address },
+        })
+    }
+
+    fn no_memory(_address: &u32, _num_bytes: usize) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn registers(values: &[(u16, u32)]) -> Registers {
+        let mut registers = Registers::default();
+        for (register, value) in values {
+            registers.add_register_value(*register, *value);
+        }
+        registers
+    }
+
+    fn eval(
+        registers: &Registers,
+        mem: &mut impl MemoryAccess,
+        byte_size: u64,
+        data_offset: u64,
+        encoding: DwAte,
+        pieces: &mut Vec<MyPiece<TestReader>>,
+    ) -> Option<BaseTypeValue> {
+        EvaluatorValue::handle_eval_piece(registers, mem, byte_size, data_offset, encoding, pieces)
+            .unwrap()
+            .to_value()
+    }
+
+    #[test]
+    fn offset_piece_signed() {
+        let mut piece = memory_piece(0x2000_0010, None).piece;
+        offset_piece(&mut piece, -4).unwrap();
+        assert!(matches!(
+            piece.location,
+            Location::Address {
+                address: 0x2000_000c
+            }
+        ));
+
+        let mut piece: Piece<TestReader> = Piece {
+            size_in_bits: None,
+            bit_offset: None,
+            location: Location::Value {
+                value: gimli::Value::Generic(0x4433_2211),
+            },
+        };
+        offset_piece(&mut piece, 2).unwrap();
+        assert!(matches!(
+            piece.location,
+            Location::Value {
+                value: gimli::Value::Generic(0x4433)
+            }
+        ));
+        assert!(offset_piece(&mut piece, -1).is_err());
+
+        let mut piece = register_piece(0, None, Some(4)).piece;
+        offset_piece(&mut piece, 1).unwrap();
+        assert_eq!(piece.bit_offset, Some(12));
+        assert!(offset_piece(&mut piece, -1).is_err());
+        assert_eq!(piece.bit_offset, Some(12));
+    }
+
+    #[test]
+    fn append_bits_unaligned() {
+        let mut bytes = vec![];
+        let mut num_bits = 0;
+        append_bits(&mut bytes, &mut num_bits, &[0b1011_0000], 4, 4);
+        append_bits(&mut bytes, &mut num_bits, &[0xff, 0x01], 4, 8);
+        assert_eq!(num_bits, 12);
+        assert_eq!(bytes, vec![0xfb, 0x01]);
+    }
+
+    #[test]
+    fn register_byte_piece_uses_low_byte() {
+        let registers = registers(&[(0, 0x1122_3344)]);
+        let mut pieces = vec![register_piece(0, Some(8), None)];
+        let value = eval(&registers, &mut no_memory, 1, 0, DwAte(7), &mut pieces);
+        assert!(matches!(value, Some(BaseTypeValue::U8(0x44))));
+        assert!(pieces.is_empty());
+    }
+
+    #[test]
+    fn bool_bit_piece_in_register() {
+        let registers = registers(&[(1, 0b1000), (2, 0)]);
+        let mut pieces = vec![
+            register_piece(1, Some(1), Some(3)),
+            register_piece(2, Some(7), None),
+        ];
+        let value = eval(&registers, &mut no_memory, 1, 0, DwAte(2), &mut pieces);
+        assert!(matches!(value, Some(BaseTypeValue::Bool(true))));
+    }
+
+    #[test]
+    fn packed_enum_fields_in_one_register() {
+        // A discriminant and a payload byte packed into the low bytes of r0.
+        let registers = registers(&[(0, 0x0000_2a01)]);
+        let mut pieces = vec![register_piece(0, None, None)];
+        let discriminant = eval(&registers, &mut no_memory, 1, 0, DwAte(7), &mut pieces);
+        let payload = eval(&registers, &mut no_memory, 1, 1, DwAte(7), &mut pieces);
+        assert!(matches!(discriminant, Some(BaseTypeValue::U8(1))));
+        assert!(matches!(payload, Some(BaseTypeValue::U8(42))));
+    }
+
+    #[test]
+    fn value_split_over_register_and_memory() {
+        let registers = registers(&[(2, 0xaaaa_5678)]);
+        let mut mem = |address: &u32, num_bytes: usize| {
+            assert_eq!(*address, 0x2000_0000);
+            assert_eq!(num_bytes, 2);
+            Some(vec![0x34, 0x12])
+        };
+        let mut pieces = vec![
+            register_piece(2, Some(16), None),
+            memory_piece(0x2000_0000, Some(16)),
+        ];
+        let value = eval(&registers, &mut mem, 4, 0, DwAte(7), &mut pieces);
+        assert!(matches!(value, Some(BaseTypeValue::U32(0x1234_5678))));
+        assert!(pieces.is_empty());
+    }
+
+    #[test]
+    fn empty_piece_only_consumes_its_own_bits() {
+        let registers = registers(&[(3, 7)]);
+        let mut pieces = vec![
+            MyPiece::new(Piece {
+                size_in_bits: Some(32),
+                bit_offset: None,
+                location: Location::Empty,
+            }),
+            register_piece(3, Some(32), None),
+        ];
+        let first = EvaluatorValue::handle_eval_piece(
+            &registers,
+            &mut no_memory,
+            4,
+            0,
+            DwAte(7),
+            &mut pieces,
+        )
+        .unwrap();
+        let second = eval(&registers, &mut no_memory, 4, 4, DwAte(7), &mut pieces);
+        assert!(matches!(first, EvaluatorValue::OptimizedOut));
+        assert!(matches!(second, Some(BaseTypeValue::U32(7))));
+    }
+}
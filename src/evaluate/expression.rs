@@ -0,0 +1,1040 @@
+use super::evaluate::{
+    ArrayTypeValue, BaseTypeValue, EvaluatorValue, ValueInformation, ValuePiece,
+};
+use crate::call_stack::MemoryAccess;
+use crate::memory_view::read_word;
+use anyhow::{anyhow, Result};
+use gimli::{Reader, RunTimeEndian};
+use log::error;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// A parsed user typed expression, e.g. `*my_struct.field[2] as u32`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    /// A variable, or a pseudo-variable like `$pc` or `$1`.
+    Variable(String),
+
+    /// A field of a struct, union or enum variant, tuple fields are written as numbers.
+    Field(Box<Expression>, String),
+
+    /// A element of a array.
+    Index(Box<Expression>, usize),
+
+    /// One field of every element of a array, e.g. `sensors[*].temp`, nested fields are
+    /// separated by `.`.
+    Wildcard(Box<Expression>, String),
+
+    /// The value a pointer points to.
+    Deref(Box<Expression>),
+
+    /// A base type value converted to another base type, e.g. `x as u8`.
+    Cast(Box<Expression>, String),
+
+    /// A integer literal, e.g. `0x20`.
+    Literal(u64),
+
+    /// The negated value, e.g. `-8`.
+    Negate(Box<Expression>),
+
+    /// The address of a value stored in memory, e.g. `&local_var`.
+    AddressOf(Box<Expression>),
+
+    /// The sum of two values, e.g. `$sp + 0x20`.
+    Add(Box<Expression>, Box<Expression>),
+
+    /// The difference of two values, e.g. `$r7 - 8`.
+    Sub(Box<Expression>, Box<Expression>),
+
+    /// The 32 bit word in memory at a address, e.g. `[$r7 - 8]`.
+    Memory(Box<Expression>),
+}
+
+impl Expression {
+    /// Parse a expression.
+    ///
+    /// Description:
+    ///
+    /// * `text` - The expression, e.g. `my_struct.field[2]`.
+    ///
+    /// The grammar is a small subset of Rust: field access with `.`, array indexing with `[n]`,
+    /// dereference with `*`, address of with `&`, casts with `as`, negation, addition and
+    /// subtraction and parentheses.
+    /// Integer literals are 64 bit.
+    /// A field of every array element is selected with `[*]`, e.g. `sensors[*].temp`.
+    /// A address in brackets, e.g. `[$sp + 4]`, reads the word at the address.
+    /// Variable names can contain `::` and pseudo-variables start with `$`.
+    pub fn parse(text: &str) -> Result<Expression> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            position: 0,
+        };
+        let expression = parser.sum()?;
+        match parser.peek() {
+            None => Ok(expression),
+            Some(token) => {
+                error!("Unexpected {} in expression {}", token, text);
+                Err(anyhow!("Unexpected {} in expression {}", token, text))
+            }
+        }
+    }
+
+    /// Get the names of the variables and pseudo-variables in the expression.
+    pub fn variables(&self) -> Vec<&str> {
+        match self {
+            Expression::Variable(name) => vec![name],
+            Expression::Literal(_) => vec![],
+            Expression::Field(inner, _)
+            | Expression::Index(inner, _)
+            | Expression::Wildcard(inner, _)
+            | Expression::Deref(inner)
+            | Expression::Negate(inner)
+            | Expression::Cast(inner, _)
+            | Expression::AddressOf(inner)
+            | Expression::Memory(inner) => inner.variables(),
+            Expression::Add(left, right) | Expression::Sub(left, right) => {
+                let mut result = left.variables();
+                result.append(&mut right.variables());
+                result
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expression::Variable(name) => write!(f, "{}", name),
+            Expression::Field(inner, field) => write!(f, "{}.{}", inner, field),
+            Expression::Index(inner, index) => write!(f, "{}[{}]", inner, index),
+            Expression::Wildcard(inner, field) => write!(f, "{}[*].{}", inner, field),
+            Expression::Deref(inner) => write!(f, "(*{})", inner),
+            Expression::Cast(inner, type_name) => write!(f, "({} as {})", inner, type_name),
+            Expression::Literal(value) => write!(f, "{:#x}", value),
+            Expression::Negate(inner) => write!(f, "(-{})", inner),
+            Expression::AddressOf(inner) => write!(f, "(&{})", inner),
+            Expression::Add(left, right) => write!(f, "({} + {})", left, right),
+            Expression::Sub(left, right) => write!(f, "({} - {})", left, right),
+            Expression::Memory(inner) => write!(f, "[{}]", inner),
+        }
+    }
+}
+
+/// A comparison operator in a condition.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    /// `==`
+    Equal,
+
+    /// `!=`
+    NotEqual,
+
+    /// `<`
+    Less,
+
+    /// `<=`
+    LessOrEqual,
+
+    /// `>`
+    Greater,
+
+    /// `>=`
+    GreaterOrEqual,
+}
+
+/// The right hand side of a comparison.
+#[derive(Debug, Clone)]
+pub enum Operand {
+    /// A number or boolean literal.
+    Literal(BaseTypeValue),
+
+    /// Another expression.
+    Expression(Expression),
+}
+
+/// A parsed condition, e.g. the condition of a conditional breakpoint.
+#[derive(Debug, Clone)]
+pub struct Condition {
+    /// The expression on the left hand side.
+    pub left: Expression,
+
+    /// The comparison, `None` if the value of `left` is the condition.
+    pub comparison: Option<(Comparison, Operand)>,
+}
+
+impl Condition {
+    /// Parse a condition.
+    ///
+    /// Description:
+    ///
+    /// * `text` - The condition, e.g. `count >= 10`, `state.ready == true` or `*flag`.
+    ///
+    /// A condition without a comparison is true if the value is not zero.
+    pub fn parse(text: &str) -> Result<Condition> {
+        let operators = [
+            ("==", Comparison::Equal),
+            ("!=", Comparison::NotEqual),
+            ("<=", Comparison::LessOrEqual),
+            (">=", Comparison::GreaterOrEqual),
+            ("<", Comparison::Less),
+            (">", Comparison::Greater),
+        ];
+        let found = operators
+            .iter()
+            .filter_map(|(operator, comparison)| {
+                text.find(operator)
+                    .map(|index| (index, *operator, *comparison))
+            })
+            .min_by_key(|(index, operator, _)| (*index, std::cmp::Reverse(operator.len())));
+
+        let (index, operator, comparison) = match found {
+            Some(val) => val,
+            None => {
+                return Ok(Condition {
+                    left: Expression::parse(text)?,
+                    comparison: None,
+                })
+            }
+        };
+        let right = text[index + operator.len()..].trim();
+        let operand = match parse_literal(right) {
+            Some(literal) => Operand::Literal(literal),
+            None => Operand::Expression(Expression::parse(right)?),
+        };
+        Ok(Condition {
+            left: Expression::parse(&text[..index])?,
+            comparison: Some((comparison, operand)),
+        })
+    }
+
+    /// Get the names of the variables and pseudo-variables the condition reads.
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names = self.left.variables();
+        if let Some((_, Operand::Expression(right))) = &self.comparison {
+            names.extend(right.variables());
+        }
+        names
+    }
+}
+
+/// Evaluate a expression by walking the value tree of the variable it starts from.
+///
+/// Description:
+///
+/// * `expression` - The parsed expression.
+/// * `lookup` - Returns the value of a variable or pseudo-variable, `None` if it doesn't exist.
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `endian` - The endianness of the debug target, used to decode the `[address]` words.
+///
+/// Field access and indexing goes through pointers, like in Rust, and the active variant of a
+/// enum is used for its fields.
+/// The memory is only read for `[address]` expressions, so only the values that are in the
+/// evaluated tree of the variable can be reached through fields and pointers.
+/// The result of address arithmetic is a address if the left value is a address, a register or
+/// a pointer.
+pub fn evaluate_expression<R: Reader<Offset = usize>, F, M: MemoryAccess>(
+    expression: &Expression,
+    lookup: &mut F,
+    mem: &mut M,
+    endian: RunTimeEndian,
+) -> Result<EvaluatorValue<R>>
+where
+    F: FnMut(&str) -> Result<Option<EvaluatorValue<R>>>,
+{
+    match expression {
+        Expression::Variable(name) => match lookup(name)? {
+            Some(value) => Ok(value),
+            None => {
+                error!("Could not find the variable {}", name);
+                Err(anyhow!("Could not find the variable {}", name))
+            }
+        },
+        Expression::Field(inner, field) => {
+            let value = evaluate_expression(inner, lookup, mem, endian)?;
+            field_value(&value, field).ok_or_else(|| {
+                error!("{} has no field {}", inner, field);
+                anyhow!("{} has no field {}", inner, field)
+            })
+        }
+        Expression::Index(inner, index) => {
+            match strip(&evaluate_expression(inner, lookup, mem, endian)?) {
+                EvaluatorValue::Array(array) => match array.values.get(*index) {
+                    Some(value) => Ok(value.clone()),
+                    None => {
+                        error!(
+                            "Index {} is out of bounds for {} with length {}",
+                            index,
+                            inner,
+                            array.values.len()
+                        );
+                        Err(anyhow!(
+                            "Index {} is out of bounds for {} with length {}",
+                            index,
+                            inner,
+                            array.values.len()
+                        ))
+                    }
+                },
+                _ => {
+                    error!("{} is not a array", inner);
+                    Err(anyhow!("{} is not a array", inner))
+                }
+            }
+        }
+        Expression::Wildcard(inner, field) => {
+            match strip(&evaluate_expression(inner, lookup, mem, endian)?) {
+                EvaluatorValue::Array(array) => {
+                    let mut values = vec![];
+                    for element in &array.values {
+                        let mut value = element.clone();
+                        for name in field.split('.') {
+                            value = field_value(&value, name).ok_or_else(|| {
+                                error!("The elements of {} have no field {}", inner, field);
+                                anyhow!("The elements of {} have no field {}", inner, field)
+                            })?;
+                        }
+                        values.push(value);
+                    }
+                    Ok(EvaluatorValue::Array(Box::new(ArrayTypeValue {
+                        subrange_type_value: array.subrange_type_value.clone(),
+                        values,
+                    })))
+                }
+                _ => {
+                    error!("{} is not a array", inner);
+                    Err(anyhow!("{} is not a array", inner))
+                }
+            }
+        }
+        Expression::Deref(inner) => {
+            match unwrap_member(&evaluate_expression(inner, lookup, mem, endian)?) {
+                EvaluatorValue::PointerTypeValue(pointer) => Ok(pointer.value.clone()),
+                _ => {
+                    error!("{} is not a pointer", inner);
+                    Err(anyhow!("{} is not a pointer", inner))
+                }
+            }
+        }
+        Expression::Cast(inner, type_name) => {
+            let value = base_value(inner, &evaluate_expression(inner, lookup, mem, endian)?)?;
+            Ok(EvaluatorValue::Value(
+                cast(&value, type_name)?,
+                ValueInformation::new(None, vec![]),
+            ))
+        }
+        Expression::Literal(value) => Ok(EvaluatorValue::Value(
+            BaseTypeValue::U64(*value),
+            ValueInformation::new(None, vec![]),
+        )),
+        Expression::Negate(inner) => {
+            let value = base_value(inner, &evaluate_expression(inner, lookup, mem, endian)?)?;
+            let value = match value {
+                BaseTypeValue::F32(val) => BaseTypeValue::F32(-val),
+                BaseTypeValue::F64(val) => BaseTypeValue::F64(-val),
+                _ => match i64::try_from(-numeric(&value).0) {
+                    Ok(val) => BaseTypeValue::I64(val),
+                    Err(_) => {
+                        error!("Can not negate {}", value);
+                        return Err(anyhow!("Can not negate {}", value));
+                    }
+                },
+            };
+            Ok(EvaluatorValue::Value(
+                value,
+                ValueInformation::new(None, vec![]),
+            ))
+        }
+        Expression::AddressOf(inner) => {
+            match evaluate_expression(inner, lookup, mem, endian)?.address() {
+                Some(address) => Ok(EvaluatorValue::Value(
+                    BaseTypeValue::Address32(address),
+                    ValueInformation::new(None, vec![]),
+                )),
+                None => {
+                    error!("{} is not stored in memory", inner);
+                    Err(anyhow!("{} is not stored in memory", inner))
+                }
+            }
+        }
+        Expression::Add(left, right) | Expression::Sub(left, right) => {
+            let left_value = base_value(left, &evaluate_expression(left, lookup, mem, endian)?)?;
+            let right_value = base_value(right, &evaluate_expression(right, lookup, mem, endian)?)?;
+            let (l, r) = (numeric(&left_value).0, numeric(&right_value).0);
+            let result = match expression {
+                Expression::Add(_, _) => l + r,
+                _ => l - r,
+            };
+            let value = match left_value {
+                BaseTypeValue::Address32(_) | BaseTypeValue::Reg32(_) => {
+                    BaseTypeValue::Address32(result as u32)
+                }
+                _ if result < 0 => BaseTypeValue::I64(result as i64),
+                _ => BaseTypeValue::U64(result as u64),
+            };
+            Ok(EvaluatorValue::Value(
+                value,
+                ValueInformation::new(None, vec![]),
+            ))
+        }
+        Expression::Memory(inner) => {
+            let value = base_value(inner, &evaluate_expression(inner, lookup, mem, endian)?)?;
+            let address = numeric(&value).0 as u32;
+            let data = mem.get_address(&address, 4);
+            match data.as_ref().and_then(|data| read_word(data, endian)) {
+                Some(word) => Ok(EvaluatorValue::Value(
+                    BaseTypeValue::U32(word),
+                    ValueInformation::new(
+                        data,
+                        vec![ValuePiece::Memory {
+                            address,
+                            byte_size: 4,
+                            bit_offset: 0,
+                            bit_size: 32,
+                        }],
+                    ),
+                )),
+                _ => {
+                    error!("Could not read 4 bytes from address {:#010x}", address);
+                    Err(anyhow!(
+                        "Could not read 4 bytes from address {:#010x}",
+                        address
+                    ))
+                }
+            }
+        }
+    }
+}
+
+/// Evaluate a condition.
+///
+/// Description:
+///
+/// * `condition` - The parsed condition.
+/// * `lookup` - Returns the value of a variable or pseudo-variable, `None` if it doesn't exist.
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `endian` - The endianness of the debug target.
+///
+/// Floats are compared as floats and all the other values as integers.
+pub fn evaluate_condition<R: Reader<Offset = usize>, F, M: MemoryAccess>(
+    condition: &Condition,
+    lookup: &mut F,
+    mem: &mut M,
+    endian: RunTimeEndian,
+) -> Result<bool>
+where
+    F: FnMut(&str) -> Result<Option<EvaluatorValue<R>>>,
+{
+    let left = base_value(
+        &condition.left,
+        &evaluate_expression(&condition.left, lookup, mem, endian)?,
+    )?;
+    let (comparison, right) = match &condition.comparison {
+        Some((comparison, Operand::Literal(literal))) => (*comparison, literal.clone()),
+        Some((comparison, Operand::Expression(expression))) => (
+            *comparison,
+            base_value(
+                expression,
+                &evaluate_expression(expression, lookup, mem, endian)?,
+            )?,
+        ),
+        None => (Comparison::NotEqual, BaseTypeValue::U8(0)),
+    };
+
+    let ordering = match (&left, &right) {
+        (BaseTypeValue::F32(_) | BaseTypeValue::F64(_), _)
+        | (_, BaseTypeValue::F32(_) | BaseTypeValue::F64(_)) => {
+            match numeric(&left).1.partial_cmp(&numeric(&right).1) {
+                Some(val) => val,
+                None => return Ok(comparison == Comparison::NotEqual),
+            }
+        }
+        _ => numeric(&left).0.cmp(&numeric(&right).0),
+    };
+    Ok(match comparison {
+        Comparison::Equal => ordering.is_eq(),
+        Comparison::NotEqual => ordering.is_ne(),
+        Comparison::Less => ordering.is_lt(),
+        Comparison::LessOrEqual => ordering.is_le(),
+        Comparison::Greater => ordering.is_gt(),
+        Comparison::GreaterOrEqual => ordering.is_ge(),
+    })
+}
+
+/// Parse a number or boolean literal, `None` if the text is not a literal.
+fn parse_literal(text: &str) -> Option<BaseTypeValue> {
+    match text {
+        "true" => return Some(BaseTypeValue::Bool(true)),
+        "false" => return Some(BaseTypeValue::Bool(false)),
+        _ => (),
+    };
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let digits = digits.replace('_', "");
+    let integer = match digits.strip_prefix("0x") {
+        Some(hex) => i128::from_str_radix(hex, 16).ok(),
+        None => digits.parse::<i128>().ok(),
+    };
+    match integer {
+        Some(val) if negative => i64::try_from(-val).ok().map(BaseTypeValue::I64),
+        Some(val) => u64::try_from(val).ok().map(BaseTypeValue::U64),
+        None => text.parse::<f64>().ok().map(BaseTypeValue::F64),
+    }
+}
+
+/// Convert a base type value to another base type, with the same semantics as `as` in Rust.
+///
+/// Description:
+///
+/// * `value` - The value that is converted.
+/// * `type_name` - The name of the new type, e.g. `u8`, `i32` or `f64`.
+pub fn cast(value: &BaseTypeValue, type_name: &str) -> Result<BaseTypeValue> {
+    let (integer, float) = numeric(value);
+    if let BaseTypeValue::F32(_) | BaseTypeValue::F64(_) = value {
+        // Float to integer casts saturate, so they are done directly from the float.
+        return Ok(match type_name {
+            "u8" => BaseTypeValue::U8(float as u8),
+            "u16" => BaseTypeValue::U16(float as u16),
+            "u32" | "usize" => BaseTypeValue::U32(float as u32),
+            "u64" => BaseTypeValue::U64(float as u64),
+            "i8" => BaseTypeValue::I8(float as i8),
+            "i16" => BaseTypeValue::I16(float as i16),
+            "i32" | "isize" => BaseTypeValue::I32(float as i32),
+            "i64" => BaseTypeValue::I64(float as i64),
+            "f32" => BaseTypeValue::F32(float as f32),
+            "f64" => BaseTypeValue::F64(float),
+            _ => {
+                error!("Can not cast {} to {}", value, type_name);
+                return Err(anyhow!("Can not cast {} to {}", value, type_name));
+            }
+        });
+    }
+
+    Ok(match type_name {
+        "u8" => BaseTypeValue::U8(integer as u8),
+        "u16" => BaseTypeValue::U16(integer as u16),
+        "u32" | "usize" => BaseTypeValue::U32(integer as u32),
+        "u64" => BaseTypeValue::U64(integer as u64),
+        "i8" => BaseTypeValue::I8(integer as i8),
+        "i16" => BaseTypeValue::I16(integer as i16),
+        "i32" | "isize" => BaseTypeValue::I32(integer as i32),
+        "i64" => BaseTypeValue::I64(integer as i64),
+        "f32" => BaseTypeValue::F32(float as f32),
+        "f64" => BaseTypeValue::F64(float),
+        _ => {
+            error!("Can not cast {} to {}", value, type_name);
+            return Err(anyhow!("Can not cast {} to {}", value, type_name));
+        }
+    })
+}
+
+/// Get a base type value as a integer and as a float, integers are converted through `i128`
+/// which holds all the values of all integer types.
+fn numeric(value: &BaseTypeValue) -> (i128, f64) {
+    match value {
+        BaseTypeValue::Generic(v) | BaseTypeValue::U64(v) => (*v as i128, *v as f64),
+        BaseTypeValue::Address32(v) | BaseTypeValue::Reg32(v) | BaseTypeValue::U32(v) => {
+            (*v as i128, *v as f64)
+        }
+        BaseTypeValue::Bool(v) => (*v as i128, *v as u8 as f64),
+        BaseTypeValue::U8(v) => (*v as i128, *v as f64),
+        BaseTypeValue::U16(v) => (*v as i128, *v as f64),
+        BaseTypeValue::I8(v) => (*v as i128, *v as f64),
+        BaseTypeValue::I16(v) => (*v as i128, *v as f64),
+        BaseTypeValue::I32(v) => (*v as i128, *v as f64),
+        BaseTypeValue::I64(v) => (*v as i128, *v as f64),
+        BaseTypeValue::F32(v) => (*v as i128, *v as f64),
+        BaseTypeValue::F64(v) => (*v as i128, *v),
+    }
+}
+
+/// Get the base type value of a evaluated expression, pointers are compared by address.
+fn base_value<R: Reader<Offset = usize>>(
+    expression: &Expression,
+    value: &EvaluatorValue<R>,
+) -> Result<BaseTypeValue> {
+    match unwrap_member(value) {
+        EvaluatorValue::Value(value, _) => Ok(value.clone()),
+        EvaluatorValue::PointerTypeValue(pointer) => match unwrap_member(&pointer.address) {
+            EvaluatorValue::Value(value, _) => Ok(value.clone()),
+            _ => {
+                error!("The address of {} is unknown", expression);
+                Err(anyhow!("The address of {} is unknown", expression))
+            }
+        },
+        _ => {
+            error!("{} is not a base type value or a pointer", expression);
+            Err(anyhow!(
+                "{} is not a base type value or a pointer",
+                expression
+            ))
+        }
+    }
+}
+
+/// Find a field of a value, tuple fields can be written without the `__` prefix.
+fn field_value<R: Reader<Offset = usize>>(
+    value: &EvaluatorValue<R>,
+    field: &str,
+) -> Option<EvaluatorValue<R>> {
+    let members = match strip(value) {
+        EvaluatorValue::Struct(structure) => match structure.members.as_slice() {
+            // The fields of a enum are in the active variant.
+            [variant_part @ EvaluatorValue::VariantPartValue(_)] => {
+                return field_value(variant_part, field)
+            }
+            members => members,
+        },
+        EvaluatorValue::Union(union) => &union.members,
+        _ => return None,
+    };
+    let tuple_field = format!("__{}", field);
+    members.iter().find_map(|member| match member {
+        EvaluatorValue::Member(member) => match member.name.as_deref() {
+            Some(name) if name == field || name == tuple_field => Some(member.value.clone()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Remove the members, pointers and enum variants around a value, like the auto dereference in
+/// Rust.
+fn strip<R: Reader<Offset = usize>>(value: &EvaluatorValue<R>) -> &EvaluatorValue<R> {
+    match value {
+        EvaluatorValue::Member(member) => strip(&member.value),
+        EvaluatorValue::PointerTypeValue(pointer) => strip(&pointer.value),
+        EvaluatorValue::VariantPartValue(variant_part) => match variant_part.active_variant() {
+            Some(variant) => strip(&variant.child.value),
+            None => value,
+        },
+        _ => value,
+    }
+}
+
+/// Remove the members around a value.
+fn unwrap_member<R: Reader<Offset = usize>>(value: &EvaluatorValue<R>) -> &EvaluatorValue<R> {
+    match value {
+        EvaluatorValue::Member(member) => unwrap_member(&member.value),
+        _ => value,
+    }
+}
+
+/// A token of a expression.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Number(u64),
+    Symbol(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Name(name) => write!(f, "`{}`", name),
+            Token::Number(number) => write!(f, "`{}`", number),
+            Token::Symbol(symbol) => write!(f, "`{}`", symbol),
+        }
+    }
+}
+
+/// Split a expression into tokens.
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let digits: String = chars[start..i].iter().filter(|c| **c != '_').collect();
+            let number = match digits.strip_prefix("0x") {
+                Some(hex) => u64::from_str_radix(hex, 16),
+                None => digits.parse(),
+            };
+            match number {
+                Ok(val) => tokens.push(Token::Number(val)),
+                Err(_) => {
+                    error!("Invalid number {} in expression", digits);
+                    return Err(anyhow!("Invalid number {} in expression", digits));
+                }
+            }
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            let start = i;
+            i += 1;
+            while i < chars.len() {
+                if chars[i].is_alphanumeric() || chars[i] == '_' {
+                    i += 1;
+                } else if chars[i] == ':' && chars.get(i + 1) == Some(&':') {
+                    i += 2;
+                } else {
+                    break;
+                }
+            }
+            tokens.push(Token::Name(chars[start..i].iter().collect()));
+        } else if "*&+-.[]()".contains(c) {
+            tokens.push(Token::Symbol(c));
+            i += 1;
+        } else {
+            error!("Unexpected character {} in expression", c);
+            return Err(anyhow!("Unexpected character {} in expression", c));
+        }
+    }
+    Ok(tokens)
+}
+
+/// A recursive descent parser for expressions.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        match self.tokens.get(self.position) {
+            Some(token) => {
+                self.position += 1;
+                Ok(token.clone())
+            }
+            None => {
+                error!("Unexpected end of expression");
+                Err(anyhow!("Unexpected end of expression"))
+            }
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<()> {
+        match self.next()? {
+            Token::Symbol(c) if c == symbol => Ok(()),
+            token => {
+                error!("Expected `{}` but found {}", symbol, token);
+                Err(anyhow!("Expected `{}` but found {}", symbol, token))
+            }
+        }
+    }
+
+    /// sum = cast (("+" | "-") cast)*
+    fn sum(&mut self) -> Result<Expression> {
+        let mut expression = self.cast()?;
+        loop {
+            match self.peek() {
+                Some(Token::Symbol('+')) => {
+                    self.position += 1;
+                    expression = Expression::Add(Box::new(expression), Box::new(self.cast()?));
+                }
+                Some(Token::Symbol('-')) => {
+                    self.position += 1;
+                    expression = Expression::Sub(Box::new(expression), Box::new(self.cast()?));
+                }
+                _ => return Ok(expression),
+            }
+        }
+    }
+
+    /// cast = unary ("as" name)*
+    fn cast(&mut self) -> Result<Expression> {
+        let mut expression = self.unary()?;
+        while self.peek() == Some(&Token::Name("as".to_string())) {
+            self.position += 1;
+            match self.next()? {
+                Token::Name(type_name) => {
+                    expression = Expression::Cast(Box::new(expression), type_name)
+                }
+                token => {
+                    error!("Expected a type name but found {}", token);
+                    return Err(anyhow!("Expected a type name but found {}", token));
+                }
+            }
+        }
+        Ok(expression)
+    }
+
+    /// unary = "*" unary | "&" unary | "-" unary | postfix
+    fn unary(&mut self) -> Result<Expression> {
+        match self.peek() {
+            Some(Token::Symbol('-')) => {
+                self.position += 1;
+                Ok(Expression::Negate(Box::new(self.unary()?)))
+            }
+            Some(Token::Symbol('*')) => {
+                self.position += 1;
+                Ok(Expression::Deref(Box::new(self.unary()?)))
+            }
+            Some(Token::Symbol('&')) => {
+                self.position += 1;
+                Ok(Expression::AddressOf(Box::new(self.unary()?)))
+            }
+            _ => self.postfix(),
+        }
+    }
+
+    /// postfix = primary ("." (name | number) | "[" number "]" | "[" "*" "]" fields)*
+    fn postfix(&mut self) -> Result<Expression> {
+        let mut expression = self.primary()?;
+        loop {
+            match self.peek() {
+                Some(Token::Symbol('.')) => {
+                    self.position += 1;
+                    expression = Expression::Field(Box::new(expression), self.field()?);
+                }
+                Some(Token::Symbol('[')) => {
+                    self.position += 1;
+                    if self.peek() == Some(&Token::Symbol('*')) {
+                        self.position += 1;
+                        self.expect(']')?;
+                        expression = Expression::Wildcard(Box::new(expression), self.fields()?);
+                        continue;
+                    }
+                    let index = match self.next()? {
+                        Token::Number(number) => match usize::try_from(number) {
+                            Ok(val) => val,
+                            Err(_) => {
+                                error!("The index {} is too large", number);
+                                return Err(anyhow!("The index {} is too large", number));
+                            }
+                        },
+                        token => {
+                            error!("Expected a index but found {}", token);
+                            return Err(anyhow!("Expected a index but found {}", token));
+                        }
+                    };
+                    self.expect(']')?;
+                    expression = Expression::Index(Box::new(expression), index);
+                }
+                _ => return Ok(expression),
+            }
+        }
+    }
+
+    /// field = name | number
+    fn field(&mut self) -> Result<String> {
+        match self.next()? {
+            Token::Name(name) => Ok(name),
+            Token::Number(number) => Ok(number.to_string()),
+            token => {
+                error!("Expected a field name but found {}", token);
+                Err(anyhow!("Expected a field name but found {}", token))
+            }
+        }
+    }
+
+    /// fields = ("." field)+
+    fn fields(&mut self) -> Result<String> {
+        let mut fields = vec![];
+        loop {
+            match self.peek() {
+                Some(Token::Symbol('.')) => {
+                    self.position += 1;
+                    fields.push(self.field()?);
+                }
+                _ if fields.is_empty() => {
+                    error!("Expected a field after `[*]`");
+                    return Err(anyhow!("Expected a field after `[*]`"));
+                }
+                _ => return Ok(fields.join(".")),
+            }
+        }
+    }
+
+    /// primary = name | number | "(" sum ")" | "[" sum "]"
+    fn primary(&mut self) -> Result<Expression> {
+        match self.next()? {
+            Token::Name(name) => Ok(Expression::Variable(name)),
+            Token::Number(number) => Ok(Expression::Literal(number)),
+            Token::Symbol('(') => {
+                let expression = self.sum()?;
+                self.expect(')')?;
+                Ok(expression)
+            }
+            Token::Symbol('[') => {
+                let expression = self.sum()?;
+                self.expect(']')?;
+                Ok(Expression::Memory(Box::new(expression)))
+            }
+            token => {
+                error!("Expected a variable but found {}", token);
+                Err(anyhow!("Expected a variable but found {}", token))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variable(name: &str) -> Box<Expression> {
+        Box::new(Expression::Variable(name.to_string()))
+    }
+
+    #[test]
+    fn parse_field_and_index() {
+        assert_eq!(
+            Expression::parse("my_struct.field[2]").unwrap(),
+            Expression::Index(
+                Box::new(Expression::Field(
+                    variable("my_struct"),
+                    "field".to_string()
+                )),
+                2
+            )
+        );
+        assert_eq!(
+            Expression::parse("pair.0").unwrap(),
+            Expression::Field(variable("pair"), "0".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_wildcard() {
+        assert_eq!(
+            Expression::parse("sensors[*].pos.x").unwrap(),
+            Expression::Wildcard(variable("sensors"), "pos.x".to_string())
+        );
+        assert_eq!(
+            Expression::parse("sensors[*].temp[1]").unwrap(),
+            Expression::Index(
+                Box::new(Expression::Wildcard(
+                    variable("sensors"),
+                    "temp".to_string()
+                )),
+                1
+            )
+        );
+        assert!(Expression::parse("sensors[*]").is_err());
+    }
+
+    #[test]
+    fn parse_deref_and_cast() {
+        assert_eq!(
+            Expression::parse("*ptr as u8").unwrap(),
+            Expression::Cast(
+                Box::new(Expression::Deref(variable("ptr"))),
+                "u8".to_string()
+            )
+        );
+        assert_eq!(
+            Expression::parse("(*app::STATE).count").unwrap(),
+            Expression::Field(
+                Box::new(Expression::Deref(variable("app::STATE"))),
+                "count".to_string()
+            )
+        );
+        assert_eq!(
+            Expression::parse("$pc as u16").unwrap().variables(),
+            vec!["$pc"]
+        );
+    }
+
+    #[test]
+    fn parse_addresses() {
+        assert_eq!(
+            Expression::parse("&local").unwrap(),
+            Expression::AddressOf(variable("local"))
+        );
+        assert_eq!(
+            Expression::parse("$sp + 0x20").unwrap(),
+            Expression::Add(variable("$sp"), Box::new(Expression::Literal(0x20)))
+        );
+        assert_eq!(
+            Expression::parse("[$r7 - 8]").unwrap(),
+            Expression::Memory(Box::new(Expression::Sub(
+                variable("$r7"),
+                Box::new(Expression::Literal(8))
+            )))
+        );
+        assert_eq!(
+            Expression::parse("$sp - -0x1_0000_0000").unwrap(),
+            Expression::Sub(
+                variable("$sp"),
+                Box::new(Expression::Negate(Box::new(Expression::Literal(
+                    0x1_0000_0000
+                ))))
+            )
+        );
+        assert!(Expression::parse("a[0x1_0000_0000_0000_0000]").is_err());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Expression::parse("a[").is_err());
+        assert!(Expression::parse("a.").is_err());
+        assert!(Expression::parse("a b").is_err());
+        assert!(Expression::parse("a +").is_err());
+        assert!(Expression::parse("[a").is_err());
+    }
+
+    #[test]
+    fn cast_base_types() {
+        assert!(matches!(
+            cast(&BaseTypeValue::I32(-1), "u8").unwrap(),
+            BaseTypeValue::U8(0xff)
+        ));
+        assert!(matches!(
+            cast(&BaseTypeValue::F32(-2.7), "i32").unwrap(),
+            BaseTypeValue::I32(-2)
+        ));
+        assert!(matches!(
+            cast(&BaseTypeValue::F64(300.0), "u8").unwrap(),
+            BaseTypeValue::U8(255)
+        ));
+        assert!(cast(&BaseTypeValue::U8(1), "bool").is_err());
+    }
+
+    type Value = EvaluatorValue<gimli::EndianSlice<'static, gimli::LittleEndian>>;
+
+    fn lookup(name: &str) -> Result<Option<Value>> {
+        let value = match name {
+            "count" => BaseTypeValue::U32(10),
+            "limit" => BaseTypeValue::I8(-3),
+            "$sp" => BaseTypeValue::Reg32(0x2000_0000),
+            _ => return Ok(None),
+        };
+        Ok(Some(EvaluatorValue::Value(
+            value,
+            ValueInformation::new(None, vec![]),
+        )))
+    }
+
+    fn check(text: &str) -> Result<bool> {
+        check_endian(text, RunTimeEndian::Little)
+    }
+
+    fn check_endian(text: &str, endian: RunTimeEndian) -> Result<bool> {
+        let mut mem = |address: &u32, num_bytes: usize| match *address {
+            0x2000_0008 => Some(vec![0x2a, 0, 0, 0][..num_bytes].to_vec()),
+            _ => None,
+        };
+        evaluate_condition(&Condition::parse(text)?, &mut lookup, &mut mem, endian)
+    }
+
+    #[test]
+    fn evaluate_conditions() {
+        assert!(check("count >= 10").unwrap());
+        assert!(!check("count<10").unwrap());
+        assert!(check("count != 0x0b").unwrap());
+        assert!(check("limit < 0").unwrap());
+        assert!(check("limit <= -3").unwrap());
+        assert!(check("count > limit").unwrap());
+        assert!(check("count == 10.0").unwrap());
+        assert!(check("count").unwrap());
+        assert!(check("missing == 1").is_err());
+        assert!(check("$sp + 8 == 0x20000008").unwrap());
+        assert!(check("[$sp + 8] == 42").unwrap());
+        assert!(check("[$sp - 4] == 0").is_err());
+        assert!(check_endian("[$sp + 8] == 0x2a000000", RunTimeEndian::Big).unwrap());
+        assert!(check("-limit == 3").unwrap());
+        assert!(check("0 - count == -10").unwrap());
+        assert!(check("count + 0xffffffff == 0x100000009").unwrap());
+    }
+}
@@ -0,0 +1,672 @@
+use super::evaluate::{
+    get_udata, BaseTypeValue, EvaluatorValue, MemberValue, StructureTypeValue, SubrangeTypeValue,
+    UnionTypeValue, VariantPartValue, VariantValue, TRUNCATED_MARKER,
+};
+use crate::call_stack::MemoryAccess;
+use anyhow::{anyhow, Result};
+use gimli::Reader;
+use log::error;
+
+/// Describes how the bytes of a string are written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StringEscape {
+    /// Control characters, quotes and bytes that are not valid UTF-8 are escaped.
+    Escaped,
+
+    /// The bytes are written as they are, invalid UTF-8 is replaced with `U+FFFD`.
+    Raw,
+}
+
+/// Describes how a union is written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnionFormat {
+    /// Every member is written, each interprets the same bytes.
+    Members,
+
+    /// The raw bytes of the union are written.
+    RawBytes,
+
+    /// Only the member selected by a tag member of the surrounding struct is written, the raw
+    /// bytes are written if there is no such tag.
+    ActiveMember,
+}
+
+/// Where a evaluated expression is shown, e.g. the `context` field of a DAP "evaluate" request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EvaluateContext {
+    /// A tooltip when hovering a variable in the editor, the value is kept short.
+    Hover,
+
+    /// A watch expression, the value is formatted like in the variables view.
+    Watch,
+
+    /// A expression typed in the debug console, the value is written in full with its address.
+    Repl,
+
+    /// The value is copied to the clipboard, it is written in full.
+    Clipboard,
+}
+
+impl EvaluateContext {
+    /// Get the context from the name used in the DAP protocol.
+    ///
+    /// Description:
+    ///
+    /// * `name` - The name of the context, e.g. `hover`.
+    ///
+    /// Unknown contexts, e.g. `variables`, are treated as `Watch`.
+    pub fn from_name(name: &str) -> EvaluateContext {
+        match name {
+            "hover" => EvaluateContext::Hover,
+            "repl" => EvaluateContext::Repl,
+            "clipboard" => EvaluateContext::Clipboard,
+            _ => EvaluateContext::Watch,
+        }
+    }
+}
+
+/// The preferences used when formatting values into `String`s.
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// Write integers in hexadecimal instead of decimal.
+    pub hex: bool,
+
+    /// Write the type of each base type value in front of it.
+    pub show_types: bool,
+
+    /// The maximum number of array elements that are written, `None` for no limit.
+    pub max_array_elements: Option<usize>,
+
+    /// How the bytes of a string are written.
+    pub string_escape: StringEscape,
+
+    /// The maximum number of bytes of a string that are written, `None` for no limit.
+    pub max_string_length: Option<usize>,
+
+    /// How a union is written.
+    pub union_format: UnionFormat,
+
+    /// Write the zero sized struct members, e.g. `PhantomData` and unit structs.
+    pub show_zero_sized: bool,
+
+    /// Write the inner value of the standard library wrappers, e.g. `Cell`, `RefCell` and
+    /// `AtomicU32`, instead of the wrapper structs.
+    pub unwrap_wrappers: bool,
+
+    /// Write the memory address a value was read from after it, e.g. for `print &expr`.
+    pub show_addresses: bool,
+
+    /// Write the well-known standard library types idiomatically, e.g. `Some(5)` and `"text"`,
+    /// instead of their raw layout.
+    pub pretty_print: bool,
+}
+
+impl Default for FormatOptions {
+    /// Creates the options that give the same result as the `Display` implementations, except
+    /// that strings are capped at 256 bytes and the standard library wrappers are unwrapped.
+    fn default() -> FormatOptions {
+        FormatOptions {
+            hex: false,
+            show_types: false,
+            max_array_elements: None,
+            string_escape: StringEscape::Escaped,
+            max_string_length: Some(256),
+            union_format: UnionFormat::Members,
+            show_zero_sized: false,
+            unwrap_wrappers: true,
+            show_addresses: false,
+            pretty_print: true,
+        }
+    }
+}
+
+impl FormatOptions {
+    /// Creates a copy of the options with the hex preference of a single request.
+    ///
+    /// Description:
+    ///
+    /// * `hex` - The hex preference of the request, e.g. the `hex` field of the DAP
+    ///   `ValueFormat`, `None` keeps the preference of the options.
+    pub fn with_hex(&self, hex: Option<bool>) -> FormatOptions {
+        FormatOptions {
+            hex: hex.unwrap_or(self.hex),
+            ..self.clone()
+        }
+    }
+
+    /// Creates a copy of the options adjusted for where the value is shown.
+    ///
+    /// Description:
+    ///
+    /// * `context` - Where the value is shown.
+    ///
+    /// Hovers are capped at 8 array elements and 64 string bytes, so large values stay readable
+    /// in a tooltip.
+    pub fn for_context(&self, context: EvaluateContext) -> FormatOptions {
+        match context {
+            EvaluateContext::Hover => FormatOptions {
+                max_array_elements: Some(self.max_array_elements.map_or(8, |max| max.min(8))),
+                max_string_length: Some(self.max_string_length.map_or(64, |max| max.min(64))),
+                show_addresses: false,
+                ..self.clone()
+            },
+            EvaluateContext::Watch => self.clone(),
+            EvaluateContext::Repl => FormatOptions {
+                max_array_elements: None,
+                show_addresses: true,
+                ..self.clone()
+            },
+            EvaluateContext::Clipboard => FormatOptions {
+                max_array_elements: None,
+                max_string_length: None,
+                show_addresses: false,
+                ..self.clone()
+            },
+        }
+    }
+}
+
+/// Format a value into a `String` and write the memory address it was read from after it, if
+/// `show_addresses` is set.
+///
+/// Description:
+///
+/// * `value` - The value that will be formatted.
+/// * `options` - The formatting preferences.
+///
+/// The address is only written for the value itself and not for its members, e.g.
+/// `Point { x: 1, y: 2 } @ 0x20000010`.
+pub fn format_value_with_address<R: Reader<Offset = usize>>(
+    value: &EvaluatorValue<R>,
+    options: &FormatOptions,
+) -> String {
+    match (options.show_addresses, value.address()) {
+        (true, Some(address)) => format!("{} @ {:#010x}", format_value(value, options), address),
+        _ => format_value(value, options),
+    }
+}
+
+/// Format a value into a `String` using the given formatting preferences.
+///
+/// Description:
+///
+/// * `value` - The value that will be formatted.
+/// * `options` - The formatting preferences.
+///
+/// The result has the same layout as the `Display` implementation of `EvaluatorValue`, except
+/// that bytes are written as a byte string.
+pub fn format_value<R: Reader<Offset = usize>>(
+    value: &EvaluatorValue<R>,
+    options: &FormatOptions,
+) -> String {
+    match value {
+        EvaluatorValue::Value(val, _) => format_base_type(val, options),
+        EvaluatorValue::PointerTypeValue(pt) => match (&pt.c_string, &pt.name) {
+            (Some(c_string), _) if pt.truncated => format!(
+                "\"{}\" {}",
+                format_string(c_string, options),
+                TRUNCATED_MARKER
+            ),
+            (Some(c_string), _) => format!("\"{}\"", format_string(c_string, options)),
+            (None, Some(name)) => format!("{}::{}", name, format_value(&pt.value, options)),
+            (None, None) => format_value(&pt.value, options),
+        },
+        EvaluatorValue::VariantValue(var) => format_variant(var, options),
+        EvaluatorValue::VariantPartValue(vpa) => format_variant_part(vpa, options),
+        EvaluatorValue::SubrangeTypeValue(srt) => format_subrange(srt, options),
+        EvaluatorValue::Bytes(byt) => match byt.to_slice() {
+            Ok(bytes) => format!("b\"{}\"", format_string(&bytes, options)),
+            Err(_) => format!("{:?}", byt),
+        },
+        EvaluatorValue::Array(arr) if arr.is_truncated() => format!(
+            "[ {}, {} ]",
+            format_elements(&arr.values, options),
+            TRUNCATED_MARKER
+        ),
+        EvaluatorValue::Array(arr) => format!("[ {} ]", format_elements(&arr.values, options)),
+        EvaluatorValue::Struct(stu) => {
+            match unwrap_wrapper(stu, options).or_else(|| format_option_or_result(stu, options)) {
+                Some(wrapped) => wrapped,
+                None => format_struct(stu, options),
+            }
+        }
+        EvaluatorValue::Enum(enu) => match enu.enumerator() {
+            Some(enumerator) => format!("{}::{}", enu.name, enumerator),
+            None => format!("{}::{}", enu.name, format_value(&enu.variant, options)),
+        },
+        EvaluatorValue::Union(uni) => match find_member(&uni.members, "value") {
+            Some(inner) if options.unwrap_wrappers && uni.name.starts_with("MaybeUninit") => {
+                format_wrapped(&uni.name, inner, options)
+            }
+            _ => format_union(uni, None, options),
+        },
+        EvaluatorValue::Member(mem) => format_member(mem, options),
+        _ => value.to_string(),
+    }
+}
+
+/// Format a base type value into a `String` using the given formatting preferences.
+///
+/// Description:
+///
+/// * `value` - The base type value that will be formatted.
+/// * `options` - The formatting preferences.
+///
+/// Signed integers are written in two's complement when written in hexadecimal.
+pub fn format_base_type(value: &BaseTypeValue, options: &FormatOptions) -> String {
+    let formatted = match (value, options.hex) {
+        (BaseTypeValue::Generic(val), true) => format!("{:#x}", val),
+        (BaseTypeValue::U8(val), true) => format!("{:#x}", val),
+        (BaseTypeValue::U16(val), true) => format!("{:#x}", val),
+        (BaseTypeValue::U32(val), true) => format!("{:#x}", val),
+        (BaseTypeValue::U64(val), true) => format!("{:#x}", val),
+        (BaseTypeValue::I8(val), true) => format!("{:#x}", val),
+        (BaseTypeValue::I16(val), true) => format!("{:#x}", val),
+        (BaseTypeValue::I32(val), true) => format!("{:#x}", val),
+        (BaseTypeValue::I64(val), true) => format!("{:#x}", val),
+        _ => value.to_string(),
+    };
+
+    match options.show_types {
+        true => format!("({}) {}", value.get_type(), formatted),
+        false => formatted,
+    }
+}
+
+/// Format the bytes of a string using the given formatting preferences.
+///
+/// Description:
+///
+/// * `bytes` - The bytes of the string.
+/// * `options` - The formatting preferences.
+///
+/// The result is not surrounded by quotes.
+/// The bytes after the string length limit are replaced with `...`, a character that is cut by
+/// the limit is written as escaped bytes.
+pub fn format_string(bytes: &[u8], options: &FormatOptions) -> String {
+    match options.max_string_length {
+        Some(max) if bytes.len() > max => {
+            format!("{}...", escape_string(&bytes[..max], options.string_escape))
+        }
+        _ => escape_string(bytes, options.string_escape),
+    }
+}
+
+/// Read a string from the memory of the debug target and format it.
+///
+/// Description:
+///
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `address` - The address of the first byte of the string.
+/// * `length` - The length of the string in bytes.
+/// * `options` - The formatting preferences.
+///
+/// Only the bytes that are written are read, thus a corrupt length will not cause a large read.
+pub fn read_and_format_string<M: MemoryAccess>(
+    mem: &mut M,
+    address: u32,
+    length: usize,
+    options: &FormatOptions,
+) -> Result<String> {
+    let read_length = match options.max_string_length {
+        Some(max) => length.min(max),
+        None => length,
+    };
+
+    let bytes = match mem.get_address(&address, read_length) {
+        Some(val) => val,
+        None => {
+            error!("Can not read the string at address {:#010x}", address);
+            return Err(anyhow!(
+                "Can not read the string at address {:#010x}",
+                address
+            ));
+        }
+    };
+
+    let formatted = escape_string(&bytes, options.string_escape);
+    match read_length < length {
+        true => Ok(format!("{}...", formatted)),
+        false => Ok(formatted),
+    }
+}
+
+/// Escape the bytes of a string according to the escape mode.
+fn escape_string(bytes: &[u8], escape: StringEscape) -> String {
+    match escape {
+        StringEscape::Escaped => {
+            let mut res = String::new();
+            let mut rest = bytes;
+            loop {
+                match std::str::from_utf8(rest) {
+                    Ok(valid) => {
+                        res.push_str(&valid.escape_debug().to_string());
+                        break;
+                    }
+                    Err(err) => {
+                        let (valid, invalid) = rest.split_at(err.valid_up_to());
+                        res.push_str(&String::from_utf8_lossy(valid).escape_debug().to_string());
+
+                        // The length is unknown if the string ends in the middle of a character.
+                        let len = err.error_len().unwrap_or(invalid.len());
+                        for byte in &invalid[..len] {
+                            res.push_str(&format!("\\x{:02x}", byte));
+                        }
+                        rest = &invalid[len..];
+                    }
+                }
+            }
+            res
+        }
+        StringEscape::Raw => String::from_utf8_lossy(bytes).to_string(),
+    }
+}
+
+/// Format a list of values separated by commas.
+fn format_list<R: Reader<Offset = usize>>(
+    values: &[EvaluatorValue<R>],
+    options: &FormatOptions,
+) -> String {
+    values
+        .iter()
+        .map(|v| format_value(v, options))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Format the elements of a array, the elements after the element limit are replaced with `...`.
+fn format_elements<R: Reader<Offset = usize>>(
+    values: &[EvaluatorValue<R>],
+    options: &FormatOptions,
+) -> String {
+    match options.max_array_elements {
+        Some(max) if values.len() > max => {
+            let mut elements: Vec<String> = values
+                .iter()
+                .take(max)
+                .map(|v| format_value(v, options))
+                .collect();
+            elements.push("...".to_string());
+            elements.join(", ")
+        }
+        _ => format_list(values, options),
+    }
+}
+
+/// Format a struct value using the given formatting preferences.
+///
+/// Description:
+///
+/// * `structure` - The struct value that will be formatted.
+/// * `options` - The formatting preferences.
+///
+/// A union member is formatted with the value of the tag member of the struct, if there is one.
+/// The zero sized members are hidden unless they are enabled in the formatting preferences.
+fn format_struct<R: Reader<Offset = usize>>(
+    structure: &StructureTypeValue<R>,
+    options: &FormatOptions,
+) -> String {
+    let tag = find_union_tag(&structure.members);
+    let members: Vec<String> = structure
+        .members
+        .iter()
+        .filter(|m| options.show_zero_sized || !m.is_zero_sized())
+        .map(|m| match m {
+            EvaluatorValue::Member(mem) => match &mem.value {
+                EvaluatorValue::Union(uni) => {
+                    let union = format_union(uni, tag, options);
+                    match &mem.name {
+                        Some(name) => format!("{}::{}", name, union),
+                        None => union,
+                    }
+                }
+                _ => format_member(mem, options),
+            },
+            _ => format_value(m, options),
+        })
+        .collect();
+    format!("{} {{ {} }}", structure.name, members.join(", "))
+}
+
+/// Format a standard library wrapper struct as its inner value.
+///
+/// Description:
+///
+/// * `structure` - The struct value that will be formatted.
+/// * `options` - The formatting preferences.
+///
+/// Will return `None` if the struct is not a wrapper or unwrapping is disabled.
+/// The borrow state of a `RefCell` is written after the inner value.
+fn unwrap_wrapper<R: Reader<Offset = usize>>(
+    structure: &StructureTypeValue<R>,
+    options: &FormatOptions,
+) -> Option<String> {
+    if !options.unwrap_wrappers {
+        return None;
+    }
+
+    let inner = find_member(&structure.members, wrapped_member(&structure.name)?)?;
+    let value = format_wrapped(&structure.name, inner, options);
+
+    if !structure.name.starts_with("RefCell<") {
+        return Some(value);
+    }
+    let borrow = match find_member(&structure.members, "borrow").map(inner_value) {
+        Some(EvaluatorValue::Value(BaseTypeValue::I64(val), _)) => *val,
+        Some(EvaluatorValue::Value(BaseTypeValue::I32(val), _)) => *val as i64,
+        Some(EvaluatorValue::Value(BaseTypeValue::I16(val), _)) => *val as i64,
+        _ => return Some(value),
+    };
+    let state = match borrow {
+        0 => "unborrowed".to_string(),
+        b if b < 0 => "mutably borrowed".to_string(),
+        b => format!("{} shared borrows", b),
+    };
+    Some(format!("{} ( {} )", value, state))
+}
+
+/// Format a `Option` or a `Result` as its active variant, e.g. `Some(5)` or `Err(Timeout)`.
+///
+/// Description:
+///
+/// * `structure` - The struct value that will be formatted.
+/// * `options` - The formatting preferences.
+///
+/// Will return `None` if the struct is not a `Option` or a `Result`, the active variant is
+/// unknown or pretty printing is disabled.
+fn format_option_or_result<R: Reader<Offset = usize>>(
+    structure: &StructureTypeValue<R>,
+    options: &FormatOptions,
+) -> Option<String> {
+    if !options.pretty_print
+        || !(structure.name.starts_with("Option<") || structure.name.starts_with("Result<"))
+    {
+        return None;
+    }
+
+    let variant = match structure.members.as_slice() {
+        [EvaluatorValue::VariantPartValue(variant_part)] => variant_part.active_variant()?,
+        _ => return None,
+    };
+    let name = variant.child.name.as_deref()?;
+    let fields: Vec<String> = match &variant.child.value {
+        EvaluatorValue::Struct(fields) => fields
+            .members
+            .iter()
+            .filter(|m| !m.is_zero_sized())
+            .map(|m| match m {
+                EvaluatorValue::Member(mem) => format_value(&mem.value, options),
+                _ => format_value(m, options),
+            })
+            .collect(),
+        _ => vec![],
+    };
+    match fields.is_empty() {
+        true => Some(name.to_string()),
+        false => Some(format!("{}({})", name, fields.join(", "))),
+    }
+}
+
+/// Format the inner value of a wrapper, nested wrappers are unwrapped as well.
+///
+/// Description:
+///
+/// * `wrapper` - The type name of the wrapper.
+/// * `inner` - The inner value of the wrapper.
+/// * `options` - The formatting preferences.
+fn format_wrapped<R: Reader<Offset = usize>>(
+    wrapper: &str,
+    inner: &EvaluatorValue<R>,
+    options: &FormatOptions,
+) -> String {
+    let value = format_value(inner_value(inner), options);
+    match options.show_types {
+        true => format!("({}) {}", wrapper, value),
+        false => value,
+    }
+}
+
+/// Find a member by name.
+fn find_member<'a, R: Reader<Offset = usize>>(
+    members: &'a [EvaluatorValue<R>],
+    name: &str,
+) -> Option<&'a EvaluatorValue<R>> {
+    members.iter().find_map(|m| match m {
+        EvaluatorValue::Member(mem) if mem.name.as_deref() == Some(name) => Some(&mem.value),
+        _ => None,
+    })
+}
+
+/// Get the innermost value of nested wrappers, e.g. the `u32` in `Cell<u32>`.
+fn inner_value<R: Reader<Offset = usize>>(value: &EvaluatorValue<R>) -> &EvaluatorValue<R> {
+    match value {
+        EvaluatorValue::Struct(stu) if !stu.name.starts_with("RefCell<") => {
+            match wrapped_member(&stu.name).and_then(|name| find_member(&stu.members, name)) {
+                Some(inner) => inner_value(inner),
+                None => value,
+            }
+        }
+        _ => value,
+    }
+}
+
+/// Get the name of the member that holds the value of a standard library wrapper.
+///
+/// Description:
+///
+/// * `type_name` - The type name of the struct, e.g. `Cell<u32>`.
+///
+/// Will return `None` if the type is not a wrapper.
+fn wrapped_member(type_name: &str) -> Option<&'static str> {
+    match type_name.split('<').next().unwrap_or("") {
+        "UnsafeCell" | "SyncUnsafeCell" | "Cell" | "RefCell" | "ManuallyDrop" => Some("value"),
+        "AtomicPtr" => Some("p"),
+        name if name.starts_with("Atomic") => Some("v"),
+        _ => None,
+    }
+}
+
+/// Format a union value using the given formatting preferences.
+///
+/// Description:
+///
+/// * `union` - The union value that will be formatted.
+/// * `tag` - The value of the tag member in the surrounding struct, it is the index of the
+///   active member.
+/// * `options` - The formatting preferences.
+fn format_union<R: Reader<Offset = usize>>(
+    union: &UnionTypeValue<R>,
+    tag: Option<u64>,
+    options: &FormatOptions,
+) -> String {
+    let active = tag.and_then(|t| union.members.get(t as usize));
+    match (options.union_format, active, &union.raw) {
+        (UnionFormat::ActiveMember, Some(member), _) => {
+            format!("{} ( {} )", union.name, format_value(member, options))
+        }
+        (UnionFormat::ActiveMember, None, Some(raw)) | (UnionFormat::RawBytes, _, Some(raw)) => {
+            let bytes: Vec<String> = raw.iter().map(|b| format!("{:#04x}", b)).collect();
+            format!("{} ( raw: [{}] )", union.name, bytes.join(", "))
+        }
+        _ => format!(
+            "{} ( {} )",
+            union.name,
+            format_list(&union.members, options)
+        ),
+    }
+}
+
+/// Find the value of the member that tells which member of a union is active.
+///
+/// The tag member is a integer or enum member named `tag`, `kind`, `type` or `discriminant`, or
+/// a name that ends with `_tag` or `_kind`.
+fn find_union_tag<R: Reader<Offset = usize>>(members: &[EvaluatorValue<R>]) -> Option<u64> {
+    members.iter().find_map(|m| match m {
+        EvaluatorValue::Member(mem) => {
+            let name = mem.name.as_deref()?;
+            let is_tag = matches!(name, "tag" | "kind" | "type" | "discriminant")
+                || name.ends_with("_tag")
+                || name.ends_with("_kind");
+            if !is_tag {
+                return None;
+            }
+
+            let value = match &mem.value {
+                EvaluatorValue::Enum(enu) => enu.variant.clone().to_value()?,
+                value => value.clone().to_value()?,
+            };
+            get_udata(value).ok()
+        }
+        _ => None,
+    })
+}
+
+/// Format a member value using the given formatting preferences.
+fn format_member<R: Reader<Offset = usize>>(
+    member: &MemberValue<R>,
+    options: &FormatOptions,
+) -> String {
+    match &member.name {
+        Some(name) => format!("{}::{}", name, format_value(&member.value, options)),
+        None => format_value(&member.value, options),
+    }
+}
+
+/// Format a variant value using the given formatting preferences.
+fn format_variant<R: Reader<Offset = usize>>(
+    variant: &VariantValue<R>,
+    options: &FormatOptions,
+) -> String {
+    match &variant.discr_value {
+        Some(discr) => format!("{}::{}", discr, format_member(&variant.child, options)),
+        None => format_member(&variant.child, options),
+    }
+}
+
+/// Format a variant_part value using the given formatting preferences.
+fn format_variant_part<R: Reader<Offset = usize>>(
+    variant_part: &VariantPartValue<R>,
+    options: &FormatOptions,
+) -> String {
+    let mut variants = "{".to_string();
+    for v in &variant_part.variants {
+        variants = format!("{} {},", variants, format_variant(v, options));
+    }
+    variants = format!("{} {}", variants, "}");
+    match &variant_part.variant {
+        Some(variant) => format!(
+            "< variant: {} >, {}",
+            format_member(variant, options),
+            variants
+        ),
+        None => variants,
+    }
+}
+
+/// Format a subrange_type value using the given formatting preferences.
+fn format_subrange(subrange: &SubrangeTypeValue, options: &FormatOptions) -> String {
+    match subrange.get_count() {
+        Ok(Some(count)) if options.hex => format!("{:#x}", count),
+        _ => subrange.to_string(),
+    }
+}
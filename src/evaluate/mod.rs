@@ -1,9 +1,30 @@
 /// Contains functions for retrieving the values of some of the DWARF attributes.
 pub mod attributes;
 
+/// Contains functions for listing the children of composite values, e.g. for expandable
+/// variables.
+pub mod children;
+
 /// Contains structs representing the different Rust data types and more.
 pub mod evaluate;
 
+/// Contains the parser and evaluator for user typed expressions and conditions, e.g.
+/// `my_struct.field[2]`.
+pub mod expression;
+
+/// Contains the formatting preferences and functions for formatting values.
+pub mod format;
+
+/// Contains functions for reading the contents of the standard library buffers, e.g. `&str` and
+/// `Vec`, for pretty printing.
+pub mod pretty;
+
+/// Contains functions for evaluating one field across all the elements of a array.
+pub mod projection;
+
+/// Contains functions for decoding and evaluating Rust trait objects.
+pub mod trait_object;
+
 use crate::call_stack::MemoryAccess;
 use crate::registers::Registers;
 use anyhow::{anyhow, Result};
@@ -200,6 +221,11 @@ pub fn evaluate_value<R: Reader<Offset = usize>, T: MemoryAccess>(
 ///
 /// This function will evaluate the given expression into a list of pieces.
 /// These pieces describe the size and location of the variable the given expression is from.
+/// All the values the evaluation requires are read using `registers` and `mem`, thus the caller
+/// never has to handle the gimli-rs `EvaluationResult` loop.
+/// Both can be backed by closures, see `MemoryAccess` and `Registers::read_registers`.
+/// Hosts that can not block on memory reads can still drive a gimli-rs `Evaluation` themselves
+/// and use `eval_base_type` to parse the values it requires.
 pub fn evaluate_pieces<R: Reader<Offset = usize>, T: MemoryAccess>(
     dwarf: &Dwarf<R>,
     unit: &Unit<R>,
@@ -300,19 +326,47 @@ pub fn evaluate_pieces<R: Reader<Offset = usize>, T: MemoryAccess>(
             },
 
             RequiresEntryValue(entry) => {
-                let entry_value = evaluate(
-                    dwarf, unit, pc, entry, frame_base, None, None, registers, mem,
-                )?;
-
-                result = eval.resume_with_entry_value(convert_to_gimli_value(match entry_value
-                    .to_value()
-                {
-                    Some(val) => val,
+                // The expression has to be evaluated with the register values at the entry of
+                // the current function, not the current register values.
+                // A value that can not be recovered is optimized out, e.g. a argument register
+                // without a call site parameter in the caller.
+                let optimized_out = vec![gimli::Piece {
+                    size_in_bits: None,
+                    bit_offset: None,
+                    location: gimli::Location::Empty,
+                }];
+                let mut entry_registers = registers.clone();
+                entry_registers.registers = match &registers.entry_registers {
+                    Some(val) => val.clone(),
                     None => {
-                        error!("Optimized Out");
-                        return Err(anyhow!("Optimized Out"));
+                        info!("Requires the register values at function entry");
+                        return Ok(optimized_out);
+                    }
+                };
+                entry_registers.entry_registers = None;
+
+                let entry_value = match evaluate(
+                    dwarf,
+                    unit,
+                    pc,
+                    entry,
+                    frame_base,
+                    None,
+                    None,
+                    &entry_registers,
+                    mem,
+                ) {
+                    Ok(val) => val.to_value(),
+                    Err(err) => {
+                        info!("Could not evaluate entry value: {}", err);
+                        None
                     }
-                }))?;
+                };
+
+                result = match entry_value {
+                    Some(val) => eval.resume_with_entry_value(convert_to_gimli_value(val))?,
+                    None => return Ok(optimized_out),
+                };
             }
 
             RequiresParameterRef(unit_offset) => {
@@ -378,8 +432,8 @@ pub fn evaluate_pieces<R: Reader<Offset = usize>, T: MemoryAccess>(
                         return Err(anyhow!("{:?}", err));
                     }
                 } {
-                    println!("Attribute name = {:?}", attr.name());
-                    println!("Attribute value = {:?}", attr.value());
+                    info!("Attribute name = {:?}", attr.name());
+                    info!("Attribute value = {:?}", attr.value());
                 }
 
                 error!("Unimplemented");
@@ -396,12 +450,14 @@ pub fn evaluate_pieces<R: Reader<Offset = usize>, T: MemoryAccess>(
 /// Description:
 ///
 /// * `unit` - A compilation unit which contains the type DIE pointed to by the given offset.
-/// * `unit` - The value to parse in bytes.
+/// * `data` - The value to parse in bytes.
 /// * `base_type` - A offset into the given compilation unit which points to a DIE with the tag
 /// `DW_TAG_base_type`.
 ///
 /// This function will parse the given value into the type given by the offset `base_type`.
-fn eval_base_type<R>(
+/// The result can be converted with `convert_to_gimli_value` and used to resume a gimli-rs
+/// `Evaluation`.
+pub fn eval_base_type<R>(
     unit: &gimli::Unit<R>,
     data: Vec<u8>,
     base_type: gimli::UnitOffset<usize>,
@@ -522,3 +578,60 @@ where
         Err(anyhow!("die has no at location"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gimli::{EndianSlice, LittleEndian, Location};
+    use std::collections::HashMap;
+
+    type TestReader = EndianSlice<'static, LittleEndian>;
+
+    /// A DWARF 4 compilation unit with a single DIE without attributes.
+    fn dwarf() -> Dwarf<TestReader> {
+        static DEBUG_INFO: [u8; 12] = [8, 0, 0, 0, 4, 0, 0, 0, 0, 0, 4, 1];
+        static DEBUG_ABBREV: [u8; 6] = [1, 0x11, 0, 0, 0, 0];
+        Dwarf {
+            debug_info: gimli::DebugInfo::new(&DEBUG_INFO, LittleEndian),
+            debug_abbrev: gimli::DebugAbbrev::new(&DEBUG_ABBREV, LittleEndian),
+            ..Default::default()
+        }
+    }
+
+    /// Evaluate `DW_OP_entry_value(DW_OP_reg0) DW_OP_stack_value`.
+    fn entry_value(registers: &Registers) -> Vec<gimli::Piece<TestReader>> {
+        static EXPR: [u8; 4] = [0xa3, 1, 0x50, 0x9f];
+        let dwarf = dwarf();
+        let unit = dwarf.unit(dwarf.units().next().unwrap().unwrap()).unwrap();
+        let expr = Expression(EndianSlice::new(&EXPR, LittleEndian));
+        let mut mem = |_: &u32, _: usize| None;
+        evaluate_pieces(&dwarf, &unit, 0, expr, None, registers, &mut mem).unwrap()
+    }
+
+    #[test]
+    fn entry_value_from_entry_registers() {
+        let mut registers = Registers::default();
+        registers.entry_registers = Some(HashMap::from([(0, 5)]));
+        let pieces = entry_value(&registers);
+        assert!(matches!(
+            pieces[0].location,
+            Location::Value {
+                value: gimli::Value::Generic(5)
+            }
+        ));
+    }
+
+    #[test]
+    fn unrecoverable_entry_value_is_optimized_out() {
+        let mut registers = Registers::default();
+        registers.entry_registers = Some(HashMap::new());
+        assert!(matches!(
+            entry_value(&registers)[0].location,
+            Location::Empty
+        ));
+        assert!(matches!(
+            entry_value(&Registers::default())[0].location,
+            Location::Empty
+        ));
+    }
+}
@@ -0,0 +1,329 @@
+use super::evaluate::{
+    get_udata, ArrayTypeValue, BaseTypeValue, EvaluatorValue, PointerTypeValue, StructureTypeValue,
+    SubrangeTypeValue, ValueInformation, ValuePiece,
+};
+use crate::call_stack::MemoryAccess;
+use gimli::{Endianity, Reader, RunTimeEndian};
+use log::trace;
+
+/// The maximum number of elements or string bytes that are read for a standard library buffer.
+pub const MAX_BUFFER_ELEMENTS: u64 = 1024;
+
+/// Read the contents of the standard library buffers in a value, so that they can be formatted
+/// idiomatically.
+///
+/// Description:
+///
+/// * `value` - The evaluated value.
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `endian` - The byte order of the debug target, see `memory_view::target_endian`.
+///
+/// A `&str` or `String` is replaced with a pointer value that holds the bytes of the string, and
+/// a `Vec` or slice of base type values is replaced with a array of its elements.
+/// Only the pointer and the length are in the evaluated value, so the contents are read from
+/// memory.
+/// At most `MAX_BUFFER_ELEMENTS` elements or bytes are read, the rest of a longer buffer is
+/// shown as a `<truncated>` marker.
+/// Buffers that can not be read, or where the element type is not a base type, are kept as they
+/// are.
+/// This is skipped when pretty printing is disabled, so the raw layout can still be seen.
+pub fn read_std_contents<R: Reader<Offset = usize>, M: MemoryAccess>(
+    value: EvaluatorValue<R>,
+    mem: &mut M,
+    endian: RunTimeEndian,
+) -> EvaluatorValue<R> {
+    match value {
+        EvaluatorValue::Struct(structure) => match read_buffer(&structure, mem, endian) {
+            Some(contents) => contents,
+            None => {
+                let StructureTypeValue { name, members } = *structure;
+                EvaluatorValue::Struct(Box::new(StructureTypeValue {
+                    name,
+                    members: members
+                        .into_iter()
+                        .map(|m| read_std_contents(m, mem, endian))
+                        .collect(),
+                }))
+            }
+        },
+        EvaluatorValue::Member(mut member) => {
+            member.value = read_std_contents(member.value, mem, endian);
+            EvaluatorValue::Member(member)
+        }
+        EvaluatorValue::Array(mut array) => {
+            array.values = array
+                .values
+                .into_iter()
+                .map(|v| read_std_contents(v, mem, endian))
+                .collect();
+            EvaluatorValue::Array(array)
+        }
+        EvaluatorValue::VariantPartValue(mut variant_part) => {
+            for variant in variant_part.variants.iter_mut() {
+                variant.child.value = read_std_contents(variant.child.value.clone(), mem, endian);
+            }
+            EvaluatorValue::VariantPartValue(variant_part)
+        }
+        value => value,
+    }
+}
+
+/// Read the contents of a `&str`, `String`, `Vec` or slice.
+///
+/// Will return `None` if the struct is not one of them or the contents can not be read.
+fn read_buffer<R: Reader<Offset = usize>, M: MemoryAccess>(
+    structure: &StructureTypeValue<R>,
+    mem: &mut M,
+    endian: RunTimeEndian,
+) -> Option<EvaluatorValue<R>> {
+    let name = structure.name.as_str();
+    let is_string = matches!(name, "&str" | "&mut str" | "String");
+    let element_type = match name.strip_prefix("Vec<") {
+        Some(rest) => rest.split([',', '>']).next(),
+        None => name
+            .strip_prefix("&[")
+            .or_else(|| name.strip_prefix("&mut ["))
+            .and_then(|rest| rest.strip_suffix(']')),
+    };
+    if !is_string && element_type.is_none() {
+        return None;
+    }
+
+    let (address, pointer, length) = find_buffer(structure)?;
+    if is_string {
+        let read_length = length.min(MAX_BUFFER_ELEMENTS) as usize;
+        let bytes = match read_length {
+            0 => vec![],
+            _ => mem.get_address(&address, read_length)?,
+        };
+        return Some(EvaluatorValue::PointerTypeValue(Box::new(
+            PointerTypeValue {
+                name: None,
+                address: EvaluatorValue::Value(
+                    BaseTypeValue::Address32(address),
+                    ValueInformation::new(None, vec![]),
+                ),
+                value: pointer.value.clone(),
+                c_string: Some(bytes),
+                truncated: length > MAX_BUFFER_ELEMENTS,
+            },
+        )));
+    }
+
+    // The element type is erased in the buffer of newer `Vec`s, so it is checked against the
+    // type name.
+    let sample = match strip_member(&pointer.value) {
+        EvaluatorValue::Value(val, _) if Some(val.get_type().as_str()) == element_type => val,
+        _ => {
+            trace!("Can not read the elements of {}", name);
+            return None;
+        }
+    };
+    let size = element_size(sample)?;
+    let count = length.min(MAX_BUFFER_ELEMENTS) as usize;
+    let bytes = match count {
+        0 => vec![],
+        _ => mem.get_address(&address, count * size)?,
+    };
+    let mut values = vec![];
+    for (i, chunk) in bytes.chunks_exact(size).enumerate() {
+        values.push(EvaluatorValue::Value(
+            decode_like(sample, chunk, endian)?,
+            ValueInformation::new(
+                Some(chunk.to_vec()),
+                vec![ValuePiece::Memory {
+                    address: address + (i * size) as u32,
+                    byte_size: size,
+                    bit_offset: 0,
+                    bit_size: 8 * size as u64,
+                }],
+            ),
+        ));
+    }
+
+    Some(EvaluatorValue::Array(Box::new(ArrayTypeValue {
+        subrange_type_value: SubrangeTypeValue {
+            lower_bound: Some(0),
+            count: Some(length),
+            base_type_value: None,
+        },
+        values,
+    })))
+}
+
+/// Find the data pointer and the length of a standard library buffer.
+///
+/// A `&str` and a slice have the members `data_ptr` and `length`, a `Vec` has the pointer
+/// somewhere in `buf` and the member `len`, and a `String` has a `Vec<u8>` in `vec`.
+fn find_buffer<R: Reader<Offset = usize>>(
+    structure: &StructureTypeValue<R>,
+) -> Option<(u32, &PointerTypeValue<R>, u64)> {
+    if let Some(EvaluatorValue::Struct(vec)) = find_member(&structure.members, "vec") {
+        return find_buffer(vec);
+    }
+
+    let length = find_member(&structure.members, "length")
+        .or_else(|| find_member(&structure.members, "len"))?;
+    let length = get_udata(length.clone().to_value()?).ok()?;
+    let pointer = find_member(&structure.members, "data_ptr")
+        .or_else(|| find_member(&structure.members, "buf"))
+        .and_then(first_pointer)?;
+    match strip_member(&pointer.address) {
+        EvaluatorValue::Value(BaseTypeValue::Address32(address), _) => {
+            Some((*address, pointer, length))
+        }
+        _ => None,
+    }
+}
+
+/// Find the first pointer in a value, the wrappers around the pointer of a `Vec` differ
+/// between Rust versions.
+fn first_pointer<R: Reader<Offset = usize>>(
+    value: &EvaluatorValue<R>,
+) -> Option<&PointerTypeValue<R>> {
+    match value {
+        EvaluatorValue::PointerTypeValue(pointer) => Some(pointer),
+        EvaluatorValue::Member(member) => first_pointer(&member.value),
+        EvaluatorValue::Struct(structure) => structure.members.iter().find_map(first_pointer),
+        _ => None,
+    }
+}
+
+/// Find a member by name.
+fn find_member<'a, R: Reader<Offset = usize>>(
+    members: &'a [EvaluatorValue<R>],
+    name: &str,
+) -> Option<&'a EvaluatorValue<R>> {
+    members.iter().find_map(|m| match m {
+        EvaluatorValue::Member(mem) if mem.name.as_deref() == Some(name) => Some(&mem.value),
+        _ => None,
+    })
+}
+
+/// Remove the members around a value.
+fn strip_member<R: Reader<Offset = usize>>(value: &EvaluatorValue<R>) -> &EvaluatorValue<R> {
+    match value {
+        EvaluatorValue::Member(member) => strip_member(&member.value),
+        _ => value,
+    }
+}
+
+/// Get the size in bytes of a base type value, `None` for the values without a fixed size.
+fn element_size(value: &BaseTypeValue) -> Option<usize> {
+    match value {
+        BaseTypeValue::Bool(_) | BaseTypeValue::U8(_) | BaseTypeValue::I8(_) => Some(1),
+        BaseTypeValue::U16(_) | BaseTypeValue::I16(_) => Some(2),
+        BaseTypeValue::U32(_) | BaseTypeValue::I32(_) | BaseTypeValue::F32(_) => Some(4),
+        BaseTypeValue::Address32(_) => Some(4),
+        BaseTypeValue::U64(_) | BaseTypeValue::I64(_) | BaseTypeValue::F64(_) => Some(8),
+        BaseTypeValue::Generic(_) | BaseTypeValue::Reg32(_) => None,
+    }
+}
+
+/// Decode bytes in the target byte order into a base type value of the same type as `sample`.
+///
+/// Will return `None` if the number of bytes does not match the size of the type.
+fn decode_like(
+    sample: &BaseTypeValue,
+    bytes: &[u8],
+    endian: RunTimeEndian,
+) -> Option<BaseTypeValue> {
+    if Some(bytes.len()) != element_size(sample) {
+        return None;
+    }
+
+    Some(match sample {
+        BaseTypeValue::Bool(_) => BaseTypeValue::Bool(bytes[0] == 1),
+        BaseTypeValue::U8(_) => BaseTypeValue::U8(bytes[0]),
+        BaseTypeValue::I8(_) => BaseTypeValue::I8(bytes[0] as i8),
+        BaseTypeValue::U16(_) => BaseTypeValue::U16(endian.read_u16(bytes)),
+        BaseTypeValue::I16(_) => BaseTypeValue::I16(endian.read_i16(bytes)),
+        BaseTypeValue::U32(_) => BaseTypeValue::U32(endian.read_u32(bytes)),
+        BaseTypeValue::I32(_) => BaseTypeValue::I32(endian.read_i32(bytes)),
+        BaseTypeValue::F32(_) => BaseTypeValue::F32(endian.read_f32(bytes)),
+        BaseTypeValue::Address32(_) => BaseTypeValue::Address32(endian.read_u32(bytes)),
+        BaseTypeValue::U64(_) => BaseTypeValue::U64(endian.read_u64(bytes)),
+        BaseTypeValue::I64(_) => BaseTypeValue::I64(endian.read_i64(bytes)),
+        BaseTypeValue::F64(_) => BaseTypeValue::F64(endian.read_f64(bytes)),
+        BaseTypeValue::Generic(_) | BaseTypeValue::Reg32(_) => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::evaluate::MemberValue;
+    use crate::evaluate::format::{format_value, FormatOptions};
+
+    type Value = EvaluatorValue<gimli::EndianSlice<'static, gimli::LittleEndian>>;
+
+    fn member(name: &str, value: Value) -> Value {
+        EvaluatorValue::Member(Box::new(MemberValue {
+            name: Some(name.to_string()),
+            value,
+        }))
+    }
+
+    fn base(value: BaseTypeValue) -> Value {
+        EvaluatorValue::Value(value, ValueInformation::new(None, vec![]))
+    }
+
+    fn buffer(name: &str, element: BaseTypeValue, length: u64) -> Value {
+        let pointer = EvaluatorValue::PointerTypeValue(Box::new(PointerTypeValue {
+            name: None,
+            address: base(BaseTypeValue::Address32(0x2000_0000)),
+            value: base(element),
+            c_string: None,
+            truncated: false,
+        }));
+        EvaluatorValue::Struct(Box::new(StructureTypeValue {
+            name: name.to_string(),
+            members: vec![
+                member("data_ptr", pointer),
+                member("length", base(BaseTypeValue::U32(length as u32))),
+            ],
+        }))
+    }
+
+    fn format(value: Value) -> String {
+        format_with_endian(value, RunTimeEndian::Little)
+    }
+
+    fn format_with_endian(value: Value, endian: RunTimeEndian) -> String {
+        let mut mem = |address: &u32, num_bytes: usize| {
+            let mut memory = b"hi\x01\x00\x02\x00".to_vec();
+            memory.resize(2 * MAX_BUFFER_ELEMENTS as usize, 0);
+            let start = address.checked_sub(0x2000_0000)? as usize;
+            memory.get(start..start + num_bytes).map(|b| b.to_vec())
+        };
+        format_value(
+            &read_std_contents(value, &mut mem, endian),
+            &FormatOptions::default(),
+        )
+    }
+
+    #[test]
+    fn std_buffers() {
+        assert_eq!(format(buffer("&str", BaseTypeValue::U8(b'h'), 2)), "\"hi\"");
+        assert_eq!(
+            format(buffer("&[u16]", BaseTypeValue::U16(0), 3)),
+            "[ 26984, 1, 2 ]"
+        );
+        // A element type that does not match the type name is not read.
+        assert!(format(buffer("&[u32]", BaseTypeValue::U8(0), 1)).starts_with("&[u32] {"));
+    }
+
+    #[test]
+    fn std_buffers_target_endian_and_truncation() {
+        assert_eq!(
+            format_with_endian(
+                buffer("&[u16]", BaseTypeValue::U16(0), 2),
+                RunTimeEndian::Big
+            ),
+            "[ 26729, 256 ]"
+        );
+        assert!(format(buffer("&[u8]", BaseTypeValue::U8(0), 2000)).ends_with(", <truncated> ]"));
+        assert!(format(buffer("&str", BaseTypeValue::U8(0), 2000)).ends_with("...\" <truncated>"));
+        assert!(!format(buffer("&[u8]", BaseTypeValue::U8(0), 1024)).contains("<truncated>"));
+    }
+}
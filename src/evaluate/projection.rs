@@ -0,0 +1,269 @@
+use super::attributes;
+use super::evaluate::{get_unit, EvaluatorValue};
+use crate::call_stack::MemoryAccess;
+use crate::registers::Registers;
+use anyhow::{anyhow, Result};
+use gimli::{
+    DebuggingInformationEntry, Dwarf, Location, Piece, Reader, Unit, UnitOffset, UnitSectionOffset,
+};
+use log::error;
+
+/// Will evaluate one field of every element in a array of structs.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `registers` - A register struct for accessing the register values.
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `pieces` - The location of the array, it is required to be stored in one piece of memory.
+/// * `array_type` - Offsets to the `Unit` and the DIE that contains the array type.
+/// * `field` - The name of the field, nested fields are separated by `.`, e.g. `pos.x`.
+///
+/// Only the field is evaluated in each element, the other fields are never read.
+/// This is used for column views of arrays, e.g. `print sensors[*].temp`.
+pub fn evaluate_array_field<R: Reader<Offset = usize>, M: MemoryAccess>(
+    dwarf: &Dwarf<R>,
+    registers: &Registers,
+    mem: &mut M,
+    pieces: &[Piece<R>],
+    array_type: (UnitSectionOffset, UnitOffset),
+    field: &str,
+) -> Result<Vec<EvaluatorValue<R>>> {
+    let address = match pieces {
+        [Piece {
+            location: Location::Address { address },
+            bit_offset: None,
+            ..
+        }] => *address,
+        _ => {
+            error!("The array is required to be stored in one piece of memory");
+            return Err(anyhow!(
+                "The array is required to be stored in one piece of memory"
+            ));
+        }
+    };
+
+    let unit = get_unit(dwarf, array_type.0)?;
+    let die = unit.entry(array_type.1)?;
+    if die.tag() != gimli::DW_TAG_array_type {
+        error!("Expected DW_TAG_array_type die");
+        return Err(anyhow!("Expected DW_TAG_array_type die"));
+    }
+
+    let count = array_count(&unit, &die)?;
+
+    // Find the element type and the field in it.
+    let (element_unit, element_offset) = resolve_type(dwarf, &unit, &die)?;
+    let element_die = element_unit.entry(element_offset)?;
+    let stride = match die.attr_value(gimli::DW_AT_byte_stride)? {
+        Some(value) => value.udata_value(),
+        None => attributes::byte_size_attribute(&element_die)?,
+    };
+    let stride = match stride {
+        Some(val) => val,
+        None => {
+            error!("Could not find the size of the array elements");
+            return Err(anyhow!("Could not find the size of the array elements"));
+        }
+    };
+
+    let mut field_offset = 0;
+    let mut field_type = (element_unit.header.offset(), element_offset);
+    for name in field.split('.') {
+        let struct_unit = get_unit(dwarf, field_type.0)?;
+        let struct_die = struct_unit.entry(field_type.1)?;
+        let (offset, member) = find_member(dwarf, &struct_unit, &struct_die, name)?;
+        let member_die = struct_unit.entry(member)?;
+        let (type_unit, type_offset) = resolve_type(dwarf, &struct_unit, &member_die)?;
+
+        field_offset += offset;
+        field_type = (type_unit.header.offset(), type_offset);
+    }
+
+    // Evaluate the field in every element.
+    let mut values = vec![];
+    for i in 0..count {
+        let field_address = match i
+            .checked_mul(stride)
+            .and_then(|v| v.checked_add(address))
+            .and_then(|v| v.checked_add(field_offset))
+        {
+            Some(val) => val,
+            None => {
+                error!("The array elements are outside of the address space");
+                return Err(anyhow!(
+                    "The array elements are outside of the address space"
+                ));
+            }
+        };
+        let field_pieces = vec![Piece {
+            size_in_bits: None,
+            bit_offset: None,
+            location: Location::Address {
+                address: field_address,
+            },
+        }];
+        values.push(EvaluatorValue::evaluate_variable_with_type(
+            dwarf,
+            registers,
+            mem,
+            &field_pieces,
+            field_type.0,
+            field_type.1,
+        )?);
+    }
+
+    Ok(values)
+}
+
+/// Find the number of elements in a array type.
+///
+/// Description:
+///
+/// * `unit` - A compilation unit which contains the given DIE.
+/// * `die` - The array type DIE.
+///
+/// The count is read from the `DW_AT_count` or `DW_AT_upper_bound` attribute of the subrange
+/// child.
+fn array_count<R: Reader<Offset = usize>>(
+    unit: &Unit<R>,
+    die: &DebuggingInformationEntry<R>,
+) -> Result<u64> {
+    let mut tree = unit.entries_tree(Some(die.offset()))?;
+    let root = tree.root()?;
+    let mut children = root.children();
+    while let Some(child) = children.next()? {
+        let child_die = child.entry();
+        if child_die.tag() != gimli::DW_TAG_subrange_type {
+            continue;
+        }
+
+        if let Some(count) = attributes::count_attribute(child_die)? {
+            return Ok(count);
+        }
+
+        let lower_bound = attributes::lower_bound_attribute(child_die)?.unwrap_or(0);
+        if let Some(upper_bound) = child_die
+            .attr_value(gimli::DW_AT_upper_bound)?
+            .and_then(|v| v.udata_value())
+        {
+            return match upper_bound
+                .checked_add(1)
+                .and_then(|v| v.checked_sub(lower_bound))
+            {
+                Some(count) => Ok(count),
+                None => {
+                    error!("Invalid array bounds {} to {}", lower_bound, upper_bound);
+                    Err(anyhow!(
+                        "Invalid array bounds {} to {}",
+                        lower_bound,
+                        upper_bound
+                    ))
+                }
+            };
+        }
+    }
+
+    error!("Could not find the number of elements in the array");
+    Err(anyhow!(
+        "Could not find the number of elements in the array"
+    ))
+}
+
+/// Find a member of a struct by name.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `unit` - A compilation unit which contains the given DIE.
+/// * `die` - The struct type DIE.
+/// * `name` - The name of the member.
+///
+/// Will return the byte offset of the member and the offset to the member DIE.
+fn find_member<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    unit: &Unit<R>,
+    die: &DebuggingInformationEntry<R>,
+    name: &str,
+) -> Result<(u64, UnitOffset)> {
+    let mut tree = unit.entries_tree(Some(die.offset()))?;
+    let root = tree.root()?;
+    let mut children = root.children();
+    while let Some(child) = children.next()? {
+        let child_die = child.entry();
+        if child_die.tag() == gimli::DW_TAG_member
+            && attributes::name_attribute(dwarf, child_die)?.as_deref() == Some(name)
+        {
+            let offset = attributes::data_member_location_attribute(child_die)?.unwrap_or(0);
+            return Ok((offset, child_die.offset()));
+        }
+    }
+
+    error!("Could not find the field {}", name);
+    Err(anyhow!("Could not find the field {}", name))
+}
+
+/// Resolve the `DW_AT_type` attribute of a DIE, typedefs and type modifiers are followed.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `unit` - A compilation unit which contains the given DIE.
+/// * `die` - The DIE that has the type attribute.
+fn resolve_type<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    unit: &Unit<R>,
+    die: &DebuggingInformationEntry<R>,
+) -> Result<(Unit<R>, UnitOffset)> {
+    let (section_offset, unit_offset) = match attributes::type_attribute(dwarf, unit, die)? {
+        Some(val) => val,
+        None => {
+            error!("Die doesn't have the required DW_AT_type attribute");
+            return Err(anyhow!(
+                "Die doesn't have the required DW_AT_type attribute"
+            ));
+        }
+    };
+
+    let type_unit = get_unit(dwarf, section_offset)?;
+    let type_die = type_unit.entry(unit_offset)?;
+    match type_die.tag() {
+        gimli::DW_TAG_typedef
+        | gimli::DW_TAG_const_type
+        | gimli::DW_TAG_volatile_type
+        | gimli::DW_TAG_restrict_type => resolve_type(dwarf, &type_unit, &type_die),
+        _ => Ok((type_unit, unit_offset)),
+    }
+}
+
+/// Split a projection expression into the array name and the field path.
+///
+/// Description:
+///
+/// * `expression` - A expression on the form `array[*].field`, e.g. `sensors[*].temp`.
+///
+/// Will return `None` if the expression doesn't contain the `[*].` wildcard.
+pub fn parse_projection(expression: &str) -> Option<(&str, &str)> {
+    let (array, field) = expression.split_once("[*].")?;
+    if array.is_empty() || field.is_empty() {
+        return None;
+    }
+    Some((array.trim(), field.trim()))
+}
+
+/// Filter the values of a projection and keep the index of each element.
+///
+/// Description:
+///
+/// * `values` - The values of the projected field, in element order.
+/// * `predicate` - Returns `true` for the values that should be kept.
+pub fn filter_elements<R: Reader<Offset = usize>, F: Fn(&EvaluatorValue<R>) -> bool>(
+    values: Vec<EvaluatorValue<R>>,
+    predicate: F,
+) -> Vec<(usize, EvaluatorValue<R>)> {
+    values
+        .into_iter()
+        .enumerate()
+        .filter(|(_, value)| predicate(value))
+        .collect()
+}
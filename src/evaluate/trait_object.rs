@@ -0,0 +1,280 @@
+use super::attributes;
+use super::evaluate::{
+    get_unit, BaseTypeValue, EvaluatorValue, PointerTypeValue, StructureTypeValue, ValueInformation,
+};
+use crate::call_stack::MemoryAccess;
+use crate::dwarf_index::DwarfIndex;
+use crate::registers::Registers;
+use anyhow::Result;
+use gimli::{Dwarf, Location, Piece, Reader, UnitOffset, UnitSectionOffset};
+use log::trace;
+
+/// A decoded Rust trait object, e.g. `&dyn Trait` or `Box<dyn Trait>`.
+#[derive(Debug, Clone)]
+pub struct TraitObject<R: Reader<Offset = usize>> {
+    /// The address of the data the trait object points to.
+    pub data: u64,
+
+    /// The address of the vtable.
+    pub vtable: u64,
+
+    /// The name of the vtable, e.g. `<app::Sensor as app::Read>::{vtable}`.
+    pub vtable_name: Option<String>,
+
+    /// The name of the concrete type behind the trait object.
+    pub concrete_type: Option<String>,
+
+    /// The data evaluated as the concrete type.
+    pub value: Option<EvaluatorValue<R>>,
+}
+
+/// Information about a vtable found in the debug information.
+#[derive(Debug, Clone)]
+pub struct VtableInfo {
+    /// The name of the vtable variable.
+    pub name: Option<String>,
+
+    /// The name of the concrete type that implements the trait.
+    pub concrete_type: Option<String>,
+
+    /// Offsets to the `Unit` and the DIE of the concrete type.
+    pub type_offset: Option<(UnitSectionOffset, UnitOffset)>,
+}
+
+/// Decode the data and vtable address of a trait object fat pointer.
+///
+/// Description:
+///
+/// * `value` - The evaluated fat pointer struct, it has the members `pointer` and `vtable`.
+///
+/// Will return `None` if the struct is not a trait object.
+pub fn decode_fat_pointer<R: Reader<Offset = usize>>(
+    value: &StructureTypeValue<R>,
+) -> Option<(u64, u64)> {
+    let mut data = None;
+    let mut vtable = None;
+    for member in &value.members {
+        if let EvaluatorValue::Member(member) = member {
+            match member.name.as_deref() {
+                Some("pointer") => data = pointer_address(&member.value),
+                Some("vtable") => vtable = pointer_address(&member.value),
+                _ => (),
+            };
+        }
+    }
+
+    Some((data?, vtable?))
+}
+
+/// Find the vtable variable that is stored at a address.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `index` - A index of the DWARF debug information.
+/// * `vtable` - The address of the vtable.
+///
+/// Rust emits the vtables as variables named `<Type as Trait>::{vtable}`, the type of the
+/// variable has a `DW_AT_containing_type` attribute that points to the concrete type.
+/// If it has not, the concrete type is found by the name in the vtable name.
+/// Will return `None` if no vtable is found at the address.
+pub fn find_vtable<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    index: &DwarfIndex,
+    vtable: u64,
+) -> Result<Option<VtableInfo>> {
+    let offset = match index.find_static_variable_at(vtable) {
+        Some(val) => val,
+        None => return Ok(None),
+    };
+    let unit = get_unit(dwarf, offset.section_offset)?;
+    let die = unit.entry(offset.unit_offset)?;
+    let name = attributes::name_attribute(dwarf, &die)?;
+
+    // Find the concrete type through the type of the vtable.
+    let mut type_offset = None;
+    if let Some((section_offset, offset)) = attributes::type_attribute(dwarf, &unit, &die)? {
+        let type_unit = get_unit(dwarf, section_offset)?;
+        let type_die = type_unit.entry(offset)?;
+        type_offset = attributes::containing_type_attribute(dwarf, &type_unit, &type_die)?;
+    }
+
+    let concrete_type = match type_offset {
+        Some((section_offset, offset)) => {
+            let type_unit = get_unit(dwarf, section_offset)?;
+            let type_die = type_unit.entry(offset)?;
+            attributes::name_attribute(dwarf, &type_die)?
+        }
+        None => None,
+    }
+    .or_else(|| name.as_deref().and_then(parse_vtable_name));
+
+    if type_offset.is_none() {
+        type_offset = concrete_type
+            .as_deref()
+            .and_then(|name| index.find_type(name))
+            .map(|offset| (offset.section_offset, offset.unit_offset));
+    }
+
+    Ok(Some(VtableInfo {
+        name,
+        concrete_type,
+        type_offset,
+    }))
+}
+
+/// Will decode a trait object and evaluate the data it points to as the concrete type.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `index` - A index of the DWARF debug information.
+/// * `registers` - A register struct for accessing the register values.
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `value` - The evaluated fat pointer struct.
+///
+/// Will return `None` if the struct is not a trait object.
+/// The data is only evaluated if the concrete type is found in the debug information.
+pub fn evaluate_trait_object<R: Reader<Offset = usize>, M: MemoryAccess>(
+    dwarf: &Dwarf<R>,
+    index: &DwarfIndex,
+    registers: &Registers,
+    mem: &mut M,
+    value: &StructureTypeValue<R>,
+) -> Result<Option<TraitObject<R>>> {
+    let (data, vtable) = match decode_fat_pointer(value) {
+        Some(val) => val,
+        None => return Ok(None),
+    };
+
+    let info = find_vtable(dwarf, index, vtable)?;
+    let (vtable_name, concrete_type, type_offset) = match info {
+        Some(info) => (info.name, info.concrete_type, info.type_offset),
+        None => (None, None, None),
+    };
+
+    let value = match type_offset {
+        Some((unit_offset, die_offset)) => {
+            let pieces = vec![Piece {
+                size_in_bits: None,
+                bit_offset: None,
+                location: Location::Address { address: data },
+            }];
+            Some(EvaluatorValue::evaluate_variable_with_type(
+                dwarf,
+                registers,
+                mem,
+                &pieces,
+                unit_offset,
+                die_offset,
+            )?)
+        }
+        None => None,
+    };
+
+    Ok(Some(TraitObject {
+        data,
+        vtable,
+        vtable_name,
+        concrete_type,
+        value,
+    }))
+}
+
+/// Replace the trait objects in a value with pointers to their data evaluated as the concrete
+/// type, so that they can be formatted and expanded like other pointers.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `index` - A index of the DWARF debug information.
+/// * `registers` - A register struct for accessing the register values.
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `value` - The evaluated value.
+///
+/// Trait objects whose concrete type is not found are kept as the fat pointer struct.
+pub fn resolve_trait_objects<R: Reader<Offset = usize>, M: MemoryAccess>(
+    dwarf: &Dwarf<R>,
+    index: &DwarfIndex,
+    registers: &Registers,
+    mem: &mut M,
+    value: EvaluatorValue<R>,
+) -> EvaluatorValue<R> {
+    match value {
+        EvaluatorValue::Struct(structure) => {
+            match evaluate_trait_object(dwarf, index, registers, mem, &structure) {
+                Ok(Some(TraitObject {
+                    data,
+                    value: Some(value),
+                    ..
+                })) => {
+                    return EvaluatorValue::PointerTypeValue(Box::new(PointerTypeValue {
+                        name: None,
+                        address: EvaluatorValue::Value(
+                            BaseTypeValue::Address32(data as u32),
+                            ValueInformation::new(None, vec![]),
+                        ),
+                        value,
+                        c_string: None,
+                        truncated: false,
+                    }))
+                }
+                Ok(_) => (),
+                Err(err) => trace!("Could not evaluate the trait object: {:?}", err),
+            };
+            let StructureTypeValue { name, members } = *structure;
+            EvaluatorValue::Struct(Box::new(StructureTypeValue {
+                name,
+                members: members
+                    .into_iter()
+                    .map(|m| resolve_trait_objects(dwarf, index, registers, mem, m))
+                    .collect(),
+            }))
+        }
+        EvaluatorValue::Member(mut member) => {
+            member.value = resolve_trait_objects(dwarf, index, registers, mem, member.value);
+            EvaluatorValue::Member(member)
+        }
+        EvaluatorValue::PointerTypeValue(mut pointer) => {
+            pointer.value = resolve_trait_objects(dwarf, index, registers, mem, pointer.value);
+            EvaluatorValue::PointerTypeValue(pointer)
+        }
+        EvaluatorValue::Array(mut array) => {
+            array.values = array
+                .values
+                .into_iter()
+                .map(|v| resolve_trait_objects(dwarf, index, registers, mem, v))
+                .collect();
+            EvaluatorValue::Array(array)
+        }
+        EvaluatorValue::VariantPartValue(mut variant_part) => {
+            for variant in variant_part.variants.iter_mut() {
+                let value = variant.child.value.clone();
+                variant.child.value = resolve_trait_objects(dwarf, index, registers, mem, value);
+            }
+            EvaluatorValue::VariantPartValue(variant_part)
+        }
+        value => value,
+    }
+}
+
+/// Get the address stored in a evaluated pointer.
+fn pointer_address<R: Reader<Offset = usize>>(value: &EvaluatorValue<R>) -> Option<u64> {
+    match value {
+        EvaluatorValue::PointerTypeValue(pointer) => match pointer.address {
+            EvaluatorValue::Value(BaseTypeValue::Address32(address), _) => Some(address as u64),
+            EvaluatorValue::Value(BaseTypeValue::Generic(address), _) => Some(address),
+            EvaluatorValue::Value(BaseTypeValue::U64(address), _) => Some(address),
+            _ => None,
+        },
+        EvaluatorValue::Value(BaseTypeValue::Address32(address), _) => Some(*address as u64),
+        _ => None,
+    }
+}
+
+/// Get the concrete type name from a vtable name on the form `<Type as Trait>::{vtable}`.
+fn parse_vtable_name(name: &str) -> Option<String> {
+    let inner = name.strip_prefix('<')?.strip_suffix(">::{vtable}")?;
+    let (concrete_type, _) = inner.rsplit_once(" as ")?;
+    Some(concrete_type.to_string())
+}
@@ -0,0 +1,207 @@
+use crate::call_stack::MemoryAccess;
+use crate::memory_view::read_word;
+use anyhow::{anyhow, Result};
+use gimli::RunTimeEndian;
+use log::error;
+use std::fmt;
+
+/// The address of the Cortex-M Configurable Fault Status Register.
+pub const CFSR_ADDRESS: u32 = 0xE000_ED28;
+
+/// The address of the Cortex-M HardFault Status Register.
+pub const HFSR_ADDRESS: u32 = 0xE000_ED2C;
+
+/// The address of the Cortex-M MemManage Fault Address Register.
+pub const MMFAR_ADDRESS: u32 = 0xE000_ED34;
+
+/// The address of the Cortex-M BusFault Address Register.
+pub const BFAR_ADDRESS: u32 = 0xE000_ED38;
+
+/// The `MMARVALID` bit in the CFSR register, it is set when `MMFAR` holds the faulting address.
+const MMARVALID: u32 = 1 << 7;
+
+/// The `BFARVALID` bit in the CFSR register, it is set when `BFAR` holds the faulting address.
+const BFARVALID: u32 = 1 << 15;
+
+/// The CFSR bits and what they mean.
+const CFSR_BITS: [(u32, &str); 18] = [
+    (0, "IACCVIOL: instruction access violation"),
+    (1, "DACCVIOL: data access violation"),
+    (
+        3,
+        "MUNSTKERR: MemManage fault on exception return unstacking",
+    ),
+    (4, "MSTKERR: MemManage fault on exception entry stacking"),
+    (
+        5,
+        "MLSPERR: MemManage fault during lazy floating point state preservation",
+    ),
+    (8, "IBUSERR: instruction bus error"),
+    (9, "PRECISERR: precise data bus error"),
+    (10, "IMPRECISERR: imprecise data bus error"),
+    (11, "UNSTKERR: BusFault on exception return unstacking"),
+    (12, "STKERR: BusFault on exception entry stacking"),
+    (
+        13,
+        "LSPERR: BusFault during lazy floating point state preservation",
+    ),
+    (16, "UNDEFINSTR: undefined instruction"),
+    (
+        17,
+        "INVSTATE: invalid state, e.g. a branch to a address without the Thumb bit",
+    ),
+    (18, "INVPC: invalid exception return value"),
+    (19, "NOCP: coprocessor access while it is disabled"),
+    (20, "STKOF: stack overflow"),
+    (24, "UNALIGNED: unaligned memory access"),
+    (25, "DIVBYZERO: division by zero"),
+];
+
+/// The HFSR bits and what they mean.
+const HFSR_BITS: [(u32, &str); 3] = [
+    (1, "VECTTBL: BusFault on a vector table read"),
+    (
+        30,
+        "FORCED: a configurable fault was escalated to a HardFault",
+    ),
+    (31, "DEBUGEVT: debug event"),
+];
+
+/// The values of the Cortex-M fault status registers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FaultStatus {
+    /// The value of the Configurable Fault Status Register.
+    pub cfsr: u32,
+
+    /// The value of the HardFault Status Register.
+    pub hfsr: u32,
+
+    /// The value of the MemManage Fault Address Register.
+    pub mmfar: u32,
+
+    /// The value of the BusFault Address Register.
+    pub bfar: u32,
+}
+
+impl FaultStatus {
+    /// Read the fault status registers from the debug target.
+    ///
+    /// Description:
+    ///
+    /// * `mem` - A struct for accessing the memory of the debug target.
+    /// * `endian` - The endianness of the debug target.
+    pub fn read<M: MemoryAccess>(mem: &mut M, endian: RunTimeEndian) -> Result<FaultStatus> {
+        let mut read = |address: u32| match mem
+            .get_address(&address, 4)
+            .and_then(|val| read_word(&val, endian))
+        {
+            Some(val) => Ok(val),
+            None => {
+                error!("Could not read the fault register at {:#010x}", address);
+                Err(anyhow!(
+                    "Could not read the fault register at {:#010x}",
+                    address
+                ))
+            }
+        };
+
+        Ok(FaultStatus {
+            cfsr: read(CFSR_ADDRESS)?,
+            hfsr: read(HFSR_ADDRESS)?,
+            mmfar: read(MMFAR_ADDRESS)?,
+            bfar: read(BFAR_ADDRESS)?,
+        })
+    }
+
+    /// Will return `true` if any fault is recorded.
+    pub fn is_fault(&self) -> bool {
+        self.cfsr != 0 || self.hfsr != 0
+    }
+
+    /// Get the descriptions of the fault bits that are set.
+    pub fn reasons(&self) -> Vec<&'static str> {
+        let set = |value: u32, bits: &[(u32, &'static str)]| -> Vec<&'static str> {
+            bits.iter()
+                .filter(|(bit, _)| value & (1 << bit) != 0)
+                .map(|(_, reason)| *reason)
+                .collect()
+        };
+        let mut result = set(self.hfsr, &HFSR_BITS);
+        result.append(&mut set(self.cfsr, &CFSR_BITS));
+        result
+    }
+
+    /// Get the address that caused the fault, if it is known.
+    ///
+    /// The MemManage address is used before the BusFault address.
+    pub fn fault_address(&self) -> Option<u32> {
+        if self.cfsr & MMARVALID != 0 {
+            Some(self.mmfar)
+        } else if self.cfsr & BFARVALID != 0 {
+            Some(self.bfar)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for FaultStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "CFSR = {:#010x}, HFSR = {:#010x}", self.cfsr, self.hfsr)?;
+        for reason in self.reasons() {
+            writeln!(f, "{}", reason)?;
+        }
+        match self.fault_address() {
+            Some(address) => write!(f, "Fault address: {:#010x}", address),
+            None => write!(f, "Fault address: unknown"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fault_reasons_and_address() {
+        let mut mem = |address: &u32, num_bytes: usize| {
+            let value: u32 = match *address {
+                CFSR_ADDRESS => (1 << 25) | BFARVALID,
+                HFSR_ADDRESS => 1 << 30,
+                MMFAR_ADDRESS => 0xE000_EDF8,
+                BFAR_ADDRESS => 0x2000_1000,
+                _ => return None,
+            };
+            Some(value.to_be_bytes()[..num_bytes].to_vec())
+        };
+        let status = FaultStatus::read(&mut mem, RunTimeEndian::Big).unwrap();
+        assert!(status.is_fault());
+        assert_eq!(
+            status.reasons(),
+            vec![
+                "FORCED: a configurable fault was escalated to a HardFault",
+                "DIVBYZERO: division by zero",
+            ]
+        );
+        // MMFAR is only used when MMARVALID is set.
+        assert_eq!(status.fault_address(), Some(0x2000_1000));
+
+        let no_fault = FaultStatus {
+            cfsr: 0,
+            hfsr: 0,
+            mmfar: 0,
+            bfar: 0,
+        };
+        assert!(!no_fault.is_fault());
+        assert_eq!(
+            no_fault.to_string(),
+            "CFSR = 0x00000000, HFSR = 0x00000000\nFault address: unknown"
+        );
+    }
+
+    #[test]
+    fn fault_registers_not_readable() {
+        let mut mem = |_: &u32, _: usize| None;
+        assert!(FaultStatus::read(&mut mem, RunTimeEndian::Little).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+/// The kind of a variable scope of a stack frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScopeKind {
+    /// The arguments of the function.
+    Arguments,
+
+    /// The local variables of the function.
+    Locals,
+
+    /// The registers of the frame.
+    Registers,
+
+    /// The variables with static storage duration, they are the same in every frame.
+    Globals,
+}
+
+/// The object a handle refers to, identified by its position in the stopped program instead of
+/// by its position in a response.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HandleKey {
+    /// A stack frame, by its index from the inner most frame.
+    Frame(usize),
+
+    /// A variable scope of a stack frame.
+    Scope(usize, ScopeKind),
+
+    /// A variable node, by the frame index, the scope and the path of the variable, e.g.
+    /// `point.x`.
+    Variable(usize, ScopeKind, String),
+}
+
+/// Allocates handles that are stable during a stop, e.g. the DAP frame ids and variable
+/// references.
+///
+/// The same object always gets the same handle until the handles are invalidated, which should
+/// be done when the debug target is resumed.
+/// The handles are never reused, so a handle from a earlier stop is never resolved to the wrong
+/// object.
+#[derive(Debug, Clone)]
+pub struct HandleAllocator {
+    /// The handles of the current stop, by object.
+    handles: HashMap<HandleKey, i64>,
+
+    /// The objects of the current stop, by handle.
+    keys: HashMap<i64, HandleKey>,
+
+    /// The next handle to allocate.
+    next: i64,
+}
+
+impl Default for HandleAllocator {
+    fn default() -> HandleAllocator {
+        HandleAllocator::new()
+    }
+}
+
+impl HandleAllocator {
+    /// Creates a new empty `HandleAllocator`.
+    pub fn new() -> HandleAllocator {
+        HandleAllocator {
+            handles: HashMap::new(),
+            keys: HashMap::new(),
+            next: 1,
+        }
+    }
+
+    /// Get the handle of a object, a new handle is allocated the first time it is requested in
+    /// the stop.
+    ///
+    /// Description:
+    ///
+    /// * `key` - The object.
+    pub fn handle(&mut self, key: HandleKey) -> i64 {
+        if let Some(handle) = self.handles.get(&key) {
+            return *handle;
+        }
+
+        let handle = self.next;
+        self.next += 1;
+        self.keys.insert(handle, key.clone());
+        self.handles.insert(key, handle);
+        handle
+    }
+
+    /// Get the object of a handle.
+    ///
+    /// Description:
+    ///
+    /// * `handle` - The handle.
+    ///
+    /// Will return `None` if the handle is unknown or from a earlier stop.
+    pub fn get(&self, handle: i64) -> Option<&HandleKey> {
+        self.keys.get(&handle)
+    }
+
+    /// Invalidate all the handles, this should be done every time the debug target is resumed.
+    pub fn invalidate(&mut self) {
+        self.handles.clear();
+        self.keys.clear();
+    }
+}
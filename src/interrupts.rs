@@ -0,0 +1,149 @@
+use crate::call_stack::MemoryAccess;
+use crate::memory_view::read_word;
+use anyhow::{anyhow, Result};
+use gimli::RunTimeEndian;
+use log::error;
+use std::collections::BTreeMap;
+
+/// The address of the Cortex-M Interrupt Control and State Register.
+pub const ICSR_ADDRESS: u32 = 0xE000_ED04;
+
+/// The mask of the `VECTACTIVE` field in the ICSR register.
+const VECTACTIVE_MASK: u32 = 0x1ff;
+
+/// Get the number of the active exception from the value of the ICSR register.
+///
+/// Description:
+///
+/// * `icsr` - The value of the ICSR register.
+///
+/// Will return `None` if the core is in thread mode.
+pub fn vect_active(icsr: u32) -> Option<u32> {
+    match icsr & VECTACTIVE_MASK {
+        0 => None,
+        exception => Some(exception),
+    }
+}
+
+/// Get the name of a Cortex-M exception.
+///
+/// Description:
+///
+/// * `exception` - The exception number, e.g. the value of `VECTACTIVE`.
+///
+/// Exception numbers from 16 and up are external interrupts, they are named `IRQ<n>` where `n`
+/// is the exception number minus 16.
+pub fn exception_name(exception: u32) -> String {
+    match exception {
+        1 => "Reset".to_string(),
+        2 => "NMI".to_string(),
+        3 => "HardFault".to_string(),
+        4 => "MemManage".to_string(),
+        5 => "BusFault".to_string(),
+        6 => "UsageFault".to_string(),
+        7 => "SecureFault".to_string(),
+        11 => "SVCall".to_string(),
+        12 => "DebugMonitor".to_string(),
+        14 => "PendSV".to_string(),
+        15 => "SysTick".to_string(),
+        n if n >= 16 => format!("IRQ{}", n - 16),
+        n => format!("Reserved{}", n),
+    }
+}
+
+/// How often a exception has been seen active.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InterruptRecord {
+    /// The exception number.
+    pub exception: u32,
+
+    /// The number of samples the exception was active in.
+    pub count: u64,
+
+    /// The timestamp of the last sample the exception was active in.
+    pub last_seen: u64,
+}
+
+/// Records which exceptions are active every time the debug target halts or is sampled.
+///
+/// This helps to find interrupt storms, e.g. a interrupt that is never cleared.
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHistory {
+    /// The records by exception number.
+    records: BTreeMap<u32, InterruptRecord>,
+
+    /// The number of samples, including the samples taken in thread mode.
+    pub samples: u64,
+}
+
+impl InterruptHistory {
+    /// Creates a new empty `InterruptHistory`.
+    pub fn new() -> InterruptHistory {
+        InterruptHistory {
+            records: BTreeMap::new(),
+            samples: 0,
+        }
+    }
+
+    /// Record a sample of the ICSR register.
+    ///
+    /// Description:
+    ///
+    /// * `icsr` - The value of the ICSR register.
+    /// * `timestamp` - The time of the sample, the unit is chosen by the caller.
+    pub fn record(&mut self, icsr: u32, timestamp: u64) {
+        self.samples += 1;
+        if let Some(exception) = vect_active(icsr) {
+            let record = self.records.entry(exception).or_insert(InterruptRecord {
+                exception,
+                count: 0,
+                last_seen: timestamp,
+            });
+            record.count += 1;
+            record.last_seen = timestamp;
+        }
+    }
+
+    /// Read the ICSR register from the debug target and record it.
+    ///
+    /// Description:
+    ///
+    /// * `mem` - A struct for accessing the memory of the debug target.
+    /// * `timestamp` - The time of the sample, the unit is chosen by the caller.
+    /// * `endian` - The endianness of the debug target.
+    ///
+    /// This should be done every time the debug target halts, and optionally during short
+    /// periodic halts while it is running.
+    pub fn sample<M: MemoryAccess>(
+        &mut self,
+        mem: &mut M,
+        timestamp: u64,
+        endian: RunTimeEndian,
+    ) -> Result<()> {
+        let icsr = match mem
+            .get_address(&ICSR_ADDRESS, 4)
+            .and_then(|val| read_word(&val, endian))
+        {
+            Some(val) => val,
+            None => {
+                error!("Could not read the ICSR register");
+                return Err(anyhow!("Could not read the ICSR register"));
+            }
+        };
+        self.record(icsr, timestamp);
+        Ok(())
+    }
+
+    /// Get the records sorted by how often the exceptions were active, the most frequent first.
+    pub fn records(&self) -> Vec<InterruptRecord> {
+        let mut records: Vec<InterruptRecord> = self.records.values().copied().collect();
+        records.sort_by_key(|r| std::cmp::Reverse(r.count));
+        records
+    }
+
+    /// Remove all the records.
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.samples = 0;
+    }
+}
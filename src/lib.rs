@@ -6,20 +6,130 @@
 //! - Easy to use
 //!
 
+/// Provides one function solutions for classifying what a address points to.
+pub mod address;
+
+/// Provides assertions that are checked every time the debug target halts.
+pub mod assertions;
+
+/// Provides rules for resuming the debug target on known benign halts.
+pub mod auto_continue;
+
+/// Provides functions for managing breakpoints in the limited hardware breakpoint units.
+pub mod breakpoints;
+
+/// Provides one function solutions for parsing call sites and recovering parameter values.
+pub mod call_site;
+
 /// Provides one function solutions for doing a stack trace
 pub mod call_stack;
 
+/// Provides guard regions that are verified every time the debug target halts.
+pub mod canary;
+
+/// Provides one function solutions for retrieving information about the compilation units.
+pub mod compilation_unit;
+
+/// Provides the STM32 debug MCU configuration for low-power modes and peripheral freezing.
+pub mod dbgmcu;
+
+/// Provides a high level facade that combines all the one function solutions.
+pub mod debug_session;
+
+/// Provides functions for dumping DIEs, attributes and locations in a readable form.
+pub mod die_dump;
+
+/// Provides functions for interleaving disassembled code with the source lines it belongs to.
+pub mod disassembly;
+
+/// Provides a checklist for localizing problems in the toolchain, probe and target path.
+pub mod doctor;
+
+/// Provides a index of the DWARF debug information for fast repeated lookups.
+pub mod dwarf_index;
+
 /// Provides one function solutions for handling evaluation the DWARF location attribute.
 pub mod evaluate;
 
+/// Provides decoding of the Cortex-M fault status registers.
+pub mod fault;
+
+/// Provides handles that are stable during a stop, for the frames, scopes and variables.
+pub mod handles;
+
+/// Provides a history of the Cortex-M exceptions that were active when the target halted.
+pub mod interrupts;
+
+/// Provides one function solutions for retrieving the macro information.
+pub mod macros;
+
+/// Provides a hex and ASCII view of the memory of the debug target.
+pub mod memory_view;
+
+/// Provides decoding of the Cortex-M0+ Micro Trace Buffer branch trace.
+pub mod mtb;
+
+/// Provides one function solutions for detecting panics and extracting the panic message.
+pub mod panic;
+
 /// Defines a struct containing information about the registers
 pub mod registers;
 
+/// Provides one function solutions for reporting the quality of the debug information.
+pub mod report;
+
+/// Provides one function solutions for retrieving the value a function returned.
+pub mod return_value;
+
+/// Provides functions for finding and describing compressed and missing DWARF sections.
+pub mod section;
+
+/// Provides saving and loading of the debug session state between runs.
+pub mod session_state;
+
 /// Provides one function solutions for retrieving the source location declaration information.
 pub mod source_information;
 
+/// Provides functions for handling POSIX and Windows source paths.
+pub mod source_path;
+
+/// Provides one function solutions for comparing static variables to their initializers.
+pub mod static_check;
+
+/// Provides filters for stepping through library code.
+pub mod step_filter;
+
+/// Provides source level step over and step into built from single instruction steps.
+pub mod stepping;
+
+/// Provides a ELF symbol table that is used when there is no DWARF debug information.
+pub mod symbol_table;
+
+/// Provides timing and access counting of the operations, used to guide performance work.
+#[cfg(feature = "telemetry")]
+pub mod telemetry;
+
+/// Provides test doubles for the debug target, used to test code without a debug target.
+#[cfg(feature = "test-support")]
+pub mod test_support;
+
+/// Provides cycle counter based timing measurements between halts.
+pub mod timing;
+
+/// Provides crash triage reports that bundle everything needed to understand a crash.
+pub mod triage;
+
 /// Provides some useful functions for reading the DWARF format.
 pub mod utils;
 
+/// Provides tracking of which variable values changed between stops.
+pub mod value_changes;
+
+/// Provides a history of evaluated values that can be referred to in later expressions.
+pub mod value_history;
+
 /// Provides one function solutions for retrieving information about a variable.
 pub mod variable;
+
+/// Provides watchpoints on memory, programmed in the DWT comparators of the debug target.
+pub mod watchpoints;
@@ -0,0 +1,349 @@
+use anyhow::{anyhow, Result};
+use gimli::{AttributeValue, Dwarf, Format, Reader, Unit};
+use log::error;
+
+// The `.debug_macinfo` entry types, they are not defined by gimli-rs.
+const DW_MACINFO_DEFINE: u8 = 0x01;
+const DW_MACINFO_UNDEF: u8 = 0x02;
+const DW_MACINFO_START_FILE: u8 = 0x03;
+const DW_MACINFO_END_FILE: u8 = 0x04;
+const DW_MACINFO_VENDOR_EXT: u8 = 0xff;
+
+/// Describes if a macro entry defines or undefines a macro.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MacroKind {
+    /// The macro is defined.
+    Define,
+
+    /// The macro is undefined.
+    Undefine,
+}
+
+/// A definition or undefinition of a macro.
+#[derive(Debug, Clone)]
+pub struct MacroEntry {
+    /// Describes if the macro is defined or undefined.
+    pub kind: MacroKind,
+
+    /// The name of the macro, including the parameter list for function like macros.
+    pub name: String,
+
+    /// The replacement text of the macro, `None` for undefinitions.
+    pub definition: Option<String>,
+
+    /// The file index in the line program of the file the macro is defined in.
+    /// `None` if it is defined before the first file is started, e.g. on the command line.
+    pub file: Option<u64>,
+
+    /// The source code line the macro is defined on, 0 if it is not defined in a file.
+    pub line: u64,
+}
+
+/// All the macro entries of a compilation unit.
+#[derive(Debug, Clone)]
+pub struct MacroInformation {
+    /// The macro entries in the order they are processed.
+    pub entries: Vec<MacroEntry>,
+}
+
+impl MacroInformation {
+    /// Retrieves the macro information of a compilation unit from the `.debug_macro` section.
+    ///
+    /// Description:
+    ///
+    /// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+    /// * `unit` - A reference to gimli-rs `Unit` struct.
+    /// * `debug_macro` - The raw data of the `.debug_macro` section.
+    ///
+    /// The macro information is found using the `DW_AT_macros` or `DW_AT_GNU_macros` attribute
+    /// of the compilation unit DIE.
+    /// Will return `None` if the compilation unit has no macro information.
+    pub fn from_debug_macro<R: Reader<Offset = usize>>(
+        dwarf: &Dwarf<R>,
+        unit: &Unit<R>,
+        debug_macro: R,
+    ) -> Result<Option<MacroInformation>> {
+        let mut entries = unit.entries();
+        let root = match entries.next_dfs()? {
+            Some((_, die)) => die,
+            None => return Ok(None),
+        };
+
+        let offset = match root.attr_value(gimli::DW_AT_macros)? {
+            Some(AttributeValue::DebugMacroRef(offset)) => offset.0,
+            _ => match root.attr_value(gimli::DW_AT_GNU_macros)? {
+                Some(AttributeValue::SecOffset(offset)) => offset,
+                Some(value) => match value.udata_value() {
+                    Some(offset) => offset as usize,
+                    None => {
+                        error!("Unimplemented for {:?}", value);
+                        return Err(anyhow!("Unimplemented for {:?}", value));
+                    }
+                },
+                None => return Ok(None),
+            },
+        };
+
+        let mut result = vec![];
+        let mut file_stack = vec![];
+        parse_debug_macro(
+            dwarf,
+            unit,
+            &debug_macro,
+            offset,
+            &mut file_stack,
+            &mut result,
+        )?;
+        Ok(Some(MacroInformation { entries: result }))
+    }
+
+    /// Retrieves the macro information of a compilation unit from the `.debug_macinfo` section.
+    ///
+    /// Description:
+    ///
+    /// * `unit` - A reference to gimli-rs `Unit` struct.
+    /// * `debug_macinfo` - The raw data of the `.debug_macinfo` section.
+    ///
+    /// The macro information is found using the `DW_AT_macro_info` attribute of the compilation
+    /// unit DIE, this is the format used before DWARF 5.
+    /// Will return `None` if the compilation unit has no macro information.
+    pub fn from_debug_macinfo<R: Reader<Offset = usize>>(
+        unit: &Unit<R>,
+        debug_macinfo: R,
+    ) -> Result<Option<MacroInformation>> {
+        let mut entries = unit.entries();
+        let root = match entries.next_dfs()? {
+            Some((_, die)) => die,
+            None => return Ok(None),
+        };
+
+        let offset = match root.attr_value(gimli::DW_AT_macro_info)? {
+            Some(AttributeValue::DebugMacinfoRef(offset)) => offset.0,
+            _ => return Ok(None),
+        };
+
+        let mut reader = debug_macinfo;
+        reader.skip(offset)?;
+
+        let mut result = vec![];
+        let mut file_stack = vec![];
+        loop {
+            match reader.read_u8()? {
+                0 => break,
+                kind @ (DW_MACINFO_DEFINE | DW_MACINFO_UNDEF) => {
+                    let line = reader.read_uleb128()?;
+                    let text = reader
+                        .read_null_terminated_slice()?
+                        .to_string()?
+                        .to_string();
+                    let kind = match kind {
+                        DW_MACINFO_DEFINE => MacroKind::Define,
+                        _ => MacroKind::Undefine,
+                    };
+                    result.push(new_entry(kind, &text, file_stack.last().copied(), line));
+                }
+                DW_MACINFO_START_FILE => {
+                    let _line = reader.read_uleb128()?;
+                    file_stack.push(reader.read_uleb128()?);
+                }
+                DW_MACINFO_END_FILE => {
+                    file_stack.pop();
+                }
+                DW_MACINFO_VENDOR_EXT => {
+                    let _constant = reader.read_uleb128()?;
+                    let _text = reader.read_null_terminated_slice()?;
+                }
+                unknown => {
+                    error!("Unknown macinfo type {}", unknown);
+                    return Err(anyhow!("Unknown macinfo type {}", unknown));
+                }
+            };
+        }
+
+        Ok(Some(MacroInformation { entries: result }))
+    }
+
+    /// Find all the macros that are defined at a source code location.
+    ///
+    /// Description:
+    ///
+    /// * `file` - The file index in the line program.
+    /// * `line` - A line number in the file.
+    ///
+    /// The entries are processed in order until the first entry in `file` after `line`.
+    pub fn macros_in_scope(&self, file: u64, line: u64) -> Vec<&MacroEntry> {
+        let mut scope: Vec<&MacroEntry> = vec![];
+        for entry in &self.entries {
+            if entry.file == Some(file) && entry.line > line {
+                break;
+            }
+
+            scope.retain(|e| macro_name(&e.name) != macro_name(&entry.name));
+            if entry.kind == MacroKind::Define {
+                scope.push(entry);
+            }
+        }
+
+        scope
+    }
+
+    /// Find the definition of a macro at a source code location.
+    ///
+    /// Description:
+    ///
+    /// * `name` - The name of the macro, without the parameter list.
+    /// * `file` - The file index in the line program.
+    /// * `line` - A line number in the file.
+    ///
+    /// This is used to find the definition site of a macro that is expanded at the given
+    /// location.
+    pub fn find_definition(&self, name: &str, file: u64, line: u64) -> Option<&MacroEntry> {
+        self.macros_in_scope(file, line)
+            .into_iter()
+            .find(|e| macro_name(&e.name) == name)
+    }
+}
+
+/// Parse a macro unit in the `.debug_macro` section.
+fn parse_debug_macro<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    unit: &Unit<R>,
+    debug_macro: &R,
+    offset: usize,
+    file_stack: &mut Vec<u64>,
+    result: &mut Vec<MacroEntry>,
+) -> Result<()> {
+    let mut reader = debug_macro.clone();
+    reader.skip(offset)?;
+
+    // Parse the header.
+    let version = reader.read_u16()?;
+    if version != 4 && version != 5 {
+        error!("Unsupported .debug_macro version {}", version);
+        return Err(anyhow!("Unsupported .debug_macro version {}", version));
+    }
+    let flags = reader.read_u8()?;
+    let format = match flags & 0x1 {
+        0 => Format::Dwarf32,
+        _ => Format::Dwarf64,
+    };
+    if flags & 0x2 != 0 {
+        let _debug_line_offset = reader.read_offset(format)?;
+    }
+    if flags & 0x4 != 0 {
+        error!("Unimplemented .debug_macro opcode operands table");
+        return Err(anyhow!("Unimplemented .debug_macro opcode operands table"));
+    }
+
+    loop {
+        let opcode = gimli::DwMacro(reader.read_u8()?);
+        match opcode {
+            gimli::DwMacro(0) => break,
+            gimli::DW_MACRO_define | gimli::DW_MACRO_undef => {
+                let line = reader.read_uleb128()?;
+                let text = reader
+                    .read_null_terminated_slice()?
+                    .to_string()?
+                    .to_string();
+                result.push(new_entry(
+                    macro_kind(opcode),
+                    &text,
+                    file_stack.last().copied(),
+                    line,
+                ));
+            }
+            gimli::DW_MACRO_define_strp | gimli::DW_MACRO_undef_strp => {
+                let line = reader.read_uleb128()?;
+                let offset = gimli::DebugStrOffset(reader.read_offset(format)?);
+                let text = dwarf.string(offset)?.to_string()?.to_string();
+                result.push(new_entry(
+                    macro_kind(opcode),
+                    &text,
+                    file_stack.last().copied(),
+                    line,
+                ));
+            }
+            gimli::DW_MACRO_define_strx | gimli::DW_MACRO_undef_strx => {
+                let line = reader.read_uleb128()?;
+                let index = gimli::DebugStrOffsetsIndex(reader.read_uleb128()? as usize);
+                let offset = dwarf.string_offset(unit, index)?;
+                let text = dwarf.string(offset)?.to_string()?.to_string();
+                result.push(new_entry(
+                    macro_kind(opcode),
+                    &text,
+                    file_stack.last().copied(),
+                    line,
+                ));
+            }
+            gimli::DW_MACRO_start_file => {
+                let _line = reader.read_uleb128()?;
+                file_stack.push(reader.read_uleb128()?);
+            }
+            gimli::DW_MACRO_end_file => {
+                file_stack.pop();
+            }
+            gimli::DW_MACRO_import => {
+                let offset = reader.read_offset(format)?;
+                parse_debug_macro(dwarf, unit, debug_macro, offset, file_stack, result)?;
+            }
+            unknown => {
+                error!("Unimplemented for macro opcode {:?}", unknown);
+                return Err(anyhow!("Unimplemented for macro opcode {:?}", unknown));
+            }
+        };
+    }
+
+    Ok(())
+}
+
+/// Get the macro kind of a define or undefine opcode.
+fn macro_kind(opcode: gimli::DwMacro) -> MacroKind {
+    match opcode {
+        gimli::DW_MACRO_define | gimli::DW_MACRO_define_strp | gimli::DW_MACRO_define_strx => {
+            MacroKind::Define
+        }
+        _ => MacroKind::Undefine,
+    }
+}
+
+/// Create a macro entry by splitting the macro text into the name and the definition.
+fn new_entry(kind: MacroKind, text: &str, file: Option<u64>, line: u64) -> MacroEntry {
+    // The name ends at the first space that is not inside the parameter list.
+    let mut depth = 0;
+    let mut split = None;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ' ' if depth == 0 => {
+                split = Some(i);
+                break;
+            }
+            _ => (),
+        };
+    }
+
+    let (name, definition) = match split {
+        Some(i) => (text[..i].to_string(), Some(text[i + 1..].to_string())),
+        None => (text.to_string(), None),
+    };
+
+    MacroEntry {
+        kind,
+        name,
+        definition: match kind {
+            MacroKind::Define => Some(definition.unwrap_or_default()),
+            MacroKind::Undefine => None,
+        },
+        file,
+        line,
+    }
+}
+
+/// Get the name of a macro without the parameter list.
+fn macro_name(name: &str) -> &str {
+    match name.find('(') {
+        Some(i) => &name[..i],
+        None => name,
+    }
+}
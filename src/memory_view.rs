@@ -0,0 +1,219 @@
+use crate::symbol_table::{SymbolKind, SymbolTable};
+use gimli::{Dwarf, Endianity, Reader, RunTimeEndian, Section};
+
+/// The default number of bytes in a row.
+pub const DEFAULT_ROW_LENGTH: usize = 16;
+
+/// A row of a hex and ASCII view of the memory of the debug target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryRow {
+    /// The address of the first byte in the row.
+    pub address: u32,
+
+    /// The bytes in the row, in address order.
+    pub bytes: Vec<u8>,
+
+    /// The bytes as ASCII, the bytes that are not printable are written as `.`.
+    pub ascii: String,
+
+    /// The symbols that start in the row, on the form `<name>` or `<name>+<offset>` for a symbol
+    /// that starts before the row.
+    pub symbols: Vec<String>,
+}
+
+/// A word of the memory of the debug target, e.g. a stack slot.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryWord {
+    /// The address of the word.
+    pub address: u32,
+
+    /// The value of the word, decoded with the endianness of the debug target.
+    pub value: u64,
+
+    /// The number of bytes in the word.
+    pub size: usize,
+}
+
+impl std::fmt::Display for MemoryWord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{:#010x}: {:#0width$x}",
+            self.address,
+            self.value,
+            width = self.size * 2 + 2
+        )
+    }
+}
+
+/// A hex and ASCII view of a memory region of the debug target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryView {
+    /// The address of the first byte.
+    pub address: u32,
+
+    /// The rows of the view.
+    pub rows: Vec<MemoryRow>,
+}
+
+impl MemoryView {
+    /// Creates a new `MemoryView`.
+    ///
+    /// Description:
+    ///
+    /// * `address` - The address of the first byte.
+    /// * `data` - The bytes read from the memory, in address order.
+    /// * `row_length` - The number of bytes in a row, e.g. `DEFAULT_ROW_LENGTH`.
+    /// * `symbols` - A symbol table used to annotate the rows, `None` for no annotations.
+    pub fn new(
+        address: u32,
+        data: &[u8],
+        row_length: usize,
+        symbols: Option<&SymbolTable>,
+    ) -> MemoryView {
+        let rows = data
+            .chunks(row_length.max(1))
+            .enumerate()
+            .map(|(i, bytes)| {
+                let row_address = address.wrapping_add((i * row_length.max(1)) as u32);
+                MemoryRow {
+                    address: row_address,
+                    bytes: bytes.to_vec(),
+                    ascii: bytes
+                        .iter()
+                        .map(|b| match b.is_ascii_graphic() || *b == b' ' {
+                            true => *b as char,
+                            false => '.',
+                        })
+                        .collect(),
+                    symbols: match symbols {
+                        Some(table) => row_symbols(table, row_address, bytes.len(), i == 0),
+                        None => vec![],
+                    },
+                }
+            })
+            .collect();
+
+        MemoryView { address, rows }
+    }
+
+    /// Decode the bytes of the view as words, e.g. for a stack dump.
+    ///
+    /// Description:
+    ///
+    /// * `word_size` - The number of bytes in a word, 1, 2, 4 or 8.
+    /// * `endian` - The endianness of the debug target.
+    ///
+    /// The bytes at the end that do not fill a whole word are skipped.
+    pub fn words(&self, word_size: usize, endian: RunTimeEndian) -> Vec<MemoryWord> {
+        read_words(self.address, &self.bytes(), word_size, endian)
+    }
+
+    /// Get all the bytes of the view, in address order.
+    pub fn bytes(&self) -> Vec<u8> {
+        self.rows.iter().flat_map(|r| r.bytes.clone()).collect()
+    }
+}
+
+impl std::fmt::Display for MemoryView {
+    /// Every row is written as the address, the bytes in hex in address order, the ASCII
+    /// characters and the symbol annotations.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let row_length = self.rows.first().map(|r| r.bytes.len()).unwrap_or(0);
+        for row in &self.rows {
+            let hex: Vec<String> = row.bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            write!(
+                f,
+                "{:#010x}: {:<width$}  |{}|",
+                row.address,
+                hex.join(" "),
+                row.ascii,
+                width = (row_length * 3).saturating_sub(1)
+            )?;
+            if !row.symbols.is_empty() {
+                write!(f, " <{}>", row.symbols.join(", "))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Get the annotations of the symbols in a row.
+///
+/// The first row is also annotated with the symbol that contains its address.
+fn row_symbols(table: &SymbolTable, address: u32, length: usize, first: bool) -> Vec<String> {
+    let start = address as u64;
+    let end = start + length as u64;
+    let mut result = vec![];
+    for symbol in table.symbols.iter().filter(|s| s.kind != SymbolKind::Other) {
+        if symbol.address >= start && symbol.address < end {
+            result.push(symbol.name.clone());
+        } else if first && symbol.address < start && start < symbol.address + symbol.size {
+            result.push(format!("{}+{:#x}", symbol.name, start - symbol.address));
+        }
+    }
+    result
+}
+
+/// Decode bytes read from the memory of the debug target as words.
+///
+/// Description:
+///
+/// * `address` - The address of the first byte.
+/// * `data` - The bytes read from the memory, in address order.
+/// * `word_size` - The number of bytes in a word, 1, 2, 4 or 8.
+/// * `endian` - The endianness of the debug target.
+///
+/// The bytes at the end that do not fill a whole word are skipped.
+pub fn read_words(
+    address: u32,
+    data: &[u8],
+    word_size: usize,
+    endian: RunTimeEndian,
+) -> Vec<MemoryWord> {
+    let word_size = word_size.clamp(1, 8);
+    data.chunks_exact(word_size)
+        .enumerate()
+        .map(|(i, bytes)| {
+            let mut value = 0u64;
+            for byte in bytes {
+                value = (value << 8) | *byte as u64;
+            }
+            if endian.is_little_endian() {
+                value = value.swap_bytes() >> (64 - 8 * word_size);
+            }
+            MemoryWord {
+                address: address.wrapping_add((i * word_size) as u32),
+                value,
+                size: word_size,
+            }
+        })
+        .collect()
+}
+
+/// Decode the first 32 bit word of bytes read from the memory of the debug target.
+///
+/// Description:
+///
+/// * `data` - The bytes read from the memory, in address order.
+/// * `endian` - The endianness of the debug target.
+///
+/// Will return `None` if there are less than 4 bytes.
+pub fn read_word(data: &[u8], endian: RunTimeEndian) -> Option<u32> {
+    read_words(0, data.get(..4)?, 4, endian)
+        .first()
+        .map(|word| word.value as u32)
+}
+
+/// Get the endianness of the debug target from its debug information.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+pub fn target_endian<R: Reader>(dwarf: &Dwarf<R>) -> RunTimeEndian {
+    match dwarf.debug_info.reader().endian().is_little_endian() {
+        true => RunTimeEndian::Little,
+        false => RunTimeEndian::Big,
+    }
+}
@@ -0,0 +1,219 @@
+use crate::dbgmcu::RegisterWrite;
+use crate::memory_view::read_words;
+use crate::source_information::find_line_row;
+use crate::symbol_table::SymbolTable;
+use anyhow::{anyhow, Result};
+use gimli::{Dwarf, Reader, RunTimeEndian};
+use log::error;
+
+/// The offset of the `MTB_POSITION` register from the MTB base address.
+pub const MTB_POSITION_OFFSET: u32 = 0x0;
+
+/// The offset of the `MTB_MASTER` register from the MTB base address.
+pub const MTB_MASTER_OFFSET: u32 = 0x4;
+
+/// The offset of the `MTB_FLOW` register from the MTB base address.
+pub const MTB_FLOW_OFFSET: u32 = 0x8;
+
+/// The offset of the `MTB_BASE` register from the MTB base address, it holds the address of the
+/// trace buffer in RAM.
+pub const MTB_BASE_OFFSET: u32 = 0xC;
+
+/// The `EN` bit in `MTB_MASTER`, it enables the tracing.
+pub const MTB_MASTER_EN: u32 = 1 << 31;
+
+/// The `WRAP` bit in `MTB_POSITION`, it is set when the trace buffer has wrapped around.
+pub const MTB_POSITION_WRAP: u32 = 1 << 2;
+
+/// A branch recorded by the Micro Trace Buffer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MtbBranch {
+    /// The address of the branch instruction.
+    pub source: u32,
+
+    /// The address the branch went to.
+    pub destination: u32,
+
+    /// `true` if this is the first branch after the tracing was started.
+    pub start: bool,
+}
+
+/// A branch with the functions and source lines of its addresses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    /// The recorded branch.
+    pub branch: MtbBranch,
+
+    /// The location of the branch instruction, e.g. `main+0x12 src/main.rs:42`.
+    pub source: String,
+
+    /// The location the branch went to.
+    pub destination: String,
+}
+
+/// Get the register writes that start the Micro Trace Buffer, this should be done before the
+/// debug target is resumed.
+///
+/// Description:
+///
+/// * `mtb_base` - The address of the MTB registers, it is chip specific.
+/// * `buffer_size` - The size of the trace buffer in bytes, a power of two that is at least 16.
+///
+/// The trace buffer is at the address in `MTB_BASE`, which is set by the chip.
+/// Will return the writes in the order they should be done.
+pub fn enable_mtb(mtb_base: u32, buffer_size: u32) -> Result<Vec<RegisterWrite>> {
+    if !buffer_size.is_power_of_two() || buffer_size < 16 {
+        error!("Invalid MTB buffer size: {}", buffer_size);
+        return Err(anyhow!("Invalid MTB buffer size: {}", buffer_size));
+    }
+
+    // The buffer size is 2^(MASK + 4) bytes.
+    let mask = buffer_size.trailing_zeros() - 4;
+    Ok(vec![
+        RegisterWrite {
+            address: mtb_base + MTB_POSITION_OFFSET,
+            mask: u32::MAX,
+            value: 0,
+        },
+        RegisterWrite {
+            address: mtb_base + MTB_FLOW_OFFSET,
+            mask: u32::MAX,
+            value: 0,
+        },
+        RegisterWrite {
+            address: mtb_base + MTB_MASTER_OFFSET,
+            mask: MTB_MASTER_EN | 0x1f,
+            value: MTB_MASTER_EN | mask,
+        },
+    ])
+}
+
+/// Decode the packed branch trace in the Micro Trace Buffer.
+///
+/// Description:
+///
+/// * `buffer` - The bytes of the trace buffer.
+/// * `position` - The value of the `MTB_POSITION` register.
+/// * `endian` - The endianness of the debug target.
+///
+/// Every branch is two words, the source address with the `A` bit and the
+/// destination address with the `S` bit in bit 0.
+/// Will return the branches with the oldest first.
+pub fn decode_mtb(buffer: &[u8], position: u32, endian: RunTimeEndian) -> Vec<MtbBranch> {
+    let entries: Vec<MtbBranch> = buffer
+        .chunks_exact(8)
+        .map(|entry| {
+            let words = read_words(0, entry, 4, endian);
+            let (source, destination) = (words[0].value as u32, words[1].value as u32);
+            MtbBranch {
+                source: source & !1,
+                destination: destination & !1,
+                start: destination & 1 == 1,
+            }
+        })
+        .collect();
+
+    // The pointer is the byte offset of the next entry that will be written.
+    let next = ((position & !0x7) as usize / 8).min(entries.len());
+    match position & MTB_POSITION_WRAP != 0 {
+        true => entries[next..]
+            .iter()
+            .chain(entries[..next].iter())
+            .copied()
+            .collect(),
+        false => entries[..next].to_vec(),
+    }
+}
+
+/// Symbolize the decoded branches.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `symbols` - The symbol table used to find the functions.
+/// * `branches` - The decoded branches.
+/// * `cwd` - The work directory of the debugged program.
+///
+/// The addresses are written as the function and offset followed by the source line, when they
+/// are known.
+pub fn symbolize_trace<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    symbols: &SymbolTable,
+    branches: &[MtbBranch],
+    cwd: &str,
+) -> Vec<TraceEntry> {
+    let location = |address: u32| {
+        let mut result = match symbols.find_function(address as u64) {
+            Some((symbol, 0)) => symbol.name.clone(),
+            Some((symbol, offset)) => format!("{}+{:#x}", symbol.name, offset),
+            None => format!("{:#010x}", address),
+        };
+        if let Ok(Some(row)) = find_line_row(dwarf, cwd, address as u64) {
+            if let (Some(file), Some(line)) = (row.file, row.line) {
+                result = format!("{} {}:{}", result, file, line);
+            }
+        }
+        result
+    };
+
+    branches
+        .iter()
+        .map(|branch| TraceEntry {
+            branch: *branch,
+            source: location(branch.source),
+            destination: location(branch.destination),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create a trace buffer entry in little endian byte order.
+    fn entry(source: u32, destination: u32) -> Vec<u8> {
+        let mut bytes = source.to_le_bytes().to_vec();
+        bytes.extend(destination.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn enable_writes() {
+        let writes = enable_mtb(0x4100_6000, 1024).unwrap();
+        assert_eq!(writes.len(), 3);
+        assert_eq!(writes[2].address, 0x4100_6004);
+        assert_eq!(writes[2].value, MTB_MASTER_EN | 6);
+        assert!(enable_mtb(0x4100_6000, 24).is_err());
+        assert!(enable_mtb(0x4100_6000, 8).is_err());
+    }
+
+    #[test]
+    fn decode_wrapped_buffer() {
+        let mut buffer = entry(0x100, 0x201);
+        buffer.extend(entry(0x301, 0x400));
+        buffer.extend(entry(0x500, 0x600));
+
+        // Without wrapping only the entries before the pointer are written.
+        let branches = decode_mtb(&buffer, 16, RunTimeEndian::Little);
+        assert_eq!(
+            branches,
+            vec![
+                MtbBranch {
+                    source: 0x100,
+                    destination: 0x200,
+                    start: true,
+                },
+                MtbBranch {
+                    source: 0x300,
+                    destination: 0x400,
+                    start: false,
+                },
+            ]
+        );
+
+        // After wrapping the entry at the pointer is the oldest.
+        let branches = decode_mtb(&buffer, 8 | MTB_POSITION_WRAP, RunTimeEndian::Little);
+        let sources: Vec<u32> = branches.iter().map(|b| b.source).collect();
+        assert_eq!(sources, vec![0x300, 0x500, 0x100]);
+    }
+}
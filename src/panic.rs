@@ -0,0 +1,274 @@
+use crate::call_stack::{MemoryAccess, StackFrame};
+use crate::evaluate::evaluate::{get_udata, BaseTypeValue, EvaluatorValue};
+use crate::memory_view::read_words;
+use anyhow::{anyhow, Result};
+use gimli::{Reader, RunTimeEndian};
+use log::error;
+use std::fmt;
+
+/// The names of the functions that are part of the panic machinery.
+const PANIC_FUNCTIONS: [&str; 12] = [
+    "rust_begin_unwind",
+    "panic_fmt",
+    "panic",
+    "panic_str",
+    "panic_display",
+    "panic_bounds_check",
+    "panic_nounwind",
+    "begin_panic",
+    "begin_panic_handler",
+    "unwrap_failed",
+    "expect_failed",
+    "assert_failed",
+];
+
+/// The maximum depth that is searched for a member in a evaluated value.
+const MAX_SEARCH_DEPTH: usize = 8;
+
+/// The maximum number of bytes read for a panic message piece or file name.
+const MAX_STR_LENGTH: u64 = 1024;
+
+/// The maximum number of panic message pieces that are read.
+const MAX_PIECES: u64 = 64;
+
+/// The panic message and location extracted from a halted panic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PanicReport {
+    /// The panic message, the formatted arguments are written as `{..}`.
+    pub message: Option<String>,
+
+    /// The source file the panic happened in.
+    pub file: Option<String>,
+
+    /// The source line the panic happened on.
+    pub line: Option<u64>,
+
+    /// The source column the panic happened on.
+    pub column: Option<u64>,
+
+    /// The index of the first stack frame that is not part of the panic machinery.
+    pub user_frame: Option<usize>,
+}
+
+impl fmt::Display for PanicReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "panicked at '{}'",
+            self.message.as_deref().unwrap_or("<unknown>")
+        )?;
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => write!(f, ", {}:{}", file, line),
+            (Some(file), None) => write!(f, ", {}", file),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Will return `true` if a function is part of the panic machinery.
+///
+/// Description:
+///
+/// * `name` - The name of the function.
+pub fn is_panic_function(name: &str) -> bool {
+    PANIC_FUNCTIONS.contains(&name) || name.contains("panicking::")
+}
+
+/// Will detect if the debug target is halted in a panic and extract the panic message and
+/// location.
+///
+/// Description:
+///
+/// * `stack_frames` - The evaluated stack frames, the inner most frame first.
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `endian` - The endianness of the debug target.
+///
+/// Will return `None` if the inner most frames are not part of the panic machinery.
+/// The message and location are read from the `PanicInfo` argument of the panic handler, they
+/// are `None` if the argument is optimized out.
+pub fn panic_report<R: Reader<Offset = usize>, M: MemoryAccess>(
+    stack_frames: &[StackFrame<R>],
+    mem: &mut M,
+    endian: RunTimeEndian,
+) -> Result<Option<PanicReport>> {
+    let panic_frames = stack_frames
+        .iter()
+        .take_while(|frame| is_panic_function(&frame.name))
+        .count();
+    if panic_frames == 0 {
+        return Ok(None);
+    }
+
+    let mut report = PanicReport {
+        message: None,
+        file: None,
+        line: None,
+        column: None,
+        user_frame: match panic_frames < stack_frames.len() {
+            true => Some(panic_frames),
+            false => None,
+        },
+    };
+
+    // The panic handler is called with a reference to the `PanicInfo`.
+    let info = stack_frames[..panic_frames]
+        .iter()
+        .flat_map(|frame| frame.arguments.iter())
+        .find(|arg| arg.value.get_type().contains("PanicInfo"))
+        .map(|arg| &arg.value);
+    let info = match info {
+        Some(val) => val,
+        None => return Ok(Some(report)),
+    };
+
+    if let Some(location) = find_member(info, "location", 0) {
+        if let Some(file) = find_member(location, "file", 0) {
+            report.file = read_str(file, mem).ok();
+        }
+        report.line = find_member(location, "line", 0).and_then(udata);
+        report.column = find_member(location, "col", 0).and_then(udata);
+    }
+
+    if let Some(pieces) = find_member(info, "pieces", 0) {
+        let has_args = matches!(
+            find_member(info, "args", 0).and_then(|args| find_member(args, "length", 0)).and_then(udata),
+            Some(len) if len > 0
+        );
+        report.message = read_pieces(pieces, has_args, mem, endian).ok();
+    }
+
+    Ok(Some(report))
+}
+
+/// Find a member by name in a evaluated value, the search follows pointers.
+fn find_member<'a, R: Reader<Offset = usize>>(
+    value: &'a EvaluatorValue<R>,
+    name: &str,
+    depth: usize,
+) -> Option<&'a EvaluatorValue<R>> {
+    if depth > MAX_SEARCH_DEPTH {
+        return None;
+    }
+
+    match value {
+        EvaluatorValue::Member(mem) => match mem.name.as_deref() == Some(name) {
+            true => Some(&mem.value),
+            false => find_member(&mem.value, name, depth + 1),
+        },
+        EvaluatorValue::PointerTypeValue(pt) => find_member(&pt.value, name, depth + 1),
+        EvaluatorValue::Struct(stu) => stu
+            .members
+            .iter()
+            .find_map(|m| find_member(m, name, depth + 1)),
+        EvaluatorValue::Enum(enu) => find_member(&enu.variant, name, depth + 1),
+        EvaluatorValue::VariantPartValue(vpa) => vpa
+            .variant
+            .iter()
+            .chain(vpa.variants.iter().map(|v| &v.child))
+            .find_map(|m| match m.name.as_deref() == Some(name) {
+                true => Some(&m.value),
+                false => find_member(&m.value, name, depth + 1),
+            }),
+        _ => None,
+    }
+}
+
+/// Get the unsigned integer value of a evaluated value.
+fn udata<R: Reader<Offset = usize>>(value: &EvaluatorValue<R>) -> Option<u64> {
+    get_udata(value.clone().to_value()?).ok()
+}
+
+/// Get the address stored in a evaluated pointer.
+fn pointer_address<R: Reader<Offset = usize>>(value: &EvaluatorValue<R>) -> Option<u32> {
+    match value {
+        EvaluatorValue::PointerTypeValue(pt) => match pt.address {
+            EvaluatorValue::Value(BaseTypeValue::Address32(address), _) => Some(address),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Read the string a evaluated `&str` points to.
+fn read_str<R: Reader<Offset = usize>, M: MemoryAccess>(
+    value: &EvaluatorValue<R>,
+    mem: &mut M,
+) -> Result<String> {
+    let address = find_member(value, "data_ptr", 0).and_then(pointer_address);
+    let length = find_member(value, "length", 0).and_then(udata);
+    match (address, length) {
+        (Some(address), Some(length)) => read_string(mem, address, length),
+        _ => {
+            error!("Expected a &str value");
+            Err(anyhow!("Expected a &str value"))
+        }
+    }
+}
+
+/// Read the string pieces of a evaluated `&[&str]`, the pieces are joined with `{..}` if there
+/// are formatted arguments.
+/// At most `MAX_PIECES` pieces are read, the length is read from the debug target and can be
+/// garbage.
+fn read_pieces<R: Reader<Offset = usize>, M: MemoryAccess>(
+    value: &EvaluatorValue<R>,
+    has_args: bool,
+    mem: &mut M,
+    endian: RunTimeEndian,
+) -> Result<String> {
+    let address = find_member(value, "data_ptr", 0).and_then(pointer_address);
+    let length = find_member(value, "length", 0).and_then(udata);
+    let (address, length) = match (address, length) {
+        (Some(address), Some(length)) => (address, length),
+        _ => {
+            error!("Expected a &[&str] value");
+            return Err(anyhow!("Expected a &[&str] value"));
+        }
+    };
+
+    // Every `&str` is a pointer and a length of 4 bytes each.
+    let size = match length.min(MAX_PIECES).checked_mul(8) {
+        Some(val) => val as usize,
+        None => {
+            error!("Invalid number of panic message pieces: {}", length);
+            return Err(anyhow!(
+                "Invalid number of panic message pieces: {}",
+                length
+            ));
+        }
+    };
+    let data = match mem.get_address(&address, size) {
+        Some(val) => val,
+        None => {
+            error!("Could not read the panic message pieces");
+            return Err(anyhow!("Could not read the panic message pieces"));
+        }
+    };
+    let mut pieces = vec![];
+    for piece in data.chunks_exact(8) {
+        let words = read_words(0, piece, 4, endian);
+        let (address, length) = (words[0].value as u32, words[1].value);
+        pieces.push(read_string(mem, address, length)?);
+    }
+
+    let separator = match has_args {
+        true => "{..}",
+        false => "",
+    };
+    Ok(pieces.join(separator))
+}
+
+/// Read a UTF-8 string from the memory of the debug target.
+fn read_string<M: MemoryAccess>(mem: &mut M, address: u32, length: u64) -> Result<String> {
+    let length = length.min(MAX_STR_LENGTH) as usize;
+    match mem.get_address(&address, length) {
+        Some(bytes) => Ok(String::from_utf8_lossy(&bytes).to_string()),
+        None => {
+            error!("Could not read {} bytes at address {:#x}", length, address);
+            Err(anyhow!(
+                "Could not read {} bytes at address {:#x}",
+                length,
+                address
+            ))
+        }
+    }
+}
@@ -1,5 +1,175 @@
+use anyhow::{anyhow, Result};
+use log::error;
 use std::collections::HashMap;
 
+/// The architecture of the debug target, it decides the register numbers, the breakpoint
+/// instruction and how return addresses are encoded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Architecture {
+    /// A Arm Cortex-M core running Thumb code.
+    CortexM,
+
+    /// A 32 bit RISC-V core, the DWARF register numbers `0` to `31` are `x0` to `x31`.
+    RiscV32 {
+        /// `true` if the core implements the compressed instruction extension, e.g. read from
+        /// the `misa` register.
+        compressed: bool,
+    },
+}
+
+impl Architecture {
+    /// Get the register number of the program counter.
+    ///
+    /// RISC-V has no DWARF register number for the program counter, so the first number after
+    /// the integer registers is used, the same as probe-rs.
+    pub fn program_counter_register(&self) -> usize {
+        match self {
+            Architecture::CortexM => 15,
+            Architecture::RiscV32 { .. } => 32,
+        }
+    }
+
+    /// Get the register number of the link register, `ra` on RISC-V.
+    pub fn link_register(&self) -> usize {
+        match self {
+            Architecture::CortexM => 14,
+            Architecture::RiscV32 { .. } => 1,
+        }
+    }
+
+    /// Get the register number of the stack pointer register.
+    pub fn stack_pointer_register(&self) -> usize {
+        match self {
+            Architecture::CortexM => 13,
+            Architecture::RiscV32 { .. } => 2,
+        }
+    }
+
+    /// Get the number of core registers, they have the register numbers `0` up to the count.
+    ///
+    /// These are `r0` to `r15` on Cortex-M, and `x0` to `x31` and the program counter on RISC-V.
+    pub fn register_count(&self) -> usize {
+        match self {
+            Architecture::CortexM => 16,
+            Architecture::RiscV32 { .. } => 33,
+        }
+    }
+
+    /// Get the little endian bytes of the breakpoint instruction that replaces a instruction.
+    ///
+    /// Description:
+    ///
+    /// * `instruction` - The little endian bytes at the instruction address.
+    ///
+    /// It is `bkpt` on Cortex-M, on RISC-V it is `c.ebreak` if the replaced instruction is a
+    /// compressed instruction and the 4 byte `ebreak` otherwise, so that a breakpoint never
+    /// overwrites the next instruction.
+    /// The lowest two bits of a compressed RISC-V instruction are not `0b11`.
+    pub fn breakpoint_instruction(&self, instruction: &[u8]) -> &'static [u8] {
+        match self {
+            Architecture::CortexM => &[0x00, 0xbe],
+            Architecture::RiscV32 { compressed: true }
+                if instruction.first().is_some_and(|byte| byte & 0b11 != 0b11) =>
+            {
+                &[0x02, 0x90]
+            }
+            Architecture::RiscV32 { .. } => &[0x73, 0x00, 0x10, 0x00],
+        }
+    }
+
+    /// Decode a breakpoint instruction, e.g. one compiled into the firmware.
+    ///
+    /// Description:
+    ///
+    /// * `bytes` - The little endian bytes at the instruction address.
+    ///
+    /// Will return the immediate of the instruction and its size in bytes, RISC-V breakpoint
+    /// instructions have no immediate so it is always `0`.
+    /// Will return `None` if the bytes are not a breakpoint instruction.
+    pub fn decode_breakpoint(&self, bytes: &[u8]) -> Option<(u8, u32)> {
+        match self {
+            Architecture::CortexM => match bytes.get(..2)? {
+                [immediate, 0xbe] => Some((*immediate, 2)),
+                _ => None,
+            },
+            Architecture::RiscV32 { .. } => match bytes {
+                [0x02, 0x90, ..] => Some((0, 2)),
+                [0x73, 0x00, 0x10, 0x00, ..] => Some((0, 4)),
+                _ => None,
+            },
+        }
+    }
+
+    /// Get a address in the calling instruction from a return address, e.g. the link register
+    /// value of a frame.
+    ///
+    /// Description:
+    ///
+    /// * `return_address` - The address the call returns to.
+    ///
+    /// The return address is the instruction after the call, which can be on a other source line
+    /// or even in a other function, so one is subtracted from it.
+    /// The lowest bit of a Cortex-M return address is set because of Thumb mode, it is removed.
+    /// Will return `None` if the return address is too small to come from a call.
+    pub fn call_address(&self, return_address: u32) -> Option<u32> {
+        match self {
+            Architecture::CortexM => (return_address & !1).checked_sub(1),
+            Architecture::RiscV32 { .. } => return_address.checked_sub(1),
+        }
+    }
+
+    /// Get the return address from a address in the calling instruction, the reverse of
+    /// `call_address`.
+    ///
+    /// Description:
+    ///
+    /// * `call_address` - A address in the calling instruction.
+    pub fn return_address(&self, call_address: u32) -> Option<u32> {
+        match self {
+            Architecture::CortexM => Some(call_address.checked_add(1)? | 1),
+            Architecture::RiscV32 { .. } => call_address.checked_add(1),
+        }
+    }
+
+    /// Get the program counter value a call continues at when it returns.
+    ///
+    /// Description:
+    ///
+    /// * `return_address` - The address the call returns to, e.g. the link register value.
+    ///
+    /// The lowest bit of a Cortex-M return address is set because of Thumb mode, it is removed.
+    pub fn return_program_counter(&self, return_address: u32) -> u32 {
+        match self {
+            Architecture::CortexM => return_address & !1,
+            Architecture::RiscV32 { .. } => return_address,
+        }
+    }
+
+    /// Check if a link register value is a Cortex-M `EXC_RETURN` value, which means the caller
+    /// was interrupted by a exception.
+    ///
+    /// Description:
+    ///
+    /// * `return_address` - The link register value of a frame.
+    ///
+    /// The reset value `0xffffffff` of the link register is not a `EXC_RETURN` value.
+    pub fn is_exception_return(&self, return_address: u32) -> bool {
+        match self {
+            Architecture::CortexM => {
+                return_address & 0xffff_ff80 == 0xffff_ff80 && return_address != 0xffff_ffff
+            }
+            Architecture::RiscV32 { .. } => false,
+        }
+    }
+}
+
+impl Default for Architecture {
+    /// The default architecture is Cortex-M, the only one supported before RISC-V.
+    fn default() -> Architecture {
+        Architecture::CortexM
+    }
+}
+
 /// A struct to hold the register values and other register information.
 #[derive(Debug, Clone)]
 pub struct Registers {
@@ -20,6 +190,18 @@ pub struct Registers {
 
     /// Canonical Frame Address, which is sometimes needed to evaluate variables.
     pub cfa: Option<u32>, // Canonical Frame Address
+
+    /// The architecture of the debug target, it decides how return addresses are unwound.
+    pub architecture: Architecture,
+
+    /// The register values at the entry of the current function, recovered from the caller frame.
+    /// It is used to evaluate `DW_OP_entry_value` expressions.
+    pub entry_registers: Option<HashMap<u16, u32>>,
+
+    /// The number of register values that have been read, it is shared with the clones so the
+    /// reads of e.g. the unwinder are also counted.
+    #[cfg(feature = "telemetry")]
+    reads: std::sync::Arc<std::sync::atomic::AtomicU64>,
 }
 
 impl Default for Registers {
@@ -32,10 +214,29 @@ impl Default for Registers {
             link_register: None,
             stack_pointer_register: None,
             cfa: None,
+            architecture: Architecture::default(),
+            entry_registers: None,
+            #[cfg(feature = "telemetry")]
+            reads: Default::default(),
         }
     }
 }
 impl Registers {
+    /// Creates a empty `Registers` struct with the register numbers of a architecture.
+    ///
+    /// Description:
+    ///
+    /// * `architecture` - The architecture of the debug target.
+    pub fn for_architecture(architecture: Architecture) -> Registers {
+        Registers {
+            program_counter_register: Some(architecture.program_counter_register()),
+            link_register: Some(architecture.link_register()),
+            stack_pointer_register: Some(architecture.stack_pointer_register()),
+            architecture,
+            ..Registers::default()
+        }
+    }
+
     /// Add a register value to the struct.
     ///
     /// Description:
@@ -49,6 +250,33 @@ impl Registers {
         self.registers.insert(register, value);
     }
 
+    /// Read register values from the debug target into the struct.
+    ///
+    /// Description:
+    ///
+    /// * `numbers` - The DWARF numbers of the registers to read.
+    /// * `access` - A struct or a closure for reading the registers of the debug target.
+    ///
+    /// This makes it possible to evaluate and unwind with register values from a closure, e.g.
+    /// `registers.read_registers(&[13, 14, 15], &mut |numbers: &[u16]| probe.read(numbers))`.
+    pub fn read_registers<A: RegisterAccess>(
+        &mut self,
+        numbers: &[u16],
+        access: &mut A,
+    ) -> Result<()> {
+        let values = match access.read_registers(numbers) {
+            Some(val) if val.len() == numbers.len() => val,
+            _ => {
+                error!("Could not read the registers");
+                return Err(anyhow!("Could not read the registers"));
+            }
+        };
+        for (number, value) in numbers.iter().zip(values) {
+            self.add_register_value(*number, value);
+        }
+        Ok(())
+    }
+
     /// Retrieve a register value.
     ///
     /// Description:
@@ -57,13 +285,38 @@ impl Registers {
     ///
     /// Will retrieve the `register` value from the `self.registers` HashMap.
     pub fn get_register_value(&self, register: &u16) -> Option<&u32> {
+        self.count_reads(1);
         self.registers.get(register)
     }
 
+    /// Retrieve all the register values, e.g. to start unwinding the call stack.
+    pub fn register_values(&self) -> impl Iterator<Item = (&u16, &u32)> {
+        self.count_reads(self.registers.len() as u64);
+        self.registers.iter()
+    }
+
+    /// Get the number of register values that have been read, see `Telemetry::measure`.
+    #[cfg(feature = "telemetry")]
+    pub fn reads(&self) -> u64 {
+        self.reads.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Count register value reads, they are only counted with the `telemetry` feature.
+    #[cfg(feature = "telemetry")]
+    fn count_reads(&self, reads: u64) {
+        self.reads
+            .fetch_add(reads, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Count register value reads, they are only counted with the `telemetry` feature.
+    #[cfg(not(feature = "telemetry"))]
+    fn count_reads(&self, _reads: u64) {}
+
     /// Sets all the register values to `None` in the struct.
     pub fn clear(&mut self) {
         self.registers = HashMap::new();
         self.stashed_registers = None;
+        self.entry_registers = None;
     }
 
     /// Temporally stash the current register values.
@@ -93,6 +346,29 @@ impl Registers {
         self.stashed_registers = None;
     }
 
+    /// Get the value of a register pseudo-variable used in expressions.
+    ///
+    /// Description:
+    ///
+    /// * `name` - The name of the pseudo-variable: `$pc`, `$sp`, `$lr`, `$cfa`, `$r<n>` or
+    ///   `$x<n>`.
+    ///
+    /// Will return `None` if the name is unknown or the register has no value.
+    pub fn pseudo_variable(&self, name: &str) -> Option<u32> {
+        let register = match name.strip_prefix('$')? {
+            "pc" => self.program_counter_register?,
+            "sp" => self.stack_pointer_register?,
+            "lr" => self.link_register?,
+            "cfa" => return self.cfa,
+            other => other
+                .strip_prefix('r')
+                .or_else(|| other.strip_prefix('x'))?
+                .parse()
+                .ok()?,
+        };
+        self.get_register_value(&(register as u16)).copied()
+    }
+
     /// Get registers as a Vec of `Variables`
     ///
     /// Description:
@@ -109,3 +385,100 @@ impl Registers {
         res
     }
 }
+
+/// A trait used for reading the core registers of the debug target.
+pub trait RegisterAccess {
+    /// Reads a number of core registers from the debugged target.
+    ///
+    /// Description:
+    ///
+    /// * `registers` - The DWARF numbers of the registers that will be read.
+    ///
+    /// The registers should be read in as few transactions as possible, e.g. in one halt window.
+    /// Will return the values in the same order as `registers`, or `None` if they could not be
+    /// read.
+    fn read_registers(&mut self, registers: &[u16]) -> Option<Vec<u32>>;
+}
+
+/// Makes it possible to use a closure for reading the registers of the debug target.
+impl<F> RegisterAccess for F
+where
+    F: FnMut(&[u16]) -> Option<Vec<u32>>,
+{
+    fn read_registers(&mut self, registers: &[u16]) -> Option<Vec<u32>> {
+        self(registers)
+    }
+}
+
+/// A snapshot of the core registers that is read once per stop.
+///
+/// The stack trace, the variable evaluation and the registers command all need the register
+/// values, and reading them one at a time costs many probe transactions per stop.
+#[derive(Debug, Clone)]
+pub struct RegisterSnapshot {
+    /// The DWARF numbers of the registers that are read.
+    pub numbers: Vec<u16>,
+
+    /// The register layout, the program counter, link register and stack pointer numbers are
+    /// copied into the snapshot.
+    layout: Registers,
+
+    /// The stop generation the snapshot was read in, and the read registers.
+    snapshot: Option<(u64, Registers)>,
+
+    /// The number of times the registers have been read from the debug target.
+    pub reads: u64,
+}
+
+impl RegisterSnapshot {
+    /// Creates a new empty `RegisterSnapshot`.
+    ///
+    /// Description:
+    ///
+    /// * `numbers` - The DWARF numbers of the registers to read, e.g. `0..16` for the Arm core
+    ///   registers.
+    /// * `layout` - The register layout, only the program counter, link register and stack
+    ///   pointer numbers are used.
+    pub fn new(numbers: &[u16], layout: &Registers) -> RegisterSnapshot {
+        let mut layout = layout.clone();
+        layout.clear();
+        layout.cfa = None;
+        RegisterSnapshot {
+            numbers: numbers.to_vec(),
+            layout,
+            snapshot: None,
+            reads: 0,
+        }
+    }
+
+    /// Get the register values of a stop, they are read the first time they are requested in
+    /// the stop.
+    ///
+    /// Description:
+    ///
+    /// * `generation` - The stop generation, it should be increased every time the debug target
+    ///   halts.
+    /// * `access` - A struct for reading the registers of the debug target.
+    pub fn get<A: RegisterAccess>(
+        &mut self,
+        generation: u64,
+        access: &mut A,
+    ) -> Result<&Registers> {
+        if !matches!(&self.snapshot, Some((g, _)) if *g == generation) {
+            let mut registers = self.layout.clone();
+            registers.read_registers(&self.numbers, access)?;
+            self.reads += 1;
+            self.snapshot = Some((generation, registers));
+        }
+
+        match &self.snapshot {
+            Some((_, registers)) => Ok(registers),
+            None => Err(anyhow!("Could not read the registers")),
+        }
+    }
+
+    /// Remove the snapshot, e.g. when a register is written.
+    pub fn invalidate(&mut self) {
+        self.snapshot = None;
+    }
+}
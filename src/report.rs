@@ -0,0 +1,147 @@
+use anyhow::Result;
+use gimli::{AttributeValue, BaseAddresses, DebugFrame, Dwarf, Reader, UnitType, UnwindSection};
+use std::collections::BTreeSet;
+
+/// The type DIE tags that can be evaluated.
+const SUPPORTED_TYPE_TAGS: [gimli::DwTag; 19] = [
+    gimli::DW_TAG_array_type,
+    gimli::DW_TAG_base_type,
+    gimli::DW_TAG_const_type,
+    gimli::DW_TAG_enumeration_type,
+    gimli::DW_TAG_generic_subrange,
+    gimli::DW_TAG_member,
+    gimli::DW_TAG_pointer_type,
+    gimli::DW_TAG_restrict_type,
+    gimli::DW_TAG_string_type,
+    gimli::DW_TAG_structure_type,
+    gimli::DW_TAG_subprogram,
+    gimli::DW_TAG_subrange_type,
+    gimli::DW_TAG_subroutine_type,
+    gimli::DW_TAG_template_type_parameter,
+    gimli::DW_TAG_typedef,
+    gimli::DW_TAG_union_type,
+    gimli::DW_TAG_variant,
+    gimli::DW_TAG_variant_part,
+    gimli::DW_TAG_volatile_type,
+];
+
+/// Statistics about the quality of the debug information of a program.
+///
+/// This helps to understand why values are shown as optimized out or why stack traces stop
+/// early.
+#[derive(Debug, Clone, Default)]
+pub struct DebugInfoReport {
+    /// The number of compilation units.
+    pub units: usize,
+
+    /// The number of compilation units without line number information.
+    pub units_without_line_info: usize,
+
+    /// The number of functions with machine code.
+    pub functions: usize,
+
+    /// The number of functions with machine code that has no unwind information in
+    /// `.debug_frame`.
+    pub functions_without_frame_info: usize,
+
+    /// The number of variables and parameters.
+    pub variables: usize,
+
+    /// The number of variables and parameters that have a location list.
+    pub variables_with_location_list: usize,
+
+    /// The number of variables and parameters that have neither a location nor a constant value.
+    pub variables_without_location: usize,
+
+    /// The DWARF features that are found in the debug information but are not supported.
+    pub unsupported_features: Vec<String>,
+}
+
+impl DebugInfoReport {
+    /// Get the percentage of the variables and parameters that have a location list.
+    pub fn location_list_percentage(&self) -> f64 {
+        match self.variables {
+            0 => 0.0,
+            variables => 100.0 * self.variables_with_location_list as f64 / variables as f64,
+        }
+    }
+}
+
+/// Will scan all the debug information and create a report about its quality.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `debug_frame` - A reference to the DWARF section `.debug_frame`.
+///
+/// A function has machine code if it has the attribute `DW_AT_low_pc`.
+/// The unsupported features are the type DIE tags that can not be evaluated, and split DWARF
+/// units.
+pub fn debug_info_report<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    debug_frame: &DebugFrame<R>,
+) -> Result<DebugInfoReport> {
+    let mut report = DebugInfoReport::default();
+    let mut unsupported = BTreeSet::new();
+    let bases = BaseAddresses::default();
+
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        let unit = dwarf.unit(header)?;
+        report.units += 1;
+
+        if unit.line_program.is_none() {
+            report.units_without_line_info += 1;
+        }
+
+        if matches!(
+            unit.header.type_(),
+            UnitType::Skeleton(_) | UnitType::SplitCompilation(_)
+        ) {
+            unsupported.insert("split DWARF units".to_string());
+        }
+
+        let mut entries = unit.entries();
+        while let Some((_, die)) = entries.next_dfs()? {
+            match die.tag() {
+                gimli::DW_TAG_subprogram => {
+                    if let Some(AttributeValue::Addr(low_pc)) =
+                        die.attr_value(gimli::DW_AT_low_pc)?
+                    {
+                        report.functions += 1;
+                        if debug_frame
+                            .fde_for_address(&bases, low_pc, DebugFrame::cie_from_offset)
+                            .is_err()
+                        {
+                            report.functions_without_frame_info += 1;
+                        }
+                    }
+                }
+                gimli::DW_TAG_variable | gimli::DW_TAG_formal_parameter => {
+                    report.variables += 1;
+                    match die.attr_value(gimli::DW_AT_location)? {
+                        Some(AttributeValue::LocationListsRef(_)) => {
+                            report.variables_with_location_list += 1
+                        }
+                        Some(_) => (),
+                        None => {
+                            if die.attr_value(gimli::DW_AT_const_value)?.is_none() {
+                                report.variables_without_location += 1;
+                            }
+                        }
+                    };
+                }
+                tag => {
+                    let is_type =
+                        matches!(tag.static_string(), Some(name) if name.ends_with("_type"));
+                    if is_type && !SUPPORTED_TYPE_TAGS.contains(&tag) {
+                        unsupported.insert(tag.to_string());
+                    }
+                }
+            };
+        }
+    }
+
+    report.unsupported_features = unsupported.into_iter().collect();
+    Ok(report)
+}
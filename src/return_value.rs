@@ -0,0 +1,182 @@
+use crate::call_stack::MemoryAccess;
+use crate::evaluate::attributes;
+use crate::evaluate::evaluate::EvaluatorValue;
+use crate::registers::Registers;
+use crate::utils::DwarfOffset;
+use anyhow::{anyhow, Result};
+use gimli::{DebuggingInformationEntry, Dwarf, Location, Piece, Reader, Register, Unit};
+use log::{error, trace};
+
+/// The register the first word of a return value is stored in, according to the ARM AAPCS.
+const RETURN_REGISTER_LOW: u16 = 0;
+
+/// The register the second word of a return value is stored in, according to the ARM AAPCS.
+const RETURN_REGISTER_HIGH: u16 = 1;
+
+/// Will evaluate the value a function returned.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `function` - The offset to the subprogram DIE of the function that returned.
+/// * `registers` - The register values right after the function returned.
+/// * `mem` - A struct for accessing the memory of the debug target.
+///
+/// The location of the return value is decided by the `DW_AT_type` of the function and the ARM
+/// AAPCS.
+/// Values of at most 8 bytes are returned in `r0` and `r1`, except composite types which are only
+/// returned in `r0` if they are at most 4 bytes.
+/// Larger composite types are returned in memory that is not described after the return, thus
+/// they are reported as optimized out.
+/// Will return `None` if the function does not return a value.
+pub fn get_return_value<R: Reader<Offset = usize>, M: MemoryAccess>(
+    dwarf: &Dwarf<R>,
+    function: DwarfOffset,
+    registers: &Registers,
+    mem: &mut M,
+) -> Result<Option<EvaluatorValue<R>>> {
+    let header = dwarf.debug_info.header_from_offset(
+        match function.section_offset.as_debug_info_offset() {
+            Some(val) => val,
+            None => {
+                error!("Could not convert section offset into debug info offset");
+                return Err(anyhow!(
+                    "Could not convert section offset into debug info offset"
+                ));
+            }
+        },
+    )?;
+    let unit = dwarf.unit(header)?;
+    let die = unit.entry(function.unit_offset)?;
+
+    let (type_section_offset, type_unit_offset) = match return_type(dwarf, &unit, &die)? {
+        Some(val) => val,
+        None => {
+            trace!("Function does not return a value");
+            return Ok(None);
+        }
+    };
+
+    let type_header =
+        dwarf
+            .debug_info
+            .header_from_offset(match type_section_offset.as_debug_info_offset() {
+                Some(val) => val,
+                None => {
+                    error!("Could not convert section offset into debug info offset");
+                    return Err(anyhow!(
+                        "Could not convert section offset into debug info offset"
+                    ));
+                }
+            })?;
+    let type_unit = dwarf.unit(type_header)?;
+    let type_die = type_unit.entry(type_unit_offset)?;
+
+    let (byte_size, composite) = match return_type_size(&type_unit, &type_die)? {
+        Some(val) => val,
+        None => {
+            error!("Could not find the size of the return type");
+            return Err(anyhow!("Could not find the size of the return type"));
+        }
+    };
+
+    let registers_used = match (byte_size, composite) {
+        (0, _) => 0,
+        (1..=4, _) => 1,
+        (5..=8, false) => 2,
+        _ => {
+            trace!("Return value of {} bytes is returned in memory", byte_size);
+            return Ok(Some(EvaluatorValue::OptimizedOut));
+        }
+    };
+
+    let pieces: Vec<Piece<R>> = [RETURN_REGISTER_LOW, RETURN_REGISTER_HIGH]
+        .iter()
+        .take(registers_used)
+        .map(|register| Piece {
+            size_in_bits: Some(32),
+            bit_offset: None,
+            location: Location::Register {
+                register: Register(*register),
+            },
+        })
+        .collect();
+
+    Ok(Some(EvaluatorValue::evaluate_variable_with_type(
+        dwarf,
+        registers,
+        mem,
+        &pieces,
+        type_section_offset,
+        type_unit_offset,
+    )?))
+}
+
+/// Will find the return type of a function.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `unit` - A reference to gimli-rs `Unit` struct which contains the given DIE.
+/// * `die` - A reference to the subprogram DIE.
+///
+/// The `DW_AT_type` attribute is also searched for in the DIE from the attribute
+/// `DW_AT_abstract_origin` and `DW_AT_specification`.
+fn return_type<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    unit: &Unit<R>,
+    die: &DebuggingInformationEntry<R>,
+) -> Result<Option<(gimli::UnitSectionOffset, gimli::UnitOffset)>> {
+    if let Some(val) = attributes::type_attribute(dwarf, unit, die)? {
+        return Ok(Some(val));
+    }
+
+    for attr in [gimli::DW_AT_abstract_origin, gimli::DW_AT_specification] {
+        if let Some(gimli::AttributeValue::UnitRef(offset)) = die.attr_value(attr)? {
+            let origin = unit.entry(offset)?;
+            return return_type(dwarf, unit, &origin);
+        }
+    }
+
+    Ok(None)
+}
+
+/// Will find the size of a type and if it is a composite type.
+///
+/// Description:
+///
+/// * `unit` - A reference to gimli-rs `Unit` struct which contains the given DIE.
+/// * `die` - A reference to the type DIE.
+///
+/// Type modifiers and typedefs are followed until a DIE with a size is found.
+fn return_type_size<R: Reader<Offset = usize>>(
+    unit: &Unit<R>,
+    die: &DebuggingInformationEntry<R>,
+) -> Result<Option<(u64, bool)>> {
+    let composite = matches!(
+        die.tag(),
+        gimli::DW_TAG_structure_type
+            | gimli::DW_TAG_union_type
+            | gimli::DW_TAG_array_type
+            | gimli::DW_TAG_class_type
+    );
+
+    if let Some(byte_size) = attributes::byte_size_attribute(die)? {
+        return Ok(Some((byte_size, composite)));
+    }
+
+    match die.tag() {
+        // 4 because 32 bit addresses.
+        gimli::DW_TAG_pointer_type | gimli::DW_TAG_reference_type => Ok(Some((4, false))),
+        gimli::DW_TAG_typedef
+        | gimli::DW_TAG_const_type
+        | gimli::DW_TAG_volatile_type
+        | gimli::DW_TAG_restrict_type => match die.attr_value(gimli::DW_AT_type)? {
+            Some(gimli::AttributeValue::UnitRef(offset)) => {
+                return_type_size(unit, &unit.entry(offset)?)
+            }
+            _ => Ok(None),
+        },
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,371 @@
+use anyhow::{anyhow, Result};
+use gimli::{DebugFrame, Dwarf, Reader, Section, SectionId};
+use log::error;
+#[cfg(feature = "compression")]
+use std::borrow::Cow;
+
+/// The `sh_flags` bit that marks a ELF section as compressed.
+pub const SHF_COMPRESSED: u64 = 0x800;
+
+/// The `ch_type` of a zlib compressed ELF section.
+const ELFCOMPRESS_ZLIB: u32 = 1;
+
+/// The `ch_type` of a zstd compressed ELF section.
+const ELFCOMPRESS_ZSTD: u32 = 2;
+
+/// The magic bytes at the start of a `.zdebug_*` section.
+const ZDEBUG_MAGIC: &[u8] = b"ZLIB";
+
+/// The compression format of a section.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompressionFormat {
+    /// The section is compressed with zlib.
+    Zlib,
+
+    /// The section is compressed with zstd.
+    Zstd,
+}
+
+/// Describes how a DWARF section is compressed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressedSection {
+    /// The compression format.
+    pub format: CompressionFormat,
+
+    /// The size of the section data after it is decompressed.
+    pub uncompressed_size: u64,
+
+    /// The offset into the section data where the compressed data starts.
+    pub data_offset: usize,
+}
+
+impl CompressedSection {
+    /// Get the compressed data of the section.
+    ///
+    /// Description:
+    ///
+    /// * `data` - The raw data of the section.
+    ///
+    /// Will return a error if the section is smaller than its compression header.
+    pub fn compressed_data<'a>(&self, data: &'a [u8]) -> Result<&'a [u8]> {
+        match data.get(self.data_offset..) {
+            Some(val) => Ok(val),
+            None => {
+                error!(
+                    "Section of {} bytes is smaller than its {} byte compression header",
+                    data.len(),
+                    self.data_offset
+                );
+                Err(anyhow!(
+                    "Section of {} bytes is smaller than its {} byte compression header",
+                    data.len(),
+                    self.data_offset
+                ))
+            }
+        }
+    }
+
+    /// Decompress the section.
+    ///
+    /// Description:
+    ///
+    /// * `data` - The raw data of the section.
+    ///
+    /// Will return a error if the data can not be decompressed, or if it does not decompress to
+    /// `uncompressed_size` bytes.
+    #[cfg(feature = "compression")]
+    pub fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        use std::convert::TryFrom;
+        use std::io::Read;
+
+        let compressed = self.compressed_data(data)?;
+        let size = usize::try_from(self.uncompressed_size)?;
+        let result = match self.format {
+            CompressionFormat::Zlib => {
+                miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(compressed, size)
+                    .map_err(|err| anyhow!("{}", err))
+            }
+            CompressionFormat::Zstd => {
+                let mut decompressed = vec![];
+                ruzstd::decoding::StreamingDecoder::new(compressed)
+                    .map_err(|err| anyhow!("{}", err))
+                    .and_then(|decoder| {
+                        // Read one byte more than expected to find too large sections.
+                        decoder
+                            .take(self.uncompressed_size.saturating_add(1))
+                            .read_to_end(&mut decompressed)?;
+                        Ok(decompressed)
+                    })
+            }
+        };
+
+        match result {
+            Ok(val) if val.len() == size => Ok(val),
+            Ok(val) => {
+                error!(
+                    "Section decompressed to {} bytes, expected {} bytes",
+                    val.len(),
+                    size
+                );
+                Err(anyhow!(
+                    "Section decompressed to {} bytes, expected {} bytes",
+                    val.len(),
+                    size
+                ))
+            }
+            Err(err) => {
+                error!("Could not decompress {:?} section: {}", self.format, err);
+                Err(anyhow!(
+                    "Could not decompress {:?} section: {}",
+                    self.format,
+                    err
+                ))
+            }
+        }
+    }
+}
+
+/// Get the names a DWARF section can have in a ELF file.
+///
+/// Description:
+///
+/// * `id` - The id of the DWARF section.
+///
+/// Will return the normal name, e.g. `.debug_info`, and the name used by the old GNU compression
+/// format, e.g. `.zdebug_info`.
+pub fn section_names(id: SectionId) -> (&'static str, String) {
+    let name = id.name();
+    (name, name.replacen(".debug_", ".zdebug_", 1))
+}
+
+/// Find out if and how a DWARF section is compressed.
+///
+/// Description:
+///
+/// * `name` - The name of the ELF section.
+/// * `data` - The raw data of the ELF section.
+/// * `flags` - The `sh_flags` of the ELF section header.
+/// * `is_64` - `true` if the ELF file is 64 bit.
+/// * `little_endian` - `true` if the ELF file is little endian.
+///
+/// Sections with the `SHF_COMPRESSED` flag start with a ELF compression header, and `.zdebug_*`
+/// sections start with `ZLIB` followed by the big endian uncompressed size.
+/// Will return `None` if the section is not compressed.
+/// The section can be decompressed with `CompressedSection::decompress` when the `compression`
+/// feature is enabled, otherwise the decompression is left to the caller.
+pub fn find_compression(
+    name: &str,
+    data: &[u8],
+    flags: u64,
+    is_64: bool,
+    little_endian: bool,
+) -> Result<Option<CompressedSection>> {
+    let read = |offset: usize, size: usize| -> Result<u64> {
+        let field = match data.get(offset..offset + size) {
+            Some(val) => val,
+            None => {
+                error!("Section {} is too small for a compression header", name);
+                return Err(anyhow!(
+                    "Section {} is too small for a compression header",
+                    name
+                ));
+            }
+        };
+        let mut bytes = [0; 8];
+        bytes[..size].copy_from_slice(field);
+        if !little_endian {
+            bytes[..size].reverse();
+        }
+        Ok(u64::from_le_bytes(bytes))
+    };
+
+    if flags & SHF_COMPRESSED != 0 {
+        // The field sizes and the header size differs between the 32 and 64 bit formats.
+        let (ch_type, uncompressed_size, data_offset) = match is_64 {
+            true => (read(0, 4)?, read(8, 8)?, 24),
+            false => (read(0, 4)?, read(4, 4)?, 12),
+        };
+        let format = match ch_type as u32 {
+            ELFCOMPRESS_ZLIB => CompressionFormat::Zlib,
+            ELFCOMPRESS_ZSTD => CompressionFormat::Zstd,
+            unknown => {
+                error!("Unknown compression type {} of section {}", unknown, name);
+                return Err(anyhow!(
+                    "Unknown compression type {} of section {}",
+                    unknown,
+                    name
+                ));
+            }
+        };
+        return Ok(Some(CompressedSection {
+            format,
+            uncompressed_size,
+            data_offset,
+        }));
+    }
+
+    if name.starts_with(".zdebug_") {
+        if !data.starts_with(ZDEBUG_MAGIC) || data.len() < 12 {
+            error!("Section {} is missing the ZLIB header", name);
+            return Err(anyhow!("Section {} is missing the ZLIB header", name));
+        }
+        let mut size = [0; 8];
+        size.copy_from_slice(&data[4..12]);
+        return Ok(Some(CompressedSection {
+            format: CompressionFormat::Zlib,
+            uncompressed_size: u64::from_be_bytes(size),
+            data_offset: 12,
+        }));
+    }
+
+    Ok(None)
+}
+
+/// Get the data of a DWARF section, decompressed if the section is compressed.
+///
+/// Description:
+///
+/// * `name` - The name of the ELF section, e.g. `.debug_info` or `.zdebug_info`.
+/// * `data` - The raw data of the ELF section.
+/// * `flags` - The `sh_flags` of the ELF section header.
+/// * `is_64` - `true` if the ELF file is 64 bit.
+/// * `little_endian` - `true` if the ELF file is little endian.
+///
+/// Will return the raw data without copying it if the section is not compressed.
+#[cfg(feature = "compression")]
+pub fn section_data<'a>(
+    name: &str,
+    data: &'a [u8],
+    flags: u64,
+    is_64: bool,
+    little_endian: bool,
+) -> Result<Cow<'a, [u8]>> {
+    match find_compression(name, data, flags, is_64, little_endian)? {
+        Some(compression) => Ok(Cow::Owned(compression.decompress(data)?)),
+        None => Ok(Cow::Borrowed(data)),
+    }
+}
+
+/// The DWARF sections that are required for source level debugging.
+pub const REQUIRED_SECTIONS: [SectionId; 4] = [
+    SectionId::DebugInfo,
+    SectionId::DebugAbbrev,
+    SectionId::DebugLine,
+    SectionId::DebugFrame,
+];
+
+/// Find the required DWARF sections that are missing or empty, e.g. in a release build without
+/// `debug = true`.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `debug_frame` - A reference to the DWARF section `.debug_frame`.
+///
+/// Will return the missing sections in the order of `REQUIRED_SECTIONS`.
+pub fn missing_sections<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    debug_frame: &DebugFrame<R>,
+) -> Vec<SectionId> {
+    REQUIRED_SECTIONS
+        .iter()
+        .copied()
+        .filter(|id| {
+            let reader = match id {
+                SectionId::DebugInfo => dwarf.debug_info.reader(),
+                SectionId::DebugAbbrev => dwarf.debug_abbrev.reader(),
+                SectionId::DebugLine => dwarf.debug_line.reader(),
+                _ => debug_frame.reader(),
+            };
+            reader.is_empty()
+        })
+        .collect()
+}
+
+/// Describe the missing DWARF sections and what can still be debugged.
+///
+/// Description:
+///
+/// * `missing` - The missing sections returned by `missing_sections`.
+///
+/// Will return `None` if no section is missing.
+pub fn missing_sections_message(missing: &[SectionId]) -> Option<String> {
+    if missing.is_empty() {
+        return None;
+    }
+
+    let names: Vec<&str> = missing.iter().map(|id| id.name()).collect();
+    let mut message = format!("The program is missing the sections: {}.", names.join(", "));
+    if missing.contains(&SectionId::DebugFrame) {
+        message.push_str(" The call stack can not be unwound.");
+    }
+    if missing.iter().any(|id| *id != SectionId::DebugFrame) {
+        message.push_str(" Source locations and variables are not available.");
+    }
+    message.push_str(
+        " Registers, memory, address breakpoints and symbol table frames still work. \
+         Build with `debug = true` in the Cargo profile to get the debug information.",
+    );
+    Some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create a `.zdebug_*` section with the `ZLIB` header.
+    fn zdebug_section(uncompressed_size: u64, compressed: &[u8]) -> Vec<u8> {
+        let mut section = ZDEBUG_MAGIC.to_vec();
+        section.extend(uncompressed_size.to_be_bytes());
+        section.extend(compressed);
+        section
+    }
+
+    #[test]
+    fn compression_header_bounds() {
+        let compression = find_compression(".zdebug_info", &zdebug_section(4, &[]), 0, false, true)
+            .unwrap()
+            .unwrap();
+        assert_eq!(compression.format, CompressionFormat::Zlib);
+        assert_eq!(compression.uncompressed_size, 4);
+        assert!(compression.compressed_data(&[0; 12]).unwrap().is_empty());
+        assert!(compression.compressed_data(&[0; 4]).is_err());
+
+        // A 32 bit ELF compression header is 12 bytes.
+        assert!(
+            find_compression(".debug_info", &[1, 0, 0, 0], SHF_COMPRESSED, false, true).is_err()
+        );
+        assert_eq!(
+            find_compression(".debug_info", &[1, 2, 3], 0, false, true).unwrap(),
+            None
+        );
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn decompress_sections() {
+        let data = b"debug information".to_vec();
+
+        let zlib = miniz_oxide::deflate::compress_to_vec_zlib(&data, 6);
+        let section = zdebug_section(data.len() as u64, &zlib);
+        assert_eq!(
+            section_data(".zdebug_info", &section, 0, false, true).unwrap(),
+            data
+        );
+        let wrong_size = zdebug_section(data.len() as u64 + 1, &zlib);
+        assert!(section_data(".zdebug_info", &wrong_size, 0, false, true).is_err());
+
+        let zstd = ruzstd::encoding::compress_to_vec(
+            &data[..],
+            ruzstd::encoding::CompressionLevel::Fastest,
+        );
+        let mut section = ELFCOMPRESS_ZSTD.to_le_bytes().to_vec();
+        section.extend((data.len() as u32).to_le_bytes());
+        section.extend(1_u32.to_le_bytes());
+        section.extend(zstd);
+        assert_eq!(
+            section_data(".debug_info", &section, SHF_COMPRESSED, false, true).unwrap(),
+            data
+        );
+    }
+}
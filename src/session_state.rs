@@ -0,0 +1,301 @@
+use crate::breakpoints::BreakpointTable;
+use crate::evaluate::format::{FormatOptions, StringEscape, UnionFormat};
+use anyhow::{anyhow, Result};
+use log::error;
+use std::num::NonZeroU64;
+use std::path::Path;
+
+/// A breakpoint stored by its source location, since the addresses change between builds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedBreakpoint {
+    /// The source file of the breakpoint.
+    pub file: String,
+
+    /// The source line of the breakpoint.
+    pub line: NonZeroU64,
+
+    /// `false` if the breakpoint is disabled.
+    pub enabled: bool,
+
+    /// The user tags of the breakpoint.
+    pub tags: Vec<String>,
+}
+
+/// The state of a debug session that is kept between runs of the same program.
+#[derive(Debug, Clone, Default)]
+pub struct SessionState {
+    /// The source breakpoints.
+    pub breakpoints: Vec<SavedBreakpoint>,
+
+    /// The watch expressions.
+    pub watches: Vec<String>,
+
+    /// The formatting preferences.
+    pub format_options: FormatOptions,
+}
+
+impl SessionState {
+    /// Creates a `SessionState` from the current session.
+    ///
+    /// Description:
+    ///
+    /// * `table` - The breakpoint table, only the source breakpoints that are not temporary are
+    ///   saved.
+    /// * `watches` - The watch expressions.
+    /// * `format_options` - The formatting preferences.
+    pub fn new(
+        table: &BreakpointTable,
+        watches: &[String],
+        format_options: &FormatOptions,
+    ) -> SessionState {
+        let breakpoints = table
+            .breakpoints
+            .iter()
+            .filter(|b| !b.temporary)
+            .filter_map(|b| {
+                Some(SavedBreakpoint {
+                    file: b.source_file.clone()?,
+                    line: NonZeroU64::new(b.line?)?,
+                    enabled: b.enabled,
+                    tags: b.tags.iter().cloned().collect(),
+                })
+            })
+            .collect();
+
+        SessionState {
+            breakpoints,
+            watches: watches.to_vec(),
+            format_options: format_options.clone(),
+        }
+    }
+
+    /// Write the state in the text format read by `parse`.
+    pub fn serialize(&self) -> String {
+        let mut lines = vec![];
+        for b in &self.breakpoints {
+            let mut line = format!("breakpoint {}:{}", b.file, b.line);
+            if !b.enabled {
+                line.push_str(" disabled");
+            }
+            for tag in &b.tags {
+                line = format!("{} #{}", line, tag);
+            }
+            lines.push(line);
+        }
+        for watch in &self.watches {
+            lines.push(format!("watch {}", watch));
+        }
+
+        let options = &self.format_options;
+        let limit = |limit: Option<usize>| match limit {
+            Some(val) => val.to_string(),
+            None => "none".to_string(),
+        };
+        lines.push(format!("format hex={}", options.hex));
+        lines.push(format!("format show_types={}", options.show_types));
+        lines.push(format!(
+            "format max_array_elements={}",
+            limit(options.max_array_elements)
+        ));
+        lines.push(format!(
+            "format max_string_length={}",
+            limit(options.max_string_length)
+        ));
+        lines.push(format!(
+            "format string_escape={}",
+            match options.string_escape {
+                StringEscape::Escaped => "escaped",
+                StringEscape::Raw => "raw",
+            }
+        ));
+        lines.push(format!(
+            "format union_format={}",
+            match options.union_format {
+                UnionFormat::Members => "members",
+                UnionFormat::RawBytes => "raw",
+                UnionFormat::ActiveMember => "active",
+            }
+        ));
+        lines.push(format!(
+            "format show_zero_sized={}",
+            options.show_zero_sized
+        ));
+        lines.push(format!(
+            "format unwrap_wrappers={}",
+            options.unwrap_wrappers
+        ));
+        lines.push(format!("format show_addresses={}", options.show_addresses));
+
+        lines.join("\n") + "\n"
+    }
+
+    /// Parse a state written by `serialize`.
+    ///
+    /// Description:
+    ///
+    /// * `text` - The serialized state.
+    ///
+    /// Empty lines and lines starting with `//` are ignored.
+    pub fn parse(text: &str) -> Result<SessionState> {
+        let mut state = SessionState::default();
+        for line in text.lines().map(|l| l.trim()) {
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match kind {
+                "breakpoint" => state.breakpoints.push(parse_breakpoint(rest)?),
+                "watch" => state.watches.push(rest.to_string()),
+                "format" => parse_format_option(&mut state.format_options, rest)?,
+                _ => {
+                    error!("Unknown session state entry: {}", line);
+                    return Err(anyhow!("Unknown session state entry: {}", line));
+                }
+            };
+        }
+
+        Ok(state)
+    }
+
+    /// Save the state to a file.
+    ///
+    /// Description:
+    ///
+    /// * `path` - The path to the state file, e.g. a file in the project directory.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.serialize())?;
+        Ok(())
+    }
+
+    /// Load the state from a file.
+    ///
+    /// Description:
+    ///
+    /// * `path` - The path to the state file.
+    ///
+    /// Will return `None` if the file does not exist.
+    pub fn load(path: &Path) -> Result<Option<SessionState>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(SessionState::parse(&std::fs::read_to_string(path)?)?))
+    }
+}
+
+/// Parse a saved breakpoint on the form `<file>:<line> [disabled] [#tag ...]`.
+fn parse_breakpoint(text: &str) -> Result<SavedBreakpoint> {
+    let mut words = text.split_whitespace();
+    let location = words.next().unwrap_or("");
+    let (file, line) = match crate::breakpoints::parse_source_location(location) {
+        Some(val) => val,
+        None => {
+            error!("Invalid breakpoint location: {}", location);
+            return Err(anyhow!("Invalid breakpoint location: {}", location));
+        }
+    };
+
+    let mut breakpoint = SavedBreakpoint {
+        file: file.to_string(),
+        line,
+        enabled: true,
+        tags: vec![],
+    };
+    for word in words {
+        match word.strip_prefix('#') {
+            Some(tag) => breakpoint.tags.push(tag.to_string()),
+            None if word == "disabled" => breakpoint.enabled = false,
+            None => {
+                error!("Unknown breakpoint flag: {}", word);
+                return Err(anyhow!("Unknown breakpoint flag: {}", word));
+            }
+        };
+    }
+
+    Ok(breakpoint)
+}
+
+/// Parse a formatting preference on the form `<name>=<value>` into the options.
+fn parse_format_option(options: &mut FormatOptions, text: &str) -> Result<()> {
+    let invalid = || {
+        error!("Invalid format option: {}", text);
+        anyhow!("Invalid format option: {}", text)
+    };
+    let (name, value) = text.split_once('=').ok_or_else(invalid)?;
+    let limit = |value: &str| match value {
+        "none" => Ok(None),
+        val => val.parse().map(Some).map_err(|_| invalid()),
+    };
+    let flag = |value: &str| value.parse::<bool>().map_err(|_| invalid());
+
+    match name {
+        "hex" => options.hex = flag(value)?,
+        "show_types" => options.show_types = flag(value)?,
+        "max_array_elements" => options.max_array_elements = limit(value)?,
+        "max_string_length" => options.max_string_length = limit(value)?,
+        "string_escape" => {
+            options.string_escape = match value {
+                "escaped" => StringEscape::Escaped,
+                "raw" => StringEscape::Raw,
+                _ => return Err(invalid()),
+            }
+        }
+        "union_format" => {
+            options.union_format = match value {
+                "members" => UnionFormat::Members,
+                "raw" => UnionFormat::RawBytes,
+                "active" => UnionFormat::ActiveMember,
+                _ => return Err(invalid()),
+            }
+        }
+        "show_zero_sized" => options.show_zero_sized = flag(value)?,
+        "unwrap_wrappers" => options.unwrap_wrappers = flag(value)?,
+        "show_addresses" => options.show_addresses = flag(value)?,
+        _ => return Err(invalid()),
+    };
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_and_parse() {
+        let mut state = SessionState {
+            breakpoints: vec![SavedBreakpoint {
+                file: "src/main.rs".to_string(),
+                line: NonZeroU64::new(42).unwrap(),
+                enabled: false,
+                tags: vec!["init".to_string()],
+            }],
+            watches: vec!["counter".to_string()],
+            format_options: FormatOptions::default(),
+        };
+        state.format_options.hex = true;
+        state.format_options.max_string_length = None;
+        state.format_options.union_format = UnionFormat::ActiveMember;
+
+        let text = state.serialize();
+        assert!(text.starts_with("breakpoint src/main.rs:42 disabled #init\nwatch counter\n"));
+        let parsed = SessionState::parse(&format!("// comment\n\n{}", text)).unwrap();
+        assert_eq!(parsed.breakpoints, state.breakpoints);
+        assert_eq!(parsed.watches, state.watches);
+        assert!(parsed.format_options.hex);
+        assert_eq!(parsed.format_options.max_string_length, None);
+        assert_eq!(
+            parsed.format_options.union_format,
+            UnionFormat::ActiveMember
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(SessionState::parse("breakpoint src/main.rs").is_err());
+        assert!(SessionState::parse("breakpoint src/main.rs:3 sometimes").is_err());
+        assert!(SessionState::parse("format hex=maybe").is_err());
+        assert!(SessionState::parse("format colors=true").is_err());
+        assert!(SessionState::parse("run").is_err());
+    }
+}
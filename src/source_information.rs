@@ -1,10 +1,15 @@
 use anyhow::{anyhow, Result};
 use log::error;
 
-use crate::utils::get_current_unit;
+use crate::call_stack::{find_function_die, find_non_inlined_function_die};
+use crate::dwarf_index::DwarfIndex;
+use crate::source_path::{is_absolute, join, paths_equal};
+use crate::utils::{get_current_unit, DwarfOffset};
 
 use gimli::{ColumnType, DebuggingInformationEntry, Dwarf, Reader, Unit};
+use std::collections::HashMap;
 use std::num::NonZeroU64;
+use std::ops::Range;
 
 /// Contains all the information about where the code was declared in the source code.
 #[derive(Debug, Clone)]
@@ -40,7 +45,59 @@ impl SourceInformation {
         die: &DebuggingInformationEntry<'_, '_, R>,
         cwd: &str,
     ) -> Result<SourceInformation> {
-        let (file, directory) = match die.attr_value(gimli::DW_AT_decl_file)? {
+        SourceInformation::get_die_attributes_source_information(
+            dwarf,
+            unit,
+            die,
+            cwd,
+            [
+                gimli::DW_AT_decl_file,
+                gimli::DW_AT_decl_line,
+                gimli::DW_AT_decl_column,
+            ],
+        )
+    }
+
+    /// Retrieves the information about where the call that the given DIE describes is located in
+    /// the source code.
+    ///
+    /// Description:
+    ///
+    /// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+    /// * `unit` - A reference to gimli-rs `Unit` struct, which the given DIE is located in.
+    /// * `die` - A reference to the DIE containing attributes starting with `DW_AT_call_`.
+    /// * `cwd` - The work directory of the debugged program.
+    ///
+    /// This function will retrieve the information stored in the attributes `DW_AT_call_file`,
+    /// `DW_AT_call_line` and `DW_AT_call_column` from the given DIE.
+    pub fn get_die_call_source_information<R: Reader<Offset = usize>>(
+        dwarf: &Dwarf<R>,
+        unit: &Unit<R>,
+        die: &DebuggingInformationEntry<'_, '_, R>,
+        cwd: &str,
+    ) -> Result<SourceInformation> {
+        SourceInformation::get_die_attributes_source_information(
+            dwarf,
+            unit,
+            die,
+            cwd,
+            [
+                gimli::DW_AT_call_file,
+                gimli::DW_AT_call_line,
+                gimli::DW_AT_call_column,
+            ],
+        )
+    }
+
+    /// Retrieves the source code location stored in the given file, line and column attributes.
+    fn get_die_attributes_source_information<R: Reader<Offset = usize>>(
+        dwarf: &Dwarf<R>,
+        unit: &Unit<R>,
+        die: &DebuggingInformationEntry<'_, '_, R>,
+        cwd: &str,
+        [file_attr, line_attr, column_attr]: [gimli::DwAt; 3],
+    ) -> Result<SourceInformation> {
+        let (file, directory) = match die.attr_value(file_attr)? {
             Some(gimli::AttributeValue::FileIndex(v)) => match &unit.line_program {
                 Some(lp) => {
                     let header = lp.header();
@@ -56,8 +113,8 @@ impl SourceInformation {
                                         .to_string();
                                     let file = file_raw.trim_start_matches(&dir_raw).to_string();
 
-                                    if !dir_raw.starts_with('/') {
-                                        dir_raw = format!("{}/{}", cwd, dir_raw);
+                                    if !is_absolute(&dir_raw) {
+                                        dir_raw = join(cwd, &dir_raw);
                                     }
 
                                     (file, Some(dir_raw))
@@ -85,7 +142,7 @@ impl SourceInformation {
             }
         };
 
-        let line = match die.attr_value(gimli::DW_AT_decl_line)? {
+        let line = match die.attr_value(line_attr)? {
             Some(gimli::AttributeValue::Udata(v)) => NonZeroU64::new(v),
             None => None,
             Some(v) => {
@@ -94,7 +151,7 @@ impl SourceInformation {
             }
         };
 
-        let column = match die.attr_value(gimli::DW_AT_decl_column)? {
+        let column = match die.attr_value(column_attr)? {
             Some(gimli::AttributeValue::Udata(v)) => NonZeroU64::new(v),
             None => None,
             Some(v) => {
@@ -116,7 +173,41 @@ impl SourceInformation {
         address: u64,
         cwd: &str,
     ) -> Result<SourceInformation> {
-        let unit = get_current_unit(dwarf, address as u32)?;
+        SourceInformation::from_address(dwarf, None, address, cwd)
+    }
+
+    /// Retrieve the source code location of a machine code address, using a index to find the
+    /// compilation unit.
+    ///
+    /// Description:
+    ///
+    /// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+    /// * `index` - A index of the DWARF debug information.
+    /// * `address` - A machine code address.
+    /// * `cwd` - The work directory of the debugged program.
+    ///
+    /// The same as `get_from_address`, but only the compilation unit of the address is parsed.
+    pub fn get_from_address_with_index<R: Reader<Offset = usize>>(
+        dwarf: &Dwarf<R>,
+        index: &DwarfIndex,
+        address: u64,
+        cwd: &str,
+    ) -> Result<SourceInformation> {
+        SourceInformation::from_address(dwarf, Some(index), address, cwd)
+    }
+
+    /// Retrieve the source code location of a machine code address, the compilation unit is
+    /// found with the index if there is one.
+    fn from_address<R: Reader<Offset = usize>>(
+        dwarf: &Dwarf<R>,
+        index: Option<&DwarfIndex>,
+        address: u64,
+        cwd: &str,
+    ) -> Result<SourceInformation> {
+        let unit = match index {
+            Some(index) => index.unit(dwarf, address as u32)?,
+            None => get_current_unit(dwarf, address as u32)?,
+        };
         let mut nearest = None;
         match unit.line_program.clone() {
             Some(line_program) => {
@@ -157,8 +248,8 @@ impl SourceInformation {
                                         let file =
                                             file_raw.trim_start_matches(&dir_raw).to_string();
 
-                                        if !dir_raw.starts_with('/') {
-                                            dir_raw = format!("{}/{}", cwd, dir_raw);
+                                        if !is_absolute(&dir_raw) {
+                                            dir_raw = join(cwd, &dir_raw);
                                         }
 
                                         (Some(file), Some(dir_raw))
@@ -232,6 +323,137 @@ pub fn find_breakpoint_location<'a, R: Reader<Offset = usize>>(
     line: NonZeroU64,
     column: Option<NonZeroU64>,
 ) -> Result<Option<u64>> {
+    let locations = find_line_locations(dwarf, cwd, path, line)?;
+
+    Ok(select_location(&locations, column))
+}
+
+/// Find the machine code addresses for a line in every function instance it is part of.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `cwd` - The work directory of the debugged program.
+/// * `path` - The relative path to the source file from the work directory of the debugged program.
+/// * `line` - A line number in the source program.
+/// * `column` - A optional column number in the source program.
+///
+/// A line in a inlined function has machine code in every function it is inlined into.
+/// The line table rows are grouped by the inner most subprogram or inlined subroutine DIE that
+/// contains them, and one address is selected for each group in the same way as
+/// `find_breakpoint_location`.
+/// This makes it possible to set one breakpoint on every inlined copy of the line.
+pub fn find_breakpoint_locations<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    cwd: &str,
+    path: &str,
+    line: NonZeroU64,
+    column: Option<NonZeroU64>,
+) -> Result<Vec<u64>> {
+    let locations = find_line_locations(dwarf, cwd, path, line)?;
+
+    let mut groups = HashMap::new();
+    for location in locations {
+        let instance = find_function_die(dwarf, location.1 as u32).ok().map(
+            |(section_offset, unit_offset)| DwarfOffset {
+                section_offset,
+                unit_offset,
+            },
+        );
+
+        groups
+            .entry(instance)
+            .or_insert_with(Vec::new)
+            .push(location);
+    }
+
+    let mut addresses: Vec<u64> = groups
+        .values()
+        .filter_map(|group| select_location(group, column))
+        .collect();
+    addresses.sort_unstable();
+    addresses.dedup();
+    Ok(addresses)
+}
+
+/// Select the location that best matches the column, the first location is used if there is no
+/// better match.
+fn select_location(locations: &[(ColumnType, u64)], column: Option<NonZeroU64>) -> Option<u64> {
+    match locations.len() {
+        0 => None,
+        len => {
+            let search = match column {
+                Some(v) => gimli::ColumnType::Column(v),
+                None => gimli::ColumnType::LeftEdge,
+            };
+
+            let mut res = locations[0];
+            for location in locations.iter().take(len).skip(1) {
+                if location.0 <= search && location.0 > res.0 {
+                    res = *location;
+                }
+            }
+
+            Some(res.1)
+        }
+    }
+}
+
+/// Find all the machine code addresses that corresponds to a line in the source file.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `cwd` - The work directory of the debugged program.
+/// * `path` - The relative path to the source file from the work directory of the debugged program.
+/// * `line` - A line number in the source program.
+///
+/// The addresses are sorted and every address is only listed once.
+/// This is used to find the possible targets when jumping to a line.
+pub fn find_line_addresses<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    cwd: &str,
+    path: &str,
+    line: NonZeroU64,
+) -> Result<Vec<u64>> {
+    let mut addresses: Vec<u64> = find_line_locations(dwarf, cwd, path, line)?
+        .into_iter()
+        .map(|(_, address)| address)
+        .collect();
+    addresses.sort_unstable();
+    addresses.dedup();
+    Ok(addresses)
+}
+
+/// Check if jumping from one address to another stays inside the same function.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `pc` - The current program counter value.
+/// * `target` - The address that the program counter will be set to.
+///
+/// Jumping to a address in another function skips the setup of that functions stack frame, thus
+/// the caller should warn about it when this function returns `false`.
+pub fn is_jump_within_function<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    pc: u32,
+    target: u32,
+) -> Result<bool> {
+    let current = find_non_inlined_function_die(dwarf, pc)?;
+    Ok(match find_non_inlined_function_die(dwarf, target) {
+        Ok(val) => val == current,
+        Err(_) => false,
+    })
+}
+
+/// Find the column and machine code address of all the rows in the line table for a source line.
+fn find_line_locations<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    cwd: &str,
+    path: &str,
+    line: NonZeroU64,
+) -> Result<Vec<(ColumnType, u64)>> {
     let mut locations = vec![];
 
     let mut units = dwarf.units();
@@ -251,14 +473,9 @@ pub fn find_breakpoint_location<'a, R: Reader<Offset = usize>>(
                 };
 
                 let file_raw = dwarf.attr_string(&unit, file_entry.path_name())?;
-                let mut file_path = format!("{}/{}", directory, file_raw.to_string()?);
+                let file_path = join(cwd, &join(&directory, &file_raw.to_string()?));
 
-                if !file_path.starts_with('/') {
-                    // TODO: Find a better solution
-                    file_path = format!("{}/{}", cwd, file_path);
-                }
-
-                if path == file_path {
+                if paths_equal(path, &file_path) {
                     let mut rows = line_program.clone().rows();
                     while let Some((header, row)) = rows.next_row()? {
                         let file_entry = match row.file(header) {
@@ -275,13 +492,9 @@ pub fn find_breakpoint_location<'a, R: Reader<Offset = usize>>(
                         };
 
                         let file_raw = dwarf.attr_string(&unit, file_entry.path_name())?;
-                        let mut file_path = format!("{}/{}", directory, file_raw.to_string()?);
-                        if !file_path.starts_with('/') {
-                            // TODO: Find a better solution
-                            file_path = format!("{}/{}", cwd, file_path);
-                        }
+                        let file_path = join(cwd, &join(&directory, &file_raw.to_string()?));
 
-                        if path == file_path {
+                        if paths_equal(path, &file_path) {
                             if let Some(l) = row.line() {
                                 if line == l {
                                     locations.push((row.column(), row.address()));
@@ -294,22 +507,270 @@ pub fn find_breakpoint_location<'a, R: Reader<Offset = usize>>(
         }
     }
 
-    match locations.len() {
-        0 => Ok(None),
-        len => {
-            let search = match column {
-                Some(v) => gimli::ColumnType::Column(v),
-                None => gimli::ColumnType::LeftEdge,
+    Ok(locations)
+}
+
+/// A row of the line table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineRow {
+    /// The machine code address of the row.
+    pub address: u64,
+
+    /// The full path of the source file.
+    pub file: Option<String>,
+
+    /// The source line number, `None` if the code has no line.
+    pub line: Option<NonZeroU64>,
+
+    /// The source column number, `None` for the left edge of the line.
+    pub column: Option<NonZeroU64>,
+
+    /// `true` if the row is a recommended breakpoint location.
+    pub is_stmt: bool,
+
+    /// `true` if the row is where the prologue of the function ends.
+    pub prologue_end: bool,
+
+    /// `true` if the row is where the epilogue of the function begins.
+    pub epilogue_begin: bool,
+
+    /// `true` if the row marks the first address after the end of a sequence.
+    pub end_sequence: bool,
+}
+
+/// Get all the rows of the line table, optionally only the rows for one source file.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `cwd` - The work directory of the debugged program.
+/// * `path` - The full path to the source file, `None` for the rows of all the files.
+///
+/// This is used to understand why a breakpoint is placed where it is, e.g. with a
+/// `dwarf line <file>` command.
+pub fn line_table_rows<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    cwd: &str,
+    path: Option<&str>,
+) -> Result<Vec<LineRow>> {
+    let mut result = vec![];
+    let mut units = dwarf.units();
+    while let Some(unit_header) = units.next()? {
+        let unit = dwarf.unit(unit_header)?;
+        let line_program = match unit.line_program.clone() {
+            Some(val) => val,
+            None => continue,
+        };
+
+        let mut rows = line_program.rows();
+        while let Some((header, row)) = rows.next_row()? {
+            let line_row = create_line_row(dwarf, &unit, header, row, cwd)?;
+            let in_file = match (path, line_row.file.as_deref()) {
+                (None, _) => true,
+                (Some(path), Some(file)) => paths_equal(path, file),
+                (Some(_), None) => false,
             };
+            if in_file {
+                result.push(line_row);
+            }
+        }
+    }
 
-            let mut res = locations[0];
-            for location in locations.iter().take(len).skip(1) {
-                if location.0 <= search && location.0 > res.0 {
-                    res = *location;
-                }
+    Ok(result)
+}
+
+/// Find the line table row that covers a machine code address.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `cwd` - The work directory of the debugged program.
+/// * `address` - A machine code address.
+///
+/// The row that covers the address is the last row before or at the address in the same
+/// sequence, this is what a `dwarf addr2line <address>` command shows.
+pub fn find_line_row<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    cwd: &str,
+    address: u64,
+) -> Result<Option<LineRow>> {
+    line_row(dwarf, None, cwd, address)
+}
+
+/// Find the line table row that covers a machine code address, using a index to find the
+/// compilation unit.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `index` - A index of the DWARF debug information.
+/// * `cwd` - The work directory of the debugged program.
+/// * `address` - A machine code address.
+///
+/// The same as `find_line_row`, but only the compilation unit of the address is parsed.
+pub fn find_line_row_with_index<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    index: &DwarfIndex,
+    cwd: &str,
+    address: u64,
+) -> Result<Option<LineRow>> {
+    line_row(dwarf, Some(index), cwd, address)
+}
+
+/// Find the line table row that covers a address, see `find_line_row`.
+fn line_row<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    index: Option<&DwarfIndex>,
+    cwd: &str,
+    address: u64,
+) -> Result<Option<LineRow>> {
+    let unit = match index {
+        Some(index) => index.unit(dwarf, address as u32)?,
+        None => get_current_unit(dwarf, address as u32)?,
+    };
+    let line_program = match unit.line_program.clone() {
+        Some(val) => val,
+        None => {
+            error!("Unit has no line program");
+            return Err(anyhow!("Unit has no line program"));
+        }
+    };
+
+    let mut previous: Option<LineRow> = None;
+    let mut rows = line_program.rows();
+    while let Some((header, row)) = rows.next_row()? {
+        if let Some(prev) = &previous {
+            if !prev.end_sequence && prev.address <= address && address < row.address() {
+                return Ok(previous);
             }
+        }
+        previous = Some(create_line_row(dwarf, &unit, header, row, cwd)?);
+    }
+
+    Ok(None)
+}
 
-            Ok(Some(res.1))
+/// Find the machine code address range of the source line that covers a address, e.g. the
+/// range a source level step over steps through.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `address` - A machine code address, usually the current program counter value.
+///
+/// The range is the consecutive rows in the same sequence with the same file and line as the
+/// row that covers the address.
+/// Will return `None` if no row covers the address or the row has no line.
+pub fn find_line_range<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    address: u64,
+) -> Result<Option<Range<u64>>> {
+    line_range(dwarf, None, address)
+}
+
+/// Find the machine code address range of the source line that covers a address, using a index
+/// to find the compilation unit.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `index` - A index of the DWARF debug information.
+/// * `address` - A machine code address, usually the current program counter value.
+///
+/// The same as `find_line_range`, but only the compilation unit of the address is parsed.
+pub fn find_line_range_with_index<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    index: &DwarfIndex,
+    address: u64,
+) -> Result<Option<Range<u64>>> {
+    line_range(dwarf, Some(index), address)
+}
+
+/// Find the address range of the source line that covers a address, the compilation unit is
+/// found with the index if there is one.
+fn line_range<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    index: Option<&DwarfIndex>,
+    address: u64,
+) -> Result<Option<Range<u64>>> {
+    let unit = match index {
+        Some(index) => index.unit(dwarf, address as u32)?,
+        None => get_current_unit(dwarf, address as u32)?,
+    };
+    let line_program = match unit.line_program.clone() {
+        Some(val) => val,
+        None => {
+            error!("Unit has no line program");
+            return Err(anyhow!("Unit has no line program"));
         }
+    };
+
+    // The rows of the current sequence as address, file index and line.
+    let mut sequence: Vec<(u64, u64, Option<NonZeroU64>)> = vec![];
+    let mut rows = line_program.rows();
+    while let Some((_, row)) = rows.next_row()? {
+        sequence.push((row.address(), row.file_index(), row.line()));
+        if !row.end_sequence() {
+            continue;
+        }
+
+        let end = sequence.len() - 1;
+        let index = (0..end)
+            .find(|i| sequence[*i].0 <= address && address < sequence[i + 1].0)
+            .filter(|i| sequence[*i].2.is_some());
+        if let Some(index) = index {
+            let same =
+                |i: usize| sequence[i].1 == sequence[index].1 && sequence[i].2 == sequence[index].2;
+            let mut first = index;
+            while first > 0 && same(first - 1) {
+                first -= 1;
+            }
+            let mut last = index;
+            while last + 1 < end && same(last + 1) {
+                last += 1;
+            }
+            return Ok(Some(sequence[first].0..sequence[last + 1].0));
+        }
+        sequence.clear();
     }
+
+    Ok(None)
+}
+
+/// Create a `LineRow` from a row of the line program.
+fn create_line_row<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    unit: &Unit<R>,
+    header: &gimli::LineProgramHeader<R>,
+    row: &gimli::LineRow,
+    cwd: &str,
+) -> Result<LineRow> {
+    let file = match row.file(header) {
+        Some(file_entry) => {
+            let file_raw = dwarf.attr_string(unit, file_entry.path_name())?;
+            let file_path = match file_entry.directory(header) {
+                Some(dir_av) => {
+                    let dir_raw = dwarf.attr_string(unit, dir_av)?;
+                    join(&dir_raw.to_string()?, &file_raw.to_string()?)
+                }
+                None => file_raw.to_string()?.to_string(),
+            };
+            Some(join(cwd, &file_path))
+        }
+        None => None,
+    };
+
+    Ok(LineRow {
+        address: row.address(),
+        file,
+        line: row.line(),
+        column: match row.column() {
+            ColumnType::LeftEdge => None,
+            ColumnType::Column(n) => Some(n),
+        },
+        is_stmt: row.is_stmt(),
+        prologue_end: row.prologue_end(),
+        epilogue_begin: row.epilogue_begin(),
+        end_sequence: row.end_sequence(),
+    })
 }
@@ -0,0 +1,343 @@
+/// Will return `true` if a source path is absolute on a POSIX or a Windows host.
+///
+/// Description:
+///
+/// * `path` - The source path, e.g. from the DWARF line program.
+///
+/// The paths in the debug information are from the host that built the program, which is not
+/// always the host running the debugger, so both forms are recognized on all hosts: `/src`,
+/// drive letter paths like `C:\src` and `C:/src`, and UNC paths like `\\server\share`.
+pub fn is_absolute(path: &str) -> bool {
+    if path.starts_with('/') || path.starts_with('\\') {
+        return true;
+    }
+
+    let bytes = path.as_bytes();
+    bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && (bytes[2] == b'/' || bytes[2] == b'\\')
+}
+
+/// Join a relative source path to a directory.
+///
+/// Description:
+///
+/// * `directory` - The directory, e.g. the compilation directory.
+/// * `path` - The path that is joined, it is returned unchanged if it is absolute.
+///
+/// The separator used by the directory is used for the join, `\` if it only contains `\`
+/// separators and `/` otherwise.
+pub fn join(directory: &str, path: &str) -> String {
+    if is_absolute(path) || directory.is_empty() {
+        return path.to_string();
+    }
+
+    let separator = match directory.contains('\\') && !directory.contains('/') {
+        true => '\\',
+        false => '/',
+    };
+    format!(
+        "{}{}{}",
+        directory.trim_end_matches(['/', '\\']),
+        separator,
+        path
+    )
+}
+
+/// Normalize a source path so that it can be compared to other paths.
+///
+/// Description:
+///
+/// * `path` - The source path.
+///
+/// All separators are written as `/`, the repeated separators and `.` components are removed
+/// and `..` components are resolved.
+/// Windows paths are compared without regard to case, so a path with a drive letter or a UNC
+/// prefix is written in lower case.
+pub fn normalize(path: &str) -> String {
+    let unified = path.replace('\\', "/");
+    let (prefix, rest) = match unified.strip_prefix("//") {
+        Some(rest) => ("//", rest),
+        None => match unified.strip_prefix('/') {
+            Some(rest) => ("/", rest),
+            None => ("", unified.as_str()),
+        },
+    };
+    let windows = prefix == "//" || is_drive_path(rest);
+
+    let mut components: Vec<&str> = vec![];
+    for component in rest.split('/') {
+        match component {
+            "" | "." => (),
+            ".." => match components.last() {
+                Some(last) if *last != ".." && !is_drive(last) => {
+                    components.pop();
+                }
+                None | Some(&"..") if prefix.is_empty() => components.push(".."),
+                _ => (),
+            },
+            _ => components.push(component),
+        }
+    }
+
+    let result = format!("{}{}", prefix, components.join("/"));
+    match windows {
+        true => result.to_lowercase(),
+        false => result,
+    }
+}
+
+/// Will return `true` if two source paths refer to the same file.
+///
+/// Description:
+///
+/// * `a` - A source path.
+/// * `b` - The other source path.
+///
+/// The paths are compared after they are normalized with `normalize`.
+pub fn paths_equal(a: &str, b: &str) -> bool {
+    normalize(a) == normalize(b)
+}
+
+/// Will return `true` if the last components of a source path are the components of a suffix.
+///
+/// Description:
+///
+/// * `path` - The source path, e.g. the full path of a breakpoint.
+/// * `suffix` - The suffix, e.g. `src/main.rs`.
+///
+/// Only whole components match, `src/main.rs` does not end with `ain.rs`.
+pub fn path_ends_with(path: &str, suffix: &str) -> bool {
+    let path = normalize(path);
+    let mut suffix = normalize(suffix);
+    if path.starts_with("//") || is_drive_path(&path) {
+        suffix = suffix.to_lowercase();
+    }
+    let suffix = suffix.trim_start_matches('/');
+    if suffix.is_empty() {
+        return false;
+    }
+
+    path == suffix || path.ends_with(&format!("/{}", suffix))
+}
+
+/// Maps the source paths in the debug information to the paths on the host running the
+/// debugger, e.g. the `sourceMap` argument of a DAP launch request.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceMap {
+    /// The prefixes from the debug information and the host prefixes they are replaced with.
+    pub mappings: Vec<(String, String)>,
+}
+
+impl SourceMap {
+    /// Creates a empty `SourceMap`, which leaves all paths unchanged.
+    pub fn new() -> SourceMap {
+        SourceMap { mappings: vec![] }
+    }
+
+    /// Add a mapping.
+    ///
+    /// Description:
+    ///
+    /// * `from` - A path prefix in the debug information, e.g. `/rustc/<hash>`.
+    /// * `to` - The path prefix on the host, e.g. the `rust-src` directory of the toolchain.
+    pub fn add(&mut self, from: &str, to: &str) {
+        self.mappings.push((from.to_string(), to.to_string()));
+    }
+
+    /// Map a path from the debug information to the host.
+    ///
+    /// Description:
+    ///
+    /// * `path` - The path from the debug information, it should be absolute.
+    ///
+    /// The mapping with the longest matching prefix is used and only whole components match.
+    /// The result is normalized, so it can be compared with `paths_equal`.
+    pub fn to_host(&self, path: &str) -> String {
+        map_prefix(
+            self.mappings
+                .iter()
+                .map(|(from, to)| (from.as_str(), to.as_str())),
+            path,
+        )
+    }
+
+    /// Map a path on the host back to the path in the debug information, e.g. the path of a
+    /// breakpoint set in the editor.
+    ///
+    /// Description:
+    ///
+    /// * `path` - The path on the host.
+    pub fn to_target(&self, path: &str) -> String {
+        map_prefix(
+            self.mappings
+                .iter()
+                .map(|(from, to)| (to.as_str(), from.as_str())),
+            path,
+        )
+    }
+
+    /// Resolve the source file of a line program row or a DIE to a path on the host.
+    ///
+    /// Description:
+    ///
+    /// * `cwd` - The work directory of the debugged program.
+    /// * `directory` - The directory of the file, `None` if unknown.
+    /// * `file` - The file path, it is often relative to the directory.
+    ///
+    /// A relative directory, e.g. a relative compilation directory, is relative to `cwd`.
+    pub fn resolve(&self, cwd: &str, directory: Option<&str>, file: &str) -> String {
+        let path = match directory {
+            Some(directory) => join(directory, file),
+            None => file.to_string(),
+        };
+        self.to_host(&join(cwd, &path))
+    }
+}
+
+/// Replace the longest matching prefix of a path and normalize it.
+fn map_prefix<'a, I: Iterator<Item = (&'a str, &'a str)>>(mappings: I, path: &str) -> String {
+    let path = normalize(path);
+    let best = mappings
+        .filter_map(|(from, to)| {
+            let from = normalize(from);
+            let from = from.trim_end_matches('/');
+            let rest = match path.strip_prefix(from) {
+                Some(rest) if rest.is_empty() || rest.starts_with('/') || from.is_empty() => rest,
+                _ => return None,
+            };
+            Some((from.len(), to, rest.to_string()))
+        })
+        .max_by_key(|(length, _, _)| *length);
+
+    match best {
+        Some((_, to, rest)) => normalize(&format!("{}{}", to.trim_end_matches(['/', '\\']), rest)),
+        None => path,
+    }
+}
+
+/// Will return `true` if a path component is a drive, e.g. `C:`.
+fn is_drive(component: &str) -> bool {
+    let bytes = component.as_bytes();
+    bytes.len() == 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+/// Will return `true` if a path starts with a drive, e.g. `C:/src`.
+fn is_drive_path(path: &str) -> bool {
+    is_drive(path.split('/').next().unwrap_or(""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn absolute_paths() {
+        assert!(is_absolute("/home/user/src/main.rs"));
+        assert!(is_absolute("C:\\Users\\user\\src\\main.rs"));
+        assert!(is_absolute("c:/Users/user/src/main.rs"));
+        assert!(is_absolute("\\\\server\\share\\src\\main.rs"));
+        assert!(!is_absolute("src/main.rs"));
+        assert!(!is_absolute("src\\main.rs"));
+        assert!(!is_absolute("C:main.rs"));
+    }
+
+    #[test]
+    fn join_uses_the_separator_of_the_directory() {
+        assert_eq!(join("/home/user", "src/main.rs"), "/home/user/src/main.rs");
+        assert_eq!(join("/home/user/", "src/main.rs"), "/home/user/src/main.rs");
+        assert_eq!(
+            join("C:\\Users\\user", "src\\main.rs"),
+            "C:\\Users\\user\\src\\main.rs"
+        );
+        assert_eq!(
+            join("\\\\server\\share", "src\\main.rs"),
+            "\\\\server\\share\\src\\main.rs"
+        );
+        assert_eq!(join("/home/user", "D:\\src\\main.rs"), "D:\\src\\main.rs");
+        assert_eq!(join("", "src/main.rs"), "src/main.rs");
+    }
+
+    #[test]
+    fn normalize_drive_letter_paths() {
+        assert_eq!(
+            normalize("C:\\Users\\User\\.\\src\\..\\src\\main.rs"),
+            "c:/users/user/src/main.rs"
+        );
+        assert_eq!(normalize("C:/..\\main.rs"), "c:/main.rs");
+    }
+
+    #[test]
+    fn normalize_unc_paths() {
+        assert_eq!(
+            normalize("\\\\Server\\Share\\src\\main.rs"),
+            "//server/share/src/main.rs"
+        );
+    }
+
+    #[test]
+    fn normalize_posix_paths() {
+        assert_eq!(
+            normalize("/home//User/./src/main.rs"),
+            "/home/User/src/main.rs"
+        );
+        assert_eq!(normalize("/home/user/../../main.rs"), "/main.rs");
+        assert_eq!(normalize("../src/main.rs"), "../src/main.rs");
+        assert_eq!(normalize("../../src/main.rs"), "../../src/main.rs");
+    }
+
+    #[test]
+    fn paths_equal_across_separators() {
+        assert!(paths_equal(
+            "C:\\Users\\user\\src\\main.rs",
+            "c:/users/user/src/main.rs"
+        ));
+        assert!(paths_equal(
+            "\\\\server\\share\\main.rs",
+            "//SERVER/share/main.rs"
+        ));
+        assert!(!paths_equal("/home/User/main.rs", "/home/user/main.rs"));
+    }
+
+    #[test]
+    fn path_ends_with_whole_components() {
+        assert!(path_ends_with("C:\\project\\src\\main.rs", "src/main.rs"));
+        assert!(path_ends_with("/project/src/main.rs", "main.rs"));
+        assert!(path_ends_with(
+            "\\\\server\\share\\src\\main.rs",
+            "src\\main.rs"
+        ));
+        assert!(!path_ends_with("/project/src/main.rs", "ain.rs"));
+        assert!(!path_ends_with("/project/src/main.rs", ""));
+    }
+
+    #[test]
+    fn source_map_uses_the_longest_prefix() {
+        let mut map = SourceMap::new();
+        map.add("/rustc/abc", "/home/user/.rustup/lib/rustlib/src/rust");
+        map.add("/build", "C:\\src");
+        map.add("/build/vendor", "/opt/vendor");
+        assert_eq!(
+            map.to_host("/rustc/abc/library/core/src/panicking.rs"),
+            "/home/user/.rustup/lib/rustlib/src/rust/library/core/src/panicking.rs"
+        );
+        assert_eq!(map.to_host("/build/src/main.rs"), "c:/src/src/main.rs");
+        assert_eq!(map.to_host("/build/vendor/lib.rs"), "/opt/vendor/lib.rs");
+        assert_eq!(map.to_host("/buildroot/main.rs"), "/buildroot/main.rs");
+        assert_eq!(map.to_target("C:\\Src\\src\\main.rs"), "/build/src/main.rs");
+    }
+
+    #[test]
+    fn source_map_resolves_relative_directories() {
+        let map = SourceMap::new();
+        assert_eq!(
+            map.resolve("/home/user/app", Some("./target/../"), "src/main.rs"),
+            "/home/user/app/src/main.rs"
+        );
+        assert_eq!(
+            map.resolve("/home/user/app", Some("/project"), "/abs/main.rs"),
+            "/abs/main.rs"
+        );
+    }
+}
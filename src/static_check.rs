@@ -0,0 +1,139 @@
+use crate::call_stack::MemoryAccess;
+use crate::symbol_table::{Symbol, SymbolKind, SymbolTable};
+use anyhow::{anyhow, Result};
+use log::error;
+
+/// A part of the load image of the program, e.g. the contents of the `.data` section.
+#[derive(Debug, Clone, Copy)]
+pub struct LoadImage<'a> {
+    /// The address the data is loaded to in the debug target, for `.data` this is the virtual
+    /// address of the section and not the flash address it is copied from.
+    pub address: u64,
+
+    /// The initial bytes of the section, as stored in the ELF file.
+    pub data: &'a [u8],
+}
+
+impl<'a> LoadImage<'a> {
+    /// Get the initial bytes of a address range, `None` if the range is not in this image.
+    ///
+    /// Description:
+    ///
+    /// * `address` - The start address of the range.
+    /// * `size` - The number of bytes in the range.
+    pub fn initial_bytes(&self, address: u64, size: u64) -> Option<&'a [u8]> {
+        let start = address.checked_sub(self.address)? as usize;
+        self.data.get(start..start + size as usize)
+    }
+}
+
+/// The result of comparing a static variable to its initializer.
+#[derive(Debug, Clone)]
+pub struct StaticCheck {
+    /// The name of the static variable.
+    pub name: String,
+
+    /// The address of the static variable.
+    pub address: u64,
+
+    /// The initial bytes of the static variable.
+    pub expected: Vec<u8>,
+
+    /// The current bytes of the static variable in the debug target.
+    pub actual: Vec<u8>,
+
+    /// The offsets of the bytes that differ from the initializer.
+    pub changed_offsets: Vec<usize>,
+}
+
+impl StaticCheck {
+    /// Will return `true` if the static variable differs from its initializer.
+    pub fn is_mutated(&self) -> bool {
+        !self.changed_offsets.is_empty()
+    }
+}
+
+/// Will compare the current value of a static variable to its initializer.
+///
+/// Description:
+///
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `symbol` - The symbol of the static variable.
+/// * `images` - The load image of the program, e.g. the `.data` section.
+///
+/// Will return `None` if the static variable is not in the load image, e.g. statics in `.bss`.
+pub fn check_static<M: MemoryAccess>(
+    mem: &mut M,
+    symbol: &Symbol,
+    images: &[LoadImage],
+) -> Result<Option<StaticCheck>> {
+    if symbol.size == 0 {
+        return Ok(None);
+    }
+
+    let expected = match images
+        .iter()
+        .find_map(|image| image.initial_bytes(symbol.address, symbol.size))
+    {
+        Some(val) => val.to_vec(),
+        None => return Ok(None),
+    };
+
+    let actual = match mem.get_address(&(symbol.address as u32), symbol.size as usize) {
+        Some(val) => val,
+        None => {
+            error!("Could not read the static variable {}", symbol.name);
+            return Err(anyhow!(
+                "Could not read the static variable {}",
+                symbol.name
+            ));
+        }
+    };
+
+    let changed_offsets = expected
+        .iter()
+        .zip(actual.iter())
+        .enumerate()
+        .filter(|(_, (e, a))| e != a)
+        .map(|(i, _)| i)
+        .collect();
+
+    Ok(Some(StaticCheck {
+        name: symbol.name.clone(),
+        address: symbol.address,
+        expected,
+        actual,
+        changed_offsets,
+    }))
+}
+
+/// Will find all the static variables that differ from their initializers.
+///
+/// Description:
+///
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `symbols` - The symbol table of the program.
+/// * `images` - The load image of the program, e.g. the `.data` section.
+///
+/// This is useful for spotting memory corruption of statics that are never supposed to change,
+/// e.g. configuration tables.
+pub fn mutated_statics<M: MemoryAccess>(
+    mem: &mut M,
+    symbols: &SymbolTable,
+    images: &[LoadImage],
+) -> Result<Vec<StaticCheck>> {
+    let mut result = vec![];
+    for symbol in symbols
+        .symbols
+        .iter()
+        .filter(|s| s.kind == SymbolKind::Object)
+    {
+        if let Some(check) = check_static(mem, symbol, images)? {
+            if check.is_mutated() {
+                result.push(check);
+            }
+        }
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,129 @@
+use crate::call_stack::StackFrame;
+use crate::source_information::SourceInformation;
+use crate::source_path::{join, normalize};
+use gimli::Reader;
+
+/// The default patterns, which filter out the Rust standard library and the dependencies.
+pub const DEFAULT_PATTERNS: [&str; 7] = [
+    "/rustc/*",
+    "*/.cargo/registry/*",
+    "*/.cargo/git/*",
+    "core::*",
+    "alloc::*",
+    "std::*",
+    "compiler_builtins::*",
+];
+
+/// Filters out the code that source level stepping should step through instead of stopping in,
+/// e.g. "Just My Code".
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepFilter {
+    /// `false` if the filter is turned off and stepping stops in all code.
+    pub enabled: bool,
+
+    /// The glob patterns, a pattern containing a `/` is matched against the normalized source
+    /// file path and the other patterns are matched against the function name.
+    pub patterns: Vec<String>,
+}
+
+impl Default for StepFilter {
+    /// Creates a enabled `StepFilter` with the `DEFAULT_PATTERNS`.
+    fn default() -> StepFilter {
+        StepFilter {
+            enabled: true,
+            patterns: DEFAULT_PATTERNS.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+}
+
+impl StepFilter {
+    /// Creates a enabled `StepFilter`.
+    ///
+    /// Description:
+    ///
+    /// * `patterns` - The glob patterns, `*` matches any sequence of characters and `?` matches
+    ///   one character.
+    pub fn new(patterns: &[&str]) -> StepFilter {
+        StepFilter {
+            enabled: true,
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    /// Will return `true` if stepping should step through the code instead of stopping in it.
+    ///
+    /// Description:
+    ///
+    /// * `function` - The name of the function, `None` if unknown.
+    /// * `source` - The source location of the code, `None` if unknown.
+    pub fn is_filtered(&self, function: Option<&str>, source: Option<&SourceInformation>) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        let path = source.and_then(source_path);
+        self.patterns.iter().any(|pattern| {
+            let subject = match pattern.contains('/') {
+                true => path.as_deref(),
+                false => function,
+            };
+            match subject {
+                Some(val) => glob_match(pattern, val),
+                None => false,
+            }
+        })
+    }
+
+    /// Will return `true` if stepping should step through the code of a stack frame.
+    ///
+    /// Description:
+    ///
+    /// * `frame` - The stack frame, e.g. the inner most frame after a step in.
+    pub fn is_filtered_frame<R: Reader<Offset = usize>>(&self, frame: &StackFrame<R>) -> bool {
+        self.is_filtered(Some(&frame.name), Some(&frame.source))
+    }
+}
+
+/// Get the full source file path of a source location.
+fn source_path(source: &SourceInformation) -> Option<String> {
+    let file = source.file.as_ref()?;
+    let path = match &source.directory {
+        Some(directory) => join(directory, file),
+        None => file.clone(),
+    };
+    Some(normalize(&path))
+}
+
+/// Match a text against a glob pattern, `*` matches any sequence of characters and `?` matches
+/// one character.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // The position after the last `*` and the text position it was matched at, which makes it
+    // possible to backtrack.
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
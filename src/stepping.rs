@@ -0,0 +1,92 @@
+use crate::call_stack::CallFrame;
+use std::ops::Range;
+
+/// The kind of source level step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepKind {
+    /// Step to the next source line in the current function, calls are stepped over.
+    Over,
+
+    /// Step to the next source line, stopping in the called functions.
+    Into,
+}
+
+/// What the debugger should do after a single instruction step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepAction {
+    /// Do another single instruction step.
+    Step,
+
+    /// The program counter is in a called function, step out of it with `step_out_target`
+    /// and then continue stepping.
+    StepOut,
+
+    /// The step is done.
+    Stop,
+}
+
+/// A source level step over or step into, built from single instruction steps.
+///
+/// The debugger single steps the debug target and calls `next` after every step until it
+/// returns `StepAction::Stop`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceStep {
+    /// The kind of step.
+    pub kind: StepKind,
+
+    /// The machine code addresses of the source line the step started on.
+    pub line: Range<u64>,
+
+    /// The machine code addresses of the function the step started in.
+    pub function: Range<u64>,
+
+    /// The CFA of the frame the step started in, the stack pointer is at or above it once the
+    /// function has returned.
+    pub cfa: u32,
+}
+
+impl SourceStep {
+    /// Creates a new `SourceStep`.
+    ///
+    /// Description:
+    ///
+    /// * `kind` - The kind of step.
+    /// * `line` - The address range of the current source line, see `find_line_range`.
+    /// * `frame` - The current call frame.
+    ///
+    /// Will return `None` if the CFA of the frame is unknown.
+    pub fn new(kind: StepKind, line: Range<u64>, frame: &CallFrame) -> Option<SourceStep> {
+        Some(SourceStep {
+            kind,
+            line,
+            function: frame.start_address..frame.end_address,
+            cfa: frame.cfa?,
+        })
+    }
+
+    /// Decide what to do after a single instruction step.
+    ///
+    /// Description:
+    ///
+    /// * `pc` - The program counter value after the step.
+    /// * `sp` - The stack pointer value after the step.
+    ///
+    /// The step stops when the program counter leaves the source line within the same function,
+    /// or when the function returns.
+    /// A call is detected by the program counter leaving the function while the stack pointer is
+    /// below the CFA, or by a jump to the start of the function for recursive calls.
+    /// Stepping into filtered code, see `StepFilter`, should be handled as a step over.
+    pub fn next(&self, pc: u32, sp: u32) -> StepAction {
+        let pc = pc as u64;
+        if self.line.contains(&pc) {
+            return StepAction::Step;
+        }
+
+        let returned = sp >= self.cfa;
+        let called = !returned && (!self.function.contains(&pc) || pc == self.function.start);
+        match (called, self.kind) {
+            (true, StepKind::Over) => StepAction::StepOut,
+            _ => StepAction::Stop,
+        }
+    }
+}
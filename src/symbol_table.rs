@@ -0,0 +1,188 @@
+use anyhow::{anyhow, Result};
+use log::error;
+
+/// The kind of object a symbol describes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    /// The symbol is a function.
+    Function,
+
+    /// The symbol is a data object, e.g. a static variable.
+    Object,
+
+    /// The symbol is something else, e.g. a section or a file.
+    Other,
+}
+
+/// A symbol from a ELF symbol table.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    /// The name of the symbol, it is often mangled.
+    pub name: String,
+
+    /// The address of the symbol.
+    /// The lowest bit used to indicate Thumb mode is cleared for function symbols.
+    pub address: u64,
+
+    /// The size of the symbol in bytes, 0 if it is not known.
+    pub size: u64,
+
+    /// The kind of object the symbol describes.
+    pub kind: SymbolKind,
+}
+
+/// A symbol table that is used when there is no DWARF debug information for a address.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    /// The symbols sorted by address.
+    pub symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    /// Creates a new `SymbolTable`.
+    ///
+    /// Description:
+    ///
+    /// * `symbols` - The symbols of the program, they do not need to be sorted.
+    pub fn new(mut symbols: Vec<Symbol>) -> SymbolTable {
+        symbols.sort_by_key(|s| s.address);
+        SymbolTable { symbols }
+    }
+
+    /// Parse the symbols from the data of a ELF symbol table section.
+    ///
+    /// Description:
+    ///
+    /// * `symtab` - The data of the `.symtab` or `.dynsym` section.
+    /// * `strtab` - The data of the string table section linked to the symbol table section,
+    ///   `.strtab` or `.dynstr`.
+    /// * `is_64` - `true` if the ELF file is 64 bit.
+    /// * `little_endian` - `true` if the ELF file is little endian.
+    ///
+    /// Undefined symbols and symbols without a name are skipped.
+    pub fn parse_elf_symbols(
+        symtab: &[u8],
+        strtab: &[u8],
+        is_64: bool,
+        little_endian: bool,
+    ) -> Result<SymbolTable> {
+        let entry_size = if is_64 { 24 } else { 16 };
+        let entries = symtab.chunks_exact(entry_size);
+        if !entries.remainder().is_empty() {
+            error!("Symbol table size is not a multiple of the entry size");
+            return Err(anyhow!(
+                "Symbol table size is not a multiple of the entry size"
+            ));
+        }
+
+        let mut symbols = vec![];
+        for entry in entries {
+            let read = |offset: usize, size: usize| -> u64 {
+                let mut bytes = [0; 8];
+                bytes[..size].copy_from_slice(&entry[offset..offset + size]);
+                if !little_endian {
+                    bytes[..size].reverse();
+                }
+                u64::from_le_bytes(bytes)
+            };
+
+            // The field order differs between the 32 and 64 bit formats.
+            let (name, value, size, info, section_index) = if is_64 {
+                (read(0, 4), read(8, 8), read(16, 8), entry[4], read(6, 2))
+            } else {
+                (read(0, 4), read(4, 4), read(8, 4), entry[12], read(14, 2))
+            };
+
+            // Skip undefined symbols.
+            if section_index == 0 {
+                continue;
+            }
+
+            let name = read_string(strtab, name as usize)?;
+            if name.is_empty() {
+                continue;
+            }
+
+            let kind = match info & 0xf {
+                1 => SymbolKind::Object,
+                2 => SymbolKind::Function,
+                _ => SymbolKind::Other,
+            };
+            let address = match kind {
+                SymbolKind::Function => value & !1,
+                _ => value,
+            };
+
+            symbols.push(Symbol {
+                name,
+                address,
+                size,
+                kind,
+            });
+        }
+
+        Ok(SymbolTable::new(symbols))
+    }
+
+    /// Add the symbols of another symbol table, e.g. the symbols from `.dynsym`.
+    ///
+    /// Description:
+    ///
+    /// * `other` - The symbol table to add the symbols from.
+    pub fn extend(&mut self, other: SymbolTable) {
+        self.symbols.extend(other.symbols);
+        self.symbols.sort_by_key(|s| s.address);
+    }
+
+    /// Find the function that contains a address.
+    ///
+    /// Description:
+    ///
+    /// * `address` - A machine code address.
+    ///
+    /// Will return the function symbol and the offset of the address from the start of it.
+    /// A symbol without a size is assumed to reach until the next function symbol.
+    pub fn find_function(&self, address: u64) -> Option<(&Symbol, u64)> {
+        let functions: Vec<&Symbol> = self
+            .symbols
+            .iter()
+            .filter(|s| s.kind == SymbolKind::Function)
+            .collect();
+        let index = functions.partition_point(|s| s.address <= address);
+        if index == 0 {
+            return None;
+        }
+
+        let symbol = functions[index - 1];
+        let offset = address - symbol.address;
+        if symbol.size != 0 && offset >= symbol.size {
+            return None;
+        }
+
+        Some((symbol, offset))
+    }
+
+    /// Find a symbol by its name.
+    ///
+    /// Description:
+    ///
+    /// * `name` - The name of the symbol.
+    ///
+    /// This is used to resolve function breakpoints for functions without debug information.
+    pub fn find_by_name(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|s| s.name == name)
+    }
+}
+
+/// Read a null terminated string from a ELF string table.
+fn read_string(strtab: &[u8], offset: usize) -> Result<String> {
+    let bytes = match strtab.get(offset..) {
+        Some(val) => val,
+        None => {
+            error!("String table offset {} is out of bounds", offset);
+            return Err(anyhow!("String table offset {} is out of bounds", offset));
+        }
+    };
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    Ok(String::from_utf8_lossy(&bytes[..end]).to_string())
+}
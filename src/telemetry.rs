@@ -0,0 +1,196 @@
+use crate::call_stack::MemoryAccess;
+use crate::registers::Registers;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Counts the reads issued to the memory of the debug target.
+pub struct CountingMemory<'a, M: MemoryAccess> {
+    /// The number of reads.
+    pub reads: u64,
+
+    /// The number of bytes read.
+    pub bytes: u64,
+
+    /// The memory that the reads are forwarded to.
+    memory: &'a mut M,
+}
+
+impl<'a, M: MemoryAccess> CountingMemory<'a, M> {
+    /// Creates a new `CountingMemory`.
+    ///
+    /// Description:
+    ///
+    /// * `memory` - Used to read the memory of the debugged target.
+    pub fn new(memory: &'a mut M) -> CountingMemory<'a, M> {
+        CountingMemory {
+            reads: 0,
+            bytes: 0,
+            memory,
+        }
+    }
+}
+
+impl<'a, M: MemoryAccess> MemoryAccess for CountingMemory<'a, M> {
+    fn get_address(&mut self, address: &u32, num_bytes: usize) -> Option<Vec<u8>> {
+        self.reads += 1;
+        self.bytes += num_bytes as u64;
+        self.memory.get_address(address, num_bytes)
+    }
+}
+
+/// The accumulated statistics of one kind of operation, e.g. unwinding.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct OperationStats {
+    /// The number of times the operation was done.
+    pub count: u64,
+
+    /// The total time spent in the operation.
+    pub total: Duration,
+
+    /// The longest time the operation took.
+    pub max: Duration,
+
+    /// The total number of memory reads the operation issued.
+    pub memory_reads: u64,
+
+    /// The total number of register reads the operation issued.
+    pub register_reads: u64,
+}
+
+/// Statistics of how long the operations took and how many accesses to the debug target they
+/// issued, e.g. for a `stats` command.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Telemetry {
+    /// The statistics by operation name, e.g. `unwind`, `stack frame` or `evaluate x`.
+    pub operations: BTreeMap<String, OperationStats>,
+}
+
+impl Telemetry {
+    /// Creates a new empty `Telemetry`.
+    pub fn new() -> Telemetry {
+        Telemetry::default()
+    }
+
+    /// Record one run of a operation.
+    ///
+    /// Description:
+    ///
+    /// * `operation` - The name of the operation.
+    /// * `duration` - The time the operation took.
+    /// * `memory_reads` - The number of memory reads the operation issued.
+    /// * `register_reads` - The number of register reads the operation issued.
+    pub fn record(
+        &mut self,
+        operation: &str,
+        duration: Duration,
+        memory_reads: u64,
+        register_reads: u64,
+    ) {
+        let stats = self.operations.entry(operation.to_string()).or_default();
+        stats.count += 1;
+        stats.total += duration;
+        stats.max = stats.max.max(duration);
+        stats.memory_reads += memory_reads;
+        stats.register_reads += register_reads;
+    }
+
+    /// Run and record a operation that reads the memory of the debug target.
+    ///
+    /// Description:
+    ///
+    /// * `operation` - The name of the operation.
+    /// * `registers` - The register values the operation reads, the reads are counted.
+    /// * `memory` - Used to read the memory of the debugged target, the reads are counted.
+    /// * `f` - The operation.
+    ///
+    /// The time is not measured on `wasm32-unknown-unknown`, where there is no clock.
+    pub fn measure<M: MemoryAccess, T, F: FnOnce(&mut CountingMemory<M>) -> T>(
+        &mut self,
+        operation: &str,
+        registers: &Registers,
+        memory: &mut M,
+        f: F,
+    ) -> T {
+        let mut counting = CountingMemory::new(memory);
+        let register_reads = registers.reads();
+        let start = now();
+        let result = f(&mut counting);
+        let duration = match (start, now()) {
+            (Some(start), Some(end)) => end.duration_since(start),
+            _ => Duration::ZERO,
+        };
+        self.record(
+            operation,
+            duration,
+            counting.reads,
+            registers.reads() - register_reads,
+        );
+        result
+    }
+
+    /// Remove all the statistics.
+    pub fn clear(&mut self) {
+        self.operations.clear();
+    }
+
+    /// Describe the statistics, one line per operation.
+    pub fn summary(&self) -> String {
+        let mut result = String::new();
+        for (name, stats) in &self.operations {
+            let average = match stats.count {
+                0 => Duration::ZERO,
+                count => stats.total / count as u32,
+            };
+            result = format!(
+                "{}{}: count={} total={:?} avg={:?} max={:?} memory_reads={} register_reads={}\n",
+                result,
+                name,
+                stats.count,
+                stats.total,
+                average,
+                stats.max,
+                stats.memory_reads,
+                stats.register_reads
+            );
+        }
+        result
+    }
+}
+
+/// Get the current time, `Instant::now` panics on `wasm32-unknown-unknown` so it is `None` there.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn now() -> Option<Instant> {
+    Some(Instant::now())
+}
+
+/// Get the current time, `Instant::now` panics on `wasm32-unknown-unknown` so it is `None` there.
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn now() -> Option<Instant> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measure_counts_reads() {
+        let mut registers = Registers::default();
+        registers.add_register_value(0, 1);
+        let mut memory = |_: &u32, num_bytes: usize| Some(vec![0; num_bytes]);
+
+        let mut telemetry = Telemetry::new();
+        let registers_clone = registers.clone();
+        telemetry.measure("unwind", &registers, &mut memory, |memory| {
+            memory.get_address(&0x2000_0000, 4);
+            registers_clone.get_register_value(&0);
+            registers_clone.register_values().count()
+        });
+
+        let stats = telemetry.operations["unwind"];
+        assert_eq!(stats.count, 1);
+        assert_eq!(stats.memory_reads, 1);
+        // The clone shares the read count with the measured registers.
+        assert_eq!(stats.register_reads, 2);
+    }
+}
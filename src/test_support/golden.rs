@@ -0,0 +1,182 @@
+use super::parse_fixture;
+use crate::call_stack::{unwind_call_stack, CallFrame};
+use anyhow::{anyhow, Result};
+use gimli::{DebugFrame, EndianSlice, LittleEndian};
+use log::error;
+use std::path::Path;
+
+/// The environment variable that makes `assert_golden` write the golden files instead of
+/// comparing against them.
+pub const UPDATE_GOLDEN: &str = "UPDATE_GOLDEN";
+
+/// Find the data of a section in a little endian ELF file.
+///
+/// Description:
+///
+/// * `elf` - The bytes of the ELF file.
+/// * `name` - The name of the section, e.g. `.debug_frame`.
+///
+/// Will return `None` if there is no section with the name.
+pub fn elf_section<'a>(elf: &'a [u8], name: &str) -> Result<Option<&'a [u8]>> {
+    let read = |offset: usize, size: usize| -> Result<usize> {
+        let field = match elf.get(offset..offset + size) {
+            Some(val) => val,
+            None => {
+                error!("ELF file is truncated at offset {}", offset);
+                return Err(anyhow!("ELF file is truncated at offset {}", offset));
+            }
+        };
+        let mut bytes = [0; 8];
+        bytes[..size].copy_from_slice(field);
+        Ok(u64::from_le_bytes(bytes) as usize)
+    };
+
+    if !elf.starts_with(b"\x7fELF") || elf.get(5) != Some(&1) {
+        error!("Not a little endian ELF file");
+        return Err(anyhow!("Not a little endian ELF file"));
+    }
+
+    // The field offsets and sizes differs between the 32 and 64 bit formats.
+    let is_64 = elf.get(4) == Some(&2);
+    let (shoff, shentsize, shnum, shstrndx) = match is_64 {
+        true => (
+            read(0x28, 8)?,
+            read(0x3a, 2)?,
+            read(0x3c, 2)?,
+            read(0x3e, 2)?,
+        ),
+        false => (
+            read(0x20, 4)?,
+            read(0x2e, 2)?,
+            read(0x30, 2)?,
+            read(0x32, 2)?,
+        ),
+    };
+    let section = |index: usize| -> Result<(usize, usize, usize)> {
+        let header = shoff + index * shentsize;
+        match is_64 {
+            true => Ok((
+                read(header, 4)?,
+                read(header + 0x18, 8)?,
+                read(header + 0x20, 8)?,
+            )),
+            false => Ok((
+                read(header, 4)?,
+                read(header + 0x10, 4)?,
+                read(header + 0x14, 4)?,
+            )),
+        }
+    };
+
+    let (_, strtab_offset, _) = section(shstrndx)?;
+    for index in 0..shnum {
+        let (name_offset, offset, size) = section(index)?;
+        let start = strtab_offset + name_offset;
+        let section_name = elf
+            .get(start..)
+            .and_then(|bytes| bytes.split(|b| *b == 0).next())
+            .unwrap_or(&[]);
+        if section_name == name.as_bytes() {
+            return match elf.get(offset..offset + size) {
+                Some(data) => Ok(Some(data)),
+                None => {
+                    error!("Section {} is outside the ELF file", name);
+                    Err(anyhow!("Section {} is outside the ELF file", name))
+                }
+            };
+        }
+    }
+
+    Ok(None)
+}
+
+/// Will unwind the call stack of a captured debug target state.
+///
+/// Description:
+///
+/// * `elf` - The bytes of the little endian ELF file of the program.
+/// * `fixture` - The register and memory snapshot, in the format read by `parse_fixture`.
+pub fn unwind_snapshot(elf: &[u8], fixture: &str) -> Result<Vec<CallFrame>> {
+    let data = match elf_section(elf, ".debug_frame")? {
+        Some(val) => val,
+        None => {
+            error!("The ELF file has no .debug_frame section");
+            return Err(anyhow!("The ELF file has no .debug_frame section"));
+        }
+    };
+    let mut debug_frame = DebugFrame::new(data, LittleEndian);
+    debug_frame.set_address_size(4);
+
+    let (mut memory, registers) = parse_fixture(fixture)?;
+    unwind_call_stack::<EndianSlice<LittleEndian>, _>(
+        registers.to_registers(),
+        &mut memory,
+        &debug_frame,
+    )
+}
+
+/// Format call frames in the stable text format used by the golden files.
+///
+/// Description:
+///
+/// * `call_stack` - The unwound call frames.
+///
+/// Every frame is one line with its code location, CFA, function address range and the
+/// registers that have a value.
+pub fn format_call_stack(call_stack: &[CallFrame]) -> String {
+    let mut result = String::new();
+    for (index, frame) in call_stack.iter().enumerate() {
+        let cfa = match frame.cfa {
+            Some(val) => format!("{:#010x}", val),
+            None => "none".to_string(),
+        };
+        let registers: Vec<String> = frame
+            .registers
+            .iter()
+            .enumerate()
+            .filter_map(|(i, value)| value.map(|v| format!("r{}={:#010x}", i, v)))
+            .collect();
+        result = format!(
+            "{}#{} pc={:#010x} cfa={} function=[{:#010x}, {:#010x}) {}\n",
+            result,
+            index,
+            frame.code_location,
+            cfa,
+            frame.start_address,
+            frame.end_address,
+            registers.join(" ")
+        );
+    }
+    result
+}
+
+/// Assert that a result matches a golden file.
+///
+/// Description:
+///
+/// * `actual` - The actual result.
+/// * `path` - The path to the golden file.
+///
+/// The golden file is written instead when the environment variable `UPDATE_GOLDEN` is set.
+/// Panics if the result differs from the golden file or if the golden file can not be read.
+pub fn assert_golden(actual: &str, path: &Path) {
+    if std::env::var_os(UPDATE_GOLDEN).is_some() {
+        std::fs::write(path, actual).expect("Could not write the golden file");
+        return;
+    }
+
+    let expected = std::fs::read_to_string(path).unwrap_or_else(|err| {
+        panic!(
+            "Could not read the golden file {}: {}, set {} to create it",
+            path.display(),
+            err,
+            UPDATE_GOLDEN
+        )
+    });
+    assert_eq!(
+        expected,
+        actual,
+        "The result differs from the golden file {}",
+        path.display()
+    );
+}
@@ -0,0 +1,68 @@
+use crate::call_stack::MemoryAccess;
+use std::collections::{BTreeMap, HashMap};
+
+/// A `MemoryAccess` implementation backed by scripted memory contents, used to test code that
+/// drives the evaluation without a debug target.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedMemory {
+    /// The memory contents by address.
+    pub bytes: BTreeMap<u32, u8>,
+
+    /// The address and number of bytes of every read, in order.
+    pub reads: Vec<(u32, usize)>,
+}
+
+impl ScriptedMemory {
+    /// Creates a new `ScriptedMemory` without any memory contents.
+    pub fn new() -> ScriptedMemory {
+        ScriptedMemory {
+            bytes: BTreeMap::new(),
+            reads: vec![],
+        }
+    }
+
+    /// Creates a new `ScriptedMemory` from a map of start addresses and bytes.
+    ///
+    /// Description:
+    ///
+    /// * `map` - The bytes stored at each start address.
+    pub fn from_map(map: &HashMap<u32, Vec<u8>>) -> ScriptedMemory {
+        let mut memory = ScriptedMemory::new();
+        for (address, bytes) in map {
+            memory.write(*address, bytes);
+        }
+        memory
+    }
+
+    /// Store bytes in the memory.
+    ///
+    /// Description:
+    ///
+    /// * `address` - The address of the first byte.
+    /// * `bytes` - The bytes.
+    pub fn write(&mut self, address: u32, bytes: &[u8]) {
+        for (i, byte) in bytes.iter().enumerate() {
+            self.bytes.insert(address + i as u32, *byte);
+        }
+    }
+
+    /// Store a little endian 32 bit value in the memory.
+    ///
+    /// Description:
+    ///
+    /// * `address` - The address of the value.
+    /// * `value` - The value.
+    pub fn write_u32(&mut self, address: u32, value: u32) {
+        self.write(address, &value.to_le_bytes());
+    }
+}
+
+impl MemoryAccess for ScriptedMemory {
+    /// Reads the scripted bytes, `None` is returned if any of the bytes is not scripted.
+    fn get_address(&mut self, address: &u32, num_bytes: usize) -> Option<Vec<u8>> {
+        self.reads.push((*address, num_bytes));
+        (0..num_bytes)
+            .map(|i| self.bytes.get(&(address + i as u32)).copied())
+            .collect()
+    }
+}
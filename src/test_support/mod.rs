@@ -0,0 +1,123 @@
+/// Contains the golden file infrastructure for call stack unwinding regression tests.
+pub mod golden;
+
+/// Contains a `MemoryAccess` implementation that is backed by scripted memory contents.
+pub mod memory;
+
+/// Contains a `Registers` wrapper that is loaded from a map or a fixture.
+pub mod registers;
+
+pub use memory::ScriptedMemory;
+pub use registers::ScriptedRegisters;
+
+use anyhow::{anyhow, Result};
+use log::error;
+
+/// Will parse a text fixture with the memory contents and register values of a debug target.
+///
+/// Description:
+///
+/// * `text` - The fixture.
+///
+/// Every line is one of:
+///
+/// * `memory <address>: <byte> <byte> ...` - The bytes stored at the address, in hexadecimal.
+/// * `register <number> = <value>` - The value of a register.
+/// * `# ...` - A comment, empty lines are also ignored.
+///
+/// Addresses and values are written in decimal or in hexadecimal with the prefix `0x`.
+pub fn parse_fixture(text: &str) -> Result<(ScriptedMemory, ScriptedRegisters)> {
+    let mut memory = ScriptedMemory::new();
+    let mut registers = ScriptedRegisters::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let invalid = || {
+            error!("Invalid fixture line {}: {}", index + 1, line);
+            anyhow!("Invalid fixture line {}: {}", index + 1, line)
+        };
+        let (kind, rest) = line.split_once(' ').ok_or_else(invalid)?;
+        match kind {
+            "memory" => {
+                let (address, bytes) = rest.split_once(':').ok_or_else(invalid)?;
+                let address = parse_number(address).ok_or_else(invalid)?;
+                let bytes = bytes
+                    .split_whitespace()
+                    .map(|b| u8::from_str_radix(b.trim_start_matches("0x"), 16).ok())
+                    .collect::<Option<Vec<u8>>>()
+                    .ok_or_else(invalid)?;
+                memory.write(address as u32, &bytes);
+            }
+            "register" => {
+                let (register, value) = rest.split_once('=').ok_or_else(invalid)?;
+                let register = parse_number(register).ok_or_else(invalid)?;
+                let value = parse_number(value).ok_or_else(invalid)?;
+                registers.set(register as u16, value as u32);
+            }
+            _ => return Err(invalid()),
+        };
+    }
+
+    Ok((memory, registers))
+}
+
+/// Will format a captured debug target state as a text fixture that `parse_fixture` can read.
+///
+/// Description:
+///
+/// * `registers` - The register numbers and values.
+/// * `memory` - The captured memory regions, e.g. the stack, with their start addresses.
+///
+/// This is used to capture new fixtures from a halted debug target.
+pub fn format_fixture(registers: &[(u16, u32)], memory: &[(u32, &[u8])]) -> String {
+    let mut result = String::new();
+    for (register, value) in registers {
+        result = format!("{}register {} = {:#010x}\n", result, register, value);
+    }
+    for (address, bytes) in memory {
+        // At most 16 bytes per line to keep the fixture readable.
+        for (i, chunk) in bytes.chunks(16).enumerate() {
+            let bytes: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            result = format!(
+                "{}memory {:#010x}: {}\n",
+                result,
+                *address as usize + 16 * i,
+                bytes.join(" ")
+            );
+        }
+    }
+    result
+}
+
+/// Parse a decimal or `0x` prefixed hexadecimal number.
+fn parse_number(text: &str) -> Option<u64> {
+    let text = text.trim();
+    match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::call_stack::MemoryAccess;
+
+    #[test]
+    fn format_fixture_round_trip() {
+        let bytes: Vec<u8> = (0..20).collect();
+        let fixture = format_fixture(&[(13, 0x2000_0ff0)], &[(0x2000_0ff0, &bytes)]);
+
+        let (mut memory, registers) = parse_fixture(&fixture).unwrap();
+        let registers = registers.to_registers();
+        assert_eq!(registers.get_register_value(&13), Some(&0x2000_0ff0));
+        assert_eq!(
+            memory.get_address(&0x2000_1000, 4),
+            Some(vec![16, 17, 18, 19])
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use crate::registers::Registers;
+use std::collections::HashMap;
+
+/// The DWARF register number of the ARM stack pointer.
+const ARM_SP: usize = 13;
+
+/// The DWARF register number of the ARM link register.
+const ARM_LR: usize = 14;
+
+/// The DWARF register number of the ARM program counter.
+const ARM_PC: usize = 15;
+
+/// A `Registers` wrapper that is loaded from a map or a fixture, used to test code that drives
+/// the evaluation without a debug target.
+///
+/// The program counter, link register and stack pointer use the ARM register numbers.
+#[derive(Debug, Clone)]
+pub struct ScriptedRegisters {
+    /// The wrapped registers.
+    pub registers: Registers,
+}
+
+impl Default for ScriptedRegisters {
+    fn default() -> ScriptedRegisters {
+        ScriptedRegisters::new()
+    }
+}
+
+impl ScriptedRegisters {
+    /// Creates a new `ScriptedRegisters` without any register values.
+    pub fn new() -> ScriptedRegisters {
+        let mut registers = Registers::default();
+        registers.program_counter_register = Some(ARM_PC);
+        registers.link_register = Some(ARM_LR);
+        registers.stack_pointer_register = Some(ARM_SP);
+        ScriptedRegisters { registers }
+    }
+
+    /// Creates a new `ScriptedRegisters` from a map of register numbers and values.
+    ///
+    /// Description:
+    ///
+    /// * `map` - The register values by register number.
+    pub fn from_map(map: &HashMap<u16, u32>) -> ScriptedRegisters {
+        let mut registers = ScriptedRegisters::new();
+        for (register, value) in map {
+            registers.set(*register, *value);
+        }
+        registers
+    }
+
+    /// Set the value of a register.
+    ///
+    /// Description:
+    ///
+    /// * `register` - The register number.
+    /// * `value` - The value of the register.
+    pub fn set(&mut self, register: u16, value: u32) {
+        self.registers.add_register_value(register, value);
+    }
+
+    /// Get a copy of the wrapped registers.
+    pub fn to_registers(&self) -> Registers {
+        self.registers.clone()
+    }
+}
@@ -0,0 +1,176 @@
+use crate::call_stack::MemoryAccess;
+use crate::memory_view::read_word;
+use anyhow::{anyhow, Result};
+use gimli::RunTimeEndian;
+use log::error;
+
+/// The address of the Cortex-M Debug Exception and Monitor Control Register.
+pub const DEMCR_ADDRESS: u32 = 0xE000_EDFC;
+
+/// The `TRCENA` bit in `DEMCR`, it has to be set for the DWT unit to work.
+pub const DEMCR_TRCENA: u32 = 1 << 24;
+
+/// The address of the DWT Control Register.
+pub const DWT_CTRL_ADDRESS: u32 = 0xE000_1000;
+
+/// The `CYCCNTENA` bit in `DWT_CTRL`, it has to be set for the cycle counter to count.
+pub const DWT_CTRL_CYCCNTENA: u32 = 1;
+
+/// The address of the DWT Cycle Count Register.
+pub const DWT_CYCCNT_ADDRESS: u32 = 0xE000_1004;
+
+/// Read the cycle counter of the debug target.
+///
+/// Description:
+///
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `endian` - The endianness of the debug target.
+///
+/// The counter has to be enabled by setting `DEMCR_TRCENA` and `DWT_CTRL_CYCCNTENA`, which is
+/// left to the caller since `MemoryAccess` can only read.
+pub fn read_cyccnt<M: MemoryAccess>(mem: &mut M, endian: RunTimeEndian) -> Result<u32> {
+    match mem
+        .get_address(&DWT_CYCCNT_ADDRESS, 4)
+        .and_then(|val| read_word(&val, endian))
+    {
+        Some(val) => Ok(val),
+        None => {
+            error!("Could not read the DWT cycle counter");
+            Err(anyhow!("Could not read the DWT cycle counter"))
+        }
+    }
+}
+
+/// A measured number of cycles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Measurement {
+    /// The number of elapsed cycles.
+    pub cycles: u64,
+
+    /// The clock frequency of the core in Hz, used to convert the cycles to time.
+    pub core_clock_hz: u64,
+}
+
+impl Measurement {
+    /// Get the elapsed time in microseconds.
+    pub fn micros(&self) -> f64 {
+        match self.core_clock_hz {
+            0 => 0.0,
+            hz => self.cycles as f64 * 1_000_000.0 / hz as f64,
+        }
+    }
+}
+
+/// Measures the cycles between the debug target leaving a breakpoint and hitting the next one.
+#[derive(Debug, Clone)]
+pub struct CycleTimer {
+    /// The clock frequency of the core in Hz.
+    pub core_clock_hz: u64,
+
+    /// The value of the cycle counter when the debug target was resumed.
+    start: Option<u32>,
+}
+
+impl CycleTimer {
+    /// Creates a new `CycleTimer`.
+    ///
+    /// Description:
+    ///
+    /// * `core_clock_hz` - The clock frequency of the core in Hz.
+    pub fn new(core_clock_hz: u64) -> CycleTimer {
+        CycleTimer {
+            core_clock_hz,
+            start: None,
+        }
+    }
+
+    /// Record the cycle counter, this should be done right before the debug target is resumed.
+    ///
+    /// Description:
+    ///
+    /// * `cyccnt` - The value of the cycle counter.
+    pub fn resume(&mut self, cyccnt: u32) {
+        self.start = Some(cyccnt);
+    }
+
+    /// Measure the cycles since the debug target was resumed, this should be done when it halts.
+    ///
+    /// Description:
+    ///
+    /// * `cyccnt` - The value of the cycle counter.
+    ///
+    /// Will return `None` if the debug target has not been resumed since the last halt.
+    /// The counter is 32 bits, so a measurement is only correct if it is less than one wrap of the
+    /// counter.
+    pub fn halt(&mut self, cyccnt: u32) -> Option<Measurement> {
+        let start = self.start.take()?;
+        Some(Measurement {
+            cycles: cyccnt.wrapping_sub(start) as u64,
+            core_clock_hz: self.core_clock_hz,
+        })
+    }
+}
+
+/// Measures the cycles between halting in two named functions, e.g. from `start_frame` to
+/// `end_frame`.
+#[derive(Debug, Clone)]
+pub struct RegionTimer {
+    /// The function that starts the region.
+    pub start_function: String,
+
+    /// The function that ends the region.
+    pub end_function: String,
+
+    /// The clock frequency of the core in Hz.
+    pub core_clock_hz: u64,
+
+    /// The completed measurements.
+    pub measurements: Vec<Measurement>,
+
+    /// The value of the cycle counter when the region was entered.
+    start: Option<u32>,
+}
+
+impl RegionTimer {
+    /// Creates a new `RegionTimer`.
+    ///
+    /// Description:
+    ///
+    /// * `start_function` - The function that starts the region.
+    /// * `end_function` - The function that ends the region.
+    /// * `core_clock_hz` - The clock frequency of the core in Hz.
+    pub fn new(start_function: &str, end_function: &str, core_clock_hz: u64) -> RegionTimer {
+        RegionTimer {
+            start_function: start_function.to_string(),
+            end_function: end_function.to_string(),
+            core_clock_hz,
+            measurements: vec![],
+            start: None,
+        }
+    }
+
+    /// Update the region with a halt of the debug target.
+    ///
+    /// Description:
+    ///
+    /// * `function` - The name of the function the debug target halted in.
+    /// * `cyccnt` - The value of the cycle counter.
+    ///
+    /// Will return the measurement if the halt completed the region.
+    pub fn on_halt(&mut self, function: &str, cyccnt: u32) -> Option<Measurement> {
+        if function == self.end_function {
+            if let Some(start) = self.start.take() {
+                let measurement = Measurement {
+                    cycles: cyccnt.wrapping_sub(start) as u64,
+                    core_clock_hz: self.core_clock_hz,
+                };
+                self.measurements.push(measurement);
+                return Some(measurement);
+            }
+        }
+        if function == self.start_function {
+            self.start = Some(cyccnt);
+        }
+        None
+    }
+}
@@ -0,0 +1,99 @@
+use crate::fault::FaultStatus;
+use crate::memory_view::MemoryView;
+use std::fmt;
+
+/// A stack frame in a crash triage report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriageFrame {
+    /// The name of the frame's function.
+    pub name: String,
+
+    /// The source location of the frame, e.g. `src/main.rs:42`, `None` if unknown.
+    pub location: Option<String>,
+
+    /// The code location of the frame.
+    pub code_location: u64,
+
+    /// The arguments and local variables as name and formatted value.
+    pub variables: Vec<(String, String)>,
+}
+
+/// Everything needed to understand a crash, bundled for attaching to a bug tracker.
+///
+/// The report is written as markdown by its `Display` implementation.
+#[derive(Debug, Clone, Default)]
+pub struct TriageReport {
+    /// Why the debug target halted, e.g. a panic message or `HardFault`.
+    pub stop_reason: String,
+
+    /// The decoded fault status registers, `None` if they could not be read.
+    pub fault: Option<FaultStatus>,
+
+    /// The register numbers and values at the halt.
+    pub registers: Vec<(u16, u32)>,
+
+    /// The stack trace, the inner most frame first.
+    pub frames: Vec<TriageFrame>,
+
+    /// The machine code around the program counter, `None` if it could not be read.
+    pub code: Option<MemoryView>,
+
+    /// The recent output of the program, e.g. from RTT or ITM, which is collected by the caller.
+    pub output: Option<String>,
+
+    /// The memory regions of the chip, one per line, which are added by the caller.
+    pub memory_map: Vec<String>,
+}
+
+impl fmt::Display for TriageReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# Crash report\n")?;
+        writeln!(f, "Stop reason: {}\n", self.stop_reason)?;
+
+        if let Some(fault) = &self.fault {
+            writeln!(f, "## Fault\n\n```\n{}\n```\n", fault)?;
+        }
+
+        writeln!(f, "## Registers\n\n```")?;
+        for (register, value) in &self.registers {
+            writeln!(f, "R{:<3} = {:#010x}", register, value)?;
+        }
+        writeln!(f, "```\n")?;
+
+        writeln!(f, "## Stack trace\n")?;
+        for (i, frame) in self.frames.iter().enumerate() {
+            write!(
+                f,
+                "{}. `{}` at {:#010x}",
+                i, frame.name, frame.code_location
+            )?;
+            match &frame.location {
+                Some(location) => writeln!(f, " in {}", location)?,
+                None => writeln!(f)?,
+            };
+            for (name, value) in &frame.variables {
+                writeln!(f, "    - `{} = {}`", name, value)?;
+            }
+        }
+        writeln!(f)?;
+
+        if let Some(code) = &self.code {
+            writeln!(
+                f,
+                "## Code around the program counter\n\n```\n{}```\n",
+                code
+            )?;
+        }
+        if let Some(output) = &self.output {
+            writeln!(f, "## Output\n\n```\n{}\n```\n", output.trim_end())?;
+        }
+        if !self.memory_map.is_empty() {
+            writeln!(
+                f,
+                "## Memory map\n\n```\n{}\n```",
+                self.memory_map.join("\n")
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -1,9 +1,10 @@
 use gimli::{
-    DebuggingInformationEntry, Dwarf, Error, Range, RangeIter, Reader, Unit, UnitOffset,
-    UnitSectionOffset,
+    DebugInfoOffset, DebuggingInformationEntry, Dwarf, Error, Range, RangeIter, Reader, Unit,
+    UnitOffset, UnitSectionOffset,
 };
 use log::error;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct DwarfOffset {
     pub section_offset: UnitSectionOffset,
     pub unit_offset: UnitOffset,
@@ -114,3 +115,31 @@ where
         None => Err(Error::MissingUnitDie),
     }
 }
+
+/// Find the compilation unit that contains a DIE, e.g. the target of a `DW_FORM_ref_addr`
+/// reference.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs Dwarf struct.
+/// * `offset` - The offset of the DIE into the `.debug_info` section.
+///
+/// Only the unit headers are read to find the unit, and only the unit that contains the DIE is
+/// parsed.
+/// Will return the unit and the offset of the DIE in it.
+pub fn get_die_unit<R>(
+    dwarf: &Dwarf<R>,
+    offset: DebugInfoOffset,
+) -> Result<(Unit<R>, UnitOffset), Error>
+where
+    R: Reader<Offset = usize>,
+{
+    let mut iter = dwarf.units();
+    while let Some(header) = iter.next()? {
+        if let Some(unit_offset) = offset.to_unit_offset(&header) {
+            return Ok((dwarf.unit(header)?, unit_offset));
+        }
+    }
+
+    Err(Error::NoEntryAtGivenOffset)
+}
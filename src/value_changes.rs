@@ -0,0 +1,95 @@
+use crate::call_stack::StackFrame;
+use crate::evaluate::evaluate::EvaluatorValue;
+use gimli::Reader;
+use std::collections::HashMap;
+
+/// The scope used for the watch expressions.
+pub const WATCH_SCOPE: &str = "watch";
+
+/// Tracks the values of the variables and watches between stops, so that the values that changed
+/// since the last stop can be highlighted.
+///
+/// The values are compared by their formatted text, which makes it possible to keep them without
+/// the `Reader` type of the debug information.
+#[derive(Debug, Clone, Default)]
+pub struct ValueChanges {
+    /// The values at the previous stop, by scope and variable path.
+    previous: HashMap<(String, String), String>,
+
+    /// The values seen since the current stop, by scope and variable path.
+    current: HashMap<(String, String), String>,
+}
+
+impl ValueChanges {
+    /// Creates a new empty `ValueChanges`.
+    pub fn new() -> ValueChanges {
+        ValueChanges::default()
+    }
+
+    /// Start a new stop, this should be done every time the debug target halts.
+    ///
+    /// The values seen since the last stop becomes the values that are compared against.
+    /// A value that was not looked at during the last stop keeps its value from the stop before
+    /// that.
+    pub fn next_stop(&mut self) {
+        let current = std::mem::take(&mut self.current);
+        self.previous.extend(current);
+    }
+
+    /// Remove all the tracked values, e.g. when the debug target is reset.
+    pub fn clear(&mut self) {
+        self.previous.clear();
+        self.current.clear();
+    }
+
+    /// Record a value and check if it changed since the previous stop.
+    ///
+    /// Description:
+    ///
+    /// * `scope` - The scope of the variable, e.g. the function name of the frame or
+    ///   `WATCH_SCOPE`.
+    /// * `path` - The path of the variable, e.g. `x` or `point.x` for a member.
+    /// * `value` - The current value of the variable.
+    ///
+    /// Will return `false` if the value was not seen at a previous stop.
+    pub fn changed<R: Reader<Offset = usize>>(
+        &mut self,
+        scope: &str,
+        path: &str,
+        value: &EvaluatorValue<R>,
+    ) -> bool {
+        let key = (scope.to_string(), path.to_string());
+        let text = value.to_string();
+        let changed = match self.previous.get(&key) {
+            Some(previous) => *previous != text,
+            None => false,
+        };
+        self.current.insert(key, text);
+        changed
+    }
+
+    /// Record the arguments and variables of a stack frame and check which changed since the
+    /// previous stop.
+    ///
+    /// Description:
+    ///
+    /// * `frame` - The stack frame, the function name is used as the scope.
+    ///
+    /// Will return the names of the arguments and variables followed by their changed flag, in
+    /// the order of the frame.
+    pub fn frame_changes<R: Reader<Offset = usize>>(
+        &mut self,
+        frame: &StackFrame<R>,
+    ) -> Vec<(String, bool)> {
+        let mut result = vec![];
+        for var in frame.arguments.iter().chain(frame.variables.iter()) {
+            let name = match &var.name {
+                Some(val) => val.clone(),
+                None => continue,
+            };
+            let changed = self.changed(&frame.name, &name, &var.value);
+            result.push((name, changed));
+        }
+        result
+    }
+}
@@ -0,0 +1,57 @@
+use crate::evaluate::evaluate::EvaluatorValue;
+use gimli::Reader;
+
+/// The results of earlier print and evaluate requests, which can be referred to in later
+/// expressions as `$1`, `$2` and so on, `$` is the last value.
+#[derive(Debug, Clone)]
+pub struct ValueHistory<R: Reader<Offset = usize>> {
+    /// The recorded values, `$1` is the first value.
+    pub values: Vec<EvaluatorValue<R>>,
+}
+
+impl<R: Reader<Offset = usize>> Default for ValueHistory<R> {
+    fn default() -> ValueHistory<R> {
+        ValueHistory::new()
+    }
+}
+
+impl<R: Reader<Offset = usize>> ValueHistory<R> {
+    /// Creates a new empty `ValueHistory`.
+    pub fn new() -> ValueHistory<R> {
+        ValueHistory { values: vec![] }
+    }
+
+    /// Record a value.
+    ///
+    /// Description:
+    ///
+    /// * `value` - The result of a print or evaluate request.
+    ///
+    /// Will return the history number of the value, e.g. `1` for `$1`.
+    pub fn record(&mut self, value: EvaluatorValue<R>) -> usize {
+        self.values.push(value);
+        self.values.len()
+    }
+
+    /// Get a value by its history variable name.
+    ///
+    /// Description:
+    ///
+    /// * `name` - The name of the history variable, e.g. `$1`, or `$` for the last value.
+    ///
+    /// Will return `None` if the name is not a history variable or the value does not exist.
+    pub fn get(&self, name: &str) -> Option<&EvaluatorValue<R>> {
+        match name.strip_prefix('$')? {
+            "" => self.values.last(),
+            number => {
+                let index: usize = number.parse().ok()?;
+                self.values.get(index.checked_sub(1)?)
+            }
+        }
+    }
+
+    /// Remove all the recorded values.
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+}
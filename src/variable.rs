@@ -1,10 +1,12 @@
 use gimli::{
     AttributeValue::{DebugInfoRef, DebugStrRef, Exprloc, LocationListsRef, UnitRef},
-    DebuggingInformationEntry, Dwarf, Reader, Unit, UnitOffset, UnitSectionOffset,
+    DebuggingInformationEntry, Dwarf, Endianity, Reader, RunTimeEndian, Unit, UnitOffset,
+    UnitSectionOffset,
 };
 
+use crate::dwarf_index::DwarfIndex;
 use crate::evaluate::attributes;
-use crate::evaluate::evaluate;
+use crate::evaluate::{evaluate, evaluate_pieces};
 use crate::registers::Registers;
 use crate::source_information::SourceInformation;
 use crate::utils::in_range;
@@ -52,6 +54,45 @@ impl<R: Reader<Offset = usize>> Variable<R> {
         dwarf_offset: DwarfOffset,
         frame_base: Option<u64>,
         cwd: &str,
+    ) -> Result<Variable<R>> {
+        Variable::read_variable::<M, false>(dwarf, registers, memory, dwarf_offset, frame_base, cwd)
+    }
+
+    /// Retrieve the variables debug information, the memory of the value is read once.
+    ///
+    /// Description:
+    ///
+    /// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+    /// * `registers` - A reference to the `Registers` struct.
+    /// * `memory` - A reference to a struct that implements the `MemoryAccess` trait.
+    /// * `dwarf_offset` - The offset to the DIE representing the variable.
+    /// * `frame_base` - The value of the frame base, often needed to evaluate the variable.
+    /// * `cwd` - The work directory of the program being debugged.
+    ///
+    /// Used for variables mapped over read sensitive peripheral registers, where reading every
+    /// member separately would have side effects, see
+    /// `EvaluatorValue::evaluate_variable_with_type_snapshot`.
+    pub fn get_variable_snapshot<M: MemoryAccess>(
+        dwarf: &Dwarf<R>,
+        registers: &Registers,
+        memory: &mut M,
+        dwarf_offset: DwarfOffset,
+        frame_base: Option<u64>,
+        cwd: &str,
+    ) -> Result<Variable<R>> {
+        Variable::read_variable::<M, true>(dwarf, registers, memory, dwarf_offset, frame_base, cwd)
+    }
+
+    /// Retrieve the variables debug information, see `get_variable`.
+    ///
+    /// `SNAPSHOT` selects if the memory of the value is read once, see `get_variable_snapshot`.
+    fn read_variable<M: MemoryAccess, const SNAPSHOT: bool>(
+        dwarf: &Dwarf<R>,
+        registers: &Registers,
+        memory: &mut M,
+        dwarf_offset: DwarfOffset,
+        frame_base: Option<u64>,
+        cwd: &str,
     ) -> Result<Variable<R>> {
         // Get the program counter.
         let pc: u32 = *registers
@@ -135,17 +176,31 @@ impl<R: Reader<Offset = usize>> Variable<R> {
 
         info!("has type");
 
-        let value = evaluate(
-            dwarf,
-            &unit,
-            pc,
-            expression,
-            frame_base,
-            Some(&type_unit),
-            Some(&type_die),
-            registers,
-            memory,
-        )?;
+        let value = match SNAPSHOT {
+            true => {
+                let pieces =
+                    evaluate_pieces(dwarf, &unit, pc, expression, frame_base, registers, memory)?;
+                EvaluatorValue::evaluate_variable_with_type_snapshot(
+                    dwarf,
+                    registers,
+                    memory,
+                    &pieces,
+                    type_section_offset,
+                    type_unit_offset,
+                )?
+            }
+            false => evaluate(
+                dwarf,
+                &unit,
+                pc,
+                expression,
+                frame_base,
+                Some(&type_unit),
+                Some(&type_die),
+                registers,
+                memory,
+            )?,
+        };
 
         Ok(Variable {
             name,
@@ -153,6 +208,40 @@ impl<R: Reader<Offset = usize>> Variable<R> {
             source,
         })
     }
+
+    /// Create a optimized out variable with the name and source location from its DIE, used
+    /// when the value of the variable could not be evaluated.
+    ///
+    /// Description:
+    ///
+    /// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+    /// * `dwarf_offset` - The offset to the DIE representing the variable.
+    /// * `cwd` - The work directory of the program being debugged.
+    pub fn optimized_out(
+        dwarf: &Dwarf<R>,
+        dwarf_offset: DwarfOffset,
+        cwd: &str,
+    ) -> Result<Variable<R>> {
+        let header = dwarf.debug_info.header_from_offset(
+            match dwarf_offset.section_offset.as_debug_info_offset() {
+                Some(val) => val,
+                None => {
+                    error!("Could not convert section offset into debug info offset");
+                    return Err(anyhow!(
+                        "Could not convert section offset into debug info offset"
+                    ));
+                }
+            },
+        )?;
+        let unit = gimli::Unit::new(dwarf, header)?;
+        let die = unit.entry(dwarf_offset.unit_offset)?;
+
+        Ok(Variable {
+            name: get_var_name(dwarf, &unit, &die)?,
+            value: EvaluatorValue::OptimizedOut,
+            source: find_variable_source_information(dwarf, &unit, &die, cwd).ok(),
+        })
+    }
 }
 
 /// Will check if the given DIE has one of the DWARF tags that represents a variable.
@@ -291,6 +380,145 @@ pub fn find_variable_location<R: Reader<Offset = usize>>(
     }
 }
 
+/// A simple description of where a variable is located.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LocationKind {
+    /// The variable is stored in a register.
+    Register(u16),
+
+    /// The variable is stored in memory at a offset from the frame base.
+    FrameBase(i64),
+
+    /// The variable is stored in memory at a offset from the value of a register.
+    RegisterOffset {
+        /// The register number.
+        register: u16,
+
+        /// The offset added to the register value.
+        offset: i64,
+    },
+
+    /// The variable is stored in memory at a static address.
+    Address(u64),
+
+    /// The value of the variable is computed by the expression, it is not stored anywhere.
+    Value,
+
+    /// The variable is split up into multiple pieces stored in different locations.
+    Pieces,
+
+    /// The variable is optimized out.
+    OptimizedOut,
+
+    /// The location is described by a expression that is more complex then the above.
+    Expression,
+}
+
+/// Describes where a variable is located for a range of machine code addresses.
+#[derive(Debug, Clone)]
+pub struct VariableLiveRange<R: Reader<Offset = usize>> {
+    /// The machine code address range the location is valid for.
+    /// `None` if the location is valid in the whole scope of the variable.
+    pub range: Option<gimli::Range>,
+
+    /// The gimli-rs expression that describes the location of the variable.
+    pub expression: gimli::Expression<R>,
+
+    /// A simple description of the location.
+    pub kind: LocationKind,
+}
+
+/// Find all the locations of a variable and the address ranges where they are valid.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `unit` - A reference to gimli-rs `Unit` struct which contains the given DIE.
+/// * `die` - A reference to the variables DIE that contains the location.
+///
+/// Will go through the whole location list in the attribute `DW_AT_location` of the variable
+/// DIE.
+/// This makes it possible to know where the variable lives, and where a watchpoint on it is
+/// valid.
+pub fn find_variable_live_ranges<R: Reader<Offset = usize>>(
+    dwarf: &Dwarf<R>,
+    unit: &Unit<R>,
+    die: &DebuggingInformationEntry<R>,
+) -> Result<Vec<VariableLiveRange<R>>> {
+    if is_variable_die(die) {
+        match die.attr_value(gimli::DW_AT_location)? {
+            Some(Exprloc(expr)) => Ok(vec![VariableLiveRange {
+                range: None,
+                kind: get_location_kind(unit, &expr)?,
+                expression: expr,
+            }]),
+            Some(LocationListsRef(offset)) => {
+                let mut live_ranges = vec![];
+                let mut locations = dwarf.locations(unit, offset)?;
+                while let Some(llent) = locations.next()? {
+                    live_ranges.push(VariableLiveRange {
+                        range: Some(llent.range),
+                        kind: get_location_kind(unit, &llent.data)?,
+                        expression: llent.data,
+                    });
+                }
+                Ok(live_ranges)
+            }
+            None => Ok(vec![]),
+            Some(v) => {
+                error!("Unimplemented for {:?}", v);
+                Err(anyhow!("Unimplemented for {:?}", v))
+            }
+        }
+    } else {
+        Err(anyhow!("This die is not a variable"))
+    }
+}
+
+/// Describe where a location expression places a variable.
+///
+/// Description:
+///
+/// * `unit` - A reference to gimli-rs `Unit` struct which the expression is from.
+/// * `expr` - The location expression.
+///
+/// Will parse the operations of the expression without evaluating it, thus no values from the
+/// debug target are needed.
+pub fn get_location_kind<R: Reader<Offset = usize>>(
+    unit: &Unit<R>,
+    expr: &gimli::Expression<R>,
+) -> Result<LocationKind> {
+    let mut operations = vec![];
+    let mut bytes = expr.0.clone();
+    while !bytes.is_empty() {
+        operations.push(gimli::Operation::parse(&mut bytes, unit.encoding())?);
+    }
+
+    if operations
+        .iter()
+        .any(|op| matches!(op, gimli::Operation::Piece { .. }))
+    {
+        return Ok(LocationKind::Pieces);
+    }
+
+    Ok(match operations.as_slice() {
+        [] => LocationKind::OptimizedOut,
+        [gimli::Operation::Register { register }] => LocationKind::Register(register.0),
+        [gimli::Operation::FrameOffset { offset }] => LocationKind::FrameBase(*offset),
+        [gimli::Operation::RegisterOffset {
+            register, offset, ..
+        }] => LocationKind::RegisterOffset {
+            register: register.0,
+            offset: *offset,
+        },
+        [gimli::Operation::Address { address }] => LocationKind::Address(*address),
+        [.., gimli::Operation::StackValue] | [gimli::Operation::ImplicitValue { .. }] => {
+            LocationKind::Value
+        }
+        _ => LocationKind::Expression,
+    })
+}
+
 /// Find the DIE representing the type of a variable.
 ///
 /// Description:
@@ -396,3 +624,532 @@ pub fn find_variable_source_information<R: Reader<Offset = usize>>(
         Err(anyhow!("This die is not a variable"))
     }
 }
+
+/// Find and evaluate all the variables with static storage duration, e.g. for a "Globals" scope.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `registers` - A reference to the `Registers` struct.
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `cwd` - The work directory of the debugged program.
+///
+/// This function will go through all the compilation units and evaluate the `DW_TAG_variable`
+/// DIEs that have a location and are not nested in a function, e.g. statics in modules.
+/// Variables that can not be evaluated are skipped.
+pub fn find_static_variables<R: Reader<Offset = usize>, M: MemoryAccess>(
+    dwarf: &Dwarf<R>,
+    registers: &Registers,
+    mem: &mut M,
+    cwd: &str,
+) -> Result<Vec<Variable<R>>> {
+    let mut variables = vec![];
+    let mut units = dwarf.units();
+    while let Some(unit_header) = units.next()? {
+        let unit = dwarf.unit(unit_header)?;
+        let mut cursor = unit.entries();
+
+        // The depth of the function the cursor is in, `None` if it is not in a function.
+        let mut function_depth: Option<isize> = None;
+        let mut depth = 0;
+        while let Some((delta_depth, current)) = cursor.next_dfs()? {
+            depth += delta_depth;
+            if function_depth.is_some_and(|d| depth <= d) {
+                function_depth = None;
+            }
+            if function_depth.is_some() {
+                continue;
+            }
+
+            match current.tag() {
+                gimli::DW_TAG_subprogram | gimli::DW_TAG_inlined_subroutine => {
+                    function_depth = Some(depth);
+                }
+                gimli::DW_TAG_variable if current.attr_value(gimli::DW_AT_location)?.is_some() => {
+                    let dwarf_offset = DwarfOffset {
+                        section_offset: unit.header.offset(),
+                        unit_offset: current.offset(),
+                    };
+                    match Variable::get_variable(dwarf, registers, mem, dwarf_offset, None, cwd) {
+                        Ok(variable) => variables.push(variable),
+                        Err(err) => trace!("Skipped a static variable, because: {:?}", err),
+                    };
+                }
+                _ => (),
+            };
+        }
+    }
+
+    Ok(variables)
+}
+
+/// Find and evaluate a variable with static storage duration by name.
+///
+/// Description:
+///
+/// * `dwarf` - A reference to gimli-rs `Dwarf` struct.
+/// * `index` - A index of the DWARF debug information.
+/// * `registers` - A reference to the `Registers` struct.
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `cwd` - The work directory of the debugged program.
+/// * `name` - The name of the variable.
+///
+/// The variable DIE is found in the index, so no compilation units are walked.
+/// If several statics have the name, the first one that can be evaluated is returned.
+/// Will return `Ok(None)` if there is no static variable with the given name.
+pub fn find_static_variable<R: Reader<Offset = usize>, M: MemoryAccess>(
+    dwarf: &Dwarf<R>,
+    index: &DwarfIndex,
+    registers: &Registers,
+    mem: &mut M,
+    cwd: &str,
+    name: &str,
+) -> Result<Option<Variable<R>>> {
+    for dwarf_offset in index.find_static_variables(name) {
+        match Variable::get_variable(dwarf, registers, mem, *dwarf_offset, None, cwd) {
+            Ok(variable) => return Ok(Some(variable)),
+            Err(err) => trace!("Skipped a static variable, because: {:?}", err),
+        };
+    }
+
+    Ok(None)
+}
+
+/// A write to the debug target that changes the value of a variable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariableWrite {
+    /// Write the bits in `mask` of a register.
+    Register {
+        /// The register that is written.
+        register: u16,
+
+        /// The new value of the bits in `mask`.
+        value: u32,
+
+        /// The bits of the register that are written.
+        mask: u32,
+    },
+
+    /// Write bytes to the memory.
+    Memory {
+        /// The address of the first byte.
+        address: u32,
+
+        /// The bytes that are written.
+        bytes: Vec<u8>,
+    },
+}
+
+/// A trait used for writing to the debug target.
+pub trait TargetWrite {
+    /// Writes bytes to the memory of the debugged target.
+    ///
+    /// Description:
+    ///
+    /// * `address` - The address of the first byte.
+    /// * `bytes` - The bytes that will be written.
+    ///
+    /// Will return `None` if the memory could not be written.
+    fn write_memory(&mut self, address: u32, bytes: &[u8]) -> Option<()>;
+
+    /// Writes some of the bits of a register of the debugged target.
+    ///
+    /// Description:
+    ///
+    /// * `register` - The register that will be written.
+    /// * `value` - The new value of the bits in `mask`.
+    /// * `mask` - The bits of the register that will be written, the other bits keep their
+    ///   value.
+    ///
+    /// Will return `None` if the register could not be written.
+    fn write_register(&mut self, register: u16, value: u32, mask: u32) -> Option<()>;
+}
+
+/// Get the writes that change the value of a variable.
+///
+/// Description:
+///
+/// * `value` - The current value of the variable, or of a base type member of it.
+/// * `new_value` - The new value as text, e.g. `42`, `0x2a`, `-1.5` or `true`, it is parsed as
+///   the type of the current value.
+/// * `endian` - The endianness of the debug target.
+///
+/// The new value is written to the same registers and memory the current value was read from.
+/// Will return a error if the value is not a base type value, or if it is not stored in memory
+/// or in registers, e.g. a optimized out value or a constant.
+/// Bit fields in registers are written with a mask, but bit fields in memory can not be written
+/// since only whole bytes are written to the memory.
+pub fn write_variable<R: Reader<Offset = usize>>(
+    value: &EvaluatorValue<R>,
+    new_value: &str,
+    endian: RunTimeEndian,
+) -> Result<Vec<VariableWrite>> {
+    let (current, info) = match value {
+        EvaluatorValue::Value(current, info) => (current, info),
+        EvaluatorValue::Member(member) => return write_variable(&member.value, new_value, endian),
+        _ => {
+            error!("Only base type values can be written");
+            return Err(anyhow!("Only base type values can be written"));
+        }
+    };
+    let new_value = parse_new_value(current, new_value.trim())?;
+    // Registers hold the value as a number, the memory holds it in the target byte order.
+    let register_bytes = base_type_bytes(&new_value, RunTimeEndian::Little);
+    let bytes = base_type_bytes(&new_value, endian);
+
+    let mut writes = vec![];
+    // The offset in bits of the next piece in the new value.
+    let mut offset = 0;
+    for piece in &info.pieces {
+        match piece {
+            evaluate::ValuePiece::Register {
+                register,
+                bit_offset,
+                bit_size,
+                ..
+            } => {
+                // Registers are 32 bits, the bits of the piece that does not fit are not written.
+                let shift = (*bit_offset).min(32);
+                let size = (*bit_size).min(32 - shift);
+                let mut value = vec![];
+                evaluate::append_bits(&mut value, &mut 0, &register_bytes, offset, size);
+                value.resize(4, 0);
+                writes.push(VariableWrite::Register {
+                    register: *register,
+                    value: (u64::from(u32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+                        << shift) as u32,
+                    mask: (((1_u64 << size) - 1) << shift) as u32,
+                });
+                offset += bit_size;
+            }
+            evaluate::ValuePiece::Memory {
+                address,
+                bit_offset,
+                bit_size,
+                ..
+            } => {
+                // Only whole bytes can be written, the other bits of a byte would be overwritten.
+                if *bit_offset != 0 || bit_size % 8 != 0 {
+                    error!(
+                        "Can not write {} bits at bit {} of {:#010x}, only whole bytes can be written",
+                        bit_size, bit_offset, address
+                    );
+                    return Err(anyhow!(
+                        "Can not write {} bits at bit {} of {:#010x}, only whole bytes can be written",
+                        bit_size,
+                        bit_offset,
+                        address
+                    ));
+                }
+                let mut value = vec![];
+                evaluate::append_bits(&mut value, &mut 0, &bytes, offset, *bit_size);
+                writes.push(VariableWrite::Memory {
+                    address: *address,
+                    bytes: value,
+                });
+                offset += bit_size;
+            }
+            _ => {
+                error!("The value is not stored in memory or in a register");
+                return Err(anyhow!(
+                    "The value is not stored in memory or in a register"
+                ));
+            }
+        };
+    }
+
+    if writes.is_empty() {
+        error!("The value has no location");
+        return Err(anyhow!("The value has no location"));
+    }
+    Ok(writes)
+}
+
+/// Perform the writes that change the value of a variable.
+///
+/// Description:
+///
+/// * `target` - A struct for writing to the debug target.
+/// * `writes` - The writes returned by `write_variable`.
+pub fn apply_variable_writes<W: TargetWrite>(
+    target: &mut W,
+    writes: &[VariableWrite],
+) -> Result<()> {
+    for write in writes {
+        let result = match write {
+            VariableWrite::Register {
+                register,
+                value,
+                mask,
+            } => target.write_register(*register, *value, *mask),
+            VariableWrite::Memory { address, bytes } => target.write_memory(*address, bytes),
+        };
+        if result.is_none() {
+            error!("Could not perform the write {:?}", write);
+            return Err(anyhow!("Could not perform the write {:?}", write));
+        }
+    }
+    Ok(())
+}
+
+/// Parse a new value as the type of the current value.
+fn parse_new_value(
+    current: &evaluate::BaseTypeValue,
+    text: &str,
+) -> Result<evaluate::BaseTypeValue> {
+    use evaluate::BaseTypeValue::*;
+
+    let invalid = || {
+        error!("Invalid {} value: {}", current.get_type(), text);
+        anyhow!("Invalid {} value: {}", current.get_type(), text)
+    };
+    // Integers can be written in decimal or in hexadecimal with a `0x` prefix.
+    let unsigned = |max: u64| -> Result<u64> {
+        let value = match text.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => text.parse(),
+        };
+        match value {
+            Ok(val) if val <= max => Ok(val),
+            _ => Err(invalid()),
+        }
+    };
+    // Hexadecimal signed integers are the two's complement bits of the value, e.g. `0xff` is
+    // `-1` for a `i8`.
+    let signed = |bits: u32| -> Result<i64> {
+        let unused = 64 - bits;
+        if text.starts_with("0x") {
+            return Ok(((unsigned(u64::MAX >> unused)? << unused) as i64) >> unused);
+        }
+        match text.parse() {
+            Ok(val) if i64::MIN >> unused <= val && val <= i64::MAX >> unused => Ok(val),
+            _ => Err(invalid()),
+        }
+    };
+
+    Ok(match current {
+        Bool(_) => Bool(text.parse().map_err(|_| invalid())?),
+        Generic(_) => Generic(unsigned(u64::MAX)?),
+        Address32(_) => Address32(unsigned(u32::MAX as u64)? as u32),
+        Reg32(_) => Reg32(unsigned(u32::MAX as u64)? as u32),
+        U8(_) => U8(unsigned(u8::MAX as u64)? as u8),
+        U16(_) => U16(unsigned(u16::MAX as u64)? as u16),
+        U32(_) => U32(unsigned(u32::MAX as u64)? as u32),
+        U64(_) => U64(unsigned(u64::MAX)?),
+        I8(_) => I8(signed(8)? as i8),
+        I16(_) => I16(signed(16)? as i16),
+        I32(_) => I32(signed(32)? as i32),
+        I64(_) => I64(signed(64)?),
+        F32(_) => F32(text.parse().map_err(|_| invalid())?),
+        F64(_) => F64(text.parse().map_err(|_| invalid())?),
+    })
+}
+
+/// Get the bytes of a base type value in the byte order of the debug target.
+fn base_type_bytes(value: &evaluate::BaseTypeValue, endian: RunTimeEndian) -> Vec<u8> {
+    use evaluate::BaseTypeValue::*;
+
+    let mut bytes = match value {
+        Bool(val) => vec![*val as u8],
+        Generic(val) => val.to_le_bytes().to_vec(),
+        Address32(val) => val.to_le_bytes().to_vec(),
+        Reg32(val) => val.to_le_bytes().to_vec(),
+        U8(val) => val.to_le_bytes().to_vec(),
+        U16(val) => val.to_le_bytes().to_vec(),
+        U32(val) => val.to_le_bytes().to_vec(),
+        U64(val) => val.to_le_bytes().to_vec(),
+        I8(val) => val.to_le_bytes().to_vec(),
+        I16(val) => val.to_le_bytes().to_vec(),
+        I32(val) => val.to_le_bytes().to_vec(),
+        I64(val) => val.to_le_bytes().to_vec(),
+        F32(val) => val.to_le_bytes().to_vec(),
+        F64(val) => val.to_le_bytes().to_vec(),
+    };
+    if endian.is_big_endian() {
+        bytes.reverse();
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registers::Architecture;
+    use evaluate::{BaseTypeValue, ValueInformation, ValuePiece};
+    use gimli::{DebugInfoOffset, EndianSlice, LittleEndian};
+
+    type TestReader = EndianSlice<'static, LittleEndian>;
+
+    /// A DWARF 4 compilation unit with the variable `r` of type `struct S { a: u32, b: u32 }`
+    /// at the address in the stack pointer, the variable DIE is at offset 12.
+    fn dwarf() -> Dwarf<TestReader> {
+        static DEBUG_INFO: [u8; 59] = [
+            55, 0, 0, 0, 4, 0, 0, 0, 0, 0, 4, // Unit header.
+            1, // Compile unit.
+            2, 0, 0, 0, 0, 24, 0, 0, 0, 2, 0x7d, 0, // Variable `r` at `DW_OP_breg13 0`.
+            3, 2, 0, 0, 0, 8, // Structure type `S`.
+            4, 4, 0, 0, 0, 51, 0, 0, 0, 0, // Member `a`.
+            4, 6, 0, 0, 0, 51, 0, 0, 0, 4, // Member `b`.
+            0, // End of `S`.
+            5, 8, 0, 0, 0, 7, 4, // Base type `u32`.
+            0, // End of the compile unit.
+        ];
+        static DEBUG_STR: [u8; 12] = *b"r\0S\0a\0b\0u32\0";
+        static DEBUG_ABBREV: [u8; 48] = [
+            1, 0x11, 1, 0, 0, // DW_TAG_compile_unit
+            2, 0x34, 0, 0x03, 0x0e, 0x49, 0x13, 0x02, 0x18, 0, 0, // DW_TAG_variable
+            3, 0x13, 1, 0x03, 0x0e, 0x0b, 0x0b, 0, 0, // DW_TAG_structure_type
+            4, 0x0d, 0, 0x03, 0x0e, 0x49, 0x13, 0x38, 0x0b, 0, 0, // DW_TAG_member
+            5, 0x24, 0, 0x03, 0x0e, 0x3e, 0x0b, 0x0b, 0x0b, 0, 0, // DW_TAG_base_type
+            0,
+        ];
+        Dwarf {
+            debug_info: gimli::DebugInfo::new(&DEBUG_INFO, LittleEndian),
+            debug_abbrev: gimli::DebugAbbrev::new(&DEBUG_ABBREV, LittleEndian),
+            debug_str: gimli::DebugStr::new(&DEBUG_STR, LittleEndian),
+            ..Default::default()
+        }
+    }
+
+    fn value(current: BaseTypeValue, pieces: Vec<ValuePiece>) -> EvaluatorValue<TestReader> {
+        EvaluatorValue::Value(current, ValueInformation::new(None, pieces))
+    }
+
+    fn register_piece(register: u16, bit_offset: u64, bit_size: u64) -> ValuePiece {
+        ValuePiece::Register {
+            register,
+            byte_size: bit_size.div_ceil(8) as usize,
+            bit_offset,
+            bit_size,
+        }
+    }
+
+    #[test]
+    fn parse_new_values() {
+        let parsed = parse_new_value(&BaseTypeValue::U16(0), "0x2a").unwrap();
+        assert!(matches!(parsed, BaseTypeValue::U16(42)));
+        let parsed = parse_new_value(&BaseTypeValue::I8(0), "-128").unwrap();
+        assert!(matches!(parsed, BaseTypeValue::I8(-128)));
+        let parsed = parse_new_value(&BaseTypeValue::Bool(false), "true").unwrap();
+        assert!(matches!(parsed, BaseTypeValue::Bool(true)));
+        assert!(parse_new_value(&BaseTypeValue::U8(0), "256").is_err());
+        assert!(parse_new_value(&BaseTypeValue::U32(0), "-1").is_err());
+        let parsed = parse_new_value(&BaseTypeValue::I8(0), "0xff").unwrap();
+        assert!(matches!(parsed, BaseTypeValue::I8(-1)));
+        let parsed = parse_new_value(&BaseTypeValue::I16(0), "0x8000").unwrap();
+        assert!(matches!(parsed, BaseTypeValue::I16(i16::MIN)));
+        assert!(parse_new_value(&BaseTypeValue::I8(0), "0x100").is_err());
+        assert!(parse_new_value(&BaseTypeValue::I8(0), "128").is_err());
+    }
+
+    #[test]
+    fn write_register_bit_pieces() {
+        // The low bit of the value is bit 3 of r1, and the other 7 bits are the low bits of r2.
+        let current = value(
+            BaseTypeValue::U8(0),
+            vec![register_piece(1, 3, 1), register_piece(2, 0, 7)],
+        );
+        assert_eq!(
+            write_variable(&current, "0x81", RunTimeEndian::Little).unwrap(),
+            vec![
+                VariableWrite::Register {
+                    register: 1,
+                    value: 1 << 3,
+                    mask: 1 << 3,
+                },
+                VariableWrite::Register {
+                    register: 2,
+                    value: 0x40,
+                    mask: 0x7f,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn write_memory_bytes() {
+        let current = value(
+            BaseTypeValue::U32(0),
+            vec![ValuePiece::Memory {
+                address: 0x2000_0000,
+                byte_size: 4,
+                bit_offset: 0,
+                bit_size: 32,
+            }],
+        );
+        assert_eq!(
+            write_variable(&current, "0x11223344", RunTimeEndian::Little).unwrap(),
+            vec![VariableWrite::Memory {
+                address: 0x2000_0000,
+                bytes: vec![0x44, 0x33, 0x22, 0x11],
+            }]
+        );
+        assert_eq!(
+            write_variable(&current, "0x11223344", RunTimeEndian::Big).unwrap(),
+            vec![VariableWrite::Memory {
+                address: 0x2000_0000,
+                bytes: vec![0x11, 0x22, 0x33, 0x44],
+            }]
+        );
+
+        // A bit field in memory would overwrite the other bits of its bytes.
+        let bit_field = value(
+            BaseTypeValue::U8(0),
+            vec![ValuePiece::Memory {
+                address: 0x2000_0000,
+                byte_size: 1,
+                bit_offset: 2,
+                bit_size: 3,
+            }],
+        );
+        assert!(write_variable(&bit_field, "5", RunTimeEndian::Little).is_err());
+    }
+
+    /// Evaluate the variable `r` and record the memory reads.
+    fn read_struct(snapshot: bool) -> (Variable<TestReader>, Vec<(u32, usize)>) {
+        let dwarf = dwarf();
+        let mut registers = Registers::for_architecture(Architecture::CortexM);
+        registers.add_register_value(13, 0x4000_0000);
+        registers.add_register_value(15, 0x0800_0000);
+        let dwarf_offset = DwarfOffset {
+            section_offset: DebugInfoOffset(0).into(),
+            unit_offset: UnitOffset(12),
+        };
+
+        let mut reads = vec![];
+        let mut mem = |address: &u32, num_bytes: usize| {
+            reads.push((*address, num_bytes));
+            let start = address.checked_sub(0x4000_0000)? as usize;
+            Some(
+                [1, 0, 0, 0, 2, 0, 0, 0]
+                    .get(start..start + num_bytes)?
+                    .to_vec(),
+            )
+        };
+        let variable = match snapshot {
+            true => Variable::get_variable_snapshot(
+                &dwarf,
+                &registers,
+                &mut mem,
+                dwarf_offset,
+                None,
+                "",
+            ),
+            false => Variable::get_variable(&dwarf, &registers, &mut mem, dwarf_offset, None, ""),
+        };
+        (variable.unwrap(), reads)
+    }
+
+    #[test]
+    fn snapshot_reads_the_value_once() {
+        let (variable, reads) = read_struct(true);
+        assert_eq!(reads, vec![(0x4000_0000, 8)]);
+        assert_eq!(variable.name.as_deref(), Some("r"));
+        assert_eq!(variable.value.to_string(), "S { a::1, b::2 }");
+
+        // Without the snapshot every member is read separately.
+        let (variable, reads) = read_struct(false);
+        assert_eq!(reads, vec![(0x4000_0000, 4), (0x4000_0004, 4)]);
+        assert_eq!(variable.value.to_string(), "S { a::1, b::2 }");
+    }
+}
@@ -0,0 +1,408 @@
+use crate::call_stack::MemoryAccess;
+use crate::dbgmcu::RegisterWrite;
+use crate::evaluate::evaluate::{EvaluatorValue, ValuePiece};
+use crate::memory_view::read_word;
+use crate::timing::DWT_CTRL_ADDRESS;
+use anyhow::{anyhow, Result};
+use gimli::{Reader, RunTimeEndian};
+use log::error;
+use std::convert::TryFrom;
+
+/// The address of the first DWT Comparator Register, the registers of the next comparator are
+/// `DWT_COMPARATOR_STRIDE` bytes later.
+pub const DWT_COMP0_ADDRESS: u32 = 0xE000_1020;
+
+/// The address of the first DWT Comparator Mask Register.
+pub const DWT_MASK0_ADDRESS: u32 = 0xE000_1024;
+
+/// The address of the first DWT Comparator Function Register.
+pub const DWT_FUNCTION0_ADDRESS: u32 = 0xE000_1028;
+
+/// The number of bytes between the registers of two DWT comparators.
+pub const DWT_COMPARATOR_STRIDE: u32 = 0x10;
+
+/// The `MATCHED` bit in `DWT_FUNCTION`, it is set when the comparator has matched and cleared
+/// when the register is read.
+pub const DWT_FUNCTION_MATCHED: u32 = 1 << 24;
+
+/// The largest number of address bits a DWT comparator can ignore.
+pub const DWT_MAX_MASK: u32 = 15;
+
+/// The memory accesses that trigger a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchKind {
+    /// Halt when the memory is read.
+    Read,
+
+    /// Halt when the memory is written.
+    Write,
+
+    /// Halt when the memory is read or written.
+    ReadWrite,
+}
+
+impl WatchKind {
+    /// Get the kind from a DAP `accessType`, e.g. `read`, `write` or `readWrite`.
+    ///
+    /// Description:
+    ///
+    /// * `name` - The name of the access type.
+    pub fn from_name(name: &str) -> Option<WatchKind> {
+        match name {
+            "read" => Some(WatchKind::Read),
+            "write" => Some(WatchKind::Write),
+            "readWrite" => Some(WatchKind::ReadWrite),
+            _ => None,
+        }
+    }
+
+    /// Get the value of the `FUNCTION` field in `DWT_FUNCTION` for the kind.
+    pub fn function(&self) -> u32 {
+        match self {
+            WatchKind::Read => 0b0101,
+            WatchKind::Write => 0b0110,
+            WatchKind::ReadWrite => 0b0111,
+        }
+    }
+}
+
+/// A watchpoint on a range of memory, also known as a data breakpoint.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Watchpoint {
+    /// The first address that is watched.
+    pub address: u32,
+
+    /// The number of bytes that are watched.
+    pub size: u32,
+
+    /// The memory accesses that trigger the watchpoint.
+    pub kind: WatchKind,
+}
+
+impl Watchpoint {
+    /// Get the number of low address bits the comparator has to ignore to cover the watched
+    /// bytes.
+    ///
+    /// A DWT comparator matches a naturally aligned power of two range, so the matched range can
+    /// be larger than the watched bytes.
+    /// Will return `None` if the range is too large for one comparator.
+    pub fn mask(&self) -> Option<u32> {
+        let last = self.address.checked_add(self.size.max(1) - 1)?;
+        (0..=DWT_MAX_MASK).find(|bits| self.address >> bits == last >> bits)
+    }
+
+    /// Get the range of addresses the comparator matches, as the first address and the number
+    /// of bytes.
+    pub fn matched_range(&self) -> Option<(u32, u32)> {
+        let mask = self.mask()?;
+        Some((self.address & !((1 << mask) - 1), 1 << mask))
+    }
+
+    /// Get the register writes that program a DWT comparator with the watchpoint.
+    ///
+    /// Description:
+    ///
+    /// * `comparator` - The index of the DWT comparator.
+    pub fn writes(&self, comparator: usize) -> Result<Vec<RegisterWrite>> {
+        let mask = match self.mask() {
+            Some(val) => val,
+            None => {
+                error!(
+                    "A watchpoint can not cover {} bytes at {:#010x}",
+                    self.size, self.address
+                );
+                return Err(anyhow!(
+                    "A watchpoint can not cover {} bytes at {:#010x}",
+                    self.size,
+                    self.address
+                ));
+            }
+        };
+        let offset = comparator as u32 * DWT_COMPARATOR_STRIDE;
+        Ok(vec![
+            RegisterWrite {
+                address: DWT_COMP0_ADDRESS + offset,
+                mask: 0xffff_ffff,
+                value: self.address & !((1 << mask) - 1),
+            },
+            RegisterWrite {
+                address: DWT_MASK0_ADDRESS + offset,
+                mask: 0x1f,
+                value: mask,
+            },
+            RegisterWrite {
+                address: DWT_FUNCTION0_ADDRESS + offset,
+                mask: 0xf,
+                value: self.kind.function(),
+            },
+        ])
+    }
+}
+
+/// Get the register write that disables a DWT comparator.
+///
+/// Description:
+///
+/// * `comparator` - The index of the DWT comparator.
+pub fn disable_write(comparator: usize) -> RegisterWrite {
+    RegisterWrite {
+        address: DWT_FUNCTION0_ADDRESS + comparator as u32 * DWT_COMPARATOR_STRIDE,
+        mask: 0xf,
+        value: 0,
+    }
+}
+
+/// Read the number of comparators the DWT unit of the debug target has.
+///
+/// Description:
+///
+/// * `mem` - A struct for accessing the memory of the debug target.
+/// * `endian` - The endianness of the debug target.
+///
+/// The number is in the `NUMCOMP` field of `DWT_CTRL`.
+pub fn read_num_comparators<M: MemoryAccess>(mem: &mut M, endian: RunTimeEndian) -> Result<usize> {
+    match mem
+        .get_address(&DWT_CTRL_ADDRESS, 4)
+        .and_then(|val| read_word(&val, endian))
+    {
+        Some(val) => Ok((val >> 28) as usize),
+        None => {
+            error!("Could not read the DWT control register");
+            Err(anyhow!("Could not read the DWT control register"))
+        }
+    }
+}
+
+/// Find the memory range a value is stored in, e.g. to set a watchpoint on a variable.
+///
+/// Description:
+///
+/// * `value` - The evaluated value.
+///
+/// Will return the first address and the number of bytes, from the lowest to the highest
+/// address of the value.
+/// Will return `None` if no part of the value is stored in memory, e.g. a value in a register.
+/// Will return a error if a part of the value goes past the end of the address space.
+pub fn memory_range<R: Reader<Offset = usize>>(
+    value: &EvaluatorValue<R>,
+) -> Result<Option<(u32, u32)>> {
+    let mut ranges: Vec<(u32, u32)> = vec![];
+    for info in value.clone().get_variable_information() {
+        for piece in info.pieces {
+            if let ValuePiece::Memory {
+                address, byte_size, ..
+            } = piece
+            {
+                let end = match u32::try_from(byte_size)
+                    .ok()
+                    .and_then(|size| address.checked_add(size))
+                {
+                    Some(val) => val,
+                    None => {
+                        error!("The value at {:#010x} overflows the address space", address);
+                        return Err(anyhow!(
+                            "The value at {:#010x} overflows the address space",
+                            address
+                        ));
+                    }
+                };
+                ranges.push((address, end));
+            }
+        }
+    }
+
+    let start = match ranges.iter().map(|r| r.0).min() {
+        Some(val) => val,
+        None => return Ok(None),
+    };
+    let end = ranges.iter().map(|r| r.1).max().unwrap_or(start);
+    Ok(Some((start, end - start)))
+}
+
+/// Keeps track of which DWT comparator each watchpoint is programmed in.
+#[derive(Debug, Clone)]
+pub struct Watchpoints {
+    /// The watchpoint of each comparator, `None` for the free comparators.
+    comparators: Vec<Option<Watchpoint>>,
+}
+
+impl Watchpoints {
+    /// Creates a new `Watchpoints` without any watchpoints.
+    ///
+    /// Description:
+    ///
+    /// * `comparators` - The number of DWT comparators, see `read_num_comparators`.
+    pub fn new(comparators: usize) -> Watchpoints {
+        Watchpoints {
+            comparators: vec![None; comparators],
+        }
+    }
+
+    /// Get the watchpoints that are set.
+    pub fn watchpoints(&self) -> Vec<&Watchpoint> {
+        self.comparators.iter().flatten().collect()
+    }
+
+    /// Add a watchpoint in a free comparator.
+    ///
+    /// Description:
+    ///
+    /// * `watchpoint` - The watchpoint.
+    ///
+    /// Will return the register writes that program the comparator, they have to be written by
+    /// the caller since `MemoryAccess` can only read.
+    pub fn add(&mut self, watchpoint: Watchpoint) -> Result<Vec<RegisterWrite>> {
+        let comparator = match self.comparators.iter().position(|c| c.is_none()) {
+            Some(val) => val,
+            None => {
+                error!("All {} DWT comparators are in use", self.comparators.len());
+                return Err(anyhow!(
+                    "All {} DWT comparators are in use",
+                    self.comparators.len()
+                ));
+            }
+        };
+        let writes = watchpoint.writes(comparator)?;
+        self.comparators[comparator] = Some(watchpoint);
+        Ok(writes)
+    }
+
+    /// Remove the watchpoints on a address.
+    ///
+    /// Description:
+    ///
+    /// * `address` - The first address of the watched memory.
+    ///
+    /// Will return the register writes that disable the comparators.
+    pub fn remove(&mut self, address: u32) -> Vec<RegisterWrite> {
+        let mut writes = vec![];
+        for (comparator, slot) in self.comparators.iter_mut().enumerate() {
+            if slot.is_some_and(|w| w.address == address) {
+                *slot = None;
+                writes.push(disable_write(comparator));
+            }
+        }
+        writes
+    }
+
+    /// Replace all the watchpoints, like the DAP `setDataBreakpoints` request.
+    ///
+    /// Description:
+    ///
+    /// * `watchpoints` - The new watchpoints.
+    ///
+    /// Will return the register writes that disable the old comparators and program the new
+    /// ones.
+    pub fn set(&mut self, watchpoints: &[Watchpoint]) -> Result<Vec<RegisterWrite>> {
+        let mut writes: Vec<RegisterWrite> = (0..self.comparators.len())
+            .filter(|c| self.comparators[*c].is_some())
+            .map(disable_write)
+            .collect();
+        self.comparators.iter_mut().for_each(|c| *c = None);
+        for watchpoint in watchpoints {
+            writes.append(&mut self.add(*watchpoint)?);
+        }
+        Ok(writes)
+    }
+
+    /// Find the watchpoint that halted the debug target.
+    ///
+    /// Description:
+    ///
+    /// * `mem` - A struct for accessing the memory of the debug target.
+    /// * `endian` - The endianness of the debug target.
+    ///
+    /// Reading `DWT_FUNCTION` clears the `MATCHED` bit, so this should only be called once per
+    /// halt.
+    pub fn find_hit<M: MemoryAccess>(
+        &self,
+        mem: &mut M,
+        endian: RunTimeEndian,
+    ) -> Option<&Watchpoint> {
+        self.comparators
+            .iter()
+            .enumerate()
+            .find_map(|(comparator, slot)| {
+                let watchpoint = slot.as_ref()?;
+                let address = DWT_FUNCTION0_ADDRESS + comparator as u32 * DWT_COMPARATOR_STRIDE;
+                let val = mem.get_address(&address, 4)?;
+                match read_word(&val, endian)? & DWT_FUNCTION_MATCHED {
+                    0 => None,
+                    _ => Some(watchpoint),
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::evaluate::{BaseTypeValue, ValueInformation};
+
+    #[test]
+    fn comparator_writes() {
+        let watchpoint = Watchpoint {
+            address: 0x2000_0006,
+            size: 4,
+            kind: WatchKind::Write,
+        };
+        // Bytes 6..10 cross a 8 byte boundary, so a 16 byte range is matched.
+        assert_eq!(watchpoint.matched_range(), Some((0x2000_0000, 16)));
+
+        let mut watchpoints = Watchpoints::new(2);
+        watchpoints.add(watchpoint).unwrap();
+        let writes = watchpoints
+            .add(Watchpoint {
+                address: 0x2000_0010,
+                size: 4,
+                kind: WatchKind::ReadWrite,
+            })
+            .unwrap();
+        assert_eq!(
+            writes,
+            vec![
+                RegisterWrite {
+                    address: 0xE000_1030,
+                    mask: 0xffff_ffff,
+                    value: 0x2000_0010,
+                },
+                RegisterWrite {
+                    address: 0xE000_1034,
+                    mask: 0x1f,
+                    value: 2,
+                },
+                RegisterWrite {
+                    address: 0xE000_1038,
+                    mask: 0xf,
+                    value: 0b0111,
+                },
+            ]
+        );
+        assert!(watchpoints.add(watchpoint).is_err());
+        assert_eq!(watchpoints.remove(0x2000_0006), vec![disable_write(0)]);
+    }
+
+    #[test]
+    fn memory_range_overflow() {
+        type Value = EvaluatorValue<gimli::EndianSlice<'static, gimli::LittleEndian>>;
+        let value = |address: u32| -> Value {
+            EvaluatorValue::Value(
+                BaseTypeValue::U32(0),
+                ValueInformation::new(
+                    None,
+                    vec![ValuePiece::Memory {
+                        address,
+                        byte_size: 4,
+                        bit_offset: 0,
+                        bit_size: 32,
+                    }],
+                ),
+            )
+        };
+        assert_eq!(
+            memory_range(&value(0x2000_0000)).unwrap(),
+            Some((0x2000_0000, 4))
+        );
+        assert!(memory_range(&value(0xffff_fffe)).is_err());
+    }
+}
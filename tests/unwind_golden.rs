@@ -0,0 +1,150 @@
+use rust_debug::test_support::golden::{assert_golden, format_call_stack, unwind_snapshot};
+use std::path::PathBuf;
+
+/// A function in the test program, the prologue pushes `saved` registers and `lr`.
+struct Function {
+    address: u32,
+    size: u32,
+    saved: &'static [u8],
+}
+
+/// The functions of the test program.
+const FUNCTIONS: [Function; 6] = [
+    // main
+    Function {
+        address: 0x1000,
+        size: 0x40,
+        saved: &[7],
+    },
+    // foo
+    Function {
+        address: 0x1100,
+        size: 0x40,
+        saved: &[7],
+    },
+    // bar
+    Function {
+        address: 0x1200,
+        size: 0x40,
+        saved: &[7],
+    },
+    // SysTick
+    Function {
+        address: 0x1300,
+        size: 0x40,
+        saved: &[7],
+    },
+    // rust_begin_unwind
+    Function {
+        address: 0x1400,
+        size: 0x40,
+        saved: &[7],
+    },
+    // panic_fmt
+    Function {
+        address: 0x1500,
+        size: 0x40,
+        saved: &[4, 7],
+    },
+];
+
+/// Pad a `.debug_frame` entry with `DW_CFA_nop` and prepend its length.
+fn entry(mut body: Vec<u8>) -> Vec<u8> {
+    while !body.len().is_multiple_of(4) {
+        body.push(0);
+    }
+    let mut result = (body.len() as u32).to_le_bytes().to_vec();
+    result.extend(body);
+    result
+}
+
+/// Build the `.debug_frame` section of the test program.
+fn debug_frame() -> Vec<u8> {
+    // CIE: version 1, no augmentation, code alignment 2, data alignment -4, return address in
+    // lr, and CFA = sp.
+    let mut section = entry(vec![0xff, 0xff, 0xff, 0xff, 1, 0, 2, 0x7c, 14, 0x0c, 13, 0]);
+
+    for function in &FUNCTIONS {
+        let mut body = 0u32.to_le_bytes().to_vec();
+        body.extend(function.address.to_le_bytes());
+        body.extend(function.size.to_le_bytes());
+
+        // After the push: CFA = sp + 4 * pushed registers, lr and the saved registers are
+        // stored below the CFA.
+        let pushed = function.saved.len() as u8 + 1;
+        body.extend([0x41, 0x0e, 4 * pushed, 0x80 | 14, 1]);
+        for (i, register) in function.saved.iter().rev().enumerate() {
+            body.extend([0x80 | register, i as u8 + 2]);
+        }
+        section.extend(entry(body));
+    }
+
+    section
+}
+
+/// Build a little endian ELF32 file with a `.debug_frame` section.
+fn elf() -> Vec<u8> {
+    let debug_frame = debug_frame();
+    let shstrtab = b"\0.debug_frame\0.shstrtab\0";
+
+    let mut elf = vec![0x7f, b'E', b'L', b'F', 1, 1, 1];
+    elf.resize(52, 0);
+    let debug_frame_offset = elf.len();
+    elf.extend(&debug_frame);
+    let shstrtab_offset = elf.len();
+    elf.extend(shstrtab);
+    while !elf.len().is_multiple_of(4) {
+        elf.push(0);
+    }
+    let shoff = elf.len();
+
+    // Section headers: null, .debug_frame and .shstrtab.
+    let sections = [
+        (0, 0, 0, 0),
+        (1, 1, debug_frame_offset, debug_frame.len()),
+        (14, 3, shstrtab_offset, shstrtab.len()),
+    ];
+    for (name, kind, offset, size) in sections {
+        let mut header = [0u32; 10];
+        header[0] = name;
+        header[1] = kind;
+        header[4] = offset as u32;
+        header[5] = size as u32;
+        for field in header {
+            elf.extend(field.to_le_bytes());
+        }
+    }
+
+    elf[0x20..0x24].copy_from_slice(&(shoff as u32).to_le_bytes());
+    elf[0x2e..0x30].copy_from_slice(&40u16.to_le_bytes());
+    elf[0x30..0x32].copy_from_slice(&3u16.to_le_bytes());
+    elf[0x32..0x34].copy_from_slice(&2u16.to_le_bytes());
+    elf
+}
+
+/// Unwind a scenario and compare the call frames against its golden file.
+fn check_scenario(name: &str) {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let fixture = std::fs::read_to_string(dir.join(format!("{}.fixture", name))).unwrap();
+
+    let call_stack = unwind_snapshot(&elf(), &fixture).unwrap();
+    assert_golden(
+        &format_call_stack(&call_stack),
+        &dir.join(format!("{}.golden", name)),
+    );
+}
+
+#[test]
+fn unwind_call_chain() {
+    check_scenario("call_chain");
+}
+
+#[test]
+fn unwind_isr() {
+    check_scenario("isr");
+}
+
+#[test]
+fn unwind_panic_handler() {
+    check_scenario("panic");
+}